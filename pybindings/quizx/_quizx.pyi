@@ -1,6 +1,6 @@
 # Type stubs for the Rust bindings
 
-from typing import final
+from typing import Callable, Optional, final
 from builtins import complex as complex_
 
 @final
@@ -20,6 +20,7 @@ class Scalar:
     def complex_value(self) -> complex_: ...
     def mul_sqrt2_pow(self, n: int) -> Scalar: ...
     def mul_phase(self, phase: float) -> Scalar: ...
+    def mul_complex(self, c: complex_) -> Scalar: ...
     @staticmethod
     def zero() -> Scalar: ...
     @staticmethod
@@ -30,6 +31,8 @@ class Scalar:
     def to_json(self) -> str: ...
     @staticmethod
     def from_json(json: str) -> Scalar: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
 
 @final
 class VecGraph:
@@ -68,6 +71,21 @@ class VecGraph:
     def adjoint(self) -> None: ...
     def plug(self, other: "VecGraph") -> None: ...
     def clone(self) -> "VecGraph": ...
+    @staticmethod
+    def from_pyzx_dict(
+        vertices: list[tuple[int, int, tuple[int, int], float, float]],
+        edges: list[tuple[int, int, int]],
+        inputs: list[int],
+        outputs: list[int],
+    ) -> tuple["VecGraph", dict[int, int]]: ...
+    def to_pyzx_dict(
+        self,
+    ) -> tuple[
+        list[tuple[int, int, tuple[int, int], float, float]],
+        list[tuple[int, int, int]],
+        list[int],
+        list[int],
+    ]: ...
 
 @final
 class Circuit:
@@ -78,7 +96,29 @@ class Circuit:
     def to_qasm(self) -> str: ...
     def to_graph(self) -> VecGraph: ...
     def num_gates(self) -> int: ...
-    def stats(self) -> str: ...
+    def stats(self) -> CircuitStats: ...
+    @staticmethod
+    def new(qubits: int) -> Circuit: ...
+    def x(self, q: int) -> None: ...
+    def z(self, q: int) -> None: ...
+    def s(self, q: int) -> None: ...
+    def sdg(self, q: int) -> None: ...
+    def t(self, q: int) -> None: ...
+    def tdg(self, q: int) -> None: ...
+    def h(self, q: int) -> None: ...
+    def rz(self, q: int, phase: tuple[int, int]) -> None: ...
+    def rx(self, q: int, phase: tuple[int, int]) -> None: ...
+    def cx(self, c: int, t: int) -> None: ...
+    def cz(self, a: int, b: int) -> None: ...
+    def xcx(self, a: int, b: int) -> None: ...
+    def swap(self, a: int, b: int) -> None: ...
+    def ccx(self, c0: int, c1: int, t: int) -> None: ...
+    def ccz(self, a: int, b: int, c: int) -> None: ...
+    def parity_phase(self, qs: list[int], phase: tuple[int, int]) -> None: ...
+    def init_ancilla(self, q: int) -> None: ...
+    def post_select(self, q: int) -> None: ...
+    def barrier(self, qs: list[int]) -> None: ...
+    def add_circuit(self, other: Circuit, qubit_map: list[int]) -> None: ...
 
 @final
 class CircuitStats:
@@ -89,8 +129,27 @@ class CircuitStats:
     def moreq(self) -> int: ...
     def cliff(self) -> int: ...
     def non_cliff(self) -> int: ...
+    def tcount(self) -> int: ...
+    def depth(self) -> int: ...
     def to_string(self) -> str: ...
 
+@final
+class ProgressInfo:
+    terms: int
+    depth: int
+    elapsed: float
+    fraction: float
+    estimate: float
+
+@final
+class DecompStats:
+    tcount: int
+    nterms: int
+    seconds: float
+    alpha: float
+    moves: dict[str, int]
+    simp_policy: Optional[str]
+
 @final
 class Decomposer:
     scalar: Scalar
@@ -107,10 +166,41 @@ class Decomposer:
     def decomp_parallel(self, depth: int) -> None: ...
     def decomp_until_depth(self, depth: int) -> None: ...
     def use_cats(self, b: bool) -> None: ...
+    def use_cut_decomp(self, max_cut_size: int) -> None: ...
+    def record_tree(self, b: bool) -> None: ...
+    def tree_json(self) -> Optional[str]: ...
+    def on_progress(
+        self, callback: Callable[[ProgressInfo], None], every: int
+    ) -> None: ...
+    def term_limit(self, n: int) -> None: ...
+    def time_limit(self, seconds: float) -> None: ...
+    def is_complete(self) -> bool: ...
     def get_nterms(self) -> int: ...
+    def stats(self) -> DecompStats: ...
+
+@final
+class StopFlag:
+    def __init__(self) -> None: ...
+    def set(self) -> None: ...
+    def clear(self) -> None: ...
+    def is_set(self) -> bool: ...
 
 def dummy(a: int) -> str: ...
 def interior_clifford_simp(g: VecGraph): ...
 def clifford_simp(g: VecGraph): ...
+def pivot_boundary_simp(g: VecGraph) -> int: ...
+def pivot_gadget_simp(g: VecGraph) -> int: ...
 def full_simp(g: VecGraph): ...
+def clifford_simp_with(
+    g: VecGraph,
+    max_iterations: Optional[int] = None,
+    seconds: Optional[float] = None,
+    stop: Optional[StopFlag] = None,
+) -> str: ...
+def full_simp_with(
+    g: VecGraph,
+    max_iterations: Optional[int] = None,
+    seconds: Optional[float] = None,
+    stop: Optional[StopFlag] = None,
+) -> str: ...
 def extract_circuit(g: VecGraph) -> Circuit: ...
@@ -24,6 +24,92 @@ class Circuit:
         c._c = _quizx.Circuit.from_qasm(qasm)
         return c
 
+    @staticmethod
+    def new(qubits: int) -> "Circuit":
+        c = Circuit()
+        c._c = _quizx.Circuit.new(qubits)
+        return c
+
+    def x(self, q: int) -> "Circuit":
+        self._c.x(q)
+        return self
+
+    def z(self, q: int) -> "Circuit":
+        self._c.z(q)
+        return self
+
+    def s(self, q: int) -> "Circuit":
+        self._c.s(q)
+        return self
+
+    def sdg(self, q: int) -> "Circuit":
+        self._c.sdg(q)
+        return self
+
+    def t(self, q: int) -> "Circuit":
+        self._c.t(q)
+        return self
+
+    def tdg(self, q: int) -> "Circuit":
+        self._c.tdg(q)
+        return self
+
+    def h(self, q: int) -> "Circuit":
+        self._c.h(q)
+        return self
+
+    def rz(self, q: int, phase: tuple[int, int]) -> "Circuit":
+        self._c.rz(q, phase)
+        return self
+
+    def rx(self, q: int, phase: tuple[int, int]) -> "Circuit":
+        self._c.rx(q, phase)
+        return self
+
+    def cx(self, c: int, t: int) -> "Circuit":
+        self._c.cx(c, t)
+        return self
+
+    def cz(self, a: int, b: int) -> "Circuit":
+        self._c.cz(a, b)
+        return self
+
+    def xcx(self, a: int, b: int) -> "Circuit":
+        self._c.xcx(a, b)
+        return self
+
+    def swap(self, a: int, b: int) -> "Circuit":
+        self._c.swap(a, b)
+        return self
+
+    def ccx(self, c0: int, c1: int, t: int) -> "Circuit":
+        self._c.ccx(c0, c1, t)
+        return self
+
+    def ccz(self, a: int, b: int, c: int) -> "Circuit":
+        self._c.ccz(a, b, c)
+        return self
+
+    def parity_phase(self, qs: list[int], phase: tuple[int, int]) -> "Circuit":
+        self._c.parity_phase(qs, phase)
+        return self
+
+    def init_ancilla(self, q: int) -> "Circuit":
+        self._c.init_ancilla(q)
+        return self
+
+    def post_select(self, q: int) -> "Circuit":
+        self._c.post_select(q)
+        return self
+
+    def barrier(self, qs: list[int]) -> "Circuit":
+        self._c.barrier(qs)
+        return self
+
+    def add_circuit(self, other: "Circuit", qubit_map: list[int]) -> "Circuit":
+        self._c.add_circuit(other._c, qubit_map)
+        return self
+
     def to_qasm(self):
         return self._c.to_qasm()
 
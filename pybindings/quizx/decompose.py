@@ -1,4 +1,5 @@
-from typing import Optional
+import json
+from typing import Any, Callable, Optional
 
 from . import _quizx
 from .graph import VecGraph
@@ -45,9 +46,72 @@ class Decomposer(object):
     def use_cats(self, b: bool):
         self._d.use_cats(b)
 
+    def use_cut_decomp(self, max_cut_size: int):
+        self._d.use_cut_decomp(max_cut_size)
+
+    def record_tree(self, b: bool):
+        """Record the decompositions taken from now on.
+
+        The tree starts with a root for each graph waiting to be decomposed.
+        """
+        self._d.record_tree(b)
+
+    def tree(self) -> Optional[dict[str, Any]]:
+        """The tree recorded since `record_tree` was turned on, or None.
+
+        This has a list of `nodes`. Each node has its `parent` and
+        `children` as indices into the list, its `tcount`, the `decomp`
+        applied to it, e.g. "bss" or "cat", the `verts` it was applied to,
+        and the `nterms` computed for the node itself.
+        """
+        tree = self._d.tree_json()
+        return None if tree is None else json.loads(tree)
+
+    def on_progress(
+        self, callback: Callable[[_quizx.ProgressInfo], None], every: int = 100
+    ):
+        """Call `callback` with the progress so far, every `every` terms.
+
+        The callback gets a `ProgressInfo`, with the number of `terms` so far,
+        the `fraction` done and an `estimate` of the total terms, which only
+        ever goes up. For example, with a tqdm bar:
+
+            bar = tqdm()
+            def update(p):
+                bar.total = round(p.estimate)
+                bar.update(p.terms - bar.n)
+            d.on_progress(update)
+        """
+        self._d.on_progress(callback, every)
+
+    def term_limit(self, n: int):
+        self._d.term_limit(n)
+
+    def time_limit(self, seconds: float):
+        self._d.time_limit(seconds)
+
+    def is_complete(self) -> bool:
+        """Whether there is nothing left to decompose.
+
+        This is false if `decomp_all` stopped early because of a limit, in
+        which case the scalar only has the terms found so far.
+        """
+        return self._d.is_complete()
+
     def get_nterms(self):
         return self._d.get_nterms()
 
+    def stats(self) -> _quizx.DecompStats:
+        """The size of the decomposition so far.
+
+        This has the `tcount` when decomposition started, the number of
+        `nterms`, the `seconds` taken and the effective `alpha`, which is
+        log2(nterms) / tcount. `moves` counts the decompositions of each
+        kind, e.g. `moves["cat"]`, and `simp_policy` says how often terms
+        were fully simplified, e.g. "Full" or "EveryN(3)".
+        """
+        return self._d.stats()
+
     @property
     def scalar(self) -> Scalar:
         return to_pyzx_scalar(self._d.scalar)
@@ -16,7 +16,8 @@
 
 from .scalar import from_pyzx_scalar, to_pyzx_scalar
 from fractions import Fraction
-from typing import Tuple, Dict, Any
+from typing import Tuple, Dict, Any, Optional
+from pyzx.graph import Graph  # type: ignore
 from pyzx.graph.base import BaseGraph  # type: ignore
 from pyzx.utils import VertexType, EdgeType  # type: ignore
 from pyzx.graph.scalar import Scalar
@@ -47,6 +48,39 @@ class VecGraph(BaseGraph[int, Tuple[int, int]]):
         g._g = rust_g
         return g
 
+    @staticmethod
+    def from_pyzx(g: BaseGraph) -> "VecGraph":
+        """Copy a pyzx graph, passing all of its vertices and edges to quizx in one go.
+
+        This is much faster than adding them one at a time. Vertices get new
+        indices, numbered from 0."""
+        ty, phases, qs, rs = g.types(), g.phases(), g.qubits(), g.rows()
+        vertices = []
+        for v in g.vertices():
+            p = Fraction(phases[v])
+            vertices.append((v, ty[v], (p.numerator, p.denominator), qs[v], rs[v]))
+        edges = [(*g.edge_st(e), g.edge_type(e)) for e in g.edges()]
+        rust_g, _ = _quizx.VecGraph.from_pyzx_dict(
+            vertices, edges, list(g.inputs()), list(g.outputs())
+        )
+        h = VecGraph.from_raw_graph(rust_g)
+        h.scalar = g.scalar
+        return h
+
+    def to_pyzx(self, backend: Optional[str] = None) -> BaseGraph:
+        """Copy this graph to a pyzx graph with the given backend, in one go."""
+        vertices, edges, inputs, outputs = self._g.to_pyzx_dict()
+        g = Graph(backend)
+        vmap = dict()
+        for v, t, (num, den), q, r in vertices:
+            vmap[v] = g.add_vertex(t, q, r, Fraction(num, den))
+        for et in (EdgeType.SIMPLE, EdgeType.HADAMARD, EdgeType.W_IO):
+            g.add_edges([(vmap[s], vmap[t]) for s, t, et1 in edges if et1 == et], et)
+        g.set_inputs(tuple(vmap[v] for v in inputs))
+        g.set_outputs(tuple(vmap[v] for v in outputs))
+        g.scalar = self.scalar
+        return g
+
     # n.b. we use python iterators to avoid issues with rust lifetimes
 
     class VIter:
@@ -1,4 +1,8 @@
+import threading
+from typing import Optional
+
 from . import _quizx
+from ._quizx import StopFlag
 
 
 def interior_clifford_simp(g):
@@ -9,5 +13,65 @@ def clifford_simp(g):
     _quizx.clifford_simp(g._g)
 
 
+def pivot_boundary_simp(g):
+    return _quizx.pivot_boundary_simp(g._g)
+
+
+def pivot_gadget_simp(g):
+    return _quizx.pivot_gadget_simp(g._g)
+
+
 def full_simp(g):
     _quizx.full_simp(g._g)
+
+
+def _run_stoppable(f, g, max_iterations, seconds, stop) -> str:
+    # the simplification runs on another thread without the GIL, so this one
+    # is free to turn a KeyboardInterrupt into setting the stop flag
+    if stop is None:
+        stop = StopFlag()
+    result = []
+
+    def run():
+        try:
+            result.append(f(g._g, max_iterations, seconds, stop))
+        except BaseException as e:
+            result.append(e)
+
+    t = threading.Thread(target=run)
+    t.start()
+    while t.is_alive():
+        try:
+            t.join(0.1)
+        except KeyboardInterrupt:
+            stop.set()
+    if isinstance(result[0], BaseException):
+        raise result[0]
+    return result[0]
+
+
+def clifford_simp_with(
+    g,
+    max_iterations: Optional[int] = None,
+    seconds: Optional[float] = None,
+    stop: Optional[StopFlag] = None,
+) -> str:
+    """Same as `clifford_simp`, but stop early, see `full_simp_with`."""
+    return _run_stoppable(_quizx.clifford_simp_with, g, max_iterations, seconds, stop)
+
+
+def full_simp_with(
+    g,
+    max_iterations: Optional[int] = None,
+    seconds: Optional[float] = None,
+    stop: Optional[StopFlag] = None,
+) -> str:
+    """Same as `full_simp`, but stop after `max_iterations` rewrites, after
+    `seconds` seconds, once `stop` is set or on Ctrl-C.
+
+    Returns "converged" if nothing is left to simplify, and otherwise the
+    reason for stopping: "max_iterations", "deadline" or "stopped". The graph
+    always stays equal to the original one, as it only stops between
+    rewrites.
+    """
+    return _run_stoppable(_quizx.full_simp_with, g, max_iterations, seconds, stop)
@@ -1,27 +1,43 @@
+// the code pyo3 0.22 generates for #[pymethods] returning PyResult converts
+// PyErr into itself
+#![allow(clippy::useless_conversion)]
+
 pub mod scalar;
 
 use crate::scalar::Scalar;
 
 use num::Rational64;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
 use quizx::extract::ToCircuit;
 use quizx::graph::*;
 use quizx::phase::Phase;
+use quizx::simplify::{SimpOptions, SimpStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[pymodule]
 fn _quizx(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(dummy, m)?)?;
     m.add_function(wrap_pyfunction!(interior_clifford_simp, m)?)?;
     m.add_function(wrap_pyfunction!(clifford_simp, m)?)?;
+    m.add_function(wrap_pyfunction!(pivot_boundary_simp, m)?)?;
+    m.add_function(wrap_pyfunction!(pivot_gadget_simp, m)?)?;
     m.add_function(wrap_pyfunction!(fuse_gadgets, m)?)?;
     m.add_function(wrap_pyfunction!(full_simp, m)?)?;
+    m.add_function(wrap_pyfunction!(clifford_simp_with, m)?)?;
+    m.add_function(wrap_pyfunction!(full_simp_with, m)?)?;
     m.add_function(wrap_pyfunction!(extract_circuit, m)?)?;
     m.add_class::<VecGraph>()?;
     m.add_class::<Circuit>()?;
     m.add_class::<CircuitStats>()?;
     m.add_class::<Decomposer>()?;
+    m.add_class::<ProgressInfo>()?;
+    m.add_class::<DecompStats>()?;
     m.add_class::<Scalar>()?;
+    m.add_class::<StopFlag>()?;
     Ok(())
 }
 
@@ -40,6 +56,16 @@ fn clifford_simp(g: &mut VecGraph) {
     quizx::simplify::clifford_simp(&mut g.g);
 }
 
+#[pyfunction]
+fn pivot_boundary_simp(g: &mut VecGraph) -> usize {
+    quizx::simplify::pivot_boundary_simp(&mut g.g)
+}
+
+#[pyfunction]
+fn pivot_gadget_simp(g: &mut VecGraph) -> usize {
+    quizx::simplify::pivot_gadget_simp(&mut g.g)
+}
+
 #[pyfunction]
 fn fuse_gadgets(g: &mut VecGraph) {
     quizx::simplify::fuse_gadgets(&mut g.g);
@@ -50,6 +76,85 @@ fn full_simp(g: &mut VecGraph) {
     quizx::simplify::full_simp(&mut g.g);
 }
 
+/// A flag for stopping a simplification from another thread
+#[pyclass]
+#[derive(Default)]
+struct StopFlag {
+    f: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl StopFlag {
+    #[new]
+    fn new() -> Self {
+        StopFlag::default()
+    }
+
+    fn set(&self) {
+        self.f.store(true, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.f.store(false, Ordering::Relaxed);
+    }
+
+    fn is_set(&self) -> bool {
+        self.f.load(Ordering::Relaxed)
+    }
+}
+
+fn simp_options(
+    max_iterations: Option<usize>,
+    seconds: Option<f64>,
+    stop: Option<&StopFlag>,
+) -> SimpOptions {
+    SimpOptions {
+        max_iterations,
+        deadline: seconds.map(Duration::from_secs_f64),
+        stop_flag: stop.map(|s| s.f.clone()),
+    }
+}
+
+fn simp_status_name(status: SimpStatus) -> String {
+    match status {
+        SimpStatus::Converged => "converged",
+        SimpStatus::MaxIterations => "max_iterations",
+        SimpStatus::Deadline => "deadline",
+        SimpStatus::Stopped => "stopped",
+    }
+    .to_string()
+}
+
+/// Runs without holding the GIL, so the stop flag can be set from Python
+#[pyfunction]
+#[pyo3(signature = (g, max_iterations=None, seconds=None, stop=None))]
+fn clifford_simp_with(
+    py: Python<'_>,
+    g: &mut VecGraph,
+    max_iterations: Option<usize>,
+    seconds: Option<f64>,
+    stop: Option<&StopFlag>,
+) -> String {
+    let opts = simp_options(max_iterations, seconds, stop);
+    let status = py.allow_threads(|| quizx::simplify::clifford_simp_with(&mut g.g, &opts));
+    simp_status_name(status)
+}
+
+/// Runs without holding the GIL, so the stop flag can be set from Python
+#[pyfunction]
+#[pyo3(signature = (g, max_iterations=None, seconds=None, stop=None))]
+fn full_simp_with(
+    py: Python<'_>,
+    g: &mut VecGraph,
+    max_iterations: Option<usize>,
+    seconds: Option<f64>,
+    stop: Option<&StopFlag>,
+) -> String {
+    let opts = simp_options(max_iterations, seconds, stop);
+    let status = py.allow_threads(|| quizx::simplify::full_simp_with(&mut g.g, &opts));
+    simp_status_name(status)
+}
+
 #[pyfunction]
 fn extract_circuit(g: &mut VecGraph) -> Circuit {
     Circuit {
@@ -107,6 +212,94 @@ impl Circuit {
         }
         CircuitStats { s: self.s.unwrap() }
     }
+
+    #[staticmethod]
+    fn new(qubits: usize) -> Circuit {
+        Circuit {
+            c: quizx::circuit::Circuit::new(qubits),
+            s: None,
+        }
+    }
+    fn x(&mut self, q: usize) {
+        self.edit().x(q);
+    }
+    fn z(&mut self, q: usize) {
+        self.edit().z(q);
+    }
+    fn s(&mut self, q: usize) {
+        self.edit().s(q);
+    }
+    fn sdg(&mut self, q: usize) {
+        self.edit().sdg(q);
+    }
+    fn t(&mut self, q: usize) {
+        self.edit().t(q);
+    }
+    fn tdg(&mut self, q: usize) {
+        self.edit().tdg(q);
+    }
+    fn h(&mut self, q: usize) {
+        self.edit().h(q);
+    }
+    fn rz(&mut self, q: usize, phase: (i64, i64)) -> PyResult<()> {
+        self.edit().rz(q, rational(phase)?);
+        Ok(())
+    }
+    fn rx(&mut self, q: usize, phase: (i64, i64)) -> PyResult<()> {
+        self.edit().rx(q, rational(phase)?);
+        Ok(())
+    }
+    fn cx(&mut self, c: usize, t: usize) {
+        self.edit().cx(c, t);
+    }
+    fn cz(&mut self, a: usize, b: usize) {
+        self.edit().cz(a, b);
+    }
+    fn xcx(&mut self, a: usize, b: usize) {
+        self.edit().xcx(a, b);
+    }
+    fn swap(&mut self, a: usize, b: usize) {
+        self.edit().swap(a, b);
+    }
+    fn ccx(&mut self, c0: usize, c1: usize, t: usize) {
+        self.edit().ccx(c0, c1, t);
+    }
+    fn ccz(&mut self, a: usize, b: usize, c: usize) {
+        self.edit().ccz(a, b, c);
+    }
+    fn parity_phase(&mut self, qs: Vec<usize>, phase: (i64, i64)) -> PyResult<()> {
+        self.edit().parity_phase(&qs, rational(phase)?);
+        Ok(())
+    }
+    fn init_ancilla(&mut self, q: usize) {
+        self.edit().init_ancilla(q);
+    }
+    fn post_select(&mut self, q: usize) {
+        self.edit().post_select(q);
+    }
+    fn barrier(&mut self, qs: Vec<usize>) {
+        self.edit().barrier(&qs);
+    }
+    fn add_circuit(&mut self, other: &Circuit, qubit_map: Vec<usize>) {
+        self.edit().add_circuit(&other.c, &qubit_map);
+    }
+}
+
+/// A phase given from python as a fraction (numerator, denominator)
+fn rational(phase: (i64, i64)) -> PyResult<Rational64> {
+    if phase.1 == 0 {
+        Err(PyValueError::new_err("Phase denominator must be non-zero"))
+    } else {
+        Ok(Rational64::new(phase.0, phase.1))
+    }
+}
+
+impl Circuit {
+    /// Mutable access to the circuit, clearing the cached stats
+    fn edit(&mut self) -> &mut quizx::circuit::Circuit {
+        self.s = None;
+        &mut self.c
+    }
 }
 
 #[pymethods]
@@ -132,12 +325,68 @@ impl CircuitStats {
     fn non_cliff(&self) -> usize {
         self.s.non_cliff
     }
+    fn tcount(&self) -> usize {
+        self.s.tcount
+    }
+    fn depth(&self) -> usize {
+        self.s.depth
+    }
     #[allow(clippy::inherent_to_string)]
     fn to_string(&self) -> String {
         self.s.to_string()
     }
 }
 
+/// A vertex as passed to and from pyzx: name, type, phase, qubit and row
+type PyzxVertex = (V, u8, (i64, i64), f64, f64);
+
+/// An edge as passed to and from pyzx: source, target and type
+type PyzxEdge = (V, V, u8);
+
+/// Converts a pyzx vertex type number
+fn vtype_from_num(ty_num: u8) -> VType {
+    match ty_num {
+        1 => VType::Z,
+        2 => VType::X,
+        3 => VType::H,
+        4 => VType::WInput,
+        5 => VType::WOutput,
+        6 => VType::ZBox,
+        _ => VType::B,
+    }
+}
+
+/// The pyzx number for a vertex type
+fn vtype_num(ty: VType) -> u8 {
+    match ty {
+        VType::B => 0,
+        VType::Z => 1,
+        VType::X => 2,
+        VType::H => 3,
+        VType::WInput => 4,
+        VType::WOutput => 5,
+        VType::ZBox => 6,
+    }
+}
+
+/// Converts a pyzx edge type number
+fn etype_from_num(et_num: u8) -> EType {
+    match et_num {
+        2 => EType::H,
+        3 => EType::Wio,
+        _ => EType::N,
+    }
+}
+
+/// The pyzx number for an edge type
+fn etype_num(et: EType) -> u8 {
+    match et {
+        EType::N => 1,
+        EType::H => 2,
+        EType::Wio => 3,
+    }
+}
+
 /// Wrapper for quizx::vec_graph::Graph
 #[pyclass]
 struct VecGraph {
@@ -178,6 +427,7 @@ impl VecGraph {
             phase,
             qubit,
             row,
+            ground: false,
         })
     }
 
@@ -215,46 +465,19 @@ impl VecGraph {
     }
 
     fn vertex_type(&self, v: usize) -> u8 {
-        match self.g.vertex_type(v) {
-            VType::B => 0,
-            VType::Z => 1,
-            VType::X => 2,
-            VType::H => 3,
-            VType::WInput => 4,
-            VType::WOutput => 5,
-            VType::ZBox => 6,
-        }
+        vtype_num(self.g.vertex_type(v))
     }
 
     fn set_vertex_type(&mut self, v: usize, ty_num: u8) {
-        let ty = match ty_num {
-            1 => VType::Z,
-            2 => VType::X,
-            3 => VType::H,
-            4 => VType::WInput,
-            5 => VType::WOutput,
-            6 => VType::ZBox,
-            _ => VType::B,
-        };
-        self.g.set_vertex_type(v, ty);
+        self.g.set_vertex_type(v, vtype_from_num(ty_num));
     }
 
     fn edge_type(&self, e: (usize, usize)) -> u8 {
-        match self.g.edge_type_opt(e.0, e.1) {
-            Some(EType::N) => 1,
-            Some(EType::H) => 2,
-            Some(EType::Wio) => 3,
-            None => 0,
-        }
+        self.g.edge_type_opt(e.0, e.1).map_or(0, etype_num)
     }
 
     fn set_edge_type(&mut self, e: (usize, usize), et_num: u8) {
-        let et = match et_num {
-            2 => EType::H,
-            3 => EType::Wio,
-            _ => EType::N,
-        };
-        self.g.set_edge_type(e.0, e.1, et);
+        self.g.set_edge_type(e.0, e.1, etype_from_num(et_num));
     }
 
     fn phase(&self, v: usize) -> (i64, i64) {
@@ -325,6 +548,100 @@ impl VecGraph {
     fn clone(&self) -> VecGraph {
         VecGraph { g: self.g.clone() }
     }
+
+    /// Builds a graph from pyzx's vertex and edge data in one go
+    ///
+    /// Also returns the map from the given vertex names to the new vertices.
+    #[staticmethod]
+    fn from_pyzx_dict(
+        vertices: Vec<PyzxVertex>,
+        edges: Vec<PyzxEdge>,
+        inputs: Vec<V>,
+        outputs: Vec<V>,
+    ) -> (VecGraph, std::collections::HashMap<V, V>) {
+        let d = quizx::json::PyzxDict {
+            vertices: vertices
+                .into_iter()
+                .map(|(v, ty_num, phase, qubit, row)| {
+                    let data = VData {
+                        ty: vtype_from_num(ty_num),
+                        phase: Phase::new(phase),
+                        qubit: qubit.round() as i32,
+                        row: row.round() as i32,
+                        ground: false,
+                    };
+                    (v, data)
+                })
+                .collect(),
+            edges: edges
+                .into_iter()
+                .map(|(s, t, et_num)| (s, t, etype_from_num(et_num)))
+                .collect(),
+            inputs,
+            outputs,
+        };
+        let (g, vmap) = quizx::json::from_pyzx_dict(&d).unwrap_or_else(|e| panic!("{}", e));
+        (VecGraph { g }, vmap.into_iter().collect())
+    }
+
+    /// Returns the vertex and edge data of the graph, with the inputs and outputs
+    fn to_pyzx_dict(&self) -> (Vec<PyzxVertex>, Vec<PyzxEdge>, Vec<V>, Vec<V>) {
+        let d = quizx::json::to_pyzx_dict(&self.g);
+        let vertices = d
+            .vertices
+            .into_iter()
+            .map(|(v, data)| {
+                let p = data.phase.to_rational();
+                let phase = (*p.numer(), *p.denom());
+                let (qubit, row) = (data.qubit as f64, data.row as f64);
+                (v, vtype_num(data.ty), phase, qubit, row)
+            })
+            .collect();
+        let edges = d
+            .edges
+            .into_iter()
+            .map(|(s, t, et)| (s, t, etype_num(et)))
+            .collect();
+        (vertices, edges, d.inputs, d.outputs)
+    }
+}
+
+/// The progress of a decomposition, see quizx::decompose::ProgressInfo
+#[pyclass(get_all)]
+#[derive(Clone)]
+struct ProgressInfo {
+    terms: usize,
+    depth: usize,
+    /// in seconds
+    elapsed: f64,
+    fraction: f64,
+    estimate: f64,
+}
+
+/// The size of a decomposition, see quizx::decompose::DecompStats
+#[pyclass(get_all)]
+#[derive(Clone)]
+struct DecompStats {
+    tcount: usize,
+    nterms: usize,
+    seconds: f64,
+    alpha: f64,
+    moves: std::collections::HashMap<String, usize>,
+    simp_policy: Option<String>,
+}
+
+#[pymethods]
+impl DecompStats {
+    fn __str__(&self) -> String {
+        quizx::decompose::DecompStats {
+            tcount: self.tcount,
+            nterms: self.nterms,
+            seconds: self.seconds,
+            alpha: self.alpha,
+            ..Default::default()
+        }
+        .to_string()
+    }
 }
 
 #[pyclass]
@@ -358,20 +675,20 @@ impl Decomposer {
         self.d.scalar = scalar.into();
     }
 
-    fn graphs(&self) -> PyResult<Vec<VecGraph>> {
+    fn graphs(&self) -> Vec<VecGraph> {
         let mut gs = vec![];
         for (_a, g) in &self.d.stack {
             gs.push(VecGraph { g: g.clone() });
         }
-        Ok(gs)
+        gs
     }
 
-    fn done(&self) -> PyResult<Vec<VecGraph>> {
+    fn done(&self) -> Vec<VecGraph> {
         let mut gs = vec![];
         for g in &self.d.done {
             gs.push(VecGraph { g: g.clone() });
         }
-        Ok(gs)
+        gs
     }
 
     fn save(&mut self, b: bool) {
@@ -404,7 +721,63 @@ impl Decomposer {
     fn use_cats(&mut self, b: bool) {
         self.d.use_cats(b);
     }
+    fn use_cut_decomp(&mut self, max_cut_size: usize) {
+        self.d.use_cut_decomp(max_cut_size);
+    }
+    fn record_tree(&mut self, b: bool) {
+        self.d.record_tree(b);
+    }
+    /// The recorded decomposition tree as JSON, see quizx::decompose::DecompTree
+    fn tree_json(&self) -> Option<String> {
+        self.d.tree().map(|t| serde_json::to_string(t).unwrap())
+    }
+    fn on_progress(&mut self, callback: PyObject, every: usize) {
+        self.d.on_progress(every, move |p| {
+            Python::with_gil(|py| {
+                let info = ProgressInfo {
+                    terms: p.terms,
+                    depth: p.depth,
+                    elapsed: p.elapsed.as_secs_f64(),
+                    fraction: p.fraction,
+                    estimate: p.estimate,
+                };
+                if let Err(e) = callback.call1(py, (info,)) {
+                    e.print(py);
+                }
+            })
+        });
+    }
+    fn term_limit(&mut self, n: usize) {
+        self.d.term_limit(n);
+    }
+    fn time_limit(&mut self, seconds: f64) {
+        self.d
+            .deadline(std::time::Instant::now() + std::time::Duration::from_secs_f64(seconds));
+    }
+    fn is_complete(&self) -> bool {
+        self.d.is_complete()
+    }
     fn get_nterms(&self) -> usize {
         self.d.nterms
     }
+    fn stats(&self) -> DecompStats {
+        let s = self.d.stats();
+        let m = s.moves;
+        let moves = [
+            ("cat", m.cat),
+            ("magic5", m.magic5),
+            ("bss", m.bss),
+            ("t_pair", m.t_pair),
+            ("single_t", m.single_t),
+            ("cut", m.cut),
+        ];
+        DecompStats {
+            tcount: s.tcount,
+            nterms: s.nterms,
+            seconds: s.seconds,
+            alpha: s.alpha,
+            moves: moves.map(|(k, n)| (k.to_string(), n)).into(),
+            simp_policy: s.simp_policy.map(|p| format!("{:?}", p)),
+        }
+    }
 }
@@ -22,6 +22,8 @@ use num::rational::Rational64;
 use num::{FromPrimitive, One, Zero};
 use pyo3::prelude::*;
 use quizx::scalar::{FromPhase, ScalarN, Sqrt2};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 /// A type for exact and approximate representation of complex
 /// numbers.
@@ -139,6 +141,13 @@ impl Scalar {
         s
     }
 
+    /// Returns the scalar multiplied by an arbitrary complex number.
+    pub fn mul_complex(&self, c: Complex<f64>) -> Self {
+        let mut s = self.clone();
+        s.s.mul_complex(c);
+        s
+    }
+
     /// Returns a zero scalar.
     #[staticmethod]
     pub fn zero() -> Self {
@@ -248,4 +257,15 @@ impl Scalar {
     pub fn __float__(&self) -> f64 {
         self.complex_value().re
     }
+
+    /// Scalars are equal if they are exactly the same number.
+    pub fn __eq__(&self, other: &Self) -> bool {
+        self.s == other.s
+    }
+
+    pub fn __hash__(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        self.s.hash(&mut h);
+        h.finish()
+    }
 }
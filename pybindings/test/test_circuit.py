@@ -0,0 +1,13 @@
+import pytest
+from quizx import Circuit
+
+
+def test_zero_denominator():
+    c = Circuit.new(2)
+    c.rz(0, (1, 4)).rx(1, (1, 2))
+    with pytest.raises(ValueError):
+        c.rz(0, (1, 0))
+    with pytest.raises(ValueError):
+        c.rx(0, (1, 0))
+    with pytest.raises(ValueError):
+        c.parity_phase([0, 1], (1, 0))
@@ -0,0 +1,14 @@
+from pyzx.generate import cliffordT
+from pyzx.tensor import compare_tensors
+from quizx import VecGraph
+
+
+def test_pyzx_roundtrip():
+    g = cliffordT(3, 20)
+    h = VecGraph.from_pyzx(g)
+    assert h.num_vertices() == g.num_vertices()
+    assert h.num_edges() == g.num_edges()
+
+    g2 = h.to_pyzx()
+    assert g2.num_vertices() == g.num_vertices()
+    assert compare_tensors(g, g2, preserve_scalar=True)
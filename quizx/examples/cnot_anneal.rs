@@ -20,6 +20,7 @@ use quizx::extract::*;
 use quizx::hash_graph::*;
 use quizx::simplify::*;
 
+use std::ops::ControlFlow;
 use std::time::Instant;
 use std::{thread, time};
 
@@ -43,7 +44,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Annealing...");
     let time = Instant::now();
     let mut annealer = Annealer::new(g);
-    annealer.seed(1337).temp(25.0).anneal();
+    annealer
+        .seed(1337)
+        .temp(25.0)
+        .callback(|s| {
+            if s.iter % 50 == 0 {
+                println!("{}: cost {}, best {}", s.iter, s.cost, s.best);
+            }
+            ControlFlow::Continue(())
+        })
+        .anneal();
     g = annealer.g;
     println!("Done annealing in {:.2?}", time.elapsed());
 
@@ -14,78 +14,184 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-// use crate::circuit::*;
-// use crate::gate::*;
+//! Simulated annealing over local complementations and pivots
+//!
+//! An [Annealer] repeatedly applies a random move to a graph, keeping it if
+//! it lowers the cost, or with a probability that shrinks as the temperature
+//! drops if it doesn't. Rejected moves are rolled back with a
+//! [TrackedGraph], so the graph is never copied just to try a move.
+
 use crate::basic_rules::*;
 use crate::extract::*;
 use crate::graph::*;
+use crate::tracked_graph::TrackedGraph;
 
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
 
-pub struct Annealer<G: GraphLike> {
-    pub g: G,
-    rng: StdRng,
-    scoref: fn(&G) -> usize,
-    actions: Vec<fn(&mut StdRng, &mut G)>,
-    temp: f64,
-    cool: f64,
-    iters: usize,
+/// Options for an [Annealer]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnnealerOptions {
+    /// Temperature at the first iteration
+    pub temp: f64,
+    /// The temperature is multiplied by `1 - cool` after each iteration
+    pub cool: f64,
+    /// Number of iterations
+    pub iters: usize,
+    /// Seed for the random number generator, or `None` to seed from entropy
+    pub seed: Option<u64>,
 }
 
-impl<G: GraphLike> Annealer<G> {
-    pub fn extract_2q_score(g: &G) -> usize {
-        let c = g.to_circuit().unwrap();
-        c.stats().twoq
+impl Default for AnnealerOptions {
+    fn default() -> Self {
+        AnnealerOptions {
+            temp: 25.0,
+            cool: 0.005,
+            iters: 1000,
+            seed: None,
+        }
     }
+}
 
-    pub fn random_local_comp(rng: &mut StdRng, g: &mut G) {
-        let candidates: Vec<_> = g.vertices().filter(|&v| check_local_comp(g, v)).collect();
-        if candidates.is_empty() {
-            return;
-        }
-        let i = rng.gen_range(0..candidates.len());
-        local_comp(g, candidates[i]);
+/// A random rewrite an [Annealer] can try
+///
+/// The annealer applies moves to a [TrackedGraph] wrapping its graph, so
+/// moves should be implemented for any [GraphLike].
+pub trait AnnealMove<G: GraphLike> {
+    /// Apply the move somewhere in g, chosen using rng
+    ///
+    /// Returns `false`, leaving g unchanged, if the move applies nowhere.
+    fn apply(&self, rng: &mut StdRng, g: &mut G) -> bool;
+}
+
+/// Pick a random element of xs
+fn choose<T: Copy>(rng: &mut StdRng, xs: &[T]) -> Option<T> {
+    if xs.is_empty() {
+        None
+    } else {
+        Some(xs[rng.gen_range(0..xs.len())])
     }
+}
 
-    pub fn random_pivot(rng: &mut StdRng, g: &mut G) {
+/// Local complementation at a random spider where it applies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomLocalComp;
+
+impl<G: GraphLike> AnnealMove<G> for RandomLocalComp {
+    fn apply(&self, rng: &mut StdRng, g: &mut G) -> bool {
+        let candidates: Vec<_> = g
+            .vertices_sorted()
+            .filter(|&v| check_local_comp(g, v))
+            .collect();
+        choose(rng, &candidates).is_some_and(|v| local_comp(g, v))
+    }
+}
+
+/// Pivot at a random edge where it applies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomPivot;
+
+impl<G: GraphLike> AnnealMove<G> for RandomPivot {
+    fn apply(&self, rng: &mut StdRng, g: &mut G) -> bool {
         let candidates: Vec<_> = g
             .edges()
             .filter(|&(s, t, _)| check_pivot(g, s, t))
             .collect();
-        if candidates.is_empty() {
-            return;
-        }
-        let i = rng.gen_range(0..candidates.len());
-        pivot(g, candidates[i].0, candidates[i].1);
+        choose(rng, &candidates).is_some_and(|(s, t, _)| pivot(g, s, t))
     }
+}
 
-    pub fn random_gen_pivot(rng: &mut StdRng, g: &mut G) {
+/// Pivot at a random edge, unfusing phases and boundaries first
+///
+/// See [gen_pivot] for the spiders which get unfused.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomGenPivot;
+
+impl<G: GraphLike> AnnealMove<G> for RandomGenPivot {
+    fn apply(&self, rng: &mut StdRng, g: &mut G) -> bool {
         let candidates: Vec<_> = g
             .edges()
             .filter(|&(s, t, _)| check_gen_pivot(g, s, t))
             .collect();
-        if candidates.is_empty() {
-            return;
+        choose(rng, &candidates).is_some_and(|(s, t, _)| gen_pivot(g, s, t))
+    }
+}
+
+/// The state of an [Annealer] after an iteration, as passed to its callback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnnealStep {
+    /// The iteration, counting from 0
+    pub iter: usize,
+    /// The temperature during this iteration
+    pub temp: f64,
+    /// Cost of the current graph
+    pub cost: usize,
+    /// Lowest cost seen so far
+    pub best: usize,
+    /// Whether the move tried in this iteration was kept
+    pub accepted: bool,
+    /// Time since the annealer started
+    pub elapsed: Duration,
+}
+
+type Callback = Box<dyn FnMut(&AnnealStep) -> ControlFlow<()>>;
+
+pub struct Annealer<G: GraphLike> {
+    pub g: G,
+    options: AnnealerOptions,
+    scoref: fn(&G) -> usize,
+    moves: Vec<Box<dyn AnnealMove<TrackedGraph<G>>>>,
+    callback: Option<Callback>,
+}
+
+impl<G: GraphLike> Annealer<G> {
+    /// Number of 2-qubit gates in the extracted circuit
+    ///
+    /// If extraction fails, this is `usize::MAX`, so the move that led to g
+    /// is never kept. A zero diagram scores 0, as there is nothing to extract.
+    pub fn extract_2q_score(g: &G) -> usize {
+        if g.is_zero() {
+            return 0;
         }
-        let i = rng.gen_range(0..candidates.len());
-        gen_pivot(g, candidates[i].0, candidates[i].1);
+        g.to_circuit().map_or(usize::MAX, |c| c.stats().twoq)
+    }
+
+    #[deprecated(note = "use the RandomLocalComp move")]
+    pub fn random_local_comp(rng: &mut StdRng, g: &mut G) {
+        RandomLocalComp.apply(rng, g);
     }
 
+    #[deprecated(note = "use the RandomPivot move")]
+    pub fn random_pivot(rng: &mut StdRng, g: &mut G) {
+        RandomPivot.apply(rng, g);
+    }
+
+    #[deprecated(note = "use the RandomGenPivot move")]
+    pub fn random_gen_pivot(rng: &mut StdRng, g: &mut G) {
+        RandomGenPivot.apply(rng, g);
+    }
+
+    /// An annealer with the default options, which minimises the number of
+    /// 2-qubit gates in the extracted circuit using local complementations
+    /// and pivots
     pub fn new(g: G) -> Self {
         Annealer {
             g,
-            rng: StdRng::from_entropy(),
+            options: AnnealerOptions::default(),
             scoref: Annealer::extract_2q_score,
-            actions: vec![Annealer::random_local_comp, Annealer::random_pivot],
-            temp: 25.0,
-            cool: 0.005,
-            iters: 1000,
+            moves: vec![Box::new(RandomLocalComp), Box::new(RandomPivot)],
+            callback: None,
         }
     }
 
+    pub fn options(&mut self, options: AnnealerOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
     pub fn seed(&mut self, seed: u64) -> &mut Self {
-        self.rng = StdRng::seed_from_u64(seed);
+        self.options.seed = Some(seed);
         self
     }
     pub fn scoref(&mut self, scoref: fn(&G) -> usize) -> &mut Self {
@@ -94,49 +200,243 @@ impl<G: GraphLike> Annealer<G> {
     }
 
     pub fn temp(&mut self, temp: f64) -> &mut Self {
-        self.temp = temp;
+        self.options.temp = temp;
         self
     }
     pub fn cool(&mut self, cool: f64) -> &mut Self {
-        self.cool = cool;
+        self.options.cool = cool;
         self
     }
     pub fn iters(&mut self, iters: usize) -> &mut Self {
-        self.iters = iters;
+        self.options.iters = iters;
         self
     }
 
-    pub fn anneal(&mut self) {
-        if self.actions.is_empty() {
-            return;
+    /// Remove all the moves, e.g. to replace the default ones
+    pub fn clear_moves(&mut self) -> &mut Self {
+        self.moves.clear();
+        self
+    }
+
+    /// Add a move, which is picked with the same probability as the others
+    pub fn add_move(&mut self, m: impl AnnealMove<TrackedGraph<G>> + 'static) -> &mut Self {
+        self.moves.push(Box::new(m));
+        self
+    }
+
+    /// Call f after every iteration, e.g. to log the cost
+    ///
+    /// This is called whether or not the cost improved. If f returns
+    /// [ControlFlow::Break], the annealer stops, keeping the best graph so
+    /// far, e.g. to give it a time budget.
+    pub fn callback(
+        &mut self,
+        f: impl FnMut(&AnnealStep) -> ControlFlow<()> + 'static,
+    ) -> &mut Self {
+        self.callback = Some(Box::new(f));
+        self
+    }
+
+    /// Run the annealer, leaving the graph with the lowest cost seen in `g`
+    ///
+    /// Returns the cost of that graph. A zero diagram is left alone.
+    pub fn anneal(&mut self) -> usize {
+        let mut current_score = (self.scoref)(&self.g);
+        let mut best_score = current_score;
+        // rewriting a zero diagram can't make it any better
+        if self.moves.is_empty() || self.g.is_zero() {
+            return best_score;
         }
-        let mut temp = self.temp;
-        let mut current_score = (self.scoref)(&self.g) as isize;
 
-        let chunk = self.iters / 20;
-        for it in 0..self.iters {
-            if it % chunk == 0 {
-                println!("{}/{}", it, self.iters);
+        let mut rng = match self.options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut g = TrackedGraph::from_graph(self.g.clone());
+        let mut temp = self.options.temp;
+        let start = Instant::now();
+
+        for iter in 0..self.options.iters {
+            // select a move uniformly at random
+            let i = rng.gen_range(0..self.moves.len());
+            let mark = g.checkpoint();
+            let mut accepted = false;
+            if self.moves[i].apply(&mut rng, &mut g) {
+                let new_score = (self.scoref)(g.graph());
+                let delta = current_score as f64 - new_score as f64;
+                accepted = new_score < current_score
+                    || (temp != 0.0 && rng.gen_bool(f64::min(1.0, (delta / temp).exp())));
+                if accepted {
+                    current_score = new_score;
+                    g.compact();
+                    if current_score < best_score {
+                        best_score = current_score;
+                        self.g = g.graph().clone();
+                    }
+                } else {
+                    g.rollback(mark).unwrap();
+                }
+            }
+
+            if let Some(f) = &mut self.callback {
+                let step = AnnealStep {
+                    iter,
+                    temp,
+                    cost: current_score,
+                    best: best_score,
+                    accepted,
+                    elapsed: start.elapsed(),
+                };
+                if f(&step).is_break() {
+                    break;
+                }
             }
-            // select and action uniformly at random
-            let i = self.rng.gen_range(0..self.actions.len());
-            let mut g = self.g.clone();
-            self.actions[i](&mut self.rng, &mut g);
-            let new_score = (self.scoref)(&self.g) as isize;
-            if new_score < current_score
-                || (temp != 0.0
-                    && self.rng.gen_bool(f64::min(
-                        1.0,
-                        ((current_score - new_score) as f64 / temp).exp(),
-                    )))
-            {
-                self.g = g;
-                current_score = new_score;
+            temp *= 1.0 - self.options.cool;
+        }
+
+        best_score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::scalar::*;
+    use crate::simplify::flow_simp;
+    use crate::tensor::{CompareTensors, Tensor4, ToTensor};
+    use crate::vec_graph::Graph;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn test_graph(qubits: usize, depth: usize) -> (Circuit, Graph) {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(qubits)
+            .depth(depth)
+            .clifford_t(0.1)
+            .build();
+        let mut g: Graph = c.to_graph();
+        flow_simp(&mut g);
+        (c, g)
+    }
+
+    #[test]
+    fn anneal_fixed_seed() {
+        let (_, g) = test_graph(8, 200);
+        let start = Annealer::extract_2q_score(&g);
+        let steps = Rc::new(RefCell::new(Vec::new()));
+        let steps1 = steps.clone();
+
+        let mut annealer = Annealer::new(g);
+        annealer
+            .options(AnnealerOptions {
+                temp: 1.0,
+                cool: 0.02,
+                iters: 300,
+                seed: Some(6),
+            })
+            .add_move(RandomGenPivot)
+            .callback(move |s| {
+                steps1.borrow_mut().push(*s);
+                ControlFlow::Continue(())
+            });
+        let best = annealer.anneal();
+
+        // if this changes, so has the behaviour of the annealer
+        assert_eq!((start, best), (68, 67));
+        assert_eq!(best, Annealer::extract_2q_score(&annealer.g));
+
+        let steps = steps.borrow();
+        assert_eq!(steps.len(), 300);
+        assert_eq!(steps.iter().map(|s| s.best).min(), Some(best));
+        assert!(steps.iter().all(|s| s.best <= s.cost));
+        // the best graph was not the last one
+        assert!(steps[299].cost > best);
+    }
+
+    #[test]
+    fn anneal_reproducible() {
+        let (c, g) = test_graph(4, 60);
+        let run = || {
+            let mut annealer = Annealer::new(g.clone());
+            annealer.seed(42).iters(50).add_move(RandomGenPivot);
+            annealer.anneal();
+            annealer.g
+        };
+        let g1 = run();
+        assert_eq!(g1, run());
+        assert!(Tensor4::scalar_compare(&c, &g1.to_circuit().unwrap()));
+    }
+
+    #[test]
+    fn anneal_no_moves() {
+        let (_, g) = test_graph(4, 60);
+        let mut annealer = Annealer::new(g.clone());
+        annealer.clear_moves().seed(0);
+        annealer.anneal();
+        assert_eq!(annealer.g, g);
+    }
+
+    #[test]
+    fn anneal_stop_early() {
+        let (_, g) = test_graph(8, 200);
+        let iters = Rc::new(RefCell::new(Vec::new()));
+        let iters1 = iters.clone();
+        let mut annealer = Annealer::new(g);
+        annealer.seed(6).iters(300).callback(move |s| {
+            iters1.borrow_mut().push(s.iter);
+            if s.iter == 20 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
             }
+        });
+        let best = annealer.anneal();
+        assert_eq!(*iters.borrow(), (0..=20).collect::<Vec<_>>());
+        assert_eq!(best, Annealer::extract_2q_score(&annealer.g));
+    }
 
-            temp *= 1.0 - self.cool;
+    #[test]
+    fn anneal_zero() {
+        let (_, mut g) = test_graph(4, 60);
+        *g.scalar_mut() = ScalarN::zero();
+        let mut annealer = Annealer::new(g.clone());
+        annealer
+            .seed(0)
+            .callback(|_| panic!("a zero diagram shouldn't be rewritten"));
+        assert_eq!(annealer.anneal(), 0);
+        assert_eq!(annealer.g, g);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_moves() {
+        let (_, g) = test_graph(4, 60);
+        let mut rng = StdRng::seed_from_u64(0);
+        let moves: [fn(&mut StdRng, &mut Graph); 3] = [
+            Annealer::random_local_comp,
+            Annealer::random_pivot,
+            Annealer::random_gen_pivot,
+        ];
+        for m in moves {
+            let mut h = g.clone();
+            m(&mut rng, &mut h);
+            assert_ne!(h, g);
+            assert_eq!(h.to_tensor4(), g.to_tensor4());
         }
+    }
 
-        println!("{}/{}", self.iters, self.iters);
+    #[test]
+    fn gen_pivot_move_candidates() {
+        // RandomGenPivot also pivots where neither spider is interior Pauli
+        let (_, g) = test_graph(4, 60);
+        let edges =
+            |f: fn(&Graph, V, V) -> bool| g.edges().filter(|&(s, t, _)| f(&g, s, t)).count();
+        assert!(edges(check_gen_pivot) > edges(check_gen_pivot_reduce));
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut h = g.clone();
+        assert!(RandomGenPivot.apply(&mut rng, &mut h));
+        assert_eq!(h.to_tensor4(), g.to_tensor4());
     }
 }
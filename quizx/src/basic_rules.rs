@@ -25,14 +25,17 @@
 //!
 //! Note calling `X_unchecked` is allowed to make unsound ZX-diagram
 //! transformations, or even panic, if `check_X` doesn't return true.
+//!
+//! The rules don't know about grounds, so no `check_X` accepts a rewrite
+//! at or next to a [grounded](VData::ground) vertex, see [near_ground].
 
 use crate::graph::*;
 use crate::phase::Phase;
 use crate::scalar::*;
+use crate::simplify::{apply_rule, Rule, RuleError};
 use num::traits::Zero;
 use num::Rational64;
 use rustc_hash::FxHashSet;
-use std::iter::FromIterator;
 
 /// Define a checked rule that takes 1 vertex
 macro_rules! checked_rule1 {
@@ -68,6 +71,23 @@ macro_rules! checked_rule2 {
     };
 }
 
+/// Whether any of the vertices, or any of their neighbours, is
+/// [grounded](VData::ground)
+pub fn near_ground(g: &impl GraphLike, vs: &[V]) -> bool {
+    vs.iter()
+        .any(|&v| g.is_ground(v) || g.neighbors(v).any(|w| g.is_ground(w)))
+}
+
+/// Remove a vertex which a rule has used up
+///
+/// Rules only ever remove spiders, so this panics on a boundary rather than
+/// leaving a missing vertex in the inputs or outputs.
+fn remove_interior(g: &mut impl GraphLike, v: V) {
+    if let Err(e) = g.try_remove_vertex(v) {
+        panic!("rewrite tried to remove a vertex it shouldn't: {}", e);
+    }
+}
+
 /// Check [spider_fusion_unchecked] applies
 ///
 /// Both vertices must be Z or X, have the same type, and be connected
@@ -92,12 +112,13 @@ pub fn check_spider_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
         && g.edge_type_opt(v0, v1) == Some(EType::N)
         && ((g.vertex_type(v0) == VType::Z && g.vertex_type(v1) == VType::Z)
             || (g.vertex_type(v0) == VType::X && g.vertex_type(v1) == VType::X))
+        && !near_ground(g, &[v0, v1])
 }
 
 /// Apply spider fusion
 ///
 /// Note the first vertex is preserved by the fusion, and the second
-/// is deleted.
+/// is deleted. This is [GraphLike::fuse_unchecked].
 ///
 /// ```
 /// # use quizx::graph::*;
@@ -120,14 +141,7 @@ pub fn check_spider_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
 /// assert_ne!(g.to_tensor4(), h.to_tensor4());
 /// ```
 pub fn spider_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
-    for (v, et) in Vec::from_iter(g.incident_edges(v1)) {
-        if v != v0 {
-            g.add_edge_smart(v0, v, et);
-        }
-    }
-
-    g.add_to_phase(v0, g.phase(v1));
-    g.remove_vertex(v1);
+    g.fuse_unchecked(v0, v1);
 }
 
 /// A checked implementation of the rule
@@ -182,17 +196,18 @@ pub fn check_pi_copy(g: &impl GraphLike, v: V) -> bool {
         return false;
     }
 
-    for neighbor in g.neighbors(v) {
+    for (neighbor, et) in g.incident_edges(v) {
         // Every neighbor must be the same color and connected by
         // a hadamard edge or the opposite color and connected by a normal edge.
-        match g.edge_type(v, neighbor) {
+        match et {
             EType::N if g.vertex_type(neighbor) != ovt => return false,
             EType::H if g.vertex_type(neighbor) != vt => return false,
+            EType::Wio => return false,
             _ => (),
         }
     }
 
-    true
+    !near_ground(g, &[v])
 }
 
 /// Apply a pi-copy
@@ -205,7 +220,9 @@ pub fn check_pi_copy(g: &impl GraphLike, v: V) -> bool {
 pub fn pi_copy_unchecked(g: &mut impl GraphLike, v: V) {
     // Flip the phase of this node
     let phase = g.phase(v);
-    g.scalar_mut().mul_phase(phase);
+    if g.track_scalar() {
+        g.scalar_mut().mul_phase(phase);
+    }
     g.set_phase(v, -phase);
 
     // Push a pi to all the surrounding nodes
@@ -220,7 +237,11 @@ checked_rule1!(check_pi_copy, pi_copy_unchecked, pi_copy);
 pub fn check_remove_id(g: &impl GraphLike, v: V) -> bool {
     let vt = g.vertex_type(v);
 
-    (vt == VType::Z || vt == VType::X) && g.phase(v).is_zero() && g.degree(v) == 2
+    (vt == VType::Z || vt == VType::X)
+        && g.phase(v).is_zero()
+        && g.degree(v) == 2
+        && g.incident_edges(v).all(|(_, et)| et != EType::Wio)
+        && !near_ground(g, &[v])
 }
 
 /// Remove an arity-2 spider with phase 0
@@ -229,8 +250,19 @@ pub fn check_remove_id(g: &impl GraphLike, v: V) -> bool {
 /// of the resulting edge is the parity of the types of
 /// original 2 edges, namely: {N,N} -> N, {N,H} -> H, and
 /// {H, H} -> N.
+///
+/// If the only edge of the spider is a self-loop, the closed loop is removed
+/// and added to the global scalar: 2 for a normal edge and 0 for a Hadamard
+/// edge.
 pub fn remove_id_unchecked(g: &mut impl GraphLike, v: V) {
     let nhd: Vec<(V, EType)> = g.incident_edges(v).collect();
+    if nhd[0].0 == v {
+        g.remove_edge(v, v);
+        g.add_edge_smart(v, v, nhd[0].1);
+        remove_single_unchecked(g, v);
+        return;
+    }
+
     let new_et = match (nhd[0].1, nhd[1].1) {
         (EType::N, EType::N) => EType::N,
         (EType::N, EType::H) => EType::H,
@@ -239,7 +271,7 @@ pub fn remove_id_unchecked(g: &mut impl GraphLike, v: V) {
         (EType::Wio, _) | (_, EType::Wio) => unimplemented!("W nodes not supported"),
     };
     g.add_edge_smart(nhd[0].0, nhd[1].0, new_et);
-    g.remove_vertex(v);
+    remove_interior(g, v);
 }
 
 checked_rule1!(check_remove_id, remove_id_unchecked, remove_id);
@@ -247,7 +279,7 @@ checked_rule1!(check_remove_id, remove_id_unchecked, remove_id);
 /// Check [color_change_unchecked] applies
 pub fn check_color_change(g: &impl GraphLike, v: V) -> bool {
     let vt = g.vertex_type(v);
-    vt == VType::X || vt == VType::Z
+    (vt == VType::X || vt == VType::Z) && !near_ground(g, &[v])
 }
 
 /// Change the color of a Z or X spider
@@ -255,11 +287,7 @@ pub fn check_color_change(g: &impl GraphLike, v: V) -> bool {
 /// All of the neighboring edge types are toggled, i.e. N -> H,
 /// H -> N.
 pub fn color_change_unchecked(g: &mut impl GraphLike, v: V) {
-    let vt = g.vertex_type(v);
-    g.set_vertex_type(v, if vt == VType::X { VType::Z } else { VType::X });
-    for w in Vec::from_iter(g.neighbors(v)) {
-        g.toggle_edge_type(v, w);
-    }
+    g.color_change(v);
 }
 
 checked_rule1!(check_color_change, color_change_unchecked, color_change);
@@ -273,6 +301,7 @@ pub fn check_local_comp(g: &impl GraphLike, v: V) -> bool {
         && g.phase(v).is_proper_clifford()
         && g.incident_edges(v)
             .all(|(v0, et)| g.vertex_type(v0) == VType::Z && et == EType::H)
+        && !near_ground(g, &[v])
 }
 
 /// Apply a local complementation
@@ -291,12 +320,14 @@ pub fn local_comp_unchecked(g: &mut impl GraphLike, v: V) {
             g.add_edge_smart(ns[i], ns[j], EType::H);
         }
     }
-    g.remove_vertex(v);
+    remove_interior(g, v);
 
-    let x = ns.len() as i32;
-    g.scalar_mut().mul_sqrt2_pow(((x - 1) * (x - 2)) / 2);
-    g.scalar_mut()
-        .mul_phase(Rational64::new(*p.to_rational().numer(), 4));
+    if g.track_scalar() {
+        let x = ns.len() as i32;
+        g.scalar_mut().mul_sqrt2_pow(((x - 1) * (x - 2)) / 2);
+        g.scalar_mut()
+            .mul_phase(Rational64::new(*p.to_rational().numer(), 4));
+    }
 }
 
 checked_rule1!(check_local_comp, local_comp_unchecked, local_comp);
@@ -315,6 +346,7 @@ pub fn check_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
             .all(|(w, et)| g.vertex_type(w) == VType::Z && et == EType::H)
         && g.incident_edges(v1)
             .all(|(w, et)| g.vertex_type(w) == VType::Z && et == EType::H)
+        && !near_ground(g, &[v0, v1])
 }
 
 /// Apply pivoting to a pair of vertices
@@ -345,15 +377,17 @@ pub fn pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
         g.add_to_phase(n1, p0);
     }
 
-    g.remove_vertex(v0);
-    g.remove_vertex(v1);
+    remove_interior(g, v0);
+    remove_interior(g, v1);
 
-    let x = ns0.len() as i32; // the number of neighbors of v0
-    let y = ns1.len() as i32; // the number of neighbors of v1
-    g.scalar_mut().mul_sqrt2_pow((x - 2) * (y - 2));
+    if g.track_scalar() {
+        let x = ns0.len() as i32; // the number of neighbors of v0
+        let y = ns1.len() as i32; // the number of neighbors of v1
+        g.scalar_mut().mul_sqrt2_pow((x - 2) * (y - 2));
 
-    if !p0.is_zero() && !p1.is_zero() {
-        g.scalar_mut().mul_phase(Rational64::new(1, 1));
+        if !p0.is_zero() && !p1.is_zero() {
+            g.scalar_mut().mul_phase(Rational64::new(1, 1));
+        }
     }
 }
 
@@ -372,6 +406,7 @@ fn unfuse_boundary(g: &mut impl GraphLike, v: V, b: V) {
         phase: Phase::zero(),
         row: g.row(v),
         qubit: g.qubit(v),
+        ground: false,
     };
     let v1 = g.add_vertex_with_data(vd);
     g.add_edge_with_type(v, v1, EType::H);
@@ -381,23 +416,114 @@ fn unfuse_boundary(g: &mut impl GraphLike, v: V, b: V) {
 
 /// Unfuse a non-Pauli phase as a degree-1 phase gadget
 ///
-/// If the vertex already has a Pauli phase, this is a noop.
-fn unfuse_gadget(g: &mut impl GraphLike, v: V) {
+/// If the vertex already has a Pauli phase, this is a noop. Otherwise,
+/// returns the new degree-1 vertex carrying the phase.
+fn unfuse_gadget(g: &mut impl GraphLike, v: V) -> Option<V> {
     if g.phase(v).is_pauli() {
-        return;
+        return None;
     }
+    Some(unfuse_phase(g, v, g.phase(v)))
+}
+
+/// Move part of the phase of v onto the leaf of a new phase gadget
+///
+/// The gadget is connected to v by a Hadamard edge, and the leaf is returned.
+fn unfuse_phase(g: &mut impl GraphLike, v: V, p: Phase) -> V {
     let vd = VData {
         ty: VType::Z,
         phase: Phase::zero(),
         row: g.row(v),
         qubit: g.qubit(v),
+        ground: false,
     };
     let v1 = g.add_vertex_with_data(vd);
     let v2 = g.add_vertex_with_data(vd);
-    g.set_phase(v2, g.phase(v));
-    g.set_phase(v, Rational64::zero());
+    g.set_phase(v2, p);
+    g.add_to_phase(v, -p);
     g.add_edge_with_type(v, v1, EType::H);
     g.add_edge_with_type(v1, v2, EType::H);
+    v2
+}
+
+/// Check [gadgetize_unchecked] applies
+///
+/// The vertex must be an interior Z spider, i.e. with no boundary neighbours,
+/// with a non-Clifford phase, which isn't already the phase of a
+/// [PhaseGadget].
+pub fn check_gadgetize(g: &impl GraphLike, v: V) -> bool {
+    g.vertex_type(v) == VType::Z
+        && !g.phase(v).is_clifford()
+        && g.neighbors(v).all(|n| g.vertex_type(n) != VType::B)
+        && g.phase_gadget(v).is_none()
+        && !near_ground(g, &[v])
+}
+
+/// Move the phase of a spider onto a new phase gadget, with the spider as
+/// its only leg
+///
+/// This preserves the scalar exactly.
+pub fn gadgetize_unchecked(g: &mut impl GraphLike, v: V) {
+    unfuse_phase(g, v, g.phase(v));
+}
+
+checked_rule1!(check_gadgetize, gadgetize_unchecked, gadgetize);
+
+/// Check [gen_local_comp_unchecked] applies
+///
+/// The vertex must be Z, with only Hadamard edges to Z spiders or edges to
+/// boundaries. Its phase may be arbitrary.
+pub fn check_gen_local_comp(g: &impl GraphLike, v: V) -> bool {
+    g.vertex_type(v) == VType::Z
+        && g.incident_edges(v).all(|(w, et)| {
+            let t = g.vertex_type(w);
+            (t == VType::Z && et == EType::H) || t == VType::B
+        })
+        && !near_ground(g, &[v])
+}
+
+/// Generic version of local complementation
+///
+/// Like [gen_pivot_unchecked], this first unfuses spiders so
+/// [local_comp_unchecked] applies: edges to boundaries get an identity
+/// spider, and if the phase is not ±π/2, the difference from π/2 is moved
+/// onto a new phase gadget. The scalar is updated exactly.
+pub fn gen_local_comp_unchecked(g: &mut impl GraphLike, v: V) {
+    for n in g.neighbor_vec(v) {
+        unfuse_boundary(g, v, n);
+    }
+    if !g.phase(v).is_proper_clifford() {
+        unfuse_phase(g, v, g.phase(v) - Phase::new(Rational64::new(1, 2)));
+    }
+    local_comp_unchecked(g, v);
+}
+
+checked_rule1!(
+    check_gen_local_comp,
+    gen_local_comp_unchecked,
+    gen_local_comp
+);
+
+/// Apply [gen_local_comp] at any Z spider, reporting why it doesn't apply
+///
+/// Unlike [local_comp], the phase of `v` is arbitrary, and it may be
+/// connected to boundaries. It is an error if `v` is not in the graph, is
+/// not a Z spider, or has an edge which is not a Hadamard edge to a Z spider
+/// or an edge to a boundary, or if `v` or a neighbour is grounded. On an
+/// error the graph is left unchanged.
+pub fn local_comp_at(g: &mut impl GraphLike, v: V) -> Result<(), RuleError> {
+    apply_rule(g, Rule::GenLocalComp, &[v])
+}
+
+/// Apply [gen_pivot] at any two adjacent Z spiders, reporting why it doesn't apply
+///
+/// Both phases are arbitrary, and either spider may be connected to
+/// boundaries. It is an error if either vertex is not in the graph, they are
+/// not distinct Z spiders connected by a Hadamard edge, or either has an edge
+/// which is not a Hadamard edge to a Z spider or an edge to a boundary, or
+/// if either or a neighbour is grounded. On an error the graph is left
+/// unchanged.
+pub fn pivot_at(g: &mut impl GraphLike, v0: V, v1: V) -> Result<(), RuleError> {
+    apply_rule(g, Rule::GenPivot, &[v0, v1])
 }
 
 /// Check gen_pivot applies
@@ -426,7 +552,7 @@ pub fn check_gen_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
         }
     }
 
-    true
+    !near_ground(g, &[v0, v1])
 }
 
 // check that a vertex is interior, has phase 0 or pi, and is not
@@ -456,6 +582,27 @@ pub fn check_boundary_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
     check_gen_pivot(g, v0, v1) && is_boundary_pauli(g, v0)
 }
 
+/// Check gen_pivot applies to an interior Pauli vertex and a boundary vertex
+///
+/// The first vertex must be interior with a phase 0 or pi, and the second
+/// must be adjacent to a boundary. Its phase may be arbitrary.
+pub fn check_pivot_boundary(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_gen_pivot(g, v0, v1)
+        && is_interior_pauli(g, v0)
+        && g.neighbors(v1).any(|n| g.vertex_type(n) == VType::B)
+}
+
+/// Check gen_pivot applies to an interior Pauli vertex and a non-Pauli vertex
+///
+/// Both vertices must be interior. The first must have a phase 0 or pi, and
+/// the second a non-Pauli phase, which is unfused into a phase gadget.
+pub fn check_pivot_gadget(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_gen_pivot(g, v0, v1)
+        && is_interior_pauli(g, v0)
+        && !g.phase(v1).is_pauli()
+        && g.neighbors(v1).all(|n| g.vertex_type(n) != VType::B)
+}
+
 /// Generic version of the pivot rule
 ///
 /// This version of the pivoting rule allows either of the vertices
@@ -464,14 +611,22 @@ pub fn check_boundary_pivot(g: &impl GraphLike, v0: V, v1: V) -> bool {
 /// non-Pauli spiders produce phase gadgets and boundary non-Pauli spiders
 /// produce phase gates on inputs/outputs.
 pub fn gen_pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    gen_pivot_unchecked_leaves(g, v0, v1);
+}
+
+/// Same as [gen_pivot_unchecked], but return where the phases of v0 and v1 went
+///
+/// If a vertex had a non-Pauli phase, it is moved onto the leaf of a new phase
+/// gadget, which is returned.
+pub(crate) fn gen_pivot_unchecked_leaves(g: &mut impl GraphLike, v0: V, v1: V) -> [Option<V>; 2] {
     let nhd0 = g.neighbor_vec(v0);
-    unfuse_gadget(g, v0);
+    let l0 = unfuse_gadget(g, v0);
     for &n in &nhd0 {
         unfuse_boundary(g, v0, n);
     }
 
     let nhd1 = g.neighbor_vec(v1);
-    unfuse_gadget(g, v1);
+    let l1 = unfuse_gadget(g, v1);
     for &n in &nhd1 {
         unfuse_boundary(g, v1, n);
     }
@@ -483,10 +638,14 @@ pub fn gen_pivot_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
     //          if remove_id(g, n) { println!("REMOVED EXTRA: {}", n); }
     //      }
     //  }
+
+    [l0, l1]
 }
 
 checked_rule2!(check_gen_pivot, gen_pivot_unchecked, gen_pivot);
 checked_rule2!(check_boundary_pivot, gen_pivot_unchecked, boundary_pivot);
+checked_rule2!(check_pivot_boundary, gen_pivot_unchecked, pivot_boundary);
+checked_rule2!(check_pivot_gadget, gen_pivot_unchecked, pivot_gadget);
 
 pub fn check_gadget_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
     if v0 == v1 {
@@ -521,7 +680,7 @@ pub fn check_gadget_fusion(g: &impl GraphLike, v0: V, v1: V) -> bool {
         }
     }
 
-    nhd[0] == nhd[1]
+    nhd[0] == nhd[1] && !near_ground(g, &[v0, v1])
 }
 
 pub fn gadget_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
@@ -534,25 +693,29 @@ pub fn gadget_fusion_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
         .find(|&n| g.degree(n) == 1)
         .expect("v1 isn't a gadget");
     g.add_to_phase(gphase0, g.phase(gphase1));
-    g.remove_vertex(v1);
-    g.remove_vertex(gphase1);
+    remove_interior(g, v1);
+    remove_interior(g, gphase1);
 
-    let d = g.degree(v0) as i32;
-    g.scalar_mut().mul_sqrt2_pow(2 - d);
+    if g.track_scalar() {
+        let d = g.degree(v0) as i32;
+        g.scalar_mut().mul_sqrt2_pow(2 - d);
+    }
 }
 
 checked_rule2!(check_gadget_fusion, gadget_fusion_unchecked, gadget_fusion);
 
 pub fn check_remove_single(g: &impl GraphLike, v: V) -> bool {
     let t = g.vertex_type(v);
-    g.neighbors(v).len() == 0 && (t == VType::Z || t == VType::X)
+    g.degree(v) == 0 && (t == VType::Z || t == VType::X) && !near_ground(g, &[v])
 }
 
 /// Remove an isolated Z or X vertex and add it as a global scalar
 pub fn remove_single_unchecked(g: &mut impl GraphLike, v: V) {
-    let p = g.phase(v);
-    *g.scalar_mut() *= ScalarN::one_plus_phase(p);
-    g.remove_vertex(v);
+    if g.track_scalar() {
+        let p = g.phase(v);
+        *g.scalar_mut() *= ScalarN::one_plus_phase(p);
+    }
+    remove_interior(g, v);
 }
 
 checked_rule1!(check_remove_single, remove_single_unchecked, remove_single);
@@ -561,39 +724,336 @@ pub fn check_remove_pair(g: &impl GraphLike, v0: V, v1: V) -> bool {
     let t0 = g.vertex_type(v0);
     let t1 = g.vertex_type(v1);
 
-    g.neighbors(v0).len() == 1
-        && g.neighbors(v1).len() == 1
+    g.degree(v0) == 1
+        && g.degree(v1) == 1
         && (t0 == VType::Z || t0 == VType::X)
         && (t1 == VType::Z || t1 == VType::X)
         && g.connected(v0, v1)
+        && !near_ground(g, &[v0, v1])
 }
 
 /// Remove an isolated Z or X vertex and add it as a global scalar
 pub fn remove_pair_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    if g.track_scalar() {
+        let t0 = g.vertex_type(v0);
+        let t1 = g.vertex_type(v1);
+        let et = g.edge_type(v0, v1);
+        let p0 = g.phase(v0);
+        let p1 = g.phase(v1);
+
+        // same color
+        if (t0 == t1 && et == EType::N) || (t0 != t1 && et == EType::H) {
+            *g.scalar_mut() *= ScalarN::one_plus_phase(p0 + p1);
+        // different colors
+        } else {
+            let p2 = Phase::one() + p0 + p1;
+            *g.scalar_mut() *= ScalarN::one()
+                + ScalarN::from_phase(p0)
+                + ScalarN::from_phase(p1)
+                + ScalarN::from_phase(p2);
+            g.scalar_mut().mul_sqrt2_pow(-1);
+        }
+    }
+
+    remove_interior(g, v0);
+    remove_interior(g, v1);
+}
+
+checked_rule2!(check_remove_pair, remove_pair_unchecked, remove_pair);
+
+/// Check [bialgebra_unchecked] applies
+///
+/// The vertices must be phase-free spiders of opposite colours, connected
+/// by a normal edge.
+pub fn check_bialgebra(g: &impl GraphLike, v0: V, v1: V) -> bool {
     let t0 = g.vertex_type(v0);
     let t1 = g.vertex_type(v1);
-    let et = g.edge_type(v0, v1);
+    ((t0 == VType::Z && t1 == VType::X) || (t0 == VType::X && t1 == VType::Z))
+        && g.edge_type_opt(v0, v1) == Some(EType::N)
+        && g.phase(v0).is_zero()
+        && g.phase(v1).is_zero()
+        && !near_ground(g, &[v0, v1])
+}
+
+/// Apply the bialgebra rule
+///
+/// This is the strong complementarity rule for a single Z and a single X
+/// spider. See [bialgebra_bipartite] for the other direction. Both spiders
+/// are removed. Each other edge of `v0` gets a new spider of the colour of
+/// `v1`, each other edge of `v1` gets a new spider of the colour of `v0`, and
+/// the new spiders are connected in a complete bipartite graph.
+pub fn bialgebra_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    let mut new_vs = [vec![], vec![]];
+    for (i, (v, w)) in [(v0, v1), (v1, v0)].into_iter().enumerate() {
+        let vd = g.vertex_data(v);
+        let ty = g.vertex_type(w);
+        for (n, et) in g.incident_edge_vec(v) {
+            if n != w {
+                let x = g.add_vertex_with_data(VData {
+                    ty,
+                    phase: Phase::zero(),
+                    qubit: vd.qubit,
+                    row: vd.row,
+                    ground: false,
+                });
+                g.add_edge_with_type(x, n, et);
+                new_vs[i].push(x);
+            }
+        }
+    }
+
+    for &x0 in &new_vs[0] {
+        for &x1 in &new_vs[1] {
+            g.add_edge(x0, x1);
+        }
+    }
+
+    remove_interior(g, v0);
+    remove_interior(g, v1);
+
+    if g.track_scalar() {
+        let m = new_vs[0].len() as i32;
+        let n = new_vs[1].len() as i32;
+        g.scalar_mut().mul_sqrt2_pow((m - 1) * (n - 1));
+    }
+}
+
+checked_rule2!(check_bialgebra, bialgebra_unchecked, bialgebra);
+
+/// Check [bialgebra_bipartite_unchecked] applies
+///
+/// The vertices `zs` must be phase-free Z spiders and `xs` phase-free X
+/// spiders, at least one of each, forming a complete bipartite graph of
+/// normal edges. Each of them must have exactly one other edge, and these
+/// edges must go to distinct vertices outside of `zs` and `xs`.
+pub fn check_bialgebra_bipartite(g: &impl GraphLike, zs: &[V], xs: &[V]) -> bool {
+    if zs.is_empty() || xs.is_empty() {
+        return false;
+    }
+
+    let vs: FxHashSet<V> = zs.iter().chain(xs.iter()).copied().collect();
+    if vs.len() != zs.len() + xs.len() {
+        return false;
+    }
+
+    let mut outside = FxHashSet::default();
+    for (ws, ty, others) in [(zs, VType::Z, xs), (xs, VType::X, zs)] {
+        for &w in ws {
+            if g.vertex_type(w) != ty
+                || !g.phase(w).is_zero()
+                || g.degree(w) != others.len() + 1
+                || others
+                    .iter()
+                    .any(|&o| g.edge_type_opt(w, o) != Some(EType::N))
+            {
+                return false;
+            }
+
+            // by the degree check, exactly one neighbour is not in `others`
+            let n = g.neighbors(w).find(|n| !others.contains(n)).unwrap();
+            if vs.contains(&n) || !outside.insert(n) {
+                return false;
+            }
+        }
+    }
+
+    !near_ground(g, zs) && !near_ground(g, xs)
+}
+
+/// Apply the bialgebra rule right-to-left
+///
+/// This is the inverse of [bialgebra]: the complete bipartite graph between
+/// `zs` and `xs` is replaced by a single X spider, connected to the outside
+/// neighbours of `zs`, and a single Z spider, connected to the outside
+/// neighbours of `xs`, joined by a normal edge. The types of the outside
+/// edges are preserved, and the scalar is divided by √2^((m-1)(n-1)), where
+/// m and n are the sizes of `zs` and `xs`.
+pub fn bialgebra_bipartite_unchecked(g: &mut impl GraphLike, zs: &[V], xs: &[V]) {
+    let mut new_vs = [0, 0];
+    for (i, (ws, others, ty)) in [(zs, xs, VType::X), (xs, zs, VType::Z)]
+        .into_iter()
+        .enumerate()
+    {
+        let vd = g.vertex_data(ws[0]);
+        let x = g.add_vertex_with_data(VData {
+            ty,
+            phase: Phase::zero(),
+            qubit: vd.qubit,
+            row: vd.row,
+            ground: false,
+        });
+        for &w in ws {
+            for (n, et) in g.incident_edge_vec(w) {
+                if !others.contains(&n) {
+                    g.add_edge_with_type(x, n, et);
+                }
+            }
+        }
+        new_vs[i] = x;
+    }
+
+    g.add_edge(new_vs[0], new_vs[1]);
+
+    for &w in zs.iter().chain(xs.iter()) {
+        remove_interior(g, w);
+    }
+
+    if g.track_scalar() {
+        let m = zs.len() as i32;
+        let n = xs.len() as i32;
+        g.scalar_mut().mul_sqrt2_pow(-(m - 1) * (n - 1));
+    }
+}
+
+/// A checked implementation of [bialgebra_bipartite_unchecked]
+pub fn bialgebra_bipartite(g: &mut impl GraphLike, zs: &[V], xs: &[V]) -> bool {
+    if check_bialgebra_bipartite(g, zs, xs) {
+        bialgebra_bipartite_unchecked(g, zs, xs);
+        true
+    } else {
+        false
+    }
+}
+
+/// Check [supplementarity_unchecked] applies
+///
+/// Both vertices must be Z spiders, surrounded by H-edges connected to other Z
+/// spiders, and have the same neighbours (apart from each other). If they are
+/// not connected, their phases must be α and α+π, or α and π-α. If they are
+/// connected, their phases must be α and α+π, or α and -α.
+pub fn check_supplementarity(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    if v0 == v1
+        || g.vertex_type(v0) != VType::Z
+        || g.vertex_type(v1) != VType::Z
+        || !g
+            .incident_edges(v0)
+            .chain(g.incident_edges(v1))
+            .all(|(w, et)| g.vertex_type(w) == VType::Z && et == EType::H)
+    {
+        return false;
+    }
+
+    let nhd0: FxHashSet<V> = g.neighbors(v0).filter(|&w| w != v1).collect();
+    let nhd1: FxHashSet<V> = g.neighbors(v1).filter(|&w| w != v0).collect();
+    if nhd0 != nhd1 {
+        return false;
+    }
+
     let p0 = g.phase(v0);
     let p1 = g.phase(v1);
-
-    // same color
-    if (t0 == t1 && et == EType::N) || (t0 != t1 && et == EType::H) {
-        *g.scalar_mut() *= ScalarN::one_plus_phase(p0 + p1);
-    // different colors
+    let phases = if g.connected(v0, v1) {
+        p1 == p0 + Phase::one() || p1 == -p0
     } else {
-        let p2 = Phase::one() + p0 + p1;
-        *g.scalar_mut() *= ScalarN::one()
-            + ScalarN::from_phase(p0)
-            + ScalarN::from_phase(p1)
-            + ScalarN::from_phase(p2);
-        g.scalar_mut().mul_sqrt2_pow(-1);
+        p1 == p0 + Phase::one() || p1 == Phase::one() - p0
+    };
+    phases && !near_ground(g, &[v0, v1])
+}
+
+/// Apply the supplementarity rule
+///
+/// Removes both spiders, multiplying the scalar by the resulting factor. In
+/// the cases where the phases are α and -α (connected) or α and π-α (not
+/// connected), this also adds π to each neighbour.
+pub fn supplementarity_unchecked(g: &mut impl GraphLike, v0: V, v1: V) {
+    let p0 = g.phase(v0);
+    let p1 = g.phase(v1);
+    let connected = g.connected(v0, v1);
+    let nhd: Vec<V> = g.neighbors(v0).filter(|&w| w != v1).collect();
+    let n = nhd.len() as i32;
+
+    // summing over the values of v0 and v1 gives a scalar, possibly times a
+    // pi phase on the parity of the neighbours
+    if g.track_scalar() {
+        if connected {
+            g.scalar_mut().mul_sqrt2_pow(-2 * n - 1);
+            *g.scalar_mut() *= ScalarN::one_plus_phase(p0 * 2);
+        } else {
+            g.scalar_mut().mul_sqrt2_pow(-2 * n);
+            *g.scalar_mut() *= ScalarN::one_plus_phase(p0 * 2 + Phase::one());
+        }
     }
 
-    g.remove_vertex(v0);
-    g.remove_vertex(v1);
+    if p1 != p0 + Phase::one() {
+        if g.track_scalar() {
+            g.scalar_mut().mul_phase(p1);
+        }
+        for w in nhd {
+            g.add_to_phase(w, 1);
+        }
+    }
+
+    remove_interior(g, v0);
+    remove_interior(g, v1);
 }
 
-checked_rule2!(check_remove_pair, remove_pair_unchecked, remove_pair);
+checked_rule2!(
+    check_supplementarity,
+    supplementarity_unchecked,
+    supplementarity
+);
+
+/// Check [copy_unchecked] applies
+///
+/// The first vertex must be a Z or X spider of arity 1 with phase 0 or π. The
+/// second must be its neighbour, a spider of the opposite colour connected by a
+/// normal edge, or of the same colour connected by a Hadamard edge.
+pub fn check_copy(g: &impl GraphLike, v: V, w: V) -> bool {
+    let vt = g.vertex_type(v);
+    let wt = g.vertex_type(w);
+    if (vt != VType::Z && vt != VType::X)
+        || (wt != VType::Z && wt != VType::X)
+        || g.degree(v) != 1
+        || !g.phase(v).is_pauli()
+        || g.incident_edges(w).any(|(_, et)| et == EType::Wio)
+    {
+        return false;
+    }
+
+    let applies = match g.edge_type_opt(v, w) {
+        Some(EType::N) => vt != wt,
+        Some(EType::H) => vt == wt,
+        _ => false,
+    };
+    applies && !near_ground(g, &[v, w])
+}
+
+/// Copy a Pauli state through a spider
+///
+/// Both vertices are removed, and a copy of the state is connected to each of
+/// the other neighbours of `w`. If the state has phase π, the phase of `w`
+/// goes into the scalar.
+pub fn copy_unchecked(g: &mut impl GraphLike, v: V, w: V) {
+    let vt = g.vertex_type(v);
+    let p = g.phase(v);
+    let flip = g.edge_type(v, w) == EType::H;
+    let wd = g.vertex_data(w);
+
+    if g.track_scalar() {
+        let d = g.degree(w) as i32 - 1;
+        g.scalar_mut().mul_sqrt2_pow(1 - d);
+        if p.is_one() {
+            g.scalar_mut().mul_phase(wd.phase);
+        }
+    }
+
+    for (n, et) in g.incident_edge_vec(w) {
+        if n != v {
+            let x = g.add_vertex_with_data(VData {
+                ty: vt,
+                phase: p,
+                qubit: wd.qubit,
+                row: wd.row,
+                ground: false,
+            });
+            g.add_edge_with_type(x, n, if flip { et.opposite() } else { et });
+        }
+    }
+
+    remove_interior(g, v);
+    remove_interior(g, w);
+}
+
+checked_rule2!(check_copy, copy_unchecked, copy);
 
 // Tests {{{
 
@@ -699,6 +1159,45 @@ mod tests {
         assert_eq!(g.phase(vs[2]), Rational64::new(3, 4).into());
     }
 
+    #[test]
+    fn local_comp_8_exact() {
+        let mut g = Graph::new();
+        let bs: Vec<_> = (0..3).map(|_| g.add_vertex(VType::Z)).collect();
+        let outs: Vec<_> = bs
+            .iter()
+            .map(|&b| {
+                let o = g.add_vertex(VType::B);
+                g.add_edge(b, o);
+                o
+            })
+            .collect();
+        g.set_outputs(outs);
+        let vs: Vec<_> = (0..8)
+            .map(|_| {
+                let v = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 2));
+                for &b in &bs {
+                    g.add_edge_with_type(v, b, EType::H);
+                }
+                v
+            })
+            .collect();
+
+        let h = g.clone();
+        for &v in &vs {
+            assert!(local_comp(&mut g, v));
+        }
+
+        // each step contributes sqrt(2) * e^(i pi/4), so the phases cancel exactly.
+        // Every other step also cancels 3 pairs of parallel H-edges, each giving 1/2.
+        assert!(g.scalar().is_exact());
+        assert_eq!(*g.scalar(), ScalarN::sqrt2_pow(8 - 4 * 3 * 2));
+        for &b in &bs {
+            assert_eq!(g.phase(b), Phase::zero());
+        }
+        assert_eq!(g.num_edges(), 3);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
     #[test]
     fn local_comp_1() {
         let mut g = Graph::new();
@@ -842,6 +1341,42 @@ mod tests {
         assert_eq!(g.phase(6), Rational64::new(1, 1).into());
     }
 
+    #[test]
+    fn pivot_shared_neighbor() {
+        let mut g = Graph::new();
+
+        for _ in 0..7 {
+            g.add_vertex(VType::Z);
+        }
+        g.set_phase(2, Rational64::new(1, 4));
+        g.set_phase(4, Rational64::new(1, 1));
+        for i in 0..3 {
+            g.add_edge_with_type(i, 3, EType::H);
+        }
+        g.add_edge_with_type(3, 4, EType::H);
+        for i in [2, 5, 6] {
+            g.add_edge_with_type(4, i, EType::H);
+        }
+        let outputs: Vec<_> = [0, 1, 2, 5, 6]
+            .iter()
+            .map(|&i| {
+                let b = g.add_vertex(VType::B);
+                g.add_edge(i, b);
+                b
+            })
+            .collect();
+        g.set_outputs(outputs);
+
+        // vertex 2 gets an H self-loop from the complete bipartite graph, which
+        // is removed by add_edge_smart
+        let mut h = g.clone();
+        assert!(pivot(&mut h, 3, 4));
+        assert_eq!(h.edge_type_opt(2, 2), None);
+        assert_eq!(h.degree(2), 5);
+        assert_eq!(h.phase(2), Rational64::new(1, 4).into());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
     #[test]
     fn get_pivot_1() {
         let mut g = Graph::new();
@@ -1026,6 +1561,431 @@ mod tests {
         pi_copy(&mut g, vs[4]);
         assert_eq!(g.to_tensor4(), h.to_tensor4());
     }
+
+    #[test]
+    fn supplementarity_simple() {
+        let cases = [
+            (false, Rational64::new(1, 4), Rational64::new(-3, 4)),
+            (false, Rational64::new(1, 4), Rational64::new(3, 4)),
+            (true, Rational64::new(1, 4), Rational64::new(-3, 4)),
+            (true, Rational64::new(3, 4), Rational64::new(-3, 4)),
+            (false, Rational64::new(1, 2), Rational64::new(1, 2)),
+            (true, Rational64::new(1, 2), Rational64::new(-1, 2)),
+        ];
+        for n in 0..4 {
+            for (connected, p0, p1) in cases {
+                let mut g = Graph::new();
+                let v0 = g.add_vertex_with_phase(VType::Z, p0);
+                let v1 = g.add_vertex_with_phase(VType::Z, p1);
+                if connected {
+                    g.add_edge_with_type(v0, v1, EType::H);
+                }
+                let mut outs = vec![];
+                for _ in 0..n {
+                    let w = g.add_vertex_with_phase(VType::Z, (1, 4));
+                    g.add_edge_with_type(v0, w, EType::H);
+                    g.add_edge_with_type(v1, w, EType::H);
+                    let b = g.add_vertex(VType::B);
+                    g.add_edge(w, b);
+                    outs.push(b);
+                }
+                g.set_outputs(outs);
+
+                let h = g.clone();
+                assert!(supplementarity(&mut g, v0, v1));
+                assert_eq!(g.num_vertices(), h.num_vertices() - 2);
+                assert_eq!(
+                    g.to_tensor4(),
+                    h.to_tensor4(),
+                    "failed for n={}, connected={}, phases {} and {}",
+                    n,
+                    connected,
+                    p0,
+                    p1
+                );
+            }
+        }
+
+        // wrong phases, wrong neighbourhoods
+        let mut g = Graph::new();
+        let v0 = g.add_vertex_with_phase(VType::Z, (1, 8));
+        let v1 = g.add_vertex_with_phase(VType::Z, (1, 8));
+        let v2 = g.add_vertex_with_phase(VType::Z, (9, 8));
+        let w = g.add_vertex(VType::Z);
+        g.add_edge_with_type(v0, w, EType::H);
+        g.add_edge_with_type(v1, w, EType::H);
+        assert!(!check_supplementarity(&g, v0, v1));
+        assert!(!check_supplementarity(&g, v0, v2));
+        g.add_edge_with_type(v2, w, EType::H);
+        assert!(check_supplementarity(&g, v0, v2));
+    }
+
+    #[test]
+    fn copy_simple() {
+        for vt in [VType::Z, VType::X] {
+            for et in [EType::N, EType::H] {
+                for p in [0, 1] {
+                    for d in 0..4 {
+                        let wt = if et == EType::N {
+                            if vt == VType::Z {
+                                VType::X
+                            } else {
+                                VType::Z
+                            }
+                        } else {
+                            vt
+                        };
+                        let mut g = Graph::new();
+                        let v = g.add_vertex_with_phase(vt, p);
+                        let w = g.add_vertex_with_phase(wt, (3, 4));
+                        g.add_edge_with_type(v, w, et);
+                        let outs: Vec<_> = (0..d)
+                            .map(|i| {
+                                let b = g.add_vertex(VType::B);
+                                let et1 = if i == 0 { EType::H } else { EType::N };
+                                g.add_edge_with_type(w, b, et1);
+                                b
+                            })
+                            .collect();
+                        g.set_outputs(outs);
+
+                        let h = g.clone();
+                        assert!(copy(&mut g, v, w));
+                        assert_eq!(g.num_vertices(), 2 * d);
+                        assert_eq!(
+                            g.to_tensor4(),
+                            h.to_tensor4(),
+                            "failed for {:?} {:?} phase {} arity {}",
+                            vt,
+                            et,
+                            p,
+                            d
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut g = Graph::new();
+        let v = g.add_vertex_with_phase(VType::Z, (1, 2));
+        let w = g.add_vertex(VType::X);
+        let u = g.add_vertex(VType::Z);
+        g.add_edge(v, w);
+        g.add_edge(w, u);
+        assert!(!check_copy(&g, v, w));
+        assert!(check_copy(&g, u, w));
+        g.set_phase(v, 1);
+        assert!(check_copy(&g, v, w));
+        assert!(!check_copy(&g, w, v));
+        g.set_vertex_type(u, VType::X);
+        assert!(!check_copy(&g, u, w));
+    }
+
+    #[test]
+    fn bialgebra_simple() {
+        for m in 1..4 {
+            for n in 1..4 {
+                let mut g = Graph::new();
+                let z = g.add_vertex(VType::Z);
+                let x = g.add_vertex(VType::X);
+                g.add_edge(z, x);
+                let ins: Vec<_> = (0..m)
+                    .map(|i| {
+                        let b = g.add_vertex(VType::B);
+                        // mix in some Hadamard edges
+                        let et = if i == 0 { EType::H } else { EType::N };
+                        g.add_edge_with_type(b, z, et);
+                        b
+                    })
+                    .collect();
+                let outs: Vec<_> = (0..n)
+                    .map(|_| {
+                        let b = g.add_vertex(VType::B);
+                        g.add_edge(x, b);
+                        b
+                    })
+                    .collect();
+                g.set_inputs(ins);
+                g.set_outputs(outs);
+
+                assert!(check_bialgebra(&g, z, x));
+                assert!(check_bialgebra(&g, x, z));
+                let h = g.clone();
+                assert!(bialgebra(&mut g, z, x));
+                assert_eq!(g.num_vertices(), 2 * (m + n));
+                assert_eq!(g.num_edges(), m * n + m + n);
+                assert_eq!(
+                    g.to_tensor4(),
+                    h.to_tensor4(),
+                    "failed for m={}, n={}",
+                    m,
+                    n
+                );
+            }
+        }
+
+        let mut g = Graph::new();
+        let z0 = g.add_vertex(VType::Z);
+        let z1 = g.add_vertex(VType::Z);
+        let x = g.add_vertex_with_phase(VType::X, 1);
+        g.add_edge(z0, z1);
+        g.add_edge(z0, x);
+        assert!(!check_bialgebra(&g, z0, z1));
+        assert!(!check_bialgebra(&g, z0, x));
+    }
+
+    #[test]
+    fn remove_id_colours() {
+        for ty in [VType::Z, VType::X] {
+            for (et0, et1) in [
+                (EType::N, EType::N),
+                (EType::N, EType::H),
+                (EType::H, EType::N),
+                (EType::H, EType::H),
+            ] {
+                let mut g = Graph::new();
+                let b0 = g.add_vertex(VType::B);
+                let w0 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+                let v = g.add_vertex(ty);
+                let w1 = g.add_vertex_with_phase(VType::X, Rational64::new(1, 2));
+                let b1 = g.add_vertex(VType::B);
+                g.add_edge(b0, w0);
+                g.add_edge_with_type(w0, v, et0);
+                g.add_edge_with_type(v, w1, et1);
+                g.add_edge(w1, b1);
+                g.set_inputs(vec![b0]);
+                g.set_outputs(vec![b1]);
+
+                let h = g.clone();
+                assert!(remove_id(&mut g, v));
+                assert!(!g.contains_vertex(v));
+                let et = if et0 == et1 { EType::N } else { EType::H };
+                assert_eq!(g.edge_type(w0, w1), et);
+                assert_eq!(g.to_tensor4(), h.to_tensor4());
+            }
+        }
+    }
+
+    #[test]
+    fn remove_id_self_loop() {
+        for ty in [VType::Z, VType::X] {
+            for (et, s) in [
+                (EType::N, ScalarN::from_int_coeffs(&[2])),
+                (EType::H, ScalarN::zero()),
+            ] {
+                let mut g = Graph::new();
+                let v = g.add_vertex(ty);
+                g.add_edge_with_type(v, v, et);
+                assert_eq!(g.degree(v), 2);
+
+                assert!(remove_id(&mut g, v));
+                assert_eq!(g.num_vertices(), 0);
+                assert_eq!(g.num_edges(), 0);
+                assert_eq!(*g.scalar(), s, "failed for {:?}, {:?}", ty, et);
+            }
+        }
+
+        // the loop is multiplied into the existing scalar, and the rest of
+        // the graph is left alone
+        let mut g = Graph::new();
+        let b = g.add_vertex(VType::B);
+        let v = g.add_vertex(VType::Z);
+        let w = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        g.add_edge(b, w);
+        g.add_edge_with_type(v, v, EType::N);
+        *g.scalar_mut() = ScalarN::from_phase(Rational64::new(1, 4));
+        g.set_outputs(vec![b]);
+        let s = g.scalar().clone();
+        assert!(remove_id(&mut g, v));
+        assert_eq!(*g.scalar(), s * ScalarN::from_int_coeffs(&[2]));
+        assert_eq!(g.num_vertices(), 2);
+    }
+
+    fn random_graph_like(seed: u64) -> Graph {
+        use crate::simplify::interior_clifford_simp;
+        use crate::simplify::test_util::random_graph;
+        let mut g = random_graph(seed, 3, 30, 0.3);
+        interior_clifford_simp(&mut g);
+        g
+    }
+
+    #[test]
+    fn local_comp_at_random() {
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for seed in 0..5 {
+            let g = random_graph_like(seed);
+            let t = g.to_tensor4();
+            let zs: Vec<_> = g
+                .vertices()
+                .filter(|&v| g.vertex_type(v) == VType::Z)
+                .collect();
+            for &v in zs.choose_multiple(&mut rng, 5) {
+                let mut h = g.clone();
+                assert_eq!(local_comp_at(&mut h, v), Ok(()));
+                assert!(!h.contains_vertex(v));
+                assert_eq!(h.to_tensor4(), t, "seed {}, vertex {}", seed, v);
+            }
+        }
+    }
+
+    #[test]
+    fn pivot_at_random() {
+        use rand::rngs::StdRng;
+        use rand::seq::SliceRandom;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for seed in 0..5 {
+            let g = random_graph_like(seed);
+            let t = g.to_tensor4();
+            let es: Vec<_> = g
+                .edges()
+                .filter(|&(s, t, _)| g.vertex_type(s) == VType::Z && g.vertex_type(t) == VType::Z)
+                .collect();
+            for &(v0, v1, _) in es.choose_multiple(&mut rng, 5) {
+                let mut h = g.clone();
+                assert_eq!(pivot_at(&mut h, v0, v1), Ok(()));
+                assert_eq!(h.to_tensor4(), t, "seed {}, edge {}-{}", seed, v0, v1);
+            }
+        }
+    }
+
+    #[test]
+    fn rewrite_at_errors() {
+        let mut g = random_graph_like(0);
+        let h = g.clone();
+        let b = g.inputs()[0];
+        let v = g.neighbors(b).next().unwrap();
+        let w = g
+            .vertices()
+            .find(|&w| g.vertex_type(w) == VType::Z && w != v && !g.connected(v, w))
+            .unwrap();
+
+        assert_eq!(
+            local_comp_at(&mut g, b),
+            Err(RuleError::NotApplicable {
+                rule: Rule::GenLocalComp,
+                vs: vec![b]
+            })
+        );
+        assert_eq!(
+            pivot_at(&mut g, v, w),
+            Err(RuleError::NotApplicable {
+                rule: Rule::GenPivot,
+                vs: vec![v, w]
+            })
+        );
+        assert!(pivot_at(&mut g, v, v).is_err());
+        assert!(pivot_at(&mut g, v, b).is_err());
+
+        let u = g.add_vertex(VType::Z);
+        g.remove_vertex(u);
+        assert_eq!(
+            local_comp_at(&mut g, u),
+            Err(RuleError::NoSuchVertex { v: u })
+        );
+        assert_eq!(
+            pivot_at(&mut g, v, u),
+            Err(RuleError::NoSuchVertex { v: u })
+        );
+        assert_eq!(g, h);
+    }
+
+    #[test]
+    fn bialgebra_bipartite_sizes() {
+        for m in 1..4 {
+            for n in 1..4 {
+                let mut g = Graph::new();
+                let zs: Vec<_> = (0..m).map(|_| g.add_vertex(VType::Z)).collect();
+                let xs: Vec<_> = (0..n).map(|_| g.add_vertex(VType::X)).collect();
+                for &z in &zs {
+                    for &x in &xs {
+                        g.add_edge(z, x);
+                    }
+                }
+                let ins: Vec<_> = zs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &z)| {
+                        let b = g.add_vertex(VType::B);
+                        // mix in some Hadamard edges
+                        let et = if i == 0 { EType::H } else { EType::N };
+                        g.add_edge_with_type(b, z, et);
+                        b
+                    })
+                    .collect();
+                let outs: Vec<_> = xs
+                    .iter()
+                    .map(|&x| {
+                        let b = g.add_vertex(VType::B);
+                        g.add_edge(x, b);
+                        b
+                    })
+                    .collect();
+                g.set_inputs(ins);
+                g.set_outputs(outs);
+
+                assert!(check_bialgebra_bipartite(&g, &zs, &xs));
+                assert!(!check_bialgebra_bipartite(&g, &xs, &zs));
+                let h = g.clone();
+                assert!(bialgebra_bipartite(&mut g, &zs, &xs));
+                assert_eq!(g.num_vertices(), m + n + 2);
+                assert_eq!(g.num_edges(), m + n + 1);
+                assert_eq!(
+                    g.to_tensor4(),
+                    h.to_tensor4(),
+                    "failed for m={}, n={}",
+                    m,
+                    n
+                );
+
+                // going back with the single-spider rule restores the shape
+                let z = g.vertices().find(|&v| g.vertex_type(v) == VType::Z);
+                let x = g.vertices().find(|&v| g.vertex_type(v) == VType::X);
+                assert!(bialgebra(&mut g, z.unwrap(), x.unwrap()));
+                assert_eq!(g.num_vertices(), h.num_vertices());
+                assert_eq!(g.num_edges(), h.num_edges());
+                assert_eq!(g.to_tensor4(), h.to_tensor4());
+            }
+        }
+    }
+
+    #[test]
+    fn bialgebra_bipartite_check() {
+        let mut g = Graph::new();
+        let zs = [g.add_vertex(VType::Z), g.add_vertex(VType::Z)];
+        let xs = [g.add_vertex(VType::X), g.add_vertex(VType::X)];
+        for &z in &zs {
+            for &x in &xs {
+                g.add_edge(z, x);
+            }
+        }
+        let bs: Vec<_> = (0..4).map(|_| g.add_vertex(VType::B)).collect();
+        for (&v, &b) in zs.iter().chain(xs.iter()).zip(bs.iter()) {
+            g.add_edge(v, b);
+        }
+        assert!(check_bialgebra_bipartite(&g, &zs, &xs));
+        assert!(!check_bialgebra_bipartite(&g, &zs, &[]));
+        assert!(!check_bialgebra_bipartite(&g, &zs, &xs[..1]));
+        assert!(!check_bialgebra_bipartite(&g, &[zs[0], zs[0]], &xs));
+
+        g.set_phase(xs[1], Rational64::new(1, 2));
+        assert!(!check_bialgebra_bipartite(&g, &zs, &xs));
+        g.set_phase(xs[1], 0);
+
+        g.set_edge_type(zs[0], xs[1], EType::H);
+        assert!(!check_bialgebra_bipartite(&g, &zs, &xs));
+        g.set_edge_type(zs[0], xs[1], EType::N);
+
+        // two legs going to the same outside vertex
+        g.remove_vertex(bs[1]);
+        g.add_edge(zs[1], bs[0]);
+        assert!(!check_bialgebra_bipartite(&g, &zs, &xs));
+    }
 }
 
 // }}}
@@ -18,17 +18,56 @@ use crate::gate::*;
 use crate::graph::*;
 use crate::linalg::RowOps;
 use crate::phase::Phase;
-use num::{Rational64, Zero};
+use crate::scalar::*;
+use derive_more::{Display, Error};
+use num::{One, Rational64, Zero};
 use openqasm::{ast::Symbol, translate::Value, GenericError, ProgramVisitor};
 use std::collections::VecDeque;
 use std::fmt;
 use std::str;
 
+/// An error from [`Circuit::t_count_with`]
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TCountError {
+    /// A rotation is not by a multiple of π/4, and the cost was
+    /// [`RotationCost::Error`].
+    #[display("Non-Clifford+T rotation by {phase} in {gate:?} gate")]
+    NonCliffordT { gate: GType, phase: Phase },
+}
+
+/// An error from [`Circuit::try_to_graph`]
+#[derive(Debug, Display, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ToGraphError {
+    /// The circuit has gates with no ZX diagram, e.g. opaque gates, given as
+    /// QASM.
+    #[display("Circuit contains unsupported gates: {}", gates.join("; "))]
+    UnsupportedGates { gates: Vec<String> },
+}
+
+/// How to count non-Clifford rotations by angles other than odd multiples of
+/// π/4 in [`Circuit::t_count_with`]
+#[derive(Clone, Copy)]
+pub enum RotationCost<'a> {
+    /// Return an error if such a rotation is found
+    Error,
+    /// Count each such rotation as a single T gate
+    One,
+    /// Estimate the number of T gates using the given synthesis cost function
+    Synth(&'a dyn Fn(Phase) -> usize),
+}
+
 /// A type for quantum circuits
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Circuit {
     nqubits: usize,
     pub gates: VecDeque<Gate>,
+    /// A global scalar factor, which is 1 unless set by e.g. [Circuit::push_paulis_to_end]
+    ///
+    /// This is included when the circuit is converted to a graph or tensor, but
+    /// not written to QASM.
+    pub scalar: ScalarN,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -40,20 +79,24 @@ pub struct CircuitStats {
     pub moreq: usize,
     pub cliff: usize,
     pub non_cliff: usize,
+    pub tcount: usize,
+    pub depth: usize,
 }
 
 impl CircuitStats {
     pub fn make(c: &Circuit) -> Self {
         let mut s = CircuitStats {
             qubits: c.num_qubits(),
-            total: c.num_gates(),
+            total: c.num_gates() - c.num_gates_of_type(Barrier),
             oneq: 0,
             twoq: 0,
             moreq: 0,
             cliff: 0,
             non_cliff: 0,
+            tcount: 0,
+            depth: c.depth(),
         };
-        for g in &c.gates {
+        for g in c.gates.iter().filter(|g| g.t != Barrier) {
             match g.qs.len() {
                 1 => {
                     s.oneq += 1;
@@ -70,22 +113,28 @@ impl CircuitStats {
                 NOT | Z | S | Sdg | CNOT | CZ | SWAP | HAD => {
                     s.cliff += 1;
                 }
-                ZPhase | XPhase => {
-                    if g.phase.is_clifford() {
-                        s.cliff += 1;
-                    } else {
-                        s.non_cliff += 1;
-                    }
+                ZPhase | XPhase | ParityPhase if g.phase.is_clifford() => {
+                    s.cliff += 1;
                 }
                 _ => {
                     s.non_cliff += 1;
                 }
             }
+
+            match g.t {
+                T | Tdg => {
+                    s.tcount += 1;
+                }
+                ZPhase | XPhase | ParityPhase if g.phase.is_t() => {
+                    s.tcount += 1;
+                }
+                _ => {}
+            }
         }
         s
     }
 
-    pub fn into_array(self) -> [usize; 7] {
+    pub fn into_array(self) -> [usize; 9] {
         [
             self.qubits,
             self.total,
@@ -94,13 +143,15 @@ impl CircuitStats {
             self.moreq,
             self.cliff,
             self.non_cliff,
+            self.tcount,
+            self.depth,
         ]
     }
 }
 
 impl fmt::Display for CircuitStats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Circuit with {} qubits, {} gates\n  1-qubit: {}\n  2-qubit: {}\n  n-qubit: {}\n  clifford: {}\n  non-clifford: {}", self.qubits, self.total, self.oneq, self.twoq, self.moreq, self.cliff, self.non_cliff)
+        write!(f, "Circuit with {} qubits, {} gates\n  1-qubit: {}\n  2-qubit: {}\n  n-qubit: {}\n  clifford: {}\n  non-clifford: {}\n  T-count: {}\n  depth: {}", self.qubits, self.total, self.oneq, self.twoq, self.moreq, self.cliff, self.non_cliff, self.tcount, self.depth)
     }
 }
 
@@ -109,6 +160,7 @@ impl Circuit {
         Circuit {
             gates: VecDeque::new(),
             nqubits,
+            scalar: ScalarN::one(),
         }
     }
 
@@ -143,11 +195,10 @@ impl Circuit {
     }
 
     pub fn add_gate_with_phase(&mut self, name: &str, qs: Vec<usize>, phase: impl Into<Phase>) {
-        self.push(Gate {
-            t: GType::from_qasm_name(name),
-            qs,
-            phase: phase.into(),
-        });
+        let mut g = Gate::from_qasm_name(name);
+        g.qs = qs;
+        g.phase = phase.into();
+        self.push(g);
     }
 
     pub fn add_gate(&mut self, name: &str, qs: Vec<usize>) {
@@ -163,6 +214,7 @@ impl Circuit {
         for g in &mut self.gates {
             g.adjoint();
         }
+        self.scalar = self.scalar.conj();
     }
 
     pub fn to_adjoint(&self) -> Circuit {
@@ -238,6 +290,7 @@ impl Circuit {
         let mut c = Circuit {
             gates: VecDeque::with_capacity(sz),
             nqubits: self.nqubits,
+            scalar: self.scalar.clone(),
         };
         for g in &self.gates {
             g.push_basic_gates(&mut c);
@@ -246,7 +299,34 @@ impl Circuit {
         c
     }
 
+    /// Lower the circuit into a ZX diagram
+    ///
+    /// The i-th input and output of the resulting graph correspond to qubit i. If
+    /// `postselect` is true, CCZ and Toffoli gates are added using a postselected
+    /// gadget with fewer T-like phases. Qubits that are postselected with `post_sel`
+    /// get no output.
     pub fn to_graph_with_options<G: GraphLike>(&self, postselect: bool) -> G {
+        self.to_graph_impl(postselect, |_, _| {})
+    }
+
+    /// Lower the circuit into a ZX diagram, and return the spider each gate adds
+    ///
+    /// For each gate, this gives the last vertex on its first qubit after the
+    /// gate was added. For single-qubit phase gates, this is the spider
+    /// carrying the phase.
+    pub(crate) fn to_graph_with_spiders<G: GraphLike>(&self) -> (G, Vec<Option<V>>) {
+        let mut spiders = Vec::with_capacity(self.gates.len());
+        let graph = self.to_graph_impl(false, |g, qs| {
+            spiders.push(g.qs.first().and_then(|&q| qs[q]))
+        });
+        (graph, spiders)
+    }
+
+    fn to_graph_impl<G: GraphLike>(
+        &self,
+        postselect: bool,
+        mut added: impl FnMut(&Gate, &[Option<V>]),
+    ) -> G {
         let mut graph = G::new();
         let mut qs = Vec::with_capacity(self.nqubits);
         let mut inputs = Vec::with_capacity(self.nqubits);
@@ -259,6 +339,7 @@ impl Circuit {
                 phase: Phase::zero(),
                 qubit: i as i32,
                 row: 1,
+                ground: false,
             });
             qs.push(Some(v));
             inputs.push(v);
@@ -268,6 +349,7 @@ impl Circuit {
 
         for g in &self.gates {
             g.add_to_graph(&mut graph, &mut qs, postselect);
+            added(g, &qs);
         }
 
         let last_row = qs
@@ -287,6 +369,7 @@ impl Circuit {
                     phase: Phase::zero(),
                     qubit: i as i32,
                     row: last_row + 1,
+                    ground: false,
                 });
                 graph.add_edge(v0, v);
                 outputs.push(v);
@@ -294,16 +377,361 @@ impl Circuit {
         }
 
         graph.set_outputs(outputs);
+        *graph.scalar_mut() *= &self.scalar;
         graph
     }
 
+    /// Lower the circuit into a ZX diagram, without postselection
+    ///
+    /// See [Circuit::to_graph_with_options].
     pub fn to_graph<G: GraphLike>(&self) -> G {
         self.to_graph_with_options(false)
     }
 
+    /// Like [`Circuit::to_graph`], but fails if the circuit contains opaque gates
+    ///
+    /// `to_graph` quietly drops gates it does not know about. This returns an
+    /// error listing all of them instead.
+    pub fn try_to_graph<G: GraphLike>(&self) -> Result<G, ToGraphError> {
+        let gates: Vec<String> = self
+            .gates
+            .iter()
+            .filter(|g| g.t == UnknownGate)
+            .map(|g| g.to_qasm())
+            .collect();
+        if gates.is_empty() {
+            Ok(self.to_graph())
+        } else {
+            Err(ToGraphError::UnsupportedGates { gates })
+        }
+    }
+
     pub fn stats(&self) -> CircuitStats {
         CircuitStats::make(self)
     }
+
+    /// The depth of the circuit, i.e. the length of its critical path
+    ///
+    /// Each gate is placed in the layer after the latest gate acting on any of
+    /// its qubits, so gates on disjoint qubits can share a layer. Barriers are
+    /// not counted.
+    pub fn depth(&self) -> usize {
+        let mut layer = vec![0; self.nqubits];
+        let mut depth = 0;
+        for g in self.gates.iter().filter(|g| g.t != Barrier) {
+            let d = 1 + g.qs.iter().map(|&q| layer[q]).max().unwrap_or(0);
+            for &q in &g.qs {
+                layer[q] = d;
+            }
+            depth = depth.max(d);
+        }
+        depth
+    }
+
+    /// The T-count of the circuit, counting arbitrary rotations as a single T gate
+    ///
+    /// See [`Circuit::t_count_with`] for details.
+    pub fn t_count(&self) -> usize {
+        self.t_count_with(RotationCost::One)
+            .expect("RotationCost::One never fails")
+    }
+
+    /// The T-count of the circuit, using the given policy for arbitrary rotations
+    ///
+    /// T and Tdg gates, as well as phase gates with angles that are odd
+    /// multiples of π/4, count as one. CCZ and Toffoli gates count as 7, their
+    /// standard Clifford+T cost. Non-Clifford rotations by any other angle are
+    /// counted according to `cost`.
+    ///
+    /// This agrees with [`GraphLike::tcount`] on the graph produced by
+    /// [`Circuit::to_graph`] for Clifford+T circuits:
+    ///
+    /// ```
+    /// use quizx::circuit::Circuit;
+    /// use quizx::graph::GraphLike;
+    /// use quizx::vec_graph::Graph;
+    ///
+    /// let c = Circuit::from_qasm(r#"
+    ///   qreg q[3];
+    ///   ccx q[0], q[1], q[2];
+    /// "#).unwrap();
+    /// assert_eq!(c.t_count(), 7);
+    ///
+    /// let mut g: Graph = c.to_graph();
+    /// quizx::simplify::full_simp(&mut g);
+    /// assert_eq!(g.tcount(), 7);
+    /// ```
+    pub fn t_count_with(&self, cost: RotationCost) -> Result<usize, TCountError> {
+        let mut n = 0;
+        for g in &self.gates {
+            match g.t {
+                T | Tdg => n += 1,
+                CCZ | TOFF => n += 7,
+                ZPhase | XPhase | ParityPhase if !g.phase.is_clifford() => {
+                    if g.phase.is_t() {
+                        n += 1;
+                    } else {
+                        n += match cost {
+                            RotationCost::Error => {
+                                return Err(TCountError::NonCliffordT {
+                                    gate: g.t,
+                                    phase: g.phase,
+                                })
+                            }
+                            RotationCost::One => 1,
+                            RotationCost::Synth(f) => f(g.phase),
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(n)
+    }
+
+    /// Push all Pauli X, Y and Z gates to the end of the circuit
+    ///
+    /// Paulis are conjugated through Clifford gates using the usual tableau
+    /// rules, and pushed through Z and X phase gates by flipping the sign of
+    /// the phase. Gates which are neither (e.g. CCZ) act as barriers: pending
+    /// Paulis on their qubits are emitted just before them. The remaining
+    /// Paulis are appended as a layer of Z then X gates at the end, where a
+    /// Y gate (read from QASM as an opaque gate "y") becomes iXZ.
+    ///
+    /// The circuit is preserved exactly, with the global phase multiplied into
+    /// [Circuit::scalar]. For example, pushing an X through a T gate produces
+    /// Tdg and a global phase of pi/4.
+    pub fn push_paulis_to_end(&mut self) {
+        // the Pauli frame on each qubit, as powers (x, z) of the operator X^x Z^z
+        let mut frame = vec![(false, false); self.nqubits];
+        let mut global = Phase::zero();
+        let mut gates = VecDeque::with_capacity(self.gates.len());
+
+        fn flush(gates: &mut VecDeque<Gate>, frame: &mut [(bool, bool)], q: usize) {
+            let (x, z) = frame[q];
+            if z {
+                gates.push_back(Gate::new(GType::Z, vec![q]));
+            }
+            if x {
+                gates.push_back(Gate::new(NOT, vec![q]));
+            }
+            frame[q] = (false, false);
+        }
+
+        // sign of a flip, for each pending Pauli that anti-commutes with a gate
+        let flip = |b: bool| if b { Phase::one() } else { Phase::zero() };
+
+        for mut g in std::mem::take(&mut self.gates) {
+            match g.t {
+                NOT => {
+                    frame[g.qs[0]].0 ^= true;
+                    continue;
+                }
+                GType::Z => {
+                    let (x, z) = frame[g.qs[0]];
+                    global += flip(x);
+                    frame[g.qs[0]] = (x, !z);
+                    continue;
+                }
+                UnknownGate if g.qasm_name() == "y" && g.qs.len() == 1 => {
+                    // Y X^x Z^z = i X Z X^x Z^z = i (-1)^x X^(x+1) Z^(z+1)
+                    let (x, z) = frame[g.qs[0]];
+                    global += Phase::new(Rational64::new(1, 2)) + flip(x);
+                    frame[g.qs[0]] = (!x, !z);
+                    continue;
+                }
+                ZPhase | T | Tdg => {
+                    if frame[g.qs[0]].0 {
+                        let p = match g.t {
+                            T => Phase::new(Rational64::new(1, 4)),
+                            Tdg => Phase::new(Rational64::new(-1, 4)),
+                            _ => g.phase,
+                        };
+                        global += p;
+                        g.adjoint();
+                    }
+                }
+                XPhase => {
+                    if frame[g.qs[0]].1 {
+                        global += g.phase;
+                        g.adjoint();
+                    }
+                }
+                S | Sdg => {
+                    let (x, z) = frame[g.qs[0]];
+                    if x {
+                        global += Phase::new(Rational64::new(if g.t == S { 1 } else { -1 }, 2));
+                    }
+                    frame[g.qs[0]] = (x, z ^ x);
+                }
+                HAD => {
+                    let (x, z) = frame[g.qs[0]];
+                    global += flip(x && z);
+                    frame[g.qs[0]] = (z, x);
+                }
+                CNOT => {
+                    let (c, t) = (g.qs[0], g.qs[1]);
+                    frame[t].0 ^= frame[c].0;
+                    frame[c].1 ^= frame[t].1;
+                }
+                CZ => {
+                    let (a, b) = (g.qs[0], g.qs[1]);
+                    let (xa, za) = frame[a];
+                    let (xb, zb) = frame[b];
+                    global += flip(xa && xb);
+                    frame[a] = (xa, za ^ xb);
+                    frame[b] = (xb, zb ^ xa);
+                }
+                SWAP => {
+                    frame.swap(g.qs[0], g.qs[1]);
+                }
+                _ => {
+                    for &q in &g.qs {
+                        flush(&mut gates, &mut frame, q);
+                    }
+                }
+            }
+            gates.push_back(g);
+        }
+
+        for q in 0..self.nqubits {
+            flush(&mut gates, &mut frame, q);
+        }
+
+        self.gates = gates;
+        self.scalar *= ScalarN::from_phase(global);
+    }
+}
+
+/// Builder-style methods for adding gates
+///
+/// These return `&mut Self`, so gates can be chained:
+///
+/// ```
+/// use quizx::circuit::Circuit;
+/// use quizx::phase::Phase;
+///
+/// let mut c = Circuit::new(3);
+/// c.h(0).cx(0, 1).rz(1, Phase::new((1, 4))).ccz(0, 1, 2);
+/// assert_eq!(c.num_gates(), 4);
+/// ```
+///
+/// In debug builds, these check that qubit indices are in range and distinct.
+impl Circuit {
+    fn push_checked(&mut self, t: GType, qs: Vec<usize>, phase: Phase) -> &mut Self {
+        debug_assert!(
+            qs.iter().all(|&q| q < self.nqubits),
+            "Qubit out of range in {:?} gate on {:?}, circuit has {} qubits",
+            t,
+            qs,
+            self.nqubits
+        );
+        debug_assert!(
+            qs.iter().enumerate().all(|(i, q)| !qs[..i].contains(q)),
+            "Repeated qubit in {:?} gate on {:?}",
+            t,
+            qs
+        );
+        self.push(Gate::new_with_phase(t, qs, phase));
+        self
+    }
+
+    pub fn x(&mut self, q: usize) -> &mut Self {
+        self.push_checked(NOT, vec![q], Phase::zero())
+    }
+
+    pub fn z(&mut self, q: usize) -> &mut Self {
+        self.push_checked(Z, vec![q], Phase::zero())
+    }
+
+    pub fn s(&mut self, q: usize) -> &mut Self {
+        self.push_checked(S, vec![q], Phase::zero())
+    }
+
+    pub fn sdg(&mut self, q: usize) -> &mut Self {
+        self.push_checked(Sdg, vec![q], Phase::zero())
+    }
+
+    pub fn t(&mut self, q: usize) -> &mut Self {
+        self.push_checked(T, vec![q], Phase::zero())
+    }
+
+    pub fn tdg(&mut self, q: usize) -> &mut Self {
+        self.push_checked(Tdg, vec![q], Phase::zero())
+    }
+
+    pub fn h(&mut self, q: usize) -> &mut Self {
+        self.push_checked(HAD, vec![q], Phase::zero())
+    }
+
+    /// Add a Z rotation by `phase`, given in units of π
+    pub fn rz(&mut self, q: usize, phase: impl Into<Phase>) -> &mut Self {
+        self.push_checked(ZPhase, vec![q], phase.into())
+    }
+
+    /// Add an X rotation by `phase`, given in units of π
+    pub fn rx(&mut self, q: usize, phase: impl Into<Phase>) -> &mut Self {
+        self.push_checked(XPhase, vec![q], phase.into())
+    }
+
+    pub fn cx(&mut self, c: usize, t: usize) -> &mut Self {
+        self.push_checked(CNOT, vec![c, t], Phase::zero())
+    }
+
+    pub fn cz(&mut self, a: usize, b: usize) -> &mut Self {
+        self.push_checked(CZ, vec![a, b], Phase::zero())
+    }
+
+    pub fn xcx(&mut self, a: usize, b: usize) -> &mut Self {
+        self.push_checked(XCX, vec![a, b], Phase::zero())
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) -> &mut Self {
+        self.push_checked(SWAP, vec![a, b], Phase::zero())
+    }
+
+    pub fn ccx(&mut self, c0: usize, c1: usize, t: usize) -> &mut Self {
+        self.push_checked(TOFF, vec![c0, c1, t], Phase::zero())
+    }
+
+    pub fn ccz(&mut self, a: usize, b: usize, c: usize) -> &mut Self {
+        self.push_checked(CCZ, vec![a, b, c], Phase::zero())
+    }
+
+    /// Add a Z rotation by `phase` on the parity of the given qubits
+    pub fn parity_phase(&mut self, qs: &[usize], phase: impl Into<Phase>) -> &mut Self {
+        self.push_checked(ParityPhase, qs.to_vec(), phase.into())
+    }
+
+    pub fn init_ancilla(&mut self, q: usize) -> &mut Self {
+        self.push_checked(InitAncilla, vec![q], Phase::zero())
+    }
+
+    pub fn post_select(&mut self, q: usize) -> &mut Self {
+        self.push_checked(PostSelect, vec![q], Phase::zero())
+    }
+
+    pub fn barrier(&mut self, qs: &[usize]) -> &mut Self {
+        self.push_checked(Barrier, qs.to_vec(), Phase::zero())
+    }
+
+    /// Append the gates of `other`, with qubit `i` of `other` sent to qubit
+    /// `qubit_map[i]` of `self`
+    pub fn add_circuit(&mut self, other: &Circuit, qubit_map: &[usize]) -> &mut Self {
+        assert_eq!(
+            qubit_map.len(),
+            other.num_qubits(),
+            "Qubit map must have an entry for every qubit of the circuit being added"
+        );
+        for g in &other.gates {
+            let qs = g.qs.iter().map(|&q| qubit_map[q]).collect();
+            self.push_checked(g.t, qs, g.phase);
+            // keep any data for opaque gates
+            self.gates.back_mut().unwrap().opaque = g.opaque.clone();
+        }
+        self.scalar *= &other.scalar;
+        self
+    }
 }
 
 impl fmt::Display for Circuit {
@@ -325,6 +753,7 @@ impl std::ops::Add<Circuit> for Circuit {
             panic!("Cannot append circuits with different numbers of qubits");
         }
         self.gates.append(&mut rhs.gates);
+        self.scalar *= rhs.scalar;
         self
     }
 }
@@ -336,6 +765,7 @@ impl std::ops::Add<&Circuit> for Circuit {
             panic!("Cannot append circuits with different numbers of qubits");
         }
         self.gates.extend(rhs.gates.iter().cloned());
+        self.scalar *= &rhs.scalar;
         self
     }
 }
@@ -355,8 +785,11 @@ impl std::ops::Add<&Circuit> for &Circuit {
 }
 
 impl std::ops::AddAssign<&Circuit> for Circuit {
+    // appending a circuit multiplies the global scalars
+    #[allow(clippy::suspicious_op_assign_impl)]
     fn add_assign(&mut self, rhs: &Self) {
         self.gates.extend(rhs.gates.iter().cloned());
+        self.scalar *= &rhs.scalar;
     }
 }
 
@@ -393,7 +826,6 @@ struct CircuitWriter {
 #[allow(clippy::enum_variant_names)]
 enum CircuitWriterError {
     UnitaryNotSupported,
-    BarrierNotSupported,
     ResetNotSupported,
     MeasureNotSupported,
     ConditionalNotSupported,
@@ -405,7 +837,6 @@ impl std::fmt::Display for CircuitWriterError {
             CircuitWriterError::UnitaryNotSupported => {
                 write!(f, "arbitrary unitaries are not supported")
             }
-            CircuitWriterError::BarrierNotSupported => write!(f, "barriers are not supported"),
             CircuitWriterError::ResetNotSupported => write!(f, "resets are not supported"),
             CircuitWriterError::MeasureNotSupported => write!(f, "measurements are not supported"),
             CircuitWriterError::ConditionalNotSupported => {
@@ -450,7 +881,9 @@ impl openqasm::GateWriter for &mut CircuitWriter {
 
         let mut g = Gate::from_qasm_name(name.as_str());
         g.qs.extend_from_slice(regs);
-        if !params.is_empty() {
+        if let Some(op) = &mut g.opaque {
+            op.params = params.iter().map(|&p| param_to_phase(p)).collect();
+        } else if !params.is_empty() {
             g.phase = param_to_phase(params[0]);
         }
 
@@ -463,8 +896,11 @@ impl openqasm::GateWriter for &mut CircuitWriter {
         Err(CircuitWriterError::UnitaryNotSupported)
     }
 
-    fn write_barrier(&mut self, _: &[usize]) -> Result<(), Self::Error> {
-        Err(CircuitWriterError::BarrierNotSupported)
+    fn write_barrier(&mut self, regs: &[usize]) -> Result<(), Self::Error> {
+        let mut qs = regs.to_vec();
+        qs.sort();
+        self.circuit.push(Gate::new(GType::Barrier, qs));
+        Ok(())
     }
 
     fn write_reset(&mut self, _: usize) -> Result<(), Self::Error> {
@@ -606,6 +1042,300 @@ mod tests {
         assert_eq!(g.to_tensor4(), Tensor::cphase(Rational64::new(1, 1), 2));
     }
 
+    #[test]
+    fn tograph_boundary_order() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            x q[2];
+            h q[0];
+        "#,
+        )
+        .unwrap();
+
+        let g: Graph = c.to_graph();
+        assert_eq!(g.inputs().len(), 3);
+        assert_eq!(g.outputs().len(), 3);
+        for q in 0..3 {
+            assert_eq!(g.qubit(g.inputs()[q]), q as i32);
+            assert_eq!(g.qubit(g.outputs()[q]), q as i32);
+        }
+
+        let n = g.neighbors(g.inputs()[2]).next().unwrap();
+        assert_eq!(g.vertex_type(n), VType::X);
+        assert_eq!(c.to_tensor4(), g.to_tensor4());
+    }
+
+    #[test]
+    fn builder() {
+        let mut c = Circuit::new(3);
+        c.h(0)
+            .cx(0, 1)
+            .rz(1, Rational64::new(1, 4))
+            .ccz(0, 1, 2)
+            .swap(0, 2);
+        let c1 = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            h q[0];
+            cx q[0], q[1];
+            rz(0.25*pi) q[1];
+            ccz q[0], q[1], q[2];
+            swap q[0], q[2];
+        "#,
+        )
+        .unwrap();
+        assert_eq!(c, c1);
+
+        let mut small = Circuit::new(2);
+        small.t(0).cx(0, 1);
+        let mut big = Circuit::new(4);
+        big.x(0).add_circuit(&small, &[3, 1]);
+        assert_eq!(big.gates[1], Gate::new(T, vec![3]));
+        assert_eq!(big.gates[2], Gate::new(CNOT, vec![3, 1]));
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn builder_out_of_range() {
+        Circuit::new(2).cx(0, 2);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn builder_repeated_qubit() {
+        Circuit::new(2).cz(1, 1);
+    }
+
+    #[test]
+    fn barrier_and_opaque() {
+        let qasm = r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            opaque foo(a, b) q;
+            qreg q[2];
+            h q[0];
+            barrier q;
+            foo(0.5*pi, 0.25*pi) q[1];
+            x q[0];
+            cx q[0], q[1];
+        "#;
+        let c = Circuit::from_qasm(qasm).unwrap();
+        assert_eq!(c.num_gates(), 5);
+        assert_eq!(c.gates[1], Gate::new(Barrier, vec![0, 1]));
+        assert_eq!(c.gates[2].t, UnknownGate);
+
+        // both gates survive a round trip through QASM
+        let qasm_out = c.to_qasm();
+        assert!(qasm_out.contains("barrier q[0], q[1];"));
+        assert!(qasm_out.contains("foo(0.5*pi, 0.25*pi) q[1];"));
+
+        // ...but unknown gates can't be converted to a graph
+        let err = c.try_to_graph::<Graph>().unwrap_err();
+        assert_eq!(
+            err,
+            ToGraphError::UnsupportedGates {
+                gates: vec!["foo(0.5*pi, 0.25*pi) q[1]".to_string()]
+            }
+        );
+        assert!(err.to_string().contains("foo(0.5*pi, 0.25*pi) q[1]"));
+
+        let mut c1 = c.clone();
+        c1.gates.retain(|g| g.t != UnknownGate);
+        let g: Graph = c1.try_to_graph().unwrap();
+        assert_eq!(c1.stats().total, 3);
+        assert_eq!(c1.depth(), 3);
+        assert!(Tensor4::scalar_compare(&c1, &g));
+
+        // barriers stop Pauli pushing
+        let mut c2 = Circuit::new(2);
+        c2.add_gate("x", vec![0]);
+        c2.push(Gate::new(Barrier, vec![0, 1]));
+        c2.add_gate("cx", vec![0, 1]);
+        c2.push_paulis_to_end();
+        assert_eq!(c2.gates[0].t, NOT);
+        assert_eq!(c2.gates[1].t, Barrier);
+    }
+
+    #[test]
+    fn depth_and_stats() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            h q[0];
+            h q[1];
+            t q[2];
+            cx q[0], q[1];
+            tdg q[1];
+            rz(0.75*pi) q[0];
+            ccz q[0], q[1], q[2];
+            x q[2];
+        "#,
+        )
+        .unwrap();
+        assert_eq!(c.depth(), 5);
+
+        let s = c.stats();
+        assert_eq!(s.total, 8);
+        assert_eq!(s.tcount, 3);
+        assert_eq!(s.depth, 5);
+        assert_eq!(s.cliff, 4);
+        assert_eq!(s.non_cliff, 4);
+        assert_eq!(Circuit::new(2).depth(), 0);
+
+        // parity phases count like single-qubit ones
+        let mut c = Circuit::new(3);
+        c.push(Gate::new_with_phase(ParityPhase, vec![0, 1, 2], (1, 4)));
+        c.push(Gate::new_with_phase(ParityPhase, vec![0, 1], (1, 2)));
+        let s = c.stats();
+        assert_eq!(s.tcount, 1);
+        assert_eq!(s.tcount, c.t_count());
+        assert_eq!(s.cliff, 1);
+        assert_eq!(s.non_cliff, 1);
+    }
+
+    #[test]
+    fn t_count() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            t q[0];
+            tdg q[1];
+            rz(0.75*pi) q[2];
+            rz(0.5*pi) q[2];
+            rx(0.1*pi) q[0];
+            ccz q[0], q[1], q[2];
+            ccx q[0], q[1], q[2];
+        "#,
+        )
+        .unwrap();
+        assert_eq!(c.t_count(), 18);
+        assert_eq!(
+            c.t_count_with(RotationCost::Error),
+            Err(TCountError::NonCliffordT {
+                gate: XPhase,
+                phase: Phase::new(Rational64::new(1, 10))
+            })
+        );
+        let synth = |_: Phase| 10;
+        assert_eq!(c.t_count_with(RotationCost::Synth(&synth)), Ok(27));
+
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            t q[0];
+            ccz q[0], q[1], q[2];
+            h q[1];
+            tdg q[1];
+        "#,
+        )
+        .unwrap();
+        let mut g: Graph = c.to_graph();
+        assert_eq!(c.t_count(), g.tcount());
+        // simplification can only fold T gates together
+        crate::simplify::full_simp(&mut g);
+        assert!(g.tcount() <= c.t_count());
+    }
+
+    #[test]
+    fn push_paulis() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for seed in 0..10 {
+            let c0 = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(30)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+
+            // sprinkle some Paulis and X rotations through the circuit, and
+            // keep a copy with each Y written as iXZ, as to_tensor ignores it
+            let mut c = Circuit::new(4);
+            let mut expected = Circuit::new(4);
+            for g in c0.gates {
+                c.push(g.clone());
+                expected.push(g);
+                let q = rng.gen_range(0..4);
+                let g1 = match rng.gen_range(0..7) {
+                    0 => Gate::new(NOT, vec![q]),
+                    1 => Gate::new(GType::Z, vec![q]),
+                    2 => Gate::new_with_phase(XPhase, vec![q], Rational64::new(1, 4)),
+                    3 => Gate::new(Sdg, vec![q]),
+                    4 => {
+                        c.add_gate("y", vec![q]);
+                        expected.z(q).x(q);
+                        expected.scalar *= ScalarN::from_phase(Rational64::new(1, 2));
+                        continue;
+                    }
+                    _ => continue,
+                };
+                c.push(g1.clone());
+                expected.push(g1);
+            }
+
+            let mut c1 = c.clone();
+            c1.push_paulis_to_end();
+            assert_eq!(c1.num_gates_of_type(UnknownGate), 0);
+
+            // all Paulis should now be in a single layer at the end
+            let first_pauli = c1
+                .gates
+                .iter()
+                .position(|g| g.t == NOT || g.t == GType::Z)
+                .unwrap_or(c1.num_gates());
+            assert!(c1
+                .gates
+                .iter()
+                .skip(first_pauli)
+                .all(|g| g.t == NOT || g.t == GType::Z));
+            assert!(c1.num_gates() - first_pauli <= 8);
+
+            let t = c1.to_tensor4();
+            assert_eq!(
+                expected.to_tensor4(),
+                t,
+                "Pauli pushing failed for seed {}",
+                seed
+            );
+            let g: Graph = c1.to_graph();
+            assert_eq!(g.to_tensor4(), t);
+        }
+    }
+
+    #[test]
+    fn push_pauli_through_t() {
+        let mut c = Circuit::new(1);
+        c.add_gate("x", vec![0]);
+        c.add_gate("t", vec![0]);
+        c.push_paulis_to_end();
+        assert_eq!(c.scalar, ScalarN::from_phase(Rational64::new(1, 4)));
+        assert_eq!(c.gates[0].t, Tdg);
+        assert_eq!(c.gates[1].t, NOT);
+
+        // Y X = -i Z, written as X^0 Z^1
+        let mut c = Circuit::new(1);
+        c.add_gate("x", vec![0]);
+        c.add_gate("y", vec![0]);
+        c.push_paulis_to_end();
+        assert_eq!(c.num_gates(), 1);
+        assert_eq!(c.gates[0].t, GType::Z);
+        assert_eq!(c.scalar, ScalarN::from_phase(Rational64::new(-1, 2)));
+
+        // CCZ acts as a barrier
+        let mut c = Circuit::new(3);
+        c.add_gate("x", vec![0]);
+        c.add_gate("ccz", vec![0, 1, 2]);
+        c.push_paulis_to_end();
+        assert_eq!(c.gates[0].t, NOT);
+        assert_eq!(c.gates[1].t, CCZ);
+    }
+
     #[test]
     fn tograph_3cnot() {
         let c = Circuit::from_qasm(
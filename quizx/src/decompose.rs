@@ -17,11 +17,40 @@
 use crate::graph::*;
 use crate::scalar::*;
 use num::Rational64;
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
 use rayon::prelude::*;
+use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod approx;
+mod cache;
+mod cat;
+mod checkpoint;
+mod cut;
+mod float_sum;
+mod progress;
+mod select;
+mod stats;
+mod tree;
+pub use approx::{value_bound, Approximation};
+pub use cache::CacheStats;
+use cache::TermCache;
+use checkpoint::AutoCheckpoint;
+pub use checkpoint::CheckpointError;
+pub use float_sum::FloatSum;
+use progress::Progress;
+pub use progress::ProgressInfo;
+pub use select::*;
+pub use stats::{DecompStats, MoveCounts};
+pub use tree::{DecompMove, DecompTree, MoveStats, TreeNode};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum SimpFunc {
     FullSimp,
     CliffordSimp,
@@ -29,6 +58,73 @@ pub enum SimpFunc {
 }
 use SimpFunc::*;
 
+/// The T-count stride of [SimpPolicy::Adaptive]
+const ADAPTIVE_SIMP_STRIDE: usize = 8;
+
+/// When a [Decomposer] runs the full simplifier on new terms, see
+/// [Decomposer::with_simp_policy]
+///
+/// Terms which are not fully simplified get [clifford_simp](crate::simplify::clifford_simp),
+/// which is much cheaper, but leaves phase gadgets which [full_simp](crate::simplify::full_simp)
+/// could remove along with their T gates.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SimpPolicy {
+    /// Fully simplify every term
+    #[default]
+    Full,
+    /// Never fully simplify
+    CliffordOnly,
+    /// Fully simplify the terms at every n-th level of the decomposition
+    EveryN(usize),
+    /// Fully simplify a term when its T-count has dropped past a multiple of
+    /// 8 since its parent, so roughly once for every 8 T gates decomposed
+    /// along a branch
+    ///
+    /// This is less often than [Full](SimpPolicy::Full) where many T gates
+    /// are decomposed at once, and more often where few are.
+    Adaptive,
+}
+
+impl SimpPolicy {
+    /// Whether a new term g at the given depth gets the full simplifier, if
+    /// its parent had `parent_tcount` T gates
+    fn full_simp_due(self, depth: usize, parent_tcount: usize, g: &impl GraphLike) -> bool {
+        match self {
+            SimpPolicy::Full => true,
+            SimpPolicy::CliffordOnly => false,
+            SimpPolicy::EveryN(n) => depth % n == 0,
+            SimpPolicy::Adaptive => {
+                g.tcount() / ADAPTIVE_SIMP_STRIDE < parent_tcount / ADAPTIVE_SIMP_STRIDE
+            }
+        }
+    }
+}
+
+/// How a [Decomposer] picks the decomposition to apply to each graph
+///
+/// Whichever is used, a graph is [cut](Decomposer::use_cut_decomp) first if
+/// it can be.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum DecompStrategy {
+    /// Only split T gates: 6 at a time with the Bravyi-Smith-Smolin
+    /// decomposition, then in pairs, and the last one on its own
+    #[default]
+    TGates,
+    /// Decompose a cat state picked by the [selector](Decomposer::with_selector)
+    /// if there is one, otherwise 5 T gates at a time with the magic5
+    /// decomposition, and the rest as [TGates](DecompStrategy::TGates)
+    Cats,
+    /// Try each of the decompositions which apply to the graph, a cat, 5 T
+    /// gates or the T gates picked by [TGates](DecompStrategy::TGates), and
+    /// keep the one whose simplified terms look cheapest to finish
+    ///
+    /// Ties go to the first in that order. Which is best depends on how well
+    /// the terms simplify, so this often needs fewer terms than either of
+    /// the others, but it simplifies up to 13 terms per step where they
+    /// simplify at most 7.
+    Adaptive,
+}
+
 /// Store the (partial) decomposition of a graph into stabilisers
 #[derive(Clone)]
 pub struct Decomposer<G: GraphLike> {
@@ -37,13 +133,80 @@ pub struct Decomposer<G: GraphLike> {
     pub scalar: ScalarN,
     pub nterms: usize,
     simp_func: SimpFunc,
+    simp_policy: SimpPolicy,
     random_t: bool,
-    use_cats: bool,
+    rng: StdRng,
+    strategy: DecompStrategy,
+    max_cut: usize,
+    selector: Arc<dyn DecompSelector<G>>,
     save: bool, // save graphs on 'done' stack
+    progress: Option<Progress>,
+    term_limit: Option<usize>,
+    // the terms found by all the decomposers of a split, for the term limit
+    shared_nterms: Option<Arc<AtomicUsize>>,
+    deadline: Option<Instant>,
+    // the error which stopped the decomposition
+    error: Option<ScalarError>,
+    checkpoint: Option<AutoCheckpoint>,
+    float_sum: Option<FloatSum>,
+    #[cfg(feature = "bigint")]
+    bigint_sum: Option<ScalarB>,
+    cache: Option<Arc<Mutex<TermCache<G>>>>,
+    cache_max_tcount: usize,
+    initial_tcount: Option<usize>,
+    elapsed: Duration,
+    moves: MoveCounts,
+    tree: Option<DecompTree>,
+    // the node of each graph on the stack, while recording the tree
+    tree_stack: VecDeque<Option<usize>>,
+    // the node of the graph being decomposed
+    tree_node: Option<usize>,
+    last_move: Option<(DecompMove, Vec<V>)>,
 }
 
 // impl<G: GraphLike> Send for Decomposer<G> {}
 
+/// A single fully-decomposed term, as produced by [`Decomposer::iter_terms`]
+#[derive(Clone, Debug)]
+pub struct TermResult<G: GraphLike> {
+    /// The scalar this term contributes to the sum
+    pub scalar: ScalarN,
+    /// The number of decompositions taken to reach this term
+    pub depth: usize,
+    /// The reduced graph, only kept if the decomposer was set to save graphs
+    ///
+    /// Products of independently decomposed components have no single graph,
+    /// so this is always `None` for them.
+    pub graph: Option<G>,
+    /// The number of stabiliser terms computed to get the scalar
+    ///
+    /// This is 1, except for products of components, where it is the total
+    /// over all of the components.
+    pub nterms: usize,
+}
+
+/// A lazy iterator over the terms of a decomposition
+///
+/// Terms are produced depth-first, so only the pending branches are held in
+/// memory. See [`Decomposer::iter_terms`].
+pub struct Terms<G: GraphLike> {
+    d: Decomposer<G>,
+}
+
+impl<G: GraphLike> Iterator for Terms<G> {
+    type Item = TermResult<G>;
+
+    fn next(&mut self) -> Option<TermResult<G>> {
+        while self.d.error.is_none() {
+            let (depth, g) = self.d.pop_entry()?;
+            if let Some(t) = self.d.decomp_graph(depth, g) {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
 /// Gives upper bound for number of terms needed for BSS decomposition
 ///
 /// Note this number can be very large. We use a float here to avoid overflows.
@@ -66,9 +229,31 @@ impl<G: GraphLike> Decomposer<G> {
             scalar: ScalarN::zero(),
             nterms: 0,
             simp_func: NoSimp,
+            simp_policy: SimpPolicy::Full,
             random_t: false,
-            use_cats: false,
+            rng: StdRng::from_rng(thread_rng()).unwrap(),
+            strategy: DecompStrategy::TGates,
+            max_cut: 0,
+            selector: Arc::new(FirstCat),
             save: false,
+            progress: None,
+            term_limit: None,
+            shared_nterms: None,
+            deadline: None,
+            error: None,
+            checkpoint: None,
+            float_sum: None,
+            #[cfg(feature = "bigint")]
+            bigint_sum: None,
+            cache: None,
+            cache_max_tcount: 0,
+            initial_tcount: None,
+            elapsed: Duration::ZERO,
+            moves: MoveCounts::default(),
+            tree: None,
+            tree_stack: VecDeque::new(),
+            tree_node: None,
+            last_move: None,
         }
     }
 
@@ -83,36 +268,107 @@ impl<G: GraphLike> Decomposer<G> {
     ///
     /// Used for parallelising. The last decomposer in the list keeps the
     /// current state (e.g. `nterms` and `scalar`).
+    ///
+    /// Each new decomposer gets its own random number generator, seeded from
+    /// this one, so a [seeded](Decomposer::with_seed) decomposer splits the
+    /// same way every time.
+    ///
+    /// All of them get the same [deadline](Decomposer::deadline), and the
+    /// [term limit](Decomposer::term_limit) applies to the terms they find
+    /// between them, until they are [merged](Decomposer::merge).
     pub fn split(mut self) -> Vec<Decomposer<G>> {
+        if self.term_limit.is_some() && self.shared_nterms.is_none() {
+            self.shared_nterms = Some(Arc::new(AtomicUsize::new(self.nterms)));
+        }
         let mut ds = vec![];
         while self.stack.len() > 1 {
             let (_, g) = self.stack.pop_front().unwrap();
-            let mut d1 = Decomposer::new(&g);
-            d1.save(self.save)
-                .random_t(self.random_t)
-                .with_simp(self.simp_func);
-            ds.push(d1);
+            let node = self.tree_stack.pop_front().flatten();
+            let mut d = self.child(&g);
+            d.graft_tree_at(node);
+            d.term_limit = self.term_limit;
+            d.shared_nterms = self.shared_nterms.clone();
+            d.deadline = self.deadline;
+            ds.push(d);
         }
         ds.push(self);
         ds
     }
 
+    /// A fresh decomposer for the given graph, with the same settings as this one
+    fn child(&mut self, g: &G) -> Decomposer<G> {
+        let mut d = self.empty_child();
+        d.stack.push_back((0, g.clone()));
+        d.record_tree(self.tree.is_some());
+        d
+    }
+
+    /// A decomposer with nothing on the stack, and the same settings as this one
+    ///
+    /// Progress reporting, limits and automatic checkpoints are not copied.
+    /// The random number generator of the new decomposer is seeded from this
+    /// one, and the term cache is shared. If this one records a tree, the new
+    /// one records its own.
+    fn empty_child(&mut self) -> Decomposer<G> {
+        let mut d = Decomposer::empty();
+        d.with_seed(self.rng.gen());
+        d.save(self.save)
+            .random_t(self.random_t)
+            .with_strategy(self.strategy)
+            .use_cut_decomp(self.max_cut)
+            .with_simp(self.simp_func)
+            .with_float_accumulation(self.float_sum.is_some());
+        #[cfg(feature = "bigint")]
+        d.with_bigint_accumulation(self.bigint_sum.is_some());
+        d.simp_policy = self.simp_policy;
+        d.selector = self.selector.clone();
+        d.cache = self.cache.clone();
+        d.cache_max_tcount = self.cache_max_tcount;
+        d.record_tree(self.tree.is_some());
+        d
+    }
+
     /// Merge N decomposers into 1, adding scalars together
     pub fn merge(mut ds: Vec<Decomposer<G>>) -> Decomposer<G> {
         if let Some(mut d) = ds.pop() {
             while let Some(d1) = ds.pop() {
-                d.scalar += d1.scalar;
+                if !d.merge_bigint_sum(&d1) {
+                    match d.scalar.checked_add(&d1.scalar) {
+                        Ok(s) => d.scalar = s,
+                        Err(e) => d.error = Some(e),
+                    }
+                }
+                d.error = d.error.or(d1.error);
                 d.nterms += d1.nterms;
+                d.moves += d1.moves;
+                d.merge_tree(d1.tree, d1.tree_stack);
                 d.stack.extend(d1.stack);
                 d.done.extend(d1.done);
+                if let (Some(s), Some(s1)) = (&mut d.float_sum, &d1.float_sum) {
+                    s.merge(s1);
+                }
+            }
+            if let Some(s) = &d.float_sum {
+                d.scalar = s.scalar();
             }
+            d.shared_nterms = None;
             d
         } else {
             Decomposer::empty()
         }
     }
 
-    // pub fn seed(&mut self, seed: u64) -> &mut Self { self.rng = StdRng::seed_from_u64(seed); self }
+    /// Seed the random choices made by the decomposer
+    ///
+    /// The only random choice is the order of T gates picked with
+    /// [random_t](Decomposer::random_t). Two runs with the same seed and
+    /// settings give the same intermediate graphs and the same `nterms`. This
+    /// also holds for [Decomposer::decomp_parallel], for any number of
+    /// threads. Without a seed, the generator is seeded from [thread_rng].
+    pub fn with_seed(&mut self, seed: u64) -> &mut Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
 
     pub fn with_simp(&mut self, f: SimpFunc) -> &mut Self {
         self.simp_func = f;
@@ -126,21 +382,291 @@ impl<G: GraphLike> Decomposer<G> {
         self.with_simp(CliffordSimp)
     }
 
+    /// Fully simplify new terms according to the given policy, and Clifford
+    /// simplify the rest
+    ///
+    /// This sets the [SimpFunc] to [FullSimp], which is only run as often as
+    /// the policy allows. The default is [SimpPolicy::Full], which runs it on
+    /// every term.
+    ///
+    /// # Panics
+    ///
+    /// If the policy is `EveryN(0)`.
+    pub fn with_simp_policy(&mut self, p: SimpPolicy) -> &mut Self {
+        assert!(p != SimpPolicy::EveryN(0), "EveryN needs a positive n");
+        self.simp_policy = p;
+        self.with_full_simp()
+    }
+
+    /// How often new terms are fully simplified, or `None` if they are not
+    /// simplified at all
+    ///
+    /// This is only the policy set by [Decomposer::with_simp_policy] if the
+    /// [SimpFunc] is [FullSimp]. [Clifford simplification](Decomposer::with_clifford_simp)
+    /// is [SimpPolicy::CliffordOnly].
+    pub fn simp_policy(&self) -> Option<SimpPolicy> {
+        match self.simp_func {
+            FullSimp => Some(self.simp_policy),
+            CliffordSimp => Some(SimpPolicy::CliffordOnly),
+            NoSimp => None,
+        }
+    }
+
     pub fn random_t(&mut self, b: bool) -> &mut Self {
         self.random_t = b;
         self
     }
 
+    /// Use [DecompStrategy::Cats] if true, and [DecompStrategy::TGates] if false
     pub fn use_cats(&mut self, b: bool) -> &mut Self {
-        self.use_cats = b;
+        self.with_strategy(if b {
+            DecompStrategy::Cats
+        } else {
+            DecompStrategy::TGates
+        })
+    }
+
+    /// Set how the decomposition of each graph is picked
+    ///
+    /// The default is [DecompStrategy::TGates].
+    pub fn with_strategy(&mut self, s: DecompStrategy) -> &mut Self {
+        self.strategy = s;
+        self
+    }
+
+    /// How the decomposition of each graph is picked
+    pub fn strategy(&self) -> DecompStrategy {
+        self.strategy
+    }
+
+    /// Cut graphs along vertex separators of at most the given size
+    ///
+    /// When a small set of spiders splits the graph into two parts with
+    /// enough T gates each, the spiders are cut by summing over their basis
+    /// states. This gives `2^k` terms, each of which is a disconnected graph.
+    /// The components of a disconnected graph are then decomposed separately
+    /// and their scalars multiplied, so the number of terms grows with the sum
+    /// rather than the product of the parts. This is much cheaper for
+    /// circuits with narrow bottlenecks. A size of 0, the default, turns it
+    /// off. See [Decomposer::find_cut].
+    pub fn use_cut_decomp(&mut self, max_cut_size: usize) -> &mut Self {
+        self.max_cut = max_cut_size;
+        self
+    }
+
+    /// Set the strategy for picking cat states, when [use_cats](Decomposer::use_cats) is set
+    /// or the [DecompStrategy] is adaptive
+    ///
+    /// The default is [FirstCat].
+    pub fn with_selector(&mut self, s: impl DecompSelector<G> + 'static) -> &mut Self {
+        self.selector = Arc::new(s);
+        self
+    }
+
+    /// The name of the strategy for picking cat states
+    pub fn selector_name(&self) -> &'static str {
+        self.selector.name()
+    }
+
+    /// Add up terms as complex floats instead of exactly
+    ///
+    /// Exact sums over millions of terms get slow, as the coefficients grow.
+    /// With this set, each term is converted to a float and added to a
+    /// [FloatSum], and `scalar` holds the float value of the sum. See
+    /// [Decomposer::float_sum] for a bound on the rounding error. The
+    /// components of disconnected graphs are still multiplied exactly, see
+    /// [use_cut_decomp](Decomposer::use_cut_decomp). This is off by default,
+    /// and turns off bigint accumulation with the `bigint` feature.
+    pub fn with_float_accumulation(&mut self, b: bool) -> &mut Self {
+        if !b {
+            self.float_sum = None;
+        } else if self.float_sum.is_none() {
+            #[cfg(feature = "bigint")]
+            {
+                self.bigint_sum = None;
+            }
+            let mut s = FloatSum::default();
+            if !self.scalar.is_zero() {
+                s.add(&self.scalar);
+            }
+            self.scalar = s.scalar();
+            self.float_sum = Some(s);
+        }
+        self
+    }
+
+    /// The float sum of the terms so far, if float accumulation is on
+    ///
+    /// See [Decomposer::with_float_accumulation].
+    pub fn float_sum(&self) -> Option<&FloatSum> {
+        self.float_sum.as_ref()
+    }
+
+    /// Add up terms exactly with arbitrary-precision coefficients
+    ///
+    /// Long decompositions can overflow the coefficients of `scalar`, which
+    /// panics with [ScalarError::Overflow]. With this set, each term is added
+    /// to a [ScalarB], see [Decomposer::bigint_sum], and `scalar` holds its
+    /// value converted back, which is a float if it doesn't fit. Each term is
+    /// still a [ScalarN], so a single term can overflow, which stops the
+    /// decomposition, see [Decomposer::error]. This turns off [float accumulation](Decomposer::with_float_accumulation),
+    /// and is off by default.
+    #[cfg(feature = "bigint")]
+    pub fn with_bigint_accumulation(&mut self, b: bool) -> &mut Self {
+        if !b {
+            self.bigint_sum = None;
+        } else if self.bigint_sum.is_none() {
+            self.with_float_accumulation(false);
+            self.bigint_sum = Some(ScalarB::from_scalar(&self.scalar));
+        }
+        self
+    }
+
+    /// The exact sum of the terms so far, if bigint accumulation is on
+    ///
+    /// See [Decomposer::with_bigint_accumulation].
+    #[cfg(feature = "bigint")]
+    pub fn bigint_sum(&self) -> Option<&ScalarB> {
+        self.bigint_sum.as_ref()
+    }
+
+    /// Add a scalar to the bigint sum, returning `false` if there is none
+    #[cfg(feature = "bigint")]
+    fn add_to_bigint_sum(&mut self, s: &ScalarN) -> bool {
+        match &mut self.bigint_sum {
+            Some(b) => {
+                *b += ScalarB::from_scalar(s);
+                self.scalar = ScalarN::from_scalar(&*b);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    fn add_to_bigint_sum(&mut self, _: &ScalarN) -> bool {
+        false
+    }
+
+    /// Add the bigint sum of another decomposer to this one, returning
+    /// `false` if this one has none
+    #[cfg(feature = "bigint")]
+    fn merge_bigint_sum(&mut self, other: &Decomposer<G>) -> bool {
+        let Some(b) = &mut self.bigint_sum else {
+            return false;
+        };
+        match &other.bigint_sum {
+            Some(b1) => *b += b1,
+            None => *b += ScalarB::from_scalar(&other.scalar),
+        }
+        self.scalar = ScalarN::from_scalar(&*b);
+        true
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    fn merge_bigint_sum(&mut self, _: &Decomposer<G>) -> bool {
+        false
+    }
+
+    /// Reuse the values of graphs isomorphic to ones already decomposed
+    ///
+    /// Graphs with at most `max_tcount` T-spiders are looked up in a cache of
+    /// up to `capacity` graphs before they are decomposed. The graphs are
+    /// compared up to isomorphism, ignoring their scalars, so a graph reached
+    /// along another branch with a different prefactor can reuse the value.
+    /// On a miss, the graph is decomposed in full and its value added to the
+    /// cache, dropping the least recently used graph if it is full. Graphs
+    /// found in the cache don't add to `nterms`.
+    ///
+    /// Larger graphs are decomposed as usual, so progress reports, limits and
+    /// checkpoints still apply to them. Clones of this decomposer and the
+    /// decomposers made by [Decomposer::split] share the cache. It is not
+    /// saved in checkpoints. A capacity of 0 turns the cache off.
+    pub fn use_term_cache(&mut self, capacity: usize, max_tcount: usize) -> &mut Self {
+        self.cache = (capacity > 0).then(|| Arc::new(Mutex::new(TermCache::new(capacity))));
+        self.cache_max_tcount = max_tcount;
         self
     }
 
+    /// The hit and miss counts of the term cache, if there is one
+    ///
+    /// See [Decomposer::use_term_cache].
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|c| c.lock().unwrap().stats())
+    }
+
     pub fn save(&mut self, b: bool) -> &mut Self {
         self.save = b;
         self
     }
 
+    /// Call f with the progress so far, every `every` terms
+    ///
+    /// This is tracked by [Decomposer::decomp_top] and so by
+    /// [Decomposer::decomp_all], which also calls f once more when it
+    /// returns. [Decomposer::decomp_parallel] only reports when it is done.
+    /// See [ProgressInfo] for how the total number of terms is estimated.
+    pub fn on_progress(
+        &mut self,
+        every: usize,
+        f: impl FnMut(ProgressInfo) + Send + 'static,
+    ) -> &mut Self {
+        self.progress = Some(Progress::new(every, f));
+        self
+    }
+
+    /// Make [Decomposer::decomp_all] stop once it has at least this many terms
+    ///
+    /// See [Decomposer::is_complete].
+    pub fn term_limit(&mut self, n: usize) -> &mut Self {
+        self.term_limit = Some(n);
+        self
+    }
+
+    /// Make [Decomposer::decomp_all] stop once the given time has passed
+    ///
+    /// See [Decomposer::is_complete].
+    pub fn deadline(&mut self, t: Instant) -> &mut Self {
+        self.deadline = Some(t);
+        self
+    }
+
+    /// Returns true if there is nothing left to decompose
+    ///
+    /// If [Decomposer::decomp_all] stopped early because of a
+    /// [term limit](Decomposer::term_limit) or [deadline](Decomposer::deadline),
+    /// this is false, and `scalar` only has the terms found so far. The rest
+    /// are still on the stack, so raising the limit and calling `decomp_all`
+    /// again carries on where it stopped.
+    pub fn is_complete(&self) -> bool {
+        self.stack.is_empty() && self.error.is_none()
+    }
+
+    /// The error which stopped the decomposition, if any
+    ///
+    /// Each term is computed as a [ScalarN], with checked arithmetic. If the
+    /// scalar of a term, or the sum of the terms without
+    /// [bigint accumulation](Decomposer::with_bigint_accumulation),
+    /// overflows, that term is dropped and the decomposition stops with
+    /// [ScalarError::Overflow]. It can't be carried on after that.
+    pub fn error(&self) -> Option<ScalarError> {
+        self.error
+    }
+
+    /// Whether the term limit or the deadline has been reached, or an error
+    /// has stopped the decomposition
+    fn out_of_budget(&self) -> bool {
+        if self.error.is_some() {
+            return true;
+        }
+        let nterms = match &self.shared_nterms {
+            Some(n) => n.load(Ordering::Relaxed),
+            None => self.nterms,
+        };
+        self.term_limit.is_some_and(|n| nterms >= n)
+            || self.deadline.is_some_and(|t| Instant::now() >= t)
+    }
+
     /// Computes `terms_for_tcount` for every graph on the stack
     pub fn max_terms(&self) -> f64 {
         let mut n = 0.0;
@@ -152,85 +678,296 @@ impl<G: GraphLike> Decomposer<G> {
     }
 
     pub fn pop_graph(&mut self) -> G {
-        let (_, g) = self.stack.pop_back().unwrap();
+        let (_, g) = self.pop_entry().unwrap();
         g
     }
 
+    /// Pop the top of the stack, and make its node in the tree the current
+    /// one, if a tree is being recorded
+    fn pop_entry(&mut self) -> Option<(usize, G)> {
+        self.tree_node = self.tree_stack.pop_back().flatten();
+        self.stack.pop_back()
+    }
+
     /// Decompose the first <= 6 T gates in the graph on the top of the
     /// stack.
     pub fn decomp_top(&mut self) -> &mut Self {
-        let (depth, g) = self.stack.pop_back().unwrap();
-        if self.use_cats {
-            let cat_nodes = Decomposer::cat_ts(&g); //gadget_ts(&g);
-                                                    //println!("{:?}", gadget_nodes);
-                                                    //let nts = cat_nodes.iter().fold(0, |acc, &x| if g.phase(x).denom() == &4 { acc + 1 } else { acc });
-            if !cat_nodes.is_empty() {
-                // println!("using cat!");
-                return self.push_cat_decomp(depth + 1, &g, &cat_nodes);
-            }
-            let ts = Decomposer::first_ts(&g);
-            if ts.len() >= 5 {
-                return self.push_magic5_from_cat_decomp(depth + 1, &g, &ts[..5]);
-            }
-        }
-        let ts = if self.random_t {
-            Decomposer::random_ts(&g, &mut thread_rng())
+        self.start_stats();
+        let (depth, g) = self.pop_entry().unwrap();
+        let len = self.stack.len();
+        if let Some(t) = self.decomp_graph(depth, g) {
+            self.add_term(t);
+        }
+        if let Some(p) = &mut self.progress {
+            p.step(depth, self.stack.len() - len, self.nterms);
+        }
+        self.auto_checkpoint();
+        self
+    }
+
+    /// Decompose one step of the given graph, pushing the resulting terms
+    /// onto the stack
+    ///
+    /// If the graph can be finished straight away, because there is nothing
+    /// left to decompose or it splits into components, its term is returned
+    /// instead.
+    ///
+    /// If a tree is being recorded, this records the step at the current node.
+    fn decomp_graph(&mut self, depth: usize, mut g: G) -> Option<TermResult<G>> {
+        let len = self.stack.len();
+        let failed = self.error.is_some();
+        // zero terms don't contribute to the sum
+        let mut t = if g.is_zero() {
+            None
         } else {
-            Decomposer::first_ts(&g)
+            // the new terms are found for the graph with its scalar set to
+            // one, and the scalar is multiplied back in with checked
+            // arithmetic, so a term which overflows stops the decomposition
+            // instead of panicking
+            let s = mem::replace(g.scalar_mut(), ScalarN::one());
+            let t = if self.use_cache_for(&g) {
+                Some(self.cached_term(depth, g))
+            } else {
+                self.expand(depth, g)
+            };
+            self.scale_terms(&s, len, t)
         };
-        self.decomp_ts(depth, g, &ts);
-        self
+        if !failed && self.error.is_some() {
+            self.stack.truncate(len);
+            t = None;
+        }
+        if self.tree.is_some() {
+            self.record_step(len, t.as_ref());
+        }
+        t
+    }
+
+    /// Multiply the given term, and the graphs on the stack from `len` on, by s
+    fn scale_terms(
+        &mut self,
+        s: &ScalarN,
+        len: usize,
+        t: Option<TermResult<G>>,
+    ) -> Option<TermResult<G>> {
+        let res = self.stack.range_mut(len..).try_for_each(|(_, g)| {
+            *g.scalar_mut() = s.checked_mul(g.scalar())?;
+            Ok(())
+        });
+        let res = res.and_then(|()| {
+            t.map(|mut t| {
+                t.scalar = s.checked_mul(&t.scalar)?;
+                if let Some(g) = &mut t.graph {
+                    *g.scalar_mut() = t.scalar.clone();
+                }
+                Ok(t)
+            })
+            .transpose()
+        });
+        match res {
+            Ok(t) => t,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    /// Same as [Decomposer::decomp_graph], without looking in the term cache
+    fn expand(&mut self, depth: usize, g: G) -> Option<TermResult<G>> {
+        if self.max_cut > 0 && g.tcount() > 0 {
+            let comps = g.component_vertices();
+            if comps.len() > 1 {
+                return Some(self.product_term(depth, &g, &comps));
+            }
+            if let Some(cut) = Decomposer::find_cut(&g, self.max_cut) {
+                self.push_cut_decomp(depth + 1, &g, &cut);
+                return None;
+            }
+        }
+        match self.strategy {
+            DecompStrategy::TGates => {}
+            DecompStrategy::Cats => {
+                let cat_nodes = self.selector.select(&g); //gadget_ts(&g);
+                                                          //println!("{:?}", gadget_nodes);
+                                                          //let nts = cat_nodes.iter().fold(0, |acc, &x| if g.phase(x).denom() == &4 { acc + 1 } else { acc });
+                if !cat_nodes.is_empty() {
+                    // println!("using cat!");
+                    self.push_cat_decomp(depth + 1, &g, &cat_nodes);
+                    return None;
+                }
+                let ts = Decomposer::first_ts(&g);
+                if ts.len() >= 5 {
+                    self.push_magic5_from_cat_decomp(depth + 1, &g, &ts[..5]);
+                    return None;
+                }
+            }
+            DecompStrategy::Adaptive => {
+                if self.push_cheapest_decomp(depth, &g) {
+                    return None;
+                }
+            }
+        }
+        let ts = self.pick_ts(&g);
+        if ts.is_empty() {
+            Some(self.term(depth, g))
+        } else {
+            self.decomp_ts(depth, g, &ts);
+            None
+        }
+    }
+
+    /// Lazily produce the terms of the decomposition, depth-first
+    ///
+    /// Unlike [`Decomposer::decomp_all`], terms are not accumulated, so
+    /// memory is bounded by the pending branches on the stack. Folding the
+    /// scalars of the terms gives the same `scalar` and `nterms` as
+    /// `decomp_all`. Graphs are only included if [`Decomposer::save`] is
+    /// set. The iterator ends early if a term overflows, see
+    /// [`Decomposer::error`].
+    pub fn iter_terms(mut self) -> Terms<G> {
+        self.start_stats();
+        Terms { d: self }
     }
 
     /// Decompose until there are no T gates left
+    ///
+    /// Terms which simplify to zero are dropped straight away. This stops
+    /// early if a [term limit](Decomposer::term_limit) or
+    /// [deadline](Decomposer::deadline) is reached, or a term overflows, see
+    /// [Decomposer::error].
     pub fn decomp_all(&mut self) -> &mut Self {
-        while !self.stack.is_empty() {
+        let start = Instant::now();
+        while !self.stack.is_empty() && !self.out_of_budget() {
             self.decomp_top();
         }
+        self.elapsed += start.elapsed();
+        let complete = self.is_complete();
+        if let Some(p) = &mut self.progress {
+            p.finish(self.nterms, complete);
+        }
         self
     }
 
-    /// Decompose breadth-first until the given depth
+    /// Decompose breadth-first until the given depth, using the same rules
+    /// as [`Decomposer::decomp_top`]
     pub fn decomp_until_depth(&mut self, depth: usize) -> &mut Self {
+        self.start_stats();
         while !self.stack.is_empty() {
             // pop from the bottom of the stack to work breadth-first
             let (d, g) = self.stack.pop_front().unwrap();
+            let node = self.tree_stack.pop_front().flatten();
             if d >= depth {
                 self.stack.push_front((d, g));
-                break;
-            } else {
-                if self.use_cats {
-                    let cat_nodes = Decomposer::cat_ts(&g); //gadget_ts(&g);
-                                                            //println!("{:?}", gadget_nodes);
-                    let nts = cat_nodes.iter().filter(|&&x| g.phase(x).is_t()).count();
-                    if nts > 2 {
-                        // println!("using cat!");
-                        return self.push_cat_decomp(depth + 1, &g, &cat_nodes);
-                    }
+                if self.tree.is_some() {
+                    self.tree_stack.push_front(node);
                 }
-                let ts = if self.random_t {
-                    Decomposer::random_ts(&g, &mut thread_rng())
-                } else {
-                    Decomposer::first_ts(&g)
-                };
-                self.decomp_ts(d, g, &ts);
+                break;
+            }
+            self.tree_node = node;
+            if let Some(t) = self.decomp_graph(d, g) {
+                self.add_term(t);
             }
         }
         self
     }
 
     /// Decompose in parallel, starting at the given depth
+    ///
+    /// This first decomposes breadth-first until every term is at the given
+    /// depth, then finishes each term on a separate rayon task, with the same
+    /// settings as this decomposer. Each level multiplies the number of terms
+    /// by between 2 and 7, so the depth should be picked to give a few terms
+    /// per thread. The partial results are added up in a fixed order, so the
+    /// final scalar and `nterms` don't depend on scheduling. A
+    /// [term limit](Decomposer::term_limit) or [deadline](Decomposer::deadline)
+    /// stops all of the tasks, see [Decomposer::split].
     pub fn decomp_parallel(mut self, depth: usize) -> Self {
+        let start = Instant::now();
+        let elapsed = self.elapsed;
+        let progress = self.progress.take();
+        let checkpoint = self.checkpoint.take();
         self.decomp_until_depth(depth);
         let ds = self.split();
-        Decomposer::merge(
+        let mut d = Decomposer::merge(
             ds.into_par_iter()
                 .map(|mut d| {
                     d.decomp_all();
                     d
                 })
                 .collect(),
-        )
+        );
+        d.progress = progress;
+        d.checkpoint = checkpoint;
+        d.elapsed = elapsed + start.elapsed();
+        let complete = d.is_complete();
+        if let Some(p) = &mut d.progress {
+            p.finish(d.nterms, complete);
+        }
+        d
+    }
+
+    /// The T gates to decompose next, picked at random if
+    /// [random_t](Decomposer::random_t) is set
+    fn pick_ts(&mut self, g: &G) -> Vec<V> {
+        if self.random_t {
+            Decomposer::random_ts(g, &mut self.rng)
+        } else {
+            Decomposer::first_ts(g)
+        }
+    }
+
+    /// Push the terms of the cheapest decomposition of g, for
+    /// [DecompStrategy::Adaptive]
+    ///
+    /// Each candidate is tried on an [empty child](Decomposer::empty_child),
+    /// and only the terms and move count of the best are kept. Returns false
+    /// if g has no cats or T gates to decompose.
+    fn push_cheapest_decomp(&mut self, depth: usize, g: &G) -> bool {
+        let cat_nodes = self.selector.select(g);
+        let ts = self.pick_ts(g);
+        let mut candidates = vec![];
+        if !cat_nodes.is_empty() {
+            let mut d = self.empty_child();
+            d.push_cat_decomp(depth + 1, g, &cat_nodes);
+            candidates.push(d);
+        }
+        if ts.len() >= 5 {
+            let mut d = self.empty_child();
+            d.push_magic5_from_cat_decomp(depth + 1, g, &ts[..5]);
+            candidates.push(d);
+        }
+        if !ts.is_empty() {
+            let mut d = self.empty_child();
+            d.decomp_ts(depth, g.clone(), &ts);
+            candidates.push(d);
+        }
+
+        // min_by keeps the first of equal candidates
+        match candidates
+            .into_iter()
+            .min_by(|d0, d1| d0.pending_cost().total_cmp(&d1.pending_cost()))
+        {
+            Some(d) => {
+                self.stack.extend(d.stack);
+                self.moves += d.moves;
+                self.last_move = d.last_move;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// An estimate of the number of terms left, taking `2^(t/4)` terms for
+    /// each graph with t T gates
+    ///
+    /// This is the rate of the cheapest decomposition, 2 terms for a cat with
+    /// 4 legs. Using the rate of BSS instead, as [Decomposer::max_terms]
+    /// does, undervalues cats, and picks BSS over them too often.
+    fn pending_cost(&self) -> f64 {
+        self.stack
+            .iter()
+            .map(|(_, g)| (g.tcount() as f64 / 4.0).exp2())
+            .sum()
     }
 
     pub fn decomp_ts(&mut self, depth: usize, g: G, ts: &[usize]) {
@@ -241,18 +978,85 @@ impl<G: GraphLike> Decomposer<G> {
         } else if !ts.is_empty() {
             self.push_single_decomp(depth + 1, &g, ts);
         } else {
-            // crate::simplify::full_simp(&mut g);
-            self.scalar = &self.scalar + g.scalar();
-            self.nterms += 1;
-            if g.num_vertices() != 0 {
-                println!("{}", g.to_dot());
-                println!("WARNING: graph was not fully reduced");
-                // println!("{}", g.to_dot());
+            let t = self.term(depth, g);
+            self.add_term(t);
+        }
+    }
+
+    /// Turn a graph with nothing left to decompose into a term
+    fn term(&self, depth: usize, g: G) -> TermResult<G> {
+        // crate::simplify::full_simp(&mut g);
+        if g.num_vertices() != 0 {
+            println!("{}", g.to_dot());
+            println!("WARNING: graph was not fully reduced");
+            // println!("{}", g.to_dot());
+        }
+        TermResult {
+            scalar: g.scalar().clone(),
+            depth,
+            graph: if self.save { Some(g) } else { None },
+            nterms: 1,
+        }
+    }
+
+    /// Decompose each component of a disconnected graph separately, and
+    /// multiply the results into a single term
+    fn product_term(&mut self, depth: usize, g: &G, comps: &[FxHashSet<V>]) -> TermResult<G> {
+        let mut scalar = g.scalar().clone();
+        let mut nterms = 0;
+        for c in comps {
+            let mut d = self.child(&g.induced_subgraph(c));
+            d.save(false)
+                .with_float_accumulation(false)
+                .record_tree(false)
+                .decomp_all();
+            nterms += d.nterms;
+            self.moves += d.moves;
+            if let Some(e) = d.error {
+                self.error = Some(e);
+                break;
+            }
+            match scalar.checked_mul(&d.scalar) {
+                Ok(s) => scalar = s,
+                Err(e) => {
+                    self.error = Some(e);
+                    break;
+                }
             }
-            if self.save {
-                self.done.push(g);
+            if scalar.is_zero() {
+                break;
             }
         }
+
+        TermResult {
+            scalar,
+            depth,
+            graph: None,
+            nterms,
+        }
+    }
+
+    /// Add a term to the running sum
+    fn add_term(&mut self, t: TermResult<G>) {
+        if let Some(s) = &mut self.float_sum {
+            s.add(&t.scalar);
+            self.scalar = s.scalar();
+        } else if !self.add_to_bigint_sum(&t.scalar) {
+            match self.scalar.checked_add(&t.scalar) {
+                Ok(s) => self.scalar = s,
+                Err(e) => {
+                    self.error = Some(e);
+                    return;
+                }
+            }
+        }
+        self.nterms += t.nterms;
+        if let Some(n) = &self.shared_nterms {
+            n.fetch_add(t.nterms, Ordering::Relaxed);
+        }
+        if let Some(g) = t.graph {
+            self.done.push(g);
+        }
     }
 
     /// Pick the first <= 6 T gates from the given graph
@@ -286,39 +1090,51 @@ impl<G: GraphLike> Decomposer<G> {
 
     /// Returns a best occurrence of a cat state
     /// The fist vertex in the result is the Clifford spider
+    ///
+    /// Cats with 4 legs are preferred, then 6, 5, 3 and any larger cat. See
+    /// [is_cat] for what counts as a cat.
     pub fn cat_ts(g: &G) -> Vec<V> {
-        // the graph g is supposed to be completely simplified
         let preferred_order = [4, 6, 5, 3];
         let mut res = vec![];
         let mut index = None;
         for v in g.vertices() {
-            if g.phase(v).is_pauli() {
+            if is_cat(g, v) {
                 let mut neigh = g.neighbor_vec(v);
-                if neigh.len() <= 6 {
-                    if let Some(this_ind) = preferred_order.iter().position(|&r| r == neigh.len()) {
-                        match index {
-                            Some(ind) if this_ind < ind => {
-                                res = vec![v];
-                                res.append(&mut neigh);
-                                index = Some(this_ind);
-                            }
-                            None => {
-                                res = vec![v];
-                                res.append(&mut neigh);
-                                index = Some(this_ind);
-                            }
-                            _ => (),
-                        }
+                let this_ind = preferred_order
+                    .iter()
+                    .position(|&r| r == neigh.len())
+                    .unwrap_or(preferred_order.len());
+                match index {
+                    Some(ind) if this_ind < ind => {
+                        res = vec![v];
+                        res.append(&mut neigh);
+                        index = Some(this_ind);
                     }
-                    if index == Some(0) {
-                        break;
+                    None => {
+                        res = vec![v];
+                        res.append(&mut neigh);
+                        index = Some(this_ind);
                     }
+                    _ => (),
+                }
+                if index == Some(0) {
+                    break;
                 }
             }
         }
         res
     }
 
+    /// Perform a decomposition along a vertex cut
+    fn push_cut_decomp(&mut self, depth: usize, g: &G, verts: &[V]) -> &mut Self {
+        self.record_move(DecompMove::Cut, verts);
+        let tcount = g.tcount();
+        for g in Decomposer::cut_terms(g, verts) {
+            self.simp_and_push(depth, tcount, g);
+        }
+        self
+    }
+
     fn push_decomp(
         &mut self,
         fs: &[fn(&G, &[V]) -> G],
@@ -326,26 +1142,40 @@ impl<G: GraphLike> Decomposer<G> {
         g: &G,
         verts: &[V],
     ) -> &mut Self {
+        let tcount = g.tcount();
         for f in fs {
-            let mut g = f(g, verts);
-            match self.simp_func {
-                FullSimp => {
+            self.simp_and_push(depth, tcount, f(g, verts));
+        }
+
+        self
+    }
+
+    /// Simplify a new term and push it on the stack, unless it is zero
+    ///
+    /// The T-count of the graph the term was decomposed from is used by
+    /// [SimpPolicy::Adaptive].
+    fn simp_and_push(&mut self, depth: usize, parent_tcount: usize, mut g: G) {
+        match self.simp_func {
+            FullSimp => {
+                if self.simp_policy.full_simp_due(depth, parent_tcount, &g) {
                     crate::simplify::full_simp(&mut g);
-                }
-                CliffordSimp => {
+                } else {
                     crate::simplify::clifford_simp(&mut g);
                 }
-                _ => {}
             }
+            CliffordSimp => {
+                crate::simplify::clifford_simp(&mut g);
+            }
+            _ => {}
+        }
 
-            // let comps = g.component_vertices();
-            // if comps.len() > 1 {
-            //     println!("GOT {} COMPONENTS ({})", comps.len(), comps.iter().map(|c| c.len()).format(","));
-            // }
+        // let comps = g.component_vertices();
+        // if comps.len() > 1 {
+        //     println!("GOT {} COMPONENTS ({})", comps.len(), comps.iter().map(|c| c.len()).format(","));
+        // }
+        if !g.is_zero() {
             self.stack.push_back((depth, g));
         }
-
-        self
     }
 
     /// Perform the Bravyi-Smith-Smolin decomposition of 6 T gates
@@ -358,6 +1188,7 @@ impl<G: GraphLike> Decomposer<G> {
     /// equation (11) itself.
     ///
     fn push_bss_decomp(&mut self, depth: usize, g: &G, verts: &[V]) -> &mut Self {
+        self.record_move(DecompMove::Bss, verts);
         self.push_decomp(
             &[
                 Decomposer::replace_b60,
@@ -377,6 +1208,7 @@ impl<G: GraphLike> Decomposer<G> {
     /// Perform a decomposition of 2 T gates in the symmetric 2-qubit
     /// space spanned by stabilisers
     fn push_sym_decomp(&mut self, depth: usize, g: &G, verts: &[V]) -> &mut Self {
+        self.record_move(DecompMove::TPair, verts);
         self.push_decomp(
             &[Decomposer::replace_bell_s, Decomposer::replace_epr],
             depth,
@@ -387,6 +1219,7 @@ impl<G: GraphLike> Decomposer<G> {
 
     /// Replace a single T gate with its decomposition
     fn push_single_decomp(&mut self, depth: usize, g: &G, verts: &[V]) -> &mut Self {
+        self.record_move(DecompMove::SingleT, verts);
         self.push_decomp(
             &[Decomposer::replace_t0, Decomposer::replace_t1],
             depth,
@@ -397,6 +1230,7 @@ impl<G: GraphLike> Decomposer<G> {
 
     /// Perform a decomposition of 5 T-spiders, with one remaining
     fn push_magic5_from_cat_decomp(&mut self, depth: usize, g: &G, verts: &[V]) -> &mut Self {
+        self.record_move(DecompMove::Magic5, verts);
         //println!("magic5");
         self.push_decomp(
             &[
@@ -412,81 +1246,12 @@ impl<G: GraphLike> Decomposer<G> {
 
     /// Perform a decomposition of cat states
     fn push_cat_decomp(&mut self, depth: usize, g: &G, verts: &[V]) -> &mut Self {
-        // verts[0] is a 0- or pi-spider, linked to all and only to vs in verts[1..] which are T-spiders
-        let mut g = g.clone(); // that is annoying ...
-        let mut verts = Vec::from(verts);
-        if g.phase(verts[0]).is_pauli() {
-            g.set_phase(verts[0], Rational64::new(0, 1));
-            let mut neigh = g.neighbor_vec(verts[1]);
-            neigh.retain(|&x| x != verts[0]);
-            for &v in &neigh {
-                g.add_to_phase(v, Rational64::new(1, 1));
-            }
-            let tmp = g.phase(verts[1]);
-            *g.scalar_mut() *= ScalarN::from_phase(tmp);
-            g.set_phase(verts[1], g.phase(verts[1]) * -1);
-        }
-        if [3, 5].contains(&verts[1..].len()) {
-            let w = g.add_vertex(VType::Z);
-            let v = g.add_vertex(VType::Z);
-            g.add_edge_with_type(v, w, EType::H);
-            g.add_edge_with_type(v, verts[0], EType::H);
-            verts.push(v);
-        }
-        if verts[1..].len() == 6 {
-            self.push_decomp(
-                &[
-                    Decomposer::replace_cat6_0,
-                    Decomposer::replace_cat6_1,
-                    Decomposer::replace_cat6_2,
-                ],
-                depth,
-                &g,
-                &verts,
-            )
-        } else if verts[1..].len() == 4 {
-            self.push_decomp(
-                &[Decomposer::replace_cat4_0, Decomposer::replace_cat4_1],
-                depth,
-                &g,
-                &verts,
-            )
-        } else {
-            println!("this shouldn't be printed");
-            self
-        }
-    }
-
-    fn replace_cat6_0(g: &G, verts: &[V]) -> G {
-        let mut g = g.clone();
-        *g.scalar_mut() *= ScalarN::Exact(-1, vec![1, 0, 0, 0]);
-        for &v in &verts[1..] {
-            g.add_to_phase(v, Rational64::new(-1, 4));
-            g.set_edge_type(v, verts[0], EType::N);
-        }
-        g.set_phase(verts[0], Rational64::new(-1, 2));
-        g
-    }
-
-    fn replace_cat6_1(g: &G, verts: &[V]) -> G {
-        let mut g = g.clone();
-        *g.scalar_mut() *= ScalarN::Exact(-1, vec![-1, 0, 1, 0]);
-        for &v in &verts[1..] {
-            g.add_to_phase(v, Rational64::new(-1, 4));
+        self.record_move(DecompMove::Cat, verts);
+        let tcount = g.tcount();
+        for g in Decomposer::cat_terms(g, verts) {
+            self.simp_and_push(depth, tcount, g);
         }
-        g
-    }
-
-    fn replace_cat6_2(g: &G, verts: &[V]) -> G {
-        let mut g = g.clone();
-        *g.scalar_mut() *= ScalarN::Exact(7, vec![0, -1, 0, 0]);
-        for i in 1..verts.len() {
-            g.add_to_phase(verts[i], Rational64::new(-1, 4));
-            for j in i + 1..verts.len() {
-                g.add_edge_smart(verts[i], verts[j], EType::H);
-            }
-        }
-        g
+        self
     }
 
     fn replace_magic5_0(g: &G, verts: &[V]) -> G {
@@ -530,27 +1295,6 @@ impl<G: GraphLike> Decomposer<G> {
         g
     }
 
-    fn replace_cat4_0(g: &G, verts: &[V]) -> G {
-        let mut g = g.clone();
-        *g.scalar_mut() *= ScalarN::Exact(0, vec![0, 0, 1, 0]);
-        for &v in &verts[1..] {
-            g.add_to_phase(v, Rational64::new(-1, 4));
-        }
-        g
-    }
-
-    fn replace_cat4_1(g: &G, verts: &[V]) -> G {
-        // same as replace_cat6_0, only with a different scalar
-        let mut g = g.clone();
-        *g.scalar_mut() *= ScalarN::Exact(-1, vec![1, 0, -1, 0]);
-        for &v in &verts[1..] {
-            g.add_to_phase(v, Rational64::new(-1, 4));
-            g.set_edge_type(v, verts[0], EType::N);
-        }
-        g.set_phase(verts[0], Rational64::new(-1, 2));
-        g
-    }
-
     fn replace_b60(g: &G, verts: &[V]) -> G {
         // println!("replace_b60");
         let mut g = g.clone();
@@ -693,9 +1437,961 @@ impl<G: GraphLike> Decomposer<G> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::circuit::Circuit;
     use crate::tensor::*;
     use crate::vec_graph::Graph;
 
+    #[test]
+    fn zero_terms() {
+        let mut c = Circuit::new(3);
+        c.x(0).cx(0, 1).t(1).ccz(0, 1, 2).t(2).t(1).cx(1, 2).t(2);
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&[BasisElem::Z0; 3]);
+        g.plug_outputs(&[BasisElem::Z0, BasisElem::X0, BasisElem::X0]);
+
+        let mut d = Decomposer::new(&g);
+        d.with_full_simp().decomp_all();
+        assert!(d.scalar.is_zero());
+        assert_eq!(d.nterms, 0);
+    }
+
+    /// The <0...0|C|0...0> amplitude of a random Clifford+T circuit, as a
+    /// simplified closed graph
+    fn random_amplitude(qubits: usize, depth: usize, p_t: f32, seed: u64) -> Graph {
+        let c = Circuit::random()
+            .seed(seed)
+            .qubits(qubits)
+            .depth(depth)
+            .p_t(p_t)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; qubits]);
+        g.plug_outputs(&vec![BasisElem::Z0; qubits]);
+        crate::simplify::full_simp(&mut g);
+        g
+    }
+
+    #[test]
+    fn adaptive_strategy() {
+        // a hidden shift circuit, which has cats, between layers of random T gates
+        let q = 8;
+        let (hs, _) = Circuit::random_hidden_shift()
+            .seed(0)
+            .qubits(q)
+            .n_ccz(4)
+            .clifford_depth(20)
+            .build();
+        let layer = |seed| {
+            Circuit::random()
+                .seed(seed)
+                .qubits(q)
+                .depth(80)
+                .clifford_t(0.2)
+                .build()
+        };
+        let c = layer(100) + &hs + &layer(200);
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; q]);
+        g.plug_outputs(&vec![BasisElem::Z0; q]);
+        crate::simplify::full_simp(&mut g);
+
+        let ds: Vec<_> = [
+            DecompStrategy::TGates,
+            DecompStrategy::Cats,
+            DecompStrategy::Adaptive,
+        ]
+        .into_iter()
+        .map(|s| {
+            let mut d = Decomposer::new(&g);
+            d.with_strategy(s).with_full_simp().decomp_all();
+            d
+        })
+        .collect();
+        assert_eq!(ds[1].scalar, ds[0].scalar);
+        assert_eq!(ds[2].scalar, ds[0].scalar);
+        let nterms: Vec<_> = ds.iter().map(|d| d.nterms).collect();
+        assert_eq!(nterms, [305, 28, 22]);
+
+        let moves: Vec<_> = ds.iter().map(|d| d.stats().moves).collect();
+        assert_eq!(moves[0].cat + moves[0].magic5, 0);
+        assert_eq!(moves[1].bss, 0);
+        // the same graphs get different decompositions
+        assert_ne!(moves[2], moves[1]);
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        for seed in 1..4 {
+            let g = random_amplitude(16, 300, 0.15, seed);
+            assert!(g.tcount() > 0);
+            let mut scalars = vec![];
+            for cats in [false, true] {
+                let mut d = Decomposer::new(&g);
+                d.use_cats(cats).with_full_simp();
+                let dp = d.clone().decomp_parallel(2);
+                d.decomp_all();
+                assert_eq!(d.scalar, dp.scalar, "seed {}, cats {}", seed, cats);
+                assert_eq!(d.nterms, dp.nterms, "seed {}, cats {}", seed, cats);
+                scalars.push(d.scalar);
+            }
+            // cat decompositions should give the same value
+            assert_eq!(scalars[0], scalars[1], "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn iter_terms_fold() {
+        let g = random_amplitude(16, 300, 0.15, 2);
+        for cats in [false, true] {
+            let mut d = Decomposer::new(&g);
+            d.use_cats(cats).with_full_simp();
+            let terms = d.clone().iter_terms();
+            d.decomp_all();
+
+            let mut scalar = ScalarN::zero();
+            let mut nterms = 0;
+            for t in terms {
+                assert!(t.depth > 0);
+                assert!(t.graph.is_none());
+                scalar += t.scalar;
+                nterms += t.nterms;
+            }
+            assert_eq!(scalar, d.scalar);
+            assert_eq!(nterms, d.nterms);
+        }
+
+        let mut d = Decomposer::new(&g);
+        d.with_full_simp().save(true);
+        for t in d.iter_terms() {
+            assert_eq!(t.graph.unwrap().scalar(), &t.scalar);
+        }
+    }
+
+    #[test]
+    fn progress() {
+        use std::sync::Mutex;
+
+        let g = random_amplitude(16, 300, 0.15, 2);
+        let infos = Arc::new(Mutex::new(vec![]));
+        let mut d = Decomposer::new(&g);
+        let infos1 = infos.clone();
+        d.use_cats(true)
+            .with_full_simp()
+            .on_progress(1, move |p| infos1.lock().unwrap().push(p))
+            .decomp_all();
+
+        // one report per term, and one at the end
+        let infos = infos.lock().unwrap();
+        assert_eq!(infos.len(), d.nterms + 1);
+        for w in infos.windows(2) {
+            assert!(w[0].terms <= w[1].terms);
+            assert!(w[0].fraction <= w[1].fraction);
+            assert!(w[0].estimate <= w[1].estimate);
+            assert!(w[0].elapsed <= w[1].elapsed);
+        }
+        assert!(infos.iter().all(|p| p.estimate >= p.terms as f64));
+        let last = infos[infos.len() - 1];
+        assert_eq!(last.terms, d.nterms);
+        assert_eq!(last.fraction, 1.0);
+        // the branches of a depth-first decomposition add up exactly
+        assert!((infos[infos.len() - 2].fraction - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn seeded() {
+        let g = random_amplitude(16, 300, 0.15, 2);
+        let seeded = |seed| {
+            let mut d = Decomposer::new(&g);
+            d.random_t(true).with_full_simp().with_seed(seed);
+            d
+        };
+
+        // the same intermediate graphs, and the same number of terms
+        let mut d0 = seeded(1);
+        let mut d1 = seeded(1);
+        d0.decomp_until_depth(2);
+        d1.decomp_until_depth(2);
+        assert_eq!(d0.stack, d1.stack);
+        d0.decomp_all();
+        d1.decomp_all();
+        assert_eq!(d0.nterms, d1.nterms);
+        assert_eq!(d0.scalar, d1.scalar);
+        // other seeds give between 20 and 34 terms, so a change here means
+        // the decomposition itself changed
+        assert_eq!(d0.nterms, 36);
+
+        for threads in [1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let dp = pool.install(|| seeded(1).decomp_parallel(2));
+            assert_eq!(dp.nterms, 36, "{} threads", threads);
+            assert_eq!(dp.scalar, d0.scalar);
+        }
+    }
+
+    #[test]
+    fn stats() {
+        for seed in 1..4 {
+            let g = random_amplitude(16, 300, 0.15, seed);
+            let t = g.tcount();
+            let mut d = Decomposer::new(&g);
+            d.with_full_simp();
+            assert_eq!(
+                d.stats(),
+                DecompStats {
+                    simp_policy: Some(SimpPolicy::Full),
+                    ..Default::default()
+                }
+            );
+            let dp = d.clone().decomp_parallel(2);
+            d.decomp_all();
+
+            let s = d.stats();
+            assert_eq!(s.tcount, t);
+            assert_eq!(s.nterms, d.nterms);
+            // BSS gives at most 7 terms for every 6 T gates, and the rest
+            // are decomposed in pairs
+            let bss = terms_for_tcount(t).log2() / t as f64;
+            assert!(s.alpha > 0.0 && s.alpha <= bss, "{}", s);
+            assert_eq!(s.alpha, (d.nterms as f64).log2() / t as f64);
+
+            let sp = dp.stats();
+            assert_eq!(
+                (sp.tcount, sp.nterms, sp.alpha),
+                (s.tcount, s.nterms, s.alpha)
+            );
+        }
+
+        let s = DecompStats {
+            tcount: 12,
+            nterms: 49,
+            seconds: 0.5,
+            alpha: 0.4679,
+            moves: MoveCounts::default(),
+            simp_policy: Some(SimpPolicy::Full),
+        };
+        assert_eq!(s.to_string(), "T-count 12, 49 terms, alpha 0.4679, 0.500s");
+        let json = serde_json::to_value(s).unwrap();
+        assert_eq!(json["nterms"], 49);
+    }
+
+    #[test]
+    fn term_cache() {
+        // two copies of the same amplitude, so many branches end up with
+        // isomorphic graphs
+        let g0 = random_amplitude(16, 300, 0.15, 2);
+        let mut g = g0.clone();
+        g.append_graph(&g0);
+        assert!(g.tcount() > 0);
+
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true).with_full_simp();
+        let mut dc = d.clone();
+        dc.use_term_cache(100, 12).decomp_all();
+        d.decomp_all();
+        assert_eq!(d.cache_stats(), None);
+
+        let stats = dc.cache_stats().unwrap();
+        assert!(stats.hits > 0);
+        assert_eq!(stats.len, stats.misses);
+        assert_eq!(dc.scalar, d.scalar);
+        assert!(dc.nterms < d.nterms);
+
+        // a cache too small to keep anything useful still gives the same value
+        let mut ds = Decomposer::new(&g);
+        ds.use_cats(true)
+            .with_full_simp()
+            .use_term_cache(1, 12)
+            .decomp_all();
+        let stats = ds.cache_stats().unwrap();
+        assert_eq!(stats.len, 1);
+        assert_eq!(stats.evictions + 1, stats.misses);
+        assert_eq!(ds.scalar, d.scalar);
+    }
+
+    #[test]
+    fn closed_probabilities() {
+        for seed in 1..4 {
+            let qubits = 5;
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(qubits)
+                .depth(40)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+            let mut state: Graph = c.to_graph();
+            state.plug_inputs(&vec![BasisElem::Z0; qubits]);
+            let amps = state.to_tensorf();
+            let a = amps[&[0; 5][..]];
+            let marginal: f64 = amps
+                .indexed_iter()
+                .filter(|(ix, _)| ix[0] == 0)
+                .map(|(_, x)| x.norm_sqr())
+                .sum();
+
+            // |<0...0|C|0...0>|^2, as an amplitude times its conjugate
+            let mut amp = state.clone();
+            amp.plug_outputs(&vec![BasisElem::Z0; qubits]);
+            let mut prob = amp.clone();
+            prob.append_graph(&amp.to_adjoint());
+
+            // the probability of measuring 0 on the first qubit, as C^dag P C
+            let mut half = state.clone();
+            half.plug_outputs(&[BasisElem::Z0]);
+            let mut marg = half.clone();
+            marg.plug(&half.to_adjoint());
+
+            for (g, expected) in [(prob, a.norm_sqr()), (marg, marginal)] {
+                assert!(g.inputs().is_empty() && g.outputs().is_empty());
+                for cats in [false, true] {
+                    let mut d = Decomposer::new(&g);
+                    d.use_cats(cats).use_cut_decomp(2).with_full_simp();
+                    let dp = d.clone().decomp_parallel(2);
+                    d.decomp_all();
+                    assert_eq!(d.scalar, dp.scalar);
+                    let p = d.scalar.complex_value();
+                    assert!(
+                        (p.re - expected).abs() < 1e-10 && p.im.abs() < 1e-10,
+                        "seed {}, cats {}: {} != {}",
+                        seed,
+                        cats,
+                        p,
+                        expected
+                    );
+                }
+            }
+        }
+
+        // a graph with nothing left is just its scalar
+        let mut d = Decomposer::new(&Graph::new());
+        d.with_full_simp().use_cats(true).decomp_all();
+        assert_eq!(d.scalar, ScalarN::one());
+        assert_eq!(d.nterms, 1);
+    }
+
+    #[test]
+    fn limits() {
+        let g = random_amplitude(16, 300, 0.15, 2);
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true).with_full_simp();
+        let mut full = d.clone();
+        full.decomp_all();
+        assert!(full.is_complete());
+
+        d.deadline(Instant::now()).decomp_all();
+        assert_eq!(d.nterms, 0);
+        assert!(!d.is_complete());
+
+        d.deadline = None;
+        d.term_limit(3).decomp_all();
+        assert!(d.nterms >= 3 && d.nterms < full.nterms);
+        assert!(!d.is_complete());
+
+        // carry on from where it stopped
+        d.term_limit(usize::MAX).decomp_all();
+        assert!(d.is_complete());
+        assert_eq!(d.nterms, full.nterms);
+        assert_eq!(d.scalar, full.scalar);
+    }
+
+    #[test]
+    fn parallel_limits() {
+        let g = random_amplitude(16, 300, 0.15, 2);
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true).with_full_simp();
+        let mut full = d.clone();
+        full.decomp_all();
+
+        let mut dp = d.clone();
+        dp.deadline(Instant::now());
+        let dp = dp.decomp_parallel(2);
+        assert_eq!(dp.nterms, 0);
+        assert!(!dp.is_complete());
+
+        // the limit is shared, so each task stops soon after it is reached
+        let mut dp = d.clone();
+        dp.term_limit(3);
+        let mut dp = dp.decomp_parallel(2);
+        assert!(dp.nterms >= 3 && dp.nterms < full.nterms);
+        assert!(!dp.is_complete());
+
+        dp.term_limit(usize::MAX).decomp_all();
+        assert!(dp.is_complete());
+        assert_eq!(dp.nterms, full.nterms);
+        assert_eq!(dp.scalar, full.scalar);
+    }
+
+    #[test]
+    fn scalar_overflow() {
+        let big = ScalarN::Exact(0, vec![isize::MAX; 4]);
+
+        // a term whose scalar overflows
+        let mut g = Graph::new();
+        let v = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let w = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        g.add_edge_with_type(v, w, EType::H);
+        *g.scalar_mut() = big.clone();
+        let mut d = Decomposer::new(&g);
+        d.with_full_simp();
+        // the first term is (1 + i) times the scalar of g
+        assert_eq!(d.clone().iter_terms().count(), 0);
+        d.decomp_all();
+        assert_eq!(d.error(), Some(ScalarError::Overflow));
+        assert_eq!(d.nterms, 0);
+        assert!(!d.is_complete());
+        assert_eq!(d.iter_terms().count(), 0);
+
+        // a sum which overflows
+        let mut g = Graph::new();
+        *g.scalar_mut() = big;
+        let mut d = Decomposer::new(&g);
+        d.stack.push_back((0, g.clone()));
+        d.decomp_all();
+        assert_eq!(d.error(), Some(ScalarError::Overflow));
+        assert_eq!(d.nterms, 1);
+        assert!(!d.is_complete());
+    }
+
+    #[test]
+    fn checkpoint_resume() {
+        let g = random_amplitude(16, 300, 0.15, 2);
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true).with_selector(LargestCat).with_full_simp();
+        let mut full = d.clone();
+        full.decomp_all();
+
+        let path = std::env::temp_dir().join(format!("quizx-checkpoint-{}", std::process::id()));
+        d.term_limit(3).decomp_all();
+        assert!(!d.is_complete());
+        d.save_checkpoint(&path).unwrap();
+
+        let mut d1: Decomposer<Graph> = Decomposer::resume(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(d1.selector_name(), "largest");
+        assert_eq!(d1.nterms, d.nterms);
+        assert_eq!(d1.scalar, d.scalar);
+        assert_eq!(d1.stack.len(), d.stack.len());
+        d1.decomp_all();
+        assert!(d1.is_complete());
+        assert_eq!(d1.scalar, full.scalar);
+
+        // a run killed after an automatic checkpoint
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true)
+            .with_full_simp()
+            .checkpoint_every(2, &path)
+            .term_limit(5)
+            .decomp_all();
+        let mut d1: Decomposer<Graph> = Decomposer::resume(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(d1.nterms >= 2 && d1.nterms <= d.nterms);
+        assert!(d.last_checkpoint_error().is_none());
+        d1.decomp_all();
+        assert_eq!(d1.scalar, full.scalar);
+
+        // a checkpoint which can't be written doesn't stop the run
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true)
+            .with_full_simp()
+            .checkpoint_every(2, path.join("missing"))
+            .decomp_all();
+        assert!(matches!(
+            d.last_checkpoint_error(),
+            Some(CheckpointError::Io(_))
+        ));
+        assert_eq!(d.scalar, full.scalar);
+    }
+
+    #[test]
+    fn float_accumulation() {
+        // compensated summation keeps the small terms
+        let mut s = FloatSum::default();
+        s.add(&ScalarN::real(1e16));
+        for _ in 0..10 {
+            s.add(&ScalarN::one());
+        }
+        s.add(&ScalarN::real(-1e16));
+        assert_eq!(s.value(), num::complex::Complex::new(10.0, 0.0));
+        assert_eq!(s.nterms(), 12);
+
+        for seed in 1..4 {
+            let g = random_amplitude(16, 300, 0.15, seed);
+            let mut d = Decomposer::new(&g);
+            d.use_cats(true).with_full_simp();
+            let mut df = d.clone();
+            df.with_float_accumulation(true);
+            let dp = df.clone().decomp_parallel(2);
+            d.decomp_all();
+            df.decomp_all();
+
+            let exact = d.scalar.complex_value();
+            for d1 in [&df, &dp] {
+                let s = d1.float_sum().unwrap();
+                assert_eq!(d1.scalar, s.scalar());
+                assert_eq!(s.nterms(), d.nterms);
+                let err = (s.value() - exact).norm();
+                assert!(
+                    err <= s.error_bound(),
+                    "seed {}: {} > {}",
+                    seed,
+                    err,
+                    s.error_bound()
+                );
+                assert!(s.error_bound() < 1e-12 * s.value().norm().max(1.0));
+            }
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint_accumulation() {
+        // terms too far apart to add with isize coefficients
+        let g = Graph::new();
+        let mut g1 = Graph::new();
+        *g1.scalar_mut() = ScalarN::sqrt2_pow(140);
+        let mut d = Decomposer::new(&g);
+        d.stack.push_back((0, g1));
+        d.with_bigint_accumulation(true).decomp_all();
+        let s = ScalarB::one() + ScalarB::sqrt2_pow(140);
+        assert_eq!(d.bigint_sum(), Some(&s));
+        assert!(d.scalar.is_float());
+        assert_eq!(d.nterms, 2);
+
+        // the sum is kept by checkpoints
+        let path = std::env::temp_dir().join(format!("quizx-bigint-{}", std::process::id()));
+        d.save_checkpoint(&path).unwrap();
+        let d1: Decomposer<Graph> = Decomposer::resume(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(d1.bigint_sum(), Some(&s));
+
+        for seed in 1..3 {
+            let g = random_amplitude(16, 300, 0.15, seed);
+            let mut d = Decomposer::new(&g);
+            d.use_cats(true).with_full_simp();
+            let mut db = d.clone();
+            db.with_bigint_accumulation(true);
+            let dp = db.clone().decomp_parallel(2);
+            d.decomp_all();
+            db.decomp_all();
+            for d1 in [&db, &dp] {
+                assert_eq!(d1.scalar, d.scalar);
+                assert_eq!(d1.bigint_sum(), Some(&ScalarB::from_scalar(&d.scalar)));
+            }
+        }
+    }
+
+    #[test]
+    fn approx() {
+        for seed in 1..4 {
+            let g = random_amplitude(20, 400, 0.1, seed);
+            let mut d = Decomposer::new(&g);
+            d.use_cats(true).with_full_simp();
+            let mut exact = d.clone();
+            exact.decomp_all();
+            let v = exact.scalar.complex_value();
+            assert!(v.norm() <= value_bound(&g));
+
+            for eps in [0.5, 0.1, 0.0] {
+                let mut d1 = d.clone();
+                let a = d1.decomp_approx(eps);
+                let err = (a.scalar.complex_value() - v).norm();
+                assert!(
+                    err <= a.error_bound,
+                    "seed {}: {} > {}",
+                    seed,
+                    err,
+                    a.error_bound
+                );
+                assert!(a.error_bound <= eps * a.scalar.complex_value().norm());
+                assert_eq!(a.complete, d1.is_complete());
+                if eps == 0.0 {
+                    assert!(a.complete);
+                    assert_eq!(a.scalar, exact.scalar);
+                    assert_eq!(d1.nterms, exact.nterms);
+                } else {
+                    assert!(d1.nterms < exact.nterms);
+                }
+
+                // the rest of the branches are still there
+                d1.decomp_all();
+                assert_eq!(d1.scalar, exact.scalar);
+                assert_eq!(d1.nterms, exact.nterms);
+            }
+        }
+
+        // a graph with no vertices is bounded by its scalar
+        let mut g = Graph::new();
+        *g.scalar_mut() = ScalarN::sqrt2_pow(-3);
+        let b = value_bound(&g);
+        assert_eq!(b, g.scalar().complex_value().norm());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored --nocapture"]
+    fn float_accumulation_terms() {
+        let g = random_amplitude(50, 1000, 0.12, 1);
+        let mut d = Decomposer::new(&g);
+        d.with_full_simp();
+        let terms: Vec<_> = d.iter_terms().map(|t| t.scalar).collect();
+        println!("T-count: {}, {} terms", g.tcount(), terms.len());
+
+        let time = Instant::now();
+        let mut exact = ScalarN::zero();
+        for t in &terms {
+            exact = &exact + t;
+        }
+        println!("exact: {:.3}s", time.elapsed().as_secs_f64());
+
+        let time = Instant::now();
+        let mut s = FloatSum::default();
+        for t in &terms {
+            s.add(t);
+        }
+        println!("float: {:.3}s", time.elapsed().as_secs_f64());
+
+        let err = (s.value() - exact.complex_value()).norm();
+        println!("error {:e}, bound {:e}", err, s.error_bound());
+        assert!(err <= s.error_bound());
+    }
+
+    /// Check that the tree recorded by d has the moves and terms of d
+    fn check_tree(d: &Decomposer<Graph>) {
+        let t = d.tree().unwrap();
+        for (i, n) in t.nodes.iter().enumerate() {
+            for &c in &n.children {
+                assert!(c > i);
+                assert_eq!(t.nodes[c].parent, Some(i));
+            }
+            assert!(n.decomp.is_some() || n.children.is_empty());
+        }
+        let terms = t.subtree_terms();
+        assert_eq!(t.roots().map(|r| terms[r]).sum::<usize>(), d.nterms);
+
+        let mut moves = MoveCounts::default();
+        for (m, s) in t.move_stats() {
+            for _ in 0..s.count {
+                moves.record(m);
+            }
+        }
+        assert_eq!(moves, d.stats().moves);
+    }
+
+    #[test]
+    fn record_tree() {
+        let g = random_amplitude(25, 400, 0.15, 1);
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true).with_full_simp();
+        assert!(d.tree().is_none());
+        d.record_tree(true);
+        let mut d_par = d.clone();
+        let mut d_approx = d.clone();
+
+        d.decomp_all();
+        check_tree(&d);
+        let t = d.tree().unwrap();
+        assert_eq!(t.roots().collect::<Vec<_>>(), [0]);
+        assert_eq!(t.nodes[0].tcount, g.tcount());
+        let stats = t.move_stats();
+        assert!(stats[&DecompMove::Cat].count > 0);
+        let json = serde_json::to_string(t).unwrap();
+        assert!(json.contains("\"decomp\":\"cat\""));
+        assert_eq!(serde_json::from_str::<DecompTree>(&json).unwrap(), *t);
+
+        d_par = d_par.decomp_parallel(2);
+        check_tree(&d_par);
+        assert_eq!(d_par.tree().unwrap().len(), t.len());
+        assert_eq!(d_par.tree().unwrap().move_stats(), stats);
+
+        d_approx.decomp_approx(0.0);
+        check_tree(&d_approx);
+        assert_eq!(d_approx.tree().unwrap().move_stats(), stats);
+
+        let mut d = Decomposer::new(&g);
+        d.with_strategy(DecompStrategy::Adaptive)
+            .with_full_simp()
+            .record_tree(true)
+            .decomp_all();
+        check_tree(&d);
+
+        d.record_tree(false);
+        assert!(d.tree().is_none());
+    }
+
+    const SIMP_POLICIES: [SimpPolicy; 5] = [
+        SimpPolicy::Full,
+        SimpPolicy::CliffordOnly,
+        SimpPolicy::EveryN(2),
+        SimpPolicy::EveryN(3),
+        SimpPolicy::Adaptive,
+    ];
+
+    #[test]
+    fn simp_policies() {
+        let g = random_amplitude(25, 400, 0.1, 1);
+        let mut full = Decomposer::new(&g);
+        full.with_full_simp().decomp_all();
+        for p in SIMP_POLICIES {
+            let mut d = Decomposer::new(&g);
+            d.with_simp_policy(p).decomp_all();
+            assert_eq!(d.scalar, full.scalar, "{:?}", p);
+            assert_eq!(d.stats().simp_policy, Some(p));
+            if p == SimpPolicy::CliffordOnly {
+                assert!(d.nterms > full.nterms);
+            }
+        }
+
+        let mut d = Decomposer::new(&g);
+        d.with_clifford_simp();
+        assert_eq!(d.simp_policy(), Some(SimpPolicy::CliffordOnly));
+        d.with_simp(NoSimp);
+        assert_eq!(d.stats().simp_policy, None);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored --nocapture"]
+    fn simp_policy_tradeoff() {
+        let g = random_amplitude(40, 800, 0.1, 1);
+        println!("T-count: {}", g.tcount());
+        for p in SIMP_POLICIES {
+            let mut d = Decomposer::new(&g);
+            d.with_simp_policy(p).use_cats(true).decomp_all();
+            let stats = d.stats();
+            println!("{:?}: {} terms, {:.2}s", p, stats.nterms, stats.seconds);
+        }
+    }
+
+    /// Decompose g with cats, using each of the built-in selectors
+    fn decomp_selectors(g: &Graph) -> Vec<Decomposer<Graph>> {
+        let mut ds = vec![Decomposer::new(g); 4];
+        ds[0].with_selector(FirstCat);
+        ds[1].with_selector(LargestCat);
+        ds[2].with_selector(MostConnectedCat);
+        ds[3].with_selector(LookaheadCat);
+        for d in &mut ds {
+            d.use_cats(true).with_full_simp().decomp_all();
+        }
+        ds
+    }
+
+    #[test]
+    fn selectors_agree() {
+        for seed in 1..4 {
+            let g = random_amplitude(16, 300, 0.15, seed);
+            let ds = decomp_selectors(&g);
+            for d in &ds[1..] {
+                assert_eq!(d.scalar, ds[0].scalar, "{}", d.selector_name());
+            }
+            if seed == 3 {
+                // the selector is used, and makes a difference
+                assert_eq!(ds[0].nterms, 9);
+                assert_eq!(ds[1].nterms, 10);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored --nocapture"]
+    fn selector_terms() {
+        for seed in 1..4 {
+            let g = random_amplitude(40, 800, 0.1, seed);
+            println!("seed {}, T-count {}", seed, g.tcount());
+            for d in decomp_selectors(&g) {
+                println!("  {}: {} terms", d.selector_name(), d.nterms);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored --nocapture"]
+    fn parallel_scaling() {
+        use std::time::Instant;
+
+        let g = random_amplitude(40, 800, 0.1, 1);
+        println!("T-count: {}", g.tcount());
+        let mut base = None;
+        for threads in [1, 2, 4, 8] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let time = Instant::now();
+            let d = pool.install(|| {
+                let mut d = Decomposer::new(&g);
+                d.use_cats(true).with_full_simp();
+                d.decomp_parallel(4)
+            });
+            let secs = time.elapsed().as_secs_f64();
+            let (t1, s1, n1) = base
+                .get_or_insert((secs, d.scalar.clone(), d.nterms))
+                .clone();
+            assert_eq!(d.scalar, s1);
+            assert_eq!(d.nterms, n1);
+            println!(
+                "{} threads: {:.2}s, speedup {:.2}, {} terms",
+                threads,
+                secs,
+                t1 / secs,
+                d.nterms
+            );
+        }
+    }
+
+    #[test]
+    fn cut_sum() {
+        let mut g = Graph::new();
+        let v = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let w = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 2));
+        g.add_edge(v, w);
+        let mut outs = vec![];
+        for (u, et) in [(v, EType::N), (v, EType::H), (w, EType::H)] {
+            let b = g.add_vertex(VType::B);
+            g.add_edge_with_type(u, b, et);
+            outs.push(b);
+        }
+        g.set_outputs(outs);
+
+        let terms = Decomposer::cut_terms(&g, &[v, w]);
+        assert_eq!(terms.len(), 4);
+        let mut tsum = Tensor4::zeros(vec![2; 3]);
+        for h in &terms {
+            tsum = tsum + h.to_tensor4();
+        }
+        assert_eq!(g.to_tensor4(), tsum);
+    }
+
+    #[test]
+    fn cat_sum() {
+        for legs in 3..=8 {
+            for center_phase in [0, 1] {
+                let mut g = Graph::new();
+                let c = g.add_vertex_with_phase(VType::Z, Rational64::from_integer(center_phase));
+                let mut verts = vec![c];
+                let mut outs = vec![];
+                for _ in 0..legs {
+                    let v = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+                    let w = g.add_vertex(VType::Z);
+                    let b = g.add_vertex(VType::B);
+                    g.add_edge_with_type(c, v, EType::H);
+                    g.add_edge_with_type(v, w, EType::H);
+                    g.add_edge(w, b);
+                    verts.push(v);
+                    outs.push(b);
+                }
+                g.set_outputs(outs);
+
+                let terms = Decomposer::cat_terms(&g, &verts);
+                // larger cats are split, and give the terms of a 6-cat
+                let nterms = if legs <= 4 { 2 } else { 3 };
+                assert_eq!(terms.len(), nterms, "{} legs", legs);
+                let mut tsum = Tensor4::zeros(vec![2; legs]);
+                for h in &terms {
+                    tsum = tsum + h.to_tensor4();
+                }
+                assert_eq!(
+                    g.to_tensor4(),
+                    tsum,
+                    "{} legs, phase {}",
+                    legs,
+                    center_phase
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn large_cats() {
+        let g = random_amplitude(20, 400, 0.1, 1);
+        assert!(g
+            .vertices()
+            .any(|v| g.phase(v).is_pauli() && g.degree(v) > 6));
+
+        let mut d = Decomposer::new(&g);
+        d.use_cats(true).with_full_simp();
+        let mut dl = d.clone();
+        dl.with_selector(LargestCat).decomp_all();
+        d.decomp_all();
+        assert_eq!(dl.scalar, d.scalar);
+        // splitting the large cats first saves a lot of terms here
+        assert_eq!(d.nterms, 176);
+        assert_eq!(dl.nterms, 124);
+    }
+
+    /// Two random Clifford+T blocks, which only share two qubits
+    fn two_blocks(qubits: usize, depth: usize, seed: u64) -> Graph {
+        let block = |seed| {
+            Circuit::random()
+                .seed(seed)
+                .qubits(qubits)
+                .depth(depth)
+                .p_t(0.2)
+                .with_cliffords()
+                .build()
+        };
+        let mut c = Circuit::new(2 * qubits - 2);
+        c.add_circuit(&block(seed), &(0..qubits).collect::<Vec<_>>());
+        c.add_circuit(
+            &block(seed + 1),
+            &(qubits - 2..2 * qubits - 2).collect::<Vec<_>>(),
+        );
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; 2 * qubits - 2]);
+        g.plug_outputs(&vec![BasisElem::Z0; 2 * qubits - 2]);
+        crate::simplify::full_simp(&mut g);
+        g
+    }
+
+    /// Decompose g using cut decompositions and cats, and using just cats
+    fn decomp_cut_and_cats(g: &Graph) -> (Decomposer<Graph>, Decomposer<Graph>) {
+        let mut dc = Decomposer::new(g);
+        dc.use_cut_decomp(4).use_cats(true).with_full_simp();
+        let mut d = Decomposer::new(g);
+        d.use_cats(true).with_full_simp();
+        (dc, d)
+    }
+
+    #[test]
+    fn cut_two_blocks() {
+        let g = two_blocks(7, 200, 7);
+        assert_eq!(Decomposer::find_cut(&g, 4).map(|c| c.len()), Some(2));
+
+        let (mut dc, mut d) = decomp_cut_and_cats(&g);
+        let dp = dc.clone().decomp_parallel(1);
+        let terms = dc.clone().iter_terms();
+        dc.decomp_all();
+        d.decomp_all();
+        assert_eq!(dc.scalar, d.scalar);
+        assert!(5 * dc.nterms < d.nterms, "{} vs {}", dc.nterms, d.nterms);
+
+        assert_eq!(dp.scalar, dc.scalar);
+        assert_eq!(dp.nterms, dc.nterms);
+        let (scalar, nterms) = terms.fold((ScalarN::zero(), 0), |(s, n), t| {
+            (s + t.scalar, n + t.nterms)
+        });
+        assert_eq!(scalar, dc.scalar);
+        assert_eq!(nterms, dc.nterms);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored --nocapture"]
+    fn cut_terms_two_blocks() {
+        use std::time::Instant;
+
+        let g = two_blocks(8, 350, 1);
+        println!("T-count: {}", g.tcount());
+        let (mut dc, mut d) = decomp_cut_and_cats(&g);
+        for (name, d) in [("cut", &mut dc), ("cats", &mut d)] {
+            let time = Instant::now();
+            d.decomp_all();
+            let secs = time.elapsed().as_secs_f64();
+            println!("{}: {} terms, {:.2}s", name, d.nterms, secs);
+        }
+        assert_eq!(dc.scalar, d.scalar);
+    }
+
     #[test]
     fn bss_scalars() {
         // this test is mainly to record how each of the exact
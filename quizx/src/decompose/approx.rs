@@ -0,0 +1,216 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stopping a decomposition early with an error bound, for [Decomposer::decomp_approx].
+
+use super::Decomposer;
+use crate::graph::*;
+use crate::scalar::*;
+use rustc_hash::FxHashSet;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::Instant;
+
+/// Relative slack added to bounds to cover their own rounding
+const SLACK: f64 = 1e-12;
+
+/// The result of [Decomposer::decomp_approx]
+#[derive(Clone, Debug)]
+pub struct Approximation {
+    /// The sum of the terms found so far
+    pub scalar: ScalarN,
+    /// An upper bound on the distance of `scalar` from the exact value
+    pub error_bound: f64,
+    /// Whether every branch was decomposed, in which case the error only
+    /// comes from [float accumulation](Decomposer::with_float_accumulation)
+    pub complete: bool,
+}
+
+/// A pending graph, ordered by the bound on its value
+struct Branch<G> {
+    bound: f64,
+    seq: usize,
+    depth: usize,
+    graph: G,
+    node: Option<usize>,
+}
+
+impl<G> PartialEq for Branch<G> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<G> Eq for Branch<G> {}
+
+impl<G> PartialOrd for Branch<G> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<G> Ord for Branch<G> {
+    // larger bounds first, then the branch queued first
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bound
+            .total_cmp(&other.bound)
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+/// The largest value of |1 + e^(i pi a)| and |1 - e^(i pi a)|
+fn phase_factor(a: f64) -> f64 {
+    let x = a * std::f64::consts::FRAC_PI_2;
+    2.0 * x.cos().abs().max(x.sin().abs())
+}
+
+/// An upper bound on the absolute value of a graph, or of each entry of its
+/// tensor if it has boundaries
+///
+/// Writing the value of a graph of Z spiders as a sum over a 0 or 1 for each
+/// spider, each Hadamard edge contributes a factor of ±1/√2 to each summand,
+/// so there are at most 2^n summands of size at most 2^(-h/2). For a set of
+/// spiders which are not next to each other and only have Hadamard edges,
+/// the sum over the values of each of them, with the others fixed, is a
+/// factor 1 ± e^(iα) rather than 2 summands. Spiders with phases far from 0
+/// and π make this much better than 2. X spiders are colour-changed first,
+/// and graphs with other generators get an infinite bound. A graph with no
+/// vertices is just its scalar, so its bound is exact.
+pub fn value_bound<G: GraphLike>(g: &G) -> f64 {
+    let s = g.scalar().complex_value().norm();
+    if g.num_vertices() == 0 || s == 0.0 {
+        return s;
+    }
+
+    let mut h;
+    let g = if g.vertices().any(|v| g.vertex_type(v) == VType::X) {
+        h = g.clone();
+        h.x_to_z();
+        &h
+    } else {
+        g
+    };
+    if g.vertices()
+        .any(|v| !matches!(g.vertex_type(v), VType::Z | VType::B))
+    {
+        return f64::INFINITY;
+    }
+
+    let hedges = g.edges().filter(|&(_, _, et)| et == EType::H).count();
+    let mut log2 = g.num_vertices() as f64 - hedges as f64 / 2.0;
+
+    // pick spiders to sum over exactly, most helpful first
+    let mut cands: Vec<_> = g
+        .vertices()
+        .filter(|&v| {
+            g.vertex_type(v) == VType::Z
+                && g.incident_edges(v).all(|(w, et)| et == EType::H && w != v)
+        })
+        .map(|v| (phase_factor(g.phase(v).to_f64()), v))
+        .filter(|&(f, _)| f < 2.0)
+        .collect();
+    cands.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut picked = FxHashSet::default();
+    for (f, v) in cands {
+        if g.neighbors(v).all(|w| !picked.contains(&w)) {
+            picked.insert(v);
+            log2 += f.log2() - 1.0;
+        }
+    }
+
+    s * log2.exp2() * (1.0 + SLACK)
+}
+
+impl<G: GraphLike> Decomposer<G> {
+    /// Decompose the branches with the largest bounds first, until the rest
+    /// are small enough to leave out
+    ///
+    /// Each pending graph is bounded with [value_bound], which takes in the
+    /// coefficients of the decompositions taken to reach it, as they are part
+    /// of its scalar. This stops once the bounds of the pending graphs add up
+    /// to at most `epsilon` times the absolute value of the sum so far, and
+    /// returns that sum with the total bound as the error. Rounding from
+    /// [float accumulation](Decomposer::with_float_accumulation) is added to
+    /// the error. An `epsilon` of 0 decomposes everything, as
+    /// [Decomposer::decomp_all] does, but in a different order.
+    ///
+    /// The pending graphs are left on the stack, largest bound on top, so
+    /// calling this again with a smaller `epsilon`, or calling `decomp_all`,
+    /// carries on from here. A [term limit](Decomposer::term_limit) or
+    /// [deadline](Decomposer::deadline) stops this early too, and the error
+    /// bound is then whatever it has got to. Progress is not reported, and
+    /// automatic checkpoints are not saved.
+    pub fn decomp_approx(&mut self, epsilon: f64) -> Approximation {
+        let start = Instant::now();
+        self.start_stats();
+        let mut queue = BinaryHeap::new();
+        let mut seq = 0;
+        // moves the stack into the queue, and returns the sum of the new bounds
+        let mut queue_stack = |d: &mut Self, queue: &mut BinaryHeap<Branch<G>>| {
+            let mut nodes = d.tree_stack.drain(..);
+            let mut added = 0.0;
+            for (depth, graph) in d.stack.drain(..) {
+                let bound = value_bound(&graph);
+                added += bound;
+                queue.push(Branch {
+                    bound,
+                    seq,
+                    depth,
+                    graph,
+                    node: nodes.next().flatten(),
+                });
+                seq += 1;
+            }
+            added
+        };
+
+        // the sum of the bounds in the queue, kept up to date as it changes
+        // rather than summed on every step
+        let mut remaining = queue_stack(self, &mut queue);
+        while !queue.is_empty() && !self.out_of_budget() {
+            if remaining <= epsilon * self.scalar.complex_value().norm() {
+                break;
+            }
+            let b = queue.pop().unwrap();
+            remaining = (remaining - b.bound).max(0.0);
+            self.tree_node = b.node;
+            if let Some(t) = self.decomp_graph(b.depth, b.graph) {
+                self.add_term(t);
+            }
+            remaining += queue_stack(self, &mut queue);
+        }
+
+        self.elapsed += start.elapsed();
+
+        // summed again, so rounding in the running total can't shrink the bound
+        let remaining = queue.iter().fold(0.0, |s, b| s + b.bound);
+        let mut error_bound = remaining * (1.0 + SLACK);
+        if let Some(s) = &self.float_sum {
+            error_bound += s.error_bound();
+        }
+        for b in queue.into_sorted_vec() {
+            self.stack.push_back((b.depth, b.graph));
+            if self.tree.is_some() {
+                self.tree_stack.push_back(b.node);
+            }
+        }
+        Approximation {
+            scalar: self.scalar.clone(),
+            error_bound,
+            complete: self.is_complete(),
+        }
+    }
+}
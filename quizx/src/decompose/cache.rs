@@ -0,0 +1,368 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reusing the values of isomorphic graphs, for [Decomposer::use_term_cache].
+
+use super::{Decomposer, TermResult};
+use crate::graph::*;
+use crate::scalar::*;
+use num::One;
+use rustc_hash::{FxHashMap, FxHashSet, FxHasher};
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+/// The most steps [is_isomorphic] takes before giving up
+const ISO_BUDGET: usize = 10_000;
+
+/// The hit and miss counts of a term cache, see [Decomposer::cache_stats]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Graphs whose value was found in the cache
+    pub hits: usize,
+    /// Graphs which had to be decomposed
+    pub misses: usize,
+    /// Graphs dropped to stay within the capacity
+    pub evictions: usize,
+    /// Graphs currently in the cache
+    pub len: usize,
+}
+
+/// A graph whose value is known
+struct Entry<G> {
+    key: u64,
+    graph: G,
+    colors: FxHashMap<V, u64>,
+    value: ScalarN,
+    nterms: usize,
+    tick: u64,
+}
+
+/// A bounded cache from graphs to their values, dropping the least recently
+/// used graph when it is full
+pub(super) struct TermCache<G> {
+    capacity: usize,
+    entries: FxHashMap<u64, Entry<G>>,
+    buckets: FxHashMap<u64, Vec<u64>>,
+    lru: BTreeMap<u64, u64>,
+    next_id: u64,
+    tick: u64,
+    stats: CacheStats,
+}
+
+fn hash_of(x: impl Hash) -> u64 {
+    let mut h = FxHasher::default();
+    x.hash(&mut h);
+    h.finish()
+}
+
+/// Colour the vertices of g by Weisfeiler-Lehman refinement
+///
+/// Vertices start out coloured by their type, phase and position in the
+/// inputs or outputs. Each round, a vertex is recoloured by its colour and
+/// the colours and edge types of its neighbours, until the number of colours
+/// stops growing. An isomorphism has to map each vertex to one of the same
+/// colour.
+fn refine_colors(g: &impl GraphLike) -> FxHashMap<V, u64> {
+    let mut boundary = FxHashMap::default();
+    for (i, &v) in g.inputs().iter().enumerate() {
+        boundary.insert(v, (0, i));
+    }
+    for (i, &v) in g.outputs().iter().enumerate() {
+        boundary.insert(v, (1, i));
+    }
+
+    let mut colors: FxHashMap<V, u64> = g
+        .vertices()
+        .map(|v| {
+            let c = (
+                g.vertex_type(v) as u8,
+                g.phase(v).to_rational(),
+                g.is_ground(v),
+                boundary.get(&v),
+            );
+            (v, hash_of(c))
+        })
+        .collect();
+    let mut ncolors = colors.values().collect::<FxHashSet<_>>().len();
+
+    loop {
+        let next: FxHashMap<V, u64> = g
+            .vertices()
+            .map(|v| {
+                let mut nhd: Vec<_> = g
+                    .incident_edges(v)
+                    .map(|(w, et)| (colors[&w], et as u8))
+                    .collect();
+                nhd.sort_unstable();
+                (v, hash_of((colors[&v], nhd)))
+            })
+            .collect();
+        let n = next.values().collect::<FxHashSet<_>>().len();
+        colors = next;
+        if n == ncolors {
+            return colors;
+        }
+        ncolors = n;
+    }
+}
+
+/// A hash of g which is the same for isomorphic graphs
+fn graph_key(g: &impl GraphLike, colors: &FxHashMap<V, u64>) -> u64 {
+    let mut cs: Vec<_> = colors.values().collect();
+    cs.sort_unstable();
+    hash_of((cs, g.num_edges()))
+}
+
+/// Look for an isomorphism from g1 to g2 which preserves colours
+///
+/// The vertices of g1 are matched one at a time, each next to as many
+/// matched vertices as possible, with backtracking. Returns false if no
+/// isomorphism is found within [ISO_BUDGET] steps.
+fn is_isomorphic<G: GraphLike>(
+    g1: &G,
+    c1: &FxHashMap<V, u64>,
+    g2: &G,
+    c2: &FxHashMap<V, u64>,
+) -> bool {
+    if g1.num_vertices() != g2.num_vertices() || g1.num_edges() != g2.num_edges() {
+        return false;
+    }
+    if g1.num_vertices() == 0 {
+        return true;
+    }
+    let mut classes: FxHashMap<u64, Vec<V>> = FxHashMap::default();
+    for v in g2.vertices() {
+        classes.entry(c2[&v]).or_default().push(v);
+    }
+
+    // pick the order to match vertices of g1 in
+    let mut order = vec![];
+    let mut placed = FxHashSet::default();
+    let mut links: FxHashMap<V, usize> = g1.vertices().map(|v| (v, 0)).collect();
+    while order.len() < g1.num_vertices() {
+        let class_len = |v: &V| classes.get(&c1[v]).map_or(0, |c| c.len());
+        let v = *links
+            .iter()
+            .filter(|(v, _)| !placed.contains(*v))
+            .min_by_key(|&(v, &n)| (usize::MAX - n, class_len(v), *v))
+            .unwrap()
+            .0;
+        placed.insert(v);
+        order.push(v);
+        for w in g1.neighbors(v) {
+            *links.get_mut(&w).unwrap() += 1;
+        }
+    }
+
+    // candidates for each vertex of g1, given the vertices matched before it
+    let candidates = |m: &FxHashMap<V, V>, v: V| -> Vec<V> {
+        let mapped = g1.neighbors(v).find_map(|u| m.get(&u));
+        match mapped {
+            Some(&u2) => g2.neighbors(u2).filter(|w| c2[w] == c1[&v]).collect(),
+            None => classes.get(&c1[&v]).cloned().unwrap_or_default(),
+        }
+    };
+    let consistent = |m: &FxHashMap<V, V>, used: &FxHashMap<V, V>, v: V, w: V| -> bool {
+        if used.contains_key(&w) {
+            return false;
+        }
+        let mut n1 = 0;
+        for (u, et) in g1.incident_edges(v) {
+            if let Some(&u2) = m.get(&u) {
+                if g2.edge_type_opt(w, u2) != Some(et) {
+                    return false;
+                }
+                n1 += 1;
+            }
+        }
+        let n2 = g2.neighbors(w).filter(|u| used.contains_key(u)).count();
+        n1 == n2
+    };
+
+    let mut m: FxHashMap<V, V> = FxHashMap::default();
+    let mut used: FxHashMap<V, V> = FxHashMap::default();
+    let mut stack: Vec<(Vec<V>, usize)> = vec![(candidates(&m, order[0]), 0)];
+    let mut steps = 0;
+    while !stack.is_empty() {
+        let k = stack.len() - 1;
+        let v = order[k];
+        if let Some(old) = m.remove(&v) {
+            used.remove(&old);
+        }
+        steps += 1;
+        if steps > ISO_BUDGET {
+            return false;
+        }
+        let (cands, i) = &mut stack[k];
+        match cands[*i..]
+            .iter()
+            .position(|&w| consistent(&m, &used, v, w))
+        {
+            Some(j) => {
+                let w = cands[*i + j];
+                *i += j + 1;
+                m.insert(v, w);
+                used.insert(w, v);
+                if m.len() == order.len() {
+                    return true;
+                }
+                stack.push((candidates(&m, order[k + 1]), 0));
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    false
+}
+
+impl<G: GraphLike> TermCache<G> {
+    pub(super) fn new(capacity: usize) -> Self {
+        TermCache {
+            capacity,
+            entries: FxHashMap::default(),
+            buckets: FxHashMap::default(),
+            lru: BTreeMap::new(),
+            next_id: 0,
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub(super) fn stats(&self) -> CacheStats {
+        CacheStats {
+            len: self.entries.len(),
+            ..self.stats
+        }
+    }
+
+    /// The value and term count of a graph isomorphic to g, ignoring scalars
+    fn get(&mut self, key: u64, g: &G, colors: &FxHashMap<V, u64>) -> Option<(ScalarN, usize)> {
+        let ids = self.buckets.get(&key).map_or(&[][..], |ids| &ids[..]);
+        let id = ids.iter().copied().find(|id| {
+            let e = &self.entries[id];
+            is_isomorphic(&e.graph, &e.colors, g, colors)
+        });
+        let Some(id) = id else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+        self.tick += 1;
+        let e = self.entries.get_mut(&id).unwrap();
+        self.lru.remove(&e.tick);
+        e.tick = self.tick;
+        self.lru.insert(e.tick, id);
+        Some((e.value.clone(), e.nterms))
+    }
+
+    fn insert(
+        &mut self,
+        key: u64,
+        graph: G,
+        colors: FxHashMap<V, u64>,
+        value: ScalarN,
+        nterms: usize,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            let (_, id) = self.lru.pop_first().unwrap();
+            let e = self.entries.remove(&id).unwrap();
+            let bucket = self.buckets.get_mut(&e.key).unwrap();
+            bucket.retain(|&i| i != id);
+            if bucket.is_empty() {
+                self.buckets.remove(&e.key);
+            }
+            self.stats.evictions += 1;
+        }
+
+        self.tick += 1;
+        self.next_id += 1;
+        let id = self.next_id;
+        self.buckets.entry(key).or_default().push(id);
+        self.lru.insert(self.tick, id);
+        self.entries.insert(
+            id,
+            Entry {
+                key,
+                graph,
+                colors,
+                value,
+                nterms,
+                tick: self.tick,
+            },
+        );
+    }
+}
+
+impl<G: GraphLike> Decomposer<G> {
+    /// Whether g should be looked up in the term cache
+    pub(super) fn use_cache_for(&self, g: &G) -> bool {
+        self.cache.is_some() && (1..=self.cache_max_tcount).contains(&g.tcount())
+    }
+
+    /// Find the value of g in the term cache, or decompose it and add it
+    ///
+    /// Values are stored for the graph with its scalar set to one, so a graph
+    /// reached with a different prefactor can reuse them.
+    pub(super) fn cached_term(&mut self, depth: usize, mut g: G) -> TermResult<G> {
+        let cache = self.cache.clone().unwrap();
+        let s = mem::replace(g.scalar_mut(), ScalarN::one());
+        let colors = refine_colors(&g);
+        let key = graph_key(&g, &colors);
+
+        let found = cache.lock().unwrap().get(key, &g, &colors);
+        let (value, nterms) = match found {
+            Some((value, _)) => (value, 0),
+            None => {
+                let mut d = self.empty_child();
+                d.save(false)
+                    .with_float_accumulation(false)
+                    .record_tree(false);
+                if let Some(t) = d.expand(depth, g.clone()) {
+                    d.add_term(t);
+                }
+                d.decomp_all();
+                self.moves += d.moves;
+                if let Some(e) = d.error {
+                    self.error = Some(e);
+                    return TermResult {
+                        scalar: ScalarN::zero(),
+                        depth,
+                        graph: None,
+                        nterms: 0,
+                    };
+                }
+                cache
+                    .lock()
+                    .unwrap()
+                    .insert(key, g, colors, d.scalar.clone(), d.nterms);
+                (d.scalar, d.nterms)
+            }
+        };
+
+        TermResult {
+            scalar: s * value,
+            depth,
+            graph: None,
+            nterms,
+        }
+    }
+}
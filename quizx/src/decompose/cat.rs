@@ -0,0 +1,217 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decompositions of cat states, for [Decomposer::use_cats].
+
+use super::Decomposer;
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use num::{Rational64, Zero};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The stabiliser state in a term of a cat decomposition
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    /// A spider with the given phase, as a fraction of pi, joined to each leg
+    /// by an edge of the given type
+    Star(EType, (i64, i64)),
+    /// A phase-free spider joined to each leg by a Hadamard edge, with
+    /// Hadamard edges between all pairs of legs
+    StarClique,
+    /// Each leg in the state |0>
+    Zeros,
+}
+
+/// A term of a cat decomposition: a scalar times a stabiliser state
+#[derive(Debug, Clone, Copy)]
+struct CatTerm {
+    /// The power of 2 and the coefficients of an exact [Scalar]
+    scalar: (i32, [isize; 4]),
+    shape: Shape,
+}
+
+const fn term(pow: i32, coeffs: [isize; 4], shape: Shape) -> CatTerm {
+    CatTerm {
+        scalar: (pow, coeffs),
+        shape,
+    }
+}
+
+/// The decompositions of the cats with 3 to 6 legs
+///
+/// A cat is a phase-free spider joined by Hadamard edges to T-spiders, its
+/// legs. Taking a T phase off each leg, it is the sum of the terms, each a
+/// stabiliser state on the wires to the legs. The decompositions with an odd
+/// number of legs are the ones with one more leg, with that leg in |0>.
+const CAT_TERMS: [&[CatTerm]; 4] = [
+    &[
+        term(0, [0, 0, 1, 0], Shape::Star(EType::H, (0, 1))),
+        term(-2, [1, 0, -1, 0], Shape::Zeros),
+    ],
+    &[
+        term(0, [0, 0, 1, 0], Shape::Star(EType::H, (0, 1))),
+        term(-1, [1, 0, -1, 0], Shape::Star(EType::N, (-1, 2))),
+    ],
+    &[
+        term(-3, [1, 0, 0, 0], Shape::Zeros),
+        term(-1, [-1, 0, 1, 0], Shape::Star(EType::H, (0, 1))),
+        term(4, [-1, 0, -1, 0], Shape::StarClique),
+    ],
+    &[
+        term(-1, [1, 0, 0, 0], Shape::Star(EType::N, (-1, 2))),
+        term(-1, [-1, 0, 1, 0], Shape::Star(EType::H, (0, 1))),
+        term(7, [0, -1, 0, 0], Shape::StarClique),
+    ],
+];
+
+impl Shape {
+    /// The state on the given number of legs, with one output for each
+    fn graph<G: GraphLike>(self, legs: usize) -> G {
+        let mut types = vec![VType::B; legs];
+        let mut phases = vec![Phase::zero(); legs];
+        let mut edges = vec![];
+        match self {
+            Shape::Star(et, (num, den)) => {
+                types.push(VType::Z);
+                phases.push(Rational64::new(num, den).into());
+                edges.extend((0..legs).map(|i| (i, legs, et)));
+            }
+            Shape::StarClique => {
+                types.resize(2 * legs + 1, VType::Z);
+                phases.resize(2 * legs + 1, Phase::zero());
+                for i in 0..legs {
+                    edges.push((i, legs + i, EType::N));
+                    edges.push((legs + i, 2 * legs, EType::H));
+                    for j in i + 1..legs {
+                        edges.push((legs + i, legs + j, EType::H));
+                    }
+                }
+            }
+            Shape::Zeros => {
+                types.resize(2 * legs, VType::Z);
+                phases.resize(2 * legs, Phase::zero());
+                edges.extend((0..legs).map(|i| (i, legs + i, EType::H)));
+            }
+        }
+        let outputs: Vec<V> = (0..legs).collect();
+        G::from_edges_with_phases(types.len(), &types, &phases, &edges, &[], &outputs)
+    }
+}
+
+/// Join the outputs of the state h to the given Z spiders of g
+///
+/// A spider of h joined to a single output by a plain edge is fused with the
+/// spider that output goes to. The rest of h is added to g.
+fn plug_legs<G: GraphLike>(g: &mut G, h: &G, legs: &[V]) {
+    let mut map: FxHashMap<V, V> = h
+        .outputs()
+        .iter()
+        .copied()
+        .zip(legs.iter().copied())
+        .collect();
+    let mut fused = FxHashSet::default();
+    for v in h.vertices() {
+        if h.vertex_type(v) == VType::B {
+            continue;
+        }
+        let outs: Vec<_> = h
+            .incident_edges(v)
+            .filter(|&(w, _)| h.vertex_type(w) == VType::B)
+            .collect();
+        match outs[..] {
+            [(o, EType::N)] if h.vertex_type(v) == VType::Z => {
+                let l = map[&o];
+                g.add_to_phase(l, h.phase(v));
+                map.insert(v, l);
+                fused.insert(o);
+            }
+            _ => {
+                let w = g.add_vertex_with_phase(h.vertex_type(v), h.phase(v));
+                map.insert(v, w);
+            }
+        }
+    }
+    for (s, t, et) in h.edges() {
+        if !fused.contains(&s) && !fused.contains(&t) {
+            g.add_edge_smart(map[&s], map[&t], et);
+        }
+    }
+}
+
+impl CatTerm {
+    /// Replace the cat in g with this term
+    fn apply<G: GraphLike>(&self, g: &G, center: V, legs: &[V]) -> G {
+        let mut g = g.clone();
+        let (pow, coeffs) = self.scalar;
+        *g.scalar_mut() *= ScalarN::Exact(pow, coeffs.to_vec());
+        g.remove_vertex(center);
+        for &v in legs {
+            g.add_to_phase(v, Rational64::new(-1, 4));
+        }
+        let h: G = self.shape.graph(legs.len());
+        plug_legs(&mut g, &h, legs);
+        g
+    }
+}
+
+impl<G: GraphLike> Decomposer<G> {
+    /// The terms of the cat decomposition at the given vertices, before they
+    /// are simplified
+    ///
+    /// The vertices should be given as returned by [Decomposer::cat_ts]. Cats
+    /// with 3 to 6 legs give 2 or 3 terms. A larger cat is split in two
+    /// first: its spider is unfused into one with the first five legs and one
+    /// with the rest, joined through a new phase-free spider. That spider is
+    /// then the sixth leg of a 6-cat, which is decomposed, and ends up as a
+    /// T-spider on the cat with the remaining legs. So the rest of the legs
+    /// are still a cat in each term, rather than T-spiders to be decomposed
+    /// one by one.
+    pub fn cat_terms(g: &G, verts: &[V]) -> Vec<G> {
+        // verts[0] is a 0- or pi-spider, linked to all and only to vs in verts[1..] which are T-spiders
+        let mut g = g.clone(); // that is annoying ...
+        let mut verts = Vec::from(verts);
+        if g.phase(verts[0]).is_one() {
+            g.set_phase(verts[0], Rational64::new(0, 1));
+            let mut neigh = g.neighbor_vec(verts[1]);
+            neigh.retain(|&x| x != verts[0]);
+            for &v in &neigh {
+                g.add_to_phase(v, Rational64::new(1, 1));
+            }
+            let tmp = g.phase(verts[1]);
+            *g.scalar_mut() *= ScalarN::from_phase(tmp);
+            g.set_phase(verts[1], g.phase(verts[1]) * -1);
+        }
+        if verts.len() > 7 {
+            let c = g.add_vertex(VType::Z);
+            let x = g.add_vertex(VType::Z);
+            for &v in &verts[6..] {
+                g.remove_edge(verts[0], v);
+                g.add_edge_with_type(v, c, EType::H);
+            }
+            g.add_edge_with_type(verts[0], x, EType::H);
+            g.add_edge_with_type(x, c, EType::H);
+            verts.truncate(6);
+            verts.push(x);
+        }
+        let legs = verts.len() - 1;
+        assert!((3..=6).contains(&legs), "A cat needs at least 3 legs");
+        CAT_TERMS[legs - 3]
+            .iter()
+            .map(|t| t.apply(&g, verts[0], &verts[1..]))
+            .collect()
+    }
+}
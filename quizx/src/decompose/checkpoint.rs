@@ -0,0 +1,362 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saving the pending work of a [Decomposer] to a file, and resuming it.
+
+use super::{
+    DecompSelector, DecompStrategy, Decomposer, FloatSum, MoveCounts, SimpFunc, SimpPolicy,
+};
+use super::{FirstCat, LargestCat, LookaheadCat, MostConnectedCat};
+use crate::graph::*;
+use crate::scalar::*;
+
+use derive_more::{Display, Error, From};
+use num::complex::Complex;
+use num::Rational64;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An error from saving or resuming a [Decomposer]
+#[derive(Debug, Display, Error, From)]
+#[non_exhaustive]
+pub enum CheckpointError {
+    /// The checkpoint file could not be read or written.
+    #[from]
+    Io(std::io::Error),
+    /// The checkpoint file is not valid.
+    #[from]
+    SerdeError(serde_json::Error),
+    /// The checkpoint uses a cat selector which isn't built in.
+    #[display("Cannot resume with unknown cat selector {name}")]
+    UnknownSelector { name: String },
+}
+
+/// Save a checkpoint every `every` terms, see [Decomposer::checkpoint_every]
+#[derive(Clone)]
+pub(super) struct AutoCheckpoint {
+    every: usize,
+    next: usize,
+    path: PathBuf,
+    error: Option<Arc<CheckpointError>>,
+}
+
+/// An exact encoding of a [ScalarN]
+///
+/// The json scalars used for pyzx only keep an approximation of the value,
+/// which isn't good enough for adding up terms. Floats are stored by their
+/// bits for the same reason.
+#[derive(Serialize, Deserialize)]
+enum JsonExactScalar {
+    Exact(i32, Vec<isize>),
+    Float(u64, u64),
+}
+
+/// The json encoding of the sum kept by
+/// [Decomposer::with_bigint_accumulation]
+#[cfg(feature = "bigint")]
+#[derive(Serialize, Deserialize)]
+enum JsonBigScalar {
+    Exact(i32, Vec<num_bigint::BigInt>),
+    Float(u64, u64),
+}
+
+/// A vertex of a [JsonCheckpointGraph]
+#[derive(Serialize, Deserialize)]
+struct JsonCheckpointVertex {
+    v: V,
+    ty: VType,
+    /// The phase, as a fraction of pi.
+    phase: (i64, i64),
+    qubit: i32,
+    row: i32,
+    #[serde(default)]
+    ground: bool,
+}
+
+/// A graph on the stack of a checkpoint
+///
+/// Unlike [JsonGraph](crate::json::JsonGraph), this keeps vertex indices, so
+/// a resumed decomposition picks the same vertices to decompose.
+#[derive(Serialize, Deserialize)]
+struct JsonCheckpointGraph {
+    vertices: Vec<JsonCheckpointVertex>,
+    edges: Vec<(V, V, EType)>,
+    inputs: Vec<V>,
+    outputs: Vec<V>,
+    scalar: JsonExactScalar,
+}
+
+/// The json encoding of a [Decomposer]
+#[derive(Serialize, Deserialize)]
+struct JsonCheckpoint {
+    stack: Vec<(usize, JsonCheckpointGraph)>,
+    done: Vec<JsonCheckpointGraph>,
+    scalar: JsonExactScalar,
+    nterms: usize,
+    simp_func: SimpFunc,
+    #[serde(default)]
+    simp_policy: SimpPolicy,
+    random_t: bool,
+    /// Only read if there is no `strategy`, from checkpoints saved before
+    /// there was one
+    use_cats: bool,
+    #[serde(default)]
+    strategy: Option<DecompStrategy>,
+    max_cut: usize,
+    selector: String,
+    save: bool,
+    #[serde(default)]
+    float_sum: Option<FloatSum>,
+    #[cfg(feature = "bigint")]
+    #[serde(default)]
+    bigint_sum: Option<JsonBigScalar>,
+    #[serde(default)]
+    initial_tcount: Option<usize>,
+    #[serde(default)]
+    moves: MoveCounts,
+}
+
+impl JsonExactScalar {
+    fn from_scalar(s: &ScalarN) -> Self {
+        match s {
+            Scalar::Exact(pow, coeffs) => JsonExactScalar::Exact(*pow, coeffs.clone()),
+            Scalar::Float(c) => JsonExactScalar::Float(c.re.to_bits(), c.im.to_bits()),
+        }
+    }
+
+    fn to_scalar(&self) -> ScalarN {
+        match self {
+            JsonExactScalar::Exact(pow, coeffs) => Scalar::Exact(*pow, coeffs.clone()),
+            JsonExactScalar::Float(re, im) => {
+                Scalar::Float(Complex::new(f64::from_bits(*re), f64::from_bits(*im)))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl JsonBigScalar {
+    fn from_scalar(s: &ScalarB) -> Self {
+        match s {
+            Scalar::Exact(pow, coeffs) => JsonBigScalar::Exact(*pow, coeffs.clone()),
+            Scalar::Float(c) => JsonBigScalar::Float(c.re.to_bits(), c.im.to_bits()),
+        }
+    }
+
+    fn to_scalar(&self) -> ScalarB {
+        match self {
+            JsonBigScalar::Exact(pow, coeffs) => Scalar::Exact(*pow, coeffs.clone()),
+            JsonBigScalar::Float(re, im) => {
+                Scalar::Float(Complex::new(f64::from_bits(*re), f64::from_bits(*im)))
+            }
+        }
+    }
+}
+
+impl JsonCheckpointGraph {
+    fn from_graph(g: &impl GraphLike) -> Self {
+        let vertices = g
+            .vertices()
+            .map(|v| {
+                let d = g.vertex_data(v);
+                let p = d.phase.to_rational();
+                JsonCheckpointVertex {
+                    v,
+                    ty: d.ty,
+                    phase: (*p.numer(), *p.denom()),
+                    qubit: d.qubit,
+                    row: d.row,
+                    ground: d.ground,
+                }
+            })
+            .collect();
+        JsonCheckpointGraph {
+            vertices,
+            edges: g.edge_vec(),
+            inputs: g.inputs().clone(),
+            outputs: g.outputs().clone(),
+            scalar: JsonExactScalar::from_scalar(g.scalar()),
+        }
+    }
+
+    fn to_graph<G: GraphLike>(&self) -> G {
+        let mut g = G::new();
+        for vd in &self.vertices {
+            let (num, den) = vd.phase;
+            g.add_vertex_at(
+                vd.v,
+                VData {
+                    ty: vd.ty,
+                    phase: Rational64::new(num, den).into(),
+                    qubit: vd.qubit,
+                    row: vd.row,
+                    ground: vd.ground,
+                },
+            );
+        }
+        for &(s, t, ety) in &self.edges {
+            g.add_edge_with_type(s, t, ety);
+        }
+        g.set_inputs(self.inputs.clone());
+        g.set_outputs(self.outputs.clone());
+        *g.scalar_mut() = self.scalar.to_scalar();
+        g
+    }
+}
+
+/// The built-in selector with the given name
+fn selector_by_name<G: GraphLike>(name: &str) -> Option<Arc<dyn DecompSelector<G>>> {
+    let s: Arc<dyn DecompSelector<G>> = match name {
+        "first" => Arc::new(FirstCat),
+        "largest" => Arc::new(LargestCat),
+        "most-connected" => Arc::new(MostConnectedCat),
+        "lookahead" => Arc::new(LookaheadCat),
+        _ => return None,
+    };
+    Some(s)
+}
+
+impl<G: GraphLike> Decomposer<G> {
+    /// Write the pending graphs, the partial sum and the settings to a file
+    ///
+    /// [Decomposer::resume] reads the file back, and finishing the resumed
+    /// decomposition gives the same scalar as an uninterrupted run, up to
+    /// rounding if [float accumulation](Decomposer::with_float_accumulation)
+    /// is on. Progress reporting, limits, automatic checkpoints, the time taken
+    /// so far and the state of the [random number
+    /// generator](Decomposer::with_seed) are not saved. The file is written next to `path` first and then moved into
+    /// place, so an interrupted save leaves the previous checkpoint intact.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), CheckpointError> {
+        let cp = JsonCheckpoint {
+            stack: self
+                .stack
+                .iter()
+                .map(|(depth, g)| (*depth, JsonCheckpointGraph::from_graph(g)))
+                .collect(),
+            done: self
+                .done
+                .iter()
+                .map(JsonCheckpointGraph::from_graph)
+                .collect(),
+            scalar: JsonExactScalar::from_scalar(&self.scalar),
+            nterms: self.nterms,
+            simp_func: self.simp_func,
+            simp_policy: self.simp_policy,
+            random_t: self.random_t,
+            use_cats: self.strategy == DecompStrategy::Cats,
+            strategy: Some(self.strategy),
+            max_cut: self.max_cut,
+            selector: self.selector_name().to_string(),
+            save: self.save,
+            float_sum: self.float_sum,
+            #[cfg(feature = "bigint")]
+            bigint_sum: self.bigint_sum.as_ref().map(JsonBigScalar::from_scalar),
+            initial_tcount: self.initial_tcount,
+            moves: self.moves,
+        };
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, &cp)?;
+        writer.flush()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Read a decomposer saved by [Decomposer::save_checkpoint]
+    ///
+    /// Only the built-in cat selectors can be restored, so this fails if the
+    /// saved decomposer used any other.
+    pub fn resume(path: &Path) -> Result<Decomposer<G>, CheckpointError> {
+        let reader = BufReader::new(File::open(path)?);
+        let cp: JsonCheckpoint = serde_json::from_reader(reader)?;
+        let selector = selector_by_name(&cp.selector)
+            .ok_or(CheckpointError::UnknownSelector { name: cp.selector })?;
+
+        let mut d = Decomposer::empty();
+        d.stack = cp
+            .stack
+            .iter()
+            .map(|(depth, g)| (*depth, g.to_graph()))
+            .collect::<VecDeque<_>>();
+        d.done = cp.done.iter().map(|g| g.to_graph()).collect();
+        d.scalar = cp.scalar.to_scalar();
+        d.nterms = cp.nterms;
+        d.save(cp.save)
+            .random_t(cp.random_t)
+            .use_cats(cp.use_cats)
+            .use_cut_decomp(cp.max_cut)
+            .with_simp(cp.simp_func);
+        d.selector = selector;
+        d.float_sum = cp.float_sum;
+        #[cfg(feature = "bigint")]
+        {
+            d.bigint_sum = cp.bigint_sum.as_ref().map(JsonBigScalar::to_scalar);
+        }
+        d.initial_tcount = cp.initial_tcount;
+        d.moves = cp.moves;
+        d.simp_policy = cp.simp_policy;
+        if let Some(s) = cp.strategy {
+            d.with_strategy(s);
+        }
+        Ok(d)
+    }
+
+    /// Save a checkpoint to the given file every `every` terms
+    ///
+    /// This is done by [Decomposer::decomp_top], and so by
+    /// [Decomposer::decomp_all], but not by [Decomposer::decomp_parallel]. If
+    /// a checkpoint can't be written the decomposition carries on, and the
+    /// error is kept for [Decomposer::last_checkpoint_error]. See
+    /// [Decomposer::save_checkpoint].
+    pub fn checkpoint_every(&mut self, every: usize, path: impl Into<PathBuf>) -> &mut Self {
+        let every = every.max(1);
+        self.checkpoint = Some(AutoCheckpoint {
+            every,
+            next: self.nterms + every,
+            path: path.into(),
+            error: None,
+        });
+        self
+    }
+
+    /// The error from the last automatic checkpoint, if it failed
+    ///
+    /// See [Decomposer::checkpoint_every].
+    pub fn last_checkpoint_error(&self) -> Option<&CheckpointError> {
+        self.checkpoint.as_ref()?.error.as_deref()
+    }
+
+    /// Save a checkpoint if enough terms have been found since the last one
+    pub(super) fn auto_checkpoint(&mut self) {
+        let Some(cp) = &self.checkpoint else {
+            return;
+        };
+        if self.nterms < cp.next {
+            return;
+        }
+        let error = self.save_checkpoint(&cp.path).err().map(Arc::new);
+        if let Some(cp) = &mut self.checkpoint {
+            cp.next = self.nterms + cp.every;
+            cp.error = error;
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finding and cutting small vertex separators, for [Decomposer::use_cut_decomp].
+
+use super::Decomposer;
+use crate::graph::*;
+use crate::scalar::*;
+use num::Rational64;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::VecDeque;
+
+impl<G: GraphLike> Decomposer<G> {
+    /// Find a small set of Z spiders splitting the T gates of a connected graph
+    ///
+    /// Two vertices far apart are picked by a pair of breadth-first searches,
+    /// and the quarter of the graph nearest to each is taken as its side. A
+    /// minimum vertex cut between these is then found with augmenting paths,
+    /// giving up once it needs more than `max_size` vertices. Starting from
+    /// whole regions rather than single vertices stops the search from
+    /// just cutting off a phase gadget. The cut is only returned if both
+    /// sides have more than two T gates per cut spider, since otherwise the
+    /// `2^k` terms from the cut cost more than they save.
+    pub fn find_cut(g: &G, max_size: usize) -> Option<Vec<V>> {
+        let s = *bfs_order(g, g.vertices().next()?).last()?;
+        let from_s = bfs_order(g, s);
+        let from_t = bfs_order(g, *from_s.last()?);
+        let n = (g.num_vertices() / 4).max(1);
+        let sources: FxHashSet<V> = from_s.into_iter().take(n).collect();
+        let sinks: FxHashSet<V> = from_t
+            .into_iter()
+            .take(n)
+            .filter(|v| !sources.contains(v))
+            .collect();
+
+        let (cut, side) = min_vertex_cut(g, &sources, &sinks, max_size)?;
+        if cut.is_empty() || cut.iter().any(|&v| g.vertex_type(v) != VType::Z) {
+            return None;
+        }
+
+        // counted the same way as GraphLike::tcount
+        let is_t = |v: &&V| {
+            matches!(g.vertex_type(**v), VType::Z | VType::X) && !g.phase(**v).is_clifford()
+        };
+        let ts_side = side.iter().filter(is_t).count();
+        let ts_rest = g.tcount() - ts_side - cut.iter().filter(is_t).count();
+        if ts_side.min(ts_rest) > 2 * cut.len() {
+            Some(cut)
+        } else {
+            None
+        }
+    }
+
+    /// The `2^k` terms from cutting the given `k` Z spiders, before they are
+    /// simplified
+    ///
+    /// Each term fixes every cut spider to a computational basis state. The
+    /// spider is removed, and each of its neighbours gets a one-legged spider
+    /// for that basis state in its place. The last vertex in `verts` gives the
+    /// lowest bit of the term index.
+    pub fn cut_terms(g: &G, verts: &[V]) -> Vec<G> {
+        (0..1usize << verts.len())
+            .map(|bits| {
+                let mut g = g.clone();
+                for (i, &v) in verts.iter().rev().enumerate() {
+                    let b = (bits >> i) & 1;
+                    let neighbors: Vec<_> = g.incident_edges(v).collect();
+                    *g.scalar_mut() *= ScalarN::sqrt2_pow(-(neighbors.len() as i32));
+                    if b == 1 {
+                        let p = g.phase(v);
+                        g.scalar_mut().mul_phase(p);
+                    }
+
+                    // |b> is an X spider, and H|b> is a Z spider
+                    let phase = Rational64::from_integer(b as i64);
+                    for (w, et) in neighbors {
+                        let ty = if et == EType::H { VType::Z } else { VType::X };
+                        let u = g.add_vertex_with_phase(ty, phase);
+                        g.add_edge(u, w);
+                    }
+                    g.remove_vertex(v);
+                }
+                g
+            })
+            .collect()
+    }
+}
+
+/// The vertices in the component of v, in breadth-first order
+fn bfs_order(g: &impl GraphLike, v: V) -> Vec<V> {
+    let mut seen = FxHashSet::default();
+    seen.insert(v);
+    let mut order = vec![v];
+    let mut i = 0;
+    while i < order.len() {
+        for w in g.neighbors(order[i]) {
+            if seen.insert(w) {
+                order.push(w);
+            }
+        }
+        i += 1;
+    }
+    order
+}
+
+/// A minimum set of vertices separating the sources from the sinks, if it has
+/// at most `max_size` vertices, along with the vertices on the side of the
+/// sources
+fn min_vertex_cut(
+    g: &impl GraphLike,
+    sources: &FxHashSet<V>,
+    sinks: &FxHashSet<V>,
+    max_size: usize,
+) -> Option<(Vec<V>, FxHashSet<V>)> {
+    let vs: Vec<V> = g.vertices().collect();
+    let index: FxHashMap<V, usize> = vs.iter().enumerate().map(|(i, &v)| (v, i)).collect();
+
+    // vertex i becomes an in-node 2i and an out-node 2i+1, and the last two
+    // nodes are joined to all of the sources and sinks. Any flow bigger than
+    // max_size is as good as unbounded.
+    let inf = max_size + 1;
+    let (source, sink) = (2 * vs.len(), 2 * vs.len() + 1);
+    let mut net = Network::new(2 * vs.len() + 2);
+    for (i, &v) in vs.iter().enumerate() {
+        if sources.contains(&v) {
+            net.add_arc(2 * i, 2 * i + 1, inf);
+            net.add_arc(source, 2 * i + 1, inf);
+        } else if sinks.contains(&v) {
+            net.add_arc(2 * i, 2 * i + 1, inf);
+            net.add_arc(2 * i, sink, inf);
+        } else {
+            net.add_arc(2 * i, 2 * i + 1, 1);
+        }
+    }
+    for (v0, v1, _) in g.edges() {
+        let (i, j) = (index[&v0], index[&v1]);
+        net.add_arc(2 * i + 1, 2 * j, inf);
+        net.add_arc(2 * j + 1, 2 * i, inf);
+    }
+
+    for _ in 0..=max_size {
+        if !net.augment(source, sink) {
+            let seen = net.search(source).0;
+            let cut = (0..vs.len())
+                .filter(|&i| seen[2 * i] && !seen[2 * i + 1])
+                .map(|i| vs[i])
+                .collect();
+            let side = (0..vs.len())
+                .filter(|&i| seen[2 * i + 1])
+                .map(|i| vs[i])
+                .collect();
+            return Some((cut, side));
+        }
+    }
+
+    None
+}
+
+/// A flow network, stored as residual capacities
+///
+/// Arcs are added in pairs, so the reverse of arc `a` is `a ^ 1`.
+struct Network {
+    arcs: Vec<Vec<usize>>,
+    target: Vec<usize>,
+    cap: Vec<usize>,
+}
+
+impl Network {
+    fn new(nodes: usize) -> Network {
+        Network {
+            arcs: vec![vec![]; nodes],
+            target: vec![],
+            cap: vec![],
+        }
+    }
+
+    fn add_arc(&mut self, u: usize, v: usize, cap: usize) {
+        self.arcs[u].push(self.target.len());
+        self.target.push(v);
+        self.cap.push(cap);
+        self.arcs[v].push(self.target.len());
+        self.target.push(u);
+        self.cap.push(0);
+    }
+
+    /// The nodes reachable from s, and the arc used to first reach each one
+    fn search(&self, s: usize) -> (Vec<bool>, Vec<usize>) {
+        let mut seen = vec![false; self.arcs.len()];
+        let mut pred = vec![0; self.arcs.len()];
+        seen[s] = true;
+        let mut queue = VecDeque::from([s]);
+        while let Some(u) = queue.pop_front() {
+            for &a in &self.arcs[u] {
+                let v = self.target[a];
+                if self.cap[a] > 0 && !seen[v] {
+                    seen[v] = true;
+                    pred[v] = a;
+                    queue.push_back(v);
+                }
+            }
+        }
+        (seen, pred)
+    }
+
+    /// Push flow along a shortest path from s to t, if there is one
+    fn augment(&mut self, s: usize, t: usize) -> bool {
+        let (seen, pred) = self.search(s);
+        if !seen[t] {
+            return false;
+        }
+
+        let mut path = vec![];
+        let mut v = t;
+        while v != s {
+            path.push(pred[v]);
+            v = self.target[pred[v] ^ 1];
+        }
+        let flow = path.iter().map(|&a| self.cap[a]).min().unwrap_or(0);
+        for a in path {
+            self.cap[a] -= flow;
+            self.cap[a ^ 1] += flow;
+        }
+        true
+    }
+}
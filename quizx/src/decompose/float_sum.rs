@@ -0,0 +1,108 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adding up terms in floating point, for [Decomposer::with_float_accumulation](super::Decomposer::with_float_accumulation).
+
+use crate::scalar::*;
+use num::complex::Complex;
+use serde::{Deserialize, Serialize};
+
+/// The unit roundoff of f64
+const U: f64 = f64::EPSILON / 2.0;
+
+/// A compensated sum of scalars as complex floats
+///
+/// The real and imaginary parts are added up with Neumaier's variant of
+/// Kahan summation, so the rounding error doesn't grow with the number of
+/// terms. See [FloatSum::error_bound].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FloatSum {
+    re: (f64, f64),
+    im: (f64, f64),
+    /// The sum of the absolute values of the terms
+    abs_sum: f64,
+    /// A bound on the error from converting exact terms to floats
+    conv_err: f64,
+    nterms: usize,
+}
+
+/// Add x to a sum with its compensation term
+fn neumaier((sum, comp): &mut (f64, f64), x: f64) {
+    let t = *sum + x;
+    if sum.abs() >= x.abs() {
+        *comp += (*sum - t) + x;
+    } else {
+        *comp += (x - t) + *sum;
+    }
+    *sum = t;
+}
+
+impl FloatSum {
+    /// Add a scalar to the sum
+    ///
+    /// Exact scalars are converted with [Scalar::complex_value]. Float scalars
+    /// are taken as they are, so any error they already have is not counted
+    /// by [FloatSum::error_bound].
+    pub fn add(&mut self, s: &ScalarN) {
+        let z = s.complex_value();
+        if let Scalar::Exact(pow, coeffs) = s {
+            // each power of omega is off by a few ulps, growing with its index
+            let mag: f64 = coeffs.iter().map(|c| c.unsigned_abs() as f64).sum();
+            self.conv_err += 2.0 * (coeffs.len() + 2) as f64 * U * mag * 2f64.powi(*pow);
+        }
+        neumaier(&mut self.re, z.re);
+        neumaier(&mut self.im, z.im);
+        self.abs_sum += z.norm();
+        self.nterms += 1;
+    }
+
+    /// Add another sum to this one
+    pub fn merge(&mut self, other: &FloatSum) {
+        neumaier(&mut self.re, other.re.0);
+        neumaier(&mut self.re, other.re.1);
+        neumaier(&mut self.im, other.im.0);
+        neumaier(&mut self.im, other.im.1);
+        self.abs_sum += other.abs_sum;
+        self.conv_err += other.conv_err;
+        self.nterms += other.nterms;
+    }
+
+    /// The sum so far
+    pub fn value(&self) -> Complex<f64> {
+        Complex::new(self.re.0 + self.re.1, self.im.0 + self.im.1)
+    }
+
+    /// The sum so far, as a [ScalarN]
+    pub fn scalar(&self) -> ScalarN {
+        Scalar::Float(self.value())
+    }
+
+    /// The number of scalars added up
+    pub fn nterms(&self) -> usize {
+        self.nterms
+    }
+
+    /// An upper bound on the distance of [FloatSum::value] from the exact sum
+    ///
+    /// Compensated summation of n terms is off by at most
+    /// `(2u + 2nu^2) * sum |x_i|` in each of the real and imaginary parts,
+    /// where u is the unit roundoff. This adds that to a bound on the error
+    /// of converting each exact term to a float.
+    pub fn error_bound(&self) -> f64 {
+        let n = self.nterms as f64;
+        std::f64::consts::SQRT_2 * (2.0 * U + 2.0 * n * U * U) * self.abs_sum + self.conv_err
+    }
+}
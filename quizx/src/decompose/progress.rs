@@ -0,0 +1,131 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Progress reporting for long decompositions.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The state of a running decomposition, as passed to
+/// [Decomposer::on_progress](super::Decomposer::on_progress)
+#[derive(Clone, Copy, Debug)]
+pub struct ProgressInfo {
+    /// The number of terms computed so far
+    pub terms: usize,
+    /// The depth of the last graph decomposed
+    pub depth: usize,
+    /// The time since the first graph was decomposed
+    pub elapsed: Duration,
+    /// The fraction of the decomposition finished, going from 0 to 1
+    ///
+    /// Each graph is assumed to take equally long as its siblings, so this is
+    /// the sum over finished graphs of one over the product of the branching
+    /// factors on their path.
+    pub fraction: f64,
+    /// An estimate of the total number of terms
+    ///
+    /// This is `terms / fraction`, which at the first term is the product of
+    /// the branching factors along the current path. It is only ever raised,
+    /// so it can be used as the total of a progress bar.
+    pub estimate: f64,
+}
+
+type ProgressFn = dyn FnMut(ProgressInfo) + Send;
+
+/// Tracks the shape of the decomposition tree, and calls the callback
+#[derive(Clone)]
+pub(super) struct Progress {
+    callback: Arc<Mutex<ProgressFn>>,
+    every: usize,
+    next_report: usize,
+    start: Option<Instant>,
+    depth: usize,
+    // the number of branches at each depth along the current path
+    branching: Vec<usize>,
+    fraction: f64,
+    estimate: f64,
+}
+
+impl Progress {
+    pub(super) fn new(every: usize, f: impl FnMut(ProgressInfo) + Send + 'static) -> Progress {
+        let every = every.max(1);
+        Progress {
+            callback: Arc::new(Mutex::new(f)),
+            every,
+            next_report: every,
+            start: None,
+            depth: 0,
+            branching: vec![],
+            fraction: 0.0,
+            estimate: 0.0,
+        }
+    }
+
+    /// Record that a graph at the given depth was decomposed into the given
+    /// number of branches, and report if enough terms have been done
+    ///
+    /// Graphs which gave a term, or turned out to be zero, have no branches.
+    /// As the decomposition is depth-first, the last branching factors
+    /// recorded at each lower depth are those on the current path.
+    pub(super) fn step(&mut self, depth: usize, branches: usize, terms: usize) {
+        self.start.get_or_insert_with(Instant::now);
+        self.depth = depth;
+        if branches == 0 {
+            let share: f64 = self
+                .branching
+                .iter()
+                .take(depth)
+                .map(|&b| b as f64)
+                .product();
+            self.fraction = (self.fraction + 1.0 / share).min(1.0);
+        } else {
+            self.branching.resize(depth, 1);
+            self.branching.push(branches);
+        }
+
+        if terms >= self.next_report {
+            self.report(terms);
+        }
+    }
+
+    /// Call the callback with the current state
+    ///
+    /// If the decomposition is complete, the fraction is set to exactly 1.
+    pub(super) fn finish(&mut self, terms: usize, complete: bool) {
+        if complete {
+            self.fraction = 1.0;
+        }
+        self.report(terms);
+    }
+
+    fn report(&mut self, terms: usize) {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        if self.fraction > 0.0 {
+            self.estimate = self.estimate.max(terms as f64 / self.fraction);
+        }
+        self.estimate = self.estimate.max(terms as f64);
+        self.next_report = terms + self.every;
+
+        let info = ProgressInfo {
+            terms,
+            depth: self.depth,
+            elapsed: start.elapsed(),
+            fraction: self.fraction,
+            estimate: self.estimate,
+        };
+        (self.callback.lock().unwrap())(info);
+    }
+}
@@ -0,0 +1,159 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Strategies for picking which cat state a [Decomposer] splits next.
+
+use super::{terms_for_tcount, Decomposer};
+use crate::graph::*;
+use num::One;
+
+/// Picks the cat state to decompose next, when cat decompositions are used
+///
+/// See [Decomposer::use_cats] and [Decomposer::with_selector].
+pub trait DecompSelector<G: GraphLike>: Send + Sync {
+    /// A short name for the strategy, e.g. for comparing term counts
+    fn name(&self) -> &'static str;
+
+    /// Pick a cat state in g, in the same form as [Decomposer::cat_ts]
+    ///
+    /// Returns an empty vector if there isn't one, in which case the
+    /// decomposer falls back to decomposing T-spiders.
+    fn select(&self, g: &G) -> Vec<V>;
+}
+
+/// Whether v is the centre of a cat state
+///
+/// That is a Pauli Z spider joined by Hadamard edges to 3 or more Z spiders
+/// with T phases, its legs. A spider with phase pi is only a cat if its first
+/// leg has just Hadamard edges to Z spiders, so that [Decomposer::cat_terms]
+/// can move the pi onto the neighbours of that leg. All of this holds for
+/// Pauli spiders with T neighbours in a graph simplified by
+/// [full_simp](crate::simplify::full_simp), but not in general.
+pub fn is_cat<G: GraphLike>(g: &G, v: V) -> bool {
+    let hadamard_to_z = |v: V| {
+        g.incident_edges(v)
+            .all(|(w, et)| et == EType::H && g.vertex_type(w) == VType::Z)
+    };
+    g.vertex_type(v) == VType::Z
+        && g.phase(v).is_pauli()
+        && g.degree(v) >= 3
+        && hadamard_to_z(v)
+        && g.neighbors(v).all(|w| g.phase(w).is_t())
+        && (!g.phase(v).is_one() || g.neighbors(v).next().is_some_and(hadamard_to_z))
+}
+
+/// Every cat state in g: a Pauli spider followed by its legs, see [is_cat]
+pub fn cat_candidates<G: GraphLike>(g: &G) -> impl Iterator<Item = Vec<V>> + '_ {
+    g.vertices().filter(|&v| is_cat(g, v)).map(|v| {
+        let mut cat = vec![v];
+        cat.extend(g.neighbors(v));
+        cat
+    })
+}
+
+/// Return the first candidate with the highest score
+fn best_by<G: GraphLike, S: PartialOrd>(g: &G, score: impl Fn(&[V]) -> S) -> Vec<V> {
+    let mut best: Option<(S, Vec<V>)> = None;
+    for cat in cat_candidates(g) {
+        let s = score(&cat);
+        if best.as_ref().map_or(true, |(s0, _)| s > *s0) {
+            best = Some((s, cat));
+        }
+    }
+    best.map(|(_, cat)| cat).unwrap_or_default()
+}
+
+/// The original strategy, using [Decomposer::cat_ts]
+///
+/// This prefers cats with 4 legs, then 6, 5, 3 and larger cats.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FirstCat;
+
+impl<G: GraphLike> DecompSelector<G> for FirstCat {
+    fn name(&self) -> &'static str {
+        "first"
+    }
+
+    fn select(&self, g: &G) -> Vec<V> {
+        Decomposer::cat_ts(g)
+    }
+}
+
+/// Pick the cat with the most legs
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LargestCat;
+
+impl<G: GraphLike> DecompSelector<G> for LargestCat {
+    fn name(&self) -> &'static str {
+        "largest"
+    }
+
+    fn select(&self, g: &G) -> Vec<V> {
+        best_by(g, |cat| cat.len())
+    }
+}
+
+/// Pick the cat whose legs have the most edges in total
+///
+/// Decomposing a cat changes the edges at its legs, so this favours cats
+/// which leave the most for simplification to work with.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MostConnectedCat;
+
+impl<G: GraphLike> DecompSelector<G> for MostConnectedCat {
+    fn name(&self) -> &'static str {
+        "most-connected"
+    }
+
+    fn select(&self, g: &G) -> Vec<V> {
+        best_by(g, |cat| {
+            cat[1..].iter().map(|&v| g.degree(v)).sum::<usize>()
+        })
+    }
+}
+
+/// Pick the cat whose terms need the fewest further terms
+///
+/// Each candidate is decomposed and the terms are simplified with
+/// [full_simp](crate::simplify::full_simp). The candidate is scored by adding
+/// up [terms_for_tcount] for the T-counts of its terms. This is much slower
+/// per step than the other strategies, but can give far fewer terms.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LookaheadCat;
+
+impl<G: GraphLike> DecompSelector<G> for LookaheadCat {
+    fn name(&self) -> &'static str {
+        "lookahead"
+    }
+
+    fn select(&self, g: &G) -> Vec<V> {
+        // lower is better, so negate the expected number of terms
+        best_by(g, |cat| {
+            let cost: f64 = Decomposer::cat_terms(g, cat)
+                .into_iter()
+                .map(|mut h| {
+                    crate::simplify::full_simp(&mut h);
+                    if h.is_zero() {
+                        0.0
+                    } else {
+                        terms_for_tcount(h.tcount())
+                    }
+                })
+                .sum();
+            -cost
+        })
+    }
+}
@@ -0,0 +1,137 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Summary statistics of a decomposition, see [Decomposer::stats].
+
+use super::{DecompMove, Decomposer, SimpPolicy};
+use crate::graph::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::AddAssign;
+
+/// How many times each kind of decomposition was applied
+///
+/// Decompositions tried and dropped by [DecompStrategy::Adaptive](super::DecompStrategy::Adaptive)
+/// are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveCounts {
+    /// Cat states, see [Decomposer::cat_terms]
+    pub cat: usize,
+    /// 5 T gates at a time, into 3 terms
+    pub magic5: usize,
+    /// 6 T gates at a time, with the Bravyi-Smith-Smolin decomposition into
+    /// 7 terms
+    pub bss: usize,
+    /// 2 T gates at a time, into 2 terms
+    pub t_pair: usize,
+    /// Single T gates, into 2 terms
+    pub single_t: usize,
+    /// Vertex cuts, see [Decomposer::use_cut_decomp]
+    pub cut: usize,
+}
+
+impl MoveCounts {
+    /// Count one more decomposition of the given kind
+    pub(super) fn record(&mut self, m: DecompMove) {
+        match m {
+            DecompMove::Cat => self.cat += 1,
+            DecompMove::Magic5 => self.magic5 += 1,
+            DecompMove::Bss => self.bss += 1,
+            DecompMove::TPair => self.t_pair += 1,
+            DecompMove::SingleT => self.single_t += 1,
+            DecompMove::Cut => self.cut += 1,
+        }
+    }
+}
+
+impl AddAssign for MoveCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.cat += other.cat;
+        self.magic5 += other.magic5;
+        self.bss += other.bss;
+        self.t_pair += other.t_pair;
+        self.single_t += other.single_t;
+        self.cut += other.cut;
+    }
+}
+
+/// The size of a decomposition, as reported in papers on stabiliser rank
+/// methods
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct DecompStats {
+    /// The total T-count of the graphs on the stack when decomposition
+    /// started
+    pub tcount: usize,
+    /// The number of stabiliser terms computed
+    pub nterms: usize,
+    /// The time spent decomposing, in seconds
+    pub seconds: f64,
+    /// The effective alpha, `log2(nterms) / tcount`, or 0 if there are no T
+    /// gates or no terms
+    ///
+    /// A decomposition into `2^(alpha t)` terms for t T gates has this alpha,
+    /// e.g. `log2(7) / 6 ≈ 0.468` for the Bravyi-Smith-Smolin decomposition.
+    pub alpha: f64,
+    /// The number of decompositions of each kind
+    pub moves: MoveCounts,
+    /// How often terms were fully simplified, see [Decomposer::simp_policy]
+    pub simp_policy: Option<SimpPolicy>,
+}
+
+impl fmt::Display for DecompStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "T-count {}, {} terms, alpha {:.4}, {:.3}s",
+            self.tcount, self.nterms, self.alpha, self.seconds
+        )
+    }
+}
+
+impl<G: GraphLike> Decomposer<G> {
+    /// The T-count at the start, the number of terms, the time taken and
+    /// the effective alpha of the decomposition so far
+    ///
+    /// The T-count is taken from the graphs on the stack when they are first
+    /// decomposed, so it is only the T-count after simplification if the
+    /// graph given to [Decomposer::new] was simplified. The time is the
+    /// total spent in [Decomposer::decomp_all], [Decomposer::decomp_parallel]
+    /// and [Decomposer::decomp_approx], and for `decomp_parallel` it is wall
+    /// time, not the sum over threads.
+    pub fn stats(&self) -> DecompStats {
+        let tcount = self.initial_tcount.unwrap_or(0);
+        let alpha = if tcount > 0 && self.nterms > 0 {
+            (self.nterms as f64).log2() / tcount as f64
+        } else {
+            0.0
+        };
+        DecompStats {
+            tcount,
+            nterms: self.nterms,
+            seconds: self.elapsed.as_secs_f64(),
+            alpha,
+            moves: self.moves,
+            simp_policy: self.simp_policy(),
+        }
+    }
+
+    /// Record the T-count of the stack, if nothing has been decomposed yet
+    pub(super) fn start_stats(&mut self) {
+        if self.initial_tcount.is_none() {
+            self.initial_tcount = Some(self.stack.iter().map(|(_, g)| g.tcount()).sum());
+        }
+    }
+}
@@ -0,0 +1,265 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording the tree of decompositions taken, see [Decomposer::record_tree].
+
+use super::{Decomposer, TermResult};
+use crate::graph::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A kind of decomposition, as counted in [MoveCounts](super::MoveCounts)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecompMove {
+    Cat,
+    Magic5,
+    Bss,
+    TPair,
+    SingleT,
+    Cut,
+}
+
+/// A graph which was decomposed, or finished, in a [DecompTree]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeNode {
+    /// The node this graph is a term of, or `None` for a graph which was on
+    /// the stack when recording started
+    pub parent: Option<usize>,
+    /// The terms of the decomposition, leaving out those which simplified to
+    /// zero
+    pub children: Vec<usize>,
+    /// The T-count of the graph, after simplification
+    pub tcount: usize,
+    /// The decomposition applied to the graph, or `None` if it was finished
+    /// or hasn't been decomposed yet
+    pub decomp: Option<DecompMove>,
+    /// The vertices the decomposition was applied to
+    pub verts: Vec<V>,
+    /// The number of terms computed for the graph itself
+    ///
+    /// This is 1 for a graph with nothing left to decompose. Graphs which
+    /// split into components, or are found in the term cache, are finished
+    /// without recording their own subtrees, so this is the number of terms
+    /// they took, or 0 for a cache hit.
+    pub nterms: usize,
+}
+
+/// Statistics for one kind of decomposition, from [DecompTree::move_stats]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveStats {
+    /// The number of times it was applied
+    pub count: usize,
+    /// The total number of non-zero terms it produced
+    pub children: usize,
+    /// The total T-count of the graphs it was applied to
+    pub tcount: usize,
+    /// The total number of T gates removed from the terms, counting the ones
+    /// removed by simplifying them
+    pub tcount_removed: usize,
+}
+
+impl MoveStats {
+    /// The average number of non-zero terms per application
+    pub fn branching(&self) -> f64 {
+        self.children as f64 / self.count as f64
+    }
+
+    /// The average number of T gates removed from each term
+    pub fn mean_tcount_removed(&self) -> f64 {
+        self.tcount_removed as f64 / self.children as f64
+    }
+}
+
+/// The decompositions taken by a [Decomposer], see [Decomposer::record_tree]
+///
+/// Nodes are stored in the order they were created, so a parent always comes
+/// before its children. Only the moves and T-counts are kept, not the graphs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecompTree {
+    /// The nodes, indexed by the `parent` and `children` of each node
+    pub nodes: Vec<TreeNode>,
+    /// The node of another tree which the root of this one stands for, when
+    /// a parallel decomposition is merged back
+    #[serde(skip)]
+    graft_at: Option<usize>,
+}
+
+impl DecompTree {
+    /// The number of nodes
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if there are no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The nodes without a parent
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&i| self.nodes[i].parent.is_none())
+    }
+
+    /// The number of terms computed below each node, including the node
+    /// itself
+    pub fn subtree_terms(&self) -> Vec<usize> {
+        let mut terms: Vec<_> = self.nodes.iter().map(|n| n.nterms).collect();
+        for i in (0..self.nodes.len()).rev() {
+            if let Some(p) = self.nodes[i].parent {
+                terms[p] += terms[i];
+            }
+        }
+        terms
+    }
+
+    /// Statistics for each kind of decomposition which was applied
+    pub fn move_stats(&self) -> BTreeMap<DecompMove, MoveStats> {
+        let mut stats = BTreeMap::<DecompMove, MoveStats>::new();
+        for n in &self.nodes {
+            if let Some(m) = n.decomp {
+                let s = stats.entry(m).or_default();
+                s.count += 1;
+                s.children += n.children.len();
+                s.tcount += n.tcount;
+                for &c in &n.children {
+                    s.tcount_removed += n.tcount.saturating_sub(self.nodes[c].tcount);
+                }
+            }
+        }
+        stats
+    }
+
+    /// Add a node for a graph with the given T-count, returning its index
+    fn add_node(&mut self, parent: Option<usize>, tcount: usize) -> usize {
+        let i = self.nodes.len();
+        self.nodes.push(TreeNode {
+            parent,
+            tcount,
+            ..Default::default()
+        });
+        if let Some(p) = parent {
+            self.nodes[p].children.push(i);
+        }
+        i
+    }
+
+    /// Add the nodes of a tree recorded by a child decomposer, returning the
+    /// map from its node indices to the indices here
+    ///
+    /// If it was made for a node here, its root is merged into that node.
+    fn graft(&mut self, other: DecompTree) -> Vec<usize> {
+        let offset = self.nodes.len();
+        let map: Vec<_> = (0..other.nodes.len())
+            .map(|i| match other.graft_at {
+                Some(at) if i == 0 => at,
+                Some(_) => offset + i - 1,
+                None => offset + i,
+            })
+            .collect();
+        for (i, mut n) in other.nodes.into_iter().enumerate() {
+            n.parent = n.parent.map(|p| map[p]);
+            n.children.iter_mut().for_each(|c| *c = map[*c]);
+            if map[i] < offset {
+                n.parent = self.nodes[map[i]].parent;
+                self.nodes[map[i]] = n;
+            } else {
+                self.nodes.push(n);
+            }
+        }
+        map
+    }
+}
+
+impl<G: GraphLike> Decomposer<G> {
+    /// Record the decompositions taken from now on, as a [DecompTree]
+    ///
+    /// This starts a new tree, with a root for each graph on the stack. It
+    /// works with every way of decomposing, and the trees of a
+    /// [parallel](Decomposer::decomp_parallel) decomposition are merged back
+    /// into one. The tree is not saved in checkpoints, and the stack should
+    /// not be changed by hand while recording. `false` drops the tree.
+    pub fn record_tree(&mut self, b: bool) -> &mut Self {
+        self.tree_stack.clear();
+        if b {
+            let mut t = DecompTree::default();
+            for (_, g) in &self.stack {
+                self.tree_stack
+                    .push_back(Some(t.add_node(None, g.tcount())));
+            }
+            self.tree = Some(t);
+        } else {
+            self.tree = None;
+        }
+        self
+    }
+
+    /// The tree recorded since [Decomposer::record_tree] was turned on
+    pub fn tree(&self) -> Option<&DecompTree> {
+        self.tree.as_ref()
+    }
+
+    /// Count a decomposition, and remember it for the tree if one is being
+    /// recorded
+    pub(super) fn record_move(&mut self, m: DecompMove, verts: &[V]) {
+        self.moves.record(m);
+        if self.tree.is_some() {
+            self.last_move = Some((m, verts.to_vec()));
+        }
+    }
+
+    /// Record the decomposition of the graph at `tree_node`, whose terms are
+    /// on the stack from index `len`, or the term it was finished with
+    pub(super) fn record_step(&mut self, len: usize, term: Option<&TermResult<G>>) {
+        let node = self.tree_node.take();
+        let last_move = self.last_move.take();
+        let Some(t) = &mut self.tree else {
+            return;
+        };
+        if let Some(n) = node {
+            if let Some((m, verts)) = last_move {
+                t.nodes[n].decomp = Some(m);
+                t.nodes[n].verts = verts;
+            }
+            t.nodes[n].nterms = term.map_or(0, |t| t.nterms);
+        }
+        for (_, g) in self.stack.range(len..) {
+            self.tree_stack
+                .push_back(node.map(|n| t.add_node(Some(n), g.tcount())));
+        }
+    }
+
+    /// Merge the tree of a child decomposer made by [Decomposer::split]
+    /// into this one, along with the nodes of its stack
+    pub(super) fn merge_tree(
+        &mut self,
+        tree: Option<DecompTree>,
+        tree_stack: impl IntoIterator<Item = Option<usize>>,
+    ) {
+        if let (Some(t), Some(t1)) = (&mut self.tree, tree) {
+            let map = t.graft(t1);
+            self.tree_stack
+                .extend(tree_stack.into_iter().map(|n| n.map(|n| map[n])));
+        }
+    }
+
+    /// Mark the tree of a child decomposer as standing for the given node
+    pub(super) fn graft_tree_at(&mut self, node: Option<usize>) {
+        if let Some(t) = &mut self.tree {
+            t.graft_at = node;
+        }
+    }
+}
@@ -50,7 +50,7 @@ pub trait ToCircuit: GraphLike {
         self.clone().to_circuit_mut()
     }
 
-    fn extractor(&mut self) -> Extractor<Self> {
+    fn extractor(&mut self) -> Extractor<'_, Self> {
         Extractor::new(self)
     }
 }
@@ -63,7 +63,7 @@ pub struct Extractor<'a, G: GraphLike> {
 }
 
 impl<'a, G: GraphLike> Extractor<'a, G> {
-    pub fn new(g: &'a mut G) -> Extractor<G> {
+    pub fn new(g: &'a mut G) -> Extractor<'a, G> {
         Extractor {
             g,
             frontier: Vec::new(),
@@ -252,7 +252,8 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
     /// connecting inputs to outputs.
     fn perm_to_cnots(&mut self, c: &mut Circuit, blocksize: usize) {
         let mut m = Mat2::build(self.g.outputs().len(), self.g.inputs().len(), |i, j| {
-            self.g.connected(self.g.outputs()[i], self.g.inputs()[j])
+            self.g
+                .connected(self.g.output_qubit(i), self.g.input_qubit(j))
         });
 
         // Extract CNOTs until adj. matrix is in reduced echelon form
@@ -272,7 +273,7 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
         self.frontier = Vec::new();
 
         for q in 0..self.g.outputs().len() {
-            let o = self.g.outputs()[q];
+            let o = self.g.output_qubit(q);
             if let Some((v, et)) = self.g.incident_edges(o).next() {
                 // replace a Hadamard edge from the output with a Hadamard gate
                 if et == EType::H {
@@ -319,6 +320,7 @@ impl<'a, G: GraphLike> Extractor<'a, G> {
                                 phase: Phase::zero(),
                                 qubit: self.g.qubit(n),
                                 row: self.g.row(n) + 1,
+                                ground: false,
                             };
                             let n1 = self.g.add_vertex_with_data(vd);
                             self.g
@@ -566,6 +568,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn extract_up_to_perm() {
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            cx q[0], q[1];
+            cx q[1], q[0];
+            cx q[0], q[1];
+            t q[0];
+            cx q[1], q[2];
+            cx q[2], q[1];
+            cx q[1], q[2];
+        "#,
+        )
+        .unwrap();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+        let c1 = g.extractor().up_to_perm().extract().unwrap();
+
+        // the remaining graph realises a permutation, which comes before c1
+        let perm = g.io_permutation().expect("expected a permutation graph");
+        let mut inv = [0; 3];
+        for (i, &j) in perm.iter().enumerate() {
+            inv[j] = i;
+        }
+
+        // realise the permutation with swaps, where wire[k] is the input on wire k
+        let mut c2 = Circuit::new(3);
+        let mut wire: Vec<usize> = (0..3).collect();
+        for (k, &i) in inv.iter().enumerate() {
+            let j = wire.iter().position(|&w| w == i).unwrap();
+            if j != k {
+                c2.swap(k, j);
+                wire.swap(k, j);
+            }
+        }
+        c2 += &c1;
+        assert!(Tensor4::scalar_compare(&c, &c2));
+    }
+
     #[test]
     fn extract_swap() {
         let c = Circuit::from_qasm(
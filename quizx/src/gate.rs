@@ -21,7 +21,7 @@ use crate::scalar::*;
 use num::{Rational64, Zero};
 use std::cmp::max;
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub enum GType {
     XPhase,
     NOT,
@@ -41,6 +41,7 @@ pub enum GType {
     CCZ,
     InitAncilla,
     PostSelect,
+    Barrier,
     UnknownGate,
 }
 
@@ -69,6 +70,7 @@ impl GType {
             "xcx" => XCX,
             "init_anc" => InitAncilla,
             "post_sel" => PostSelect,
+            "barrier" => Barrier,
             _ => UnknownGate,
         }
     }
@@ -94,6 +96,7 @@ impl GType {
             XCX => "xcx",
             InitAncilla => "init_anc",
             PostSelect => "post_sel",
+            Barrier => "barrier",
             UnknownGate => "UNKNOWN",
         }
     }
@@ -106,30 +109,53 @@ impl GType {
         match self {
             CNOT | CZ | XCX | SWAP => Some(2),
             TOFF | CCZ => Some(3),
-            ParityPhase | UnknownGate => None,
+            ParityPhase | Barrier | UnknownGate => None,
             _ => Some(1),
         }
     }
 }
 
+/// The name and parameters of an opaque gate not known to quizx
+///
+/// This is kept around for gates of type [`GType::UnknownGate`], so they can
+/// be written back out to QASM.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct OpaqueGate {
+    pub name: String,
+    pub params: Vec<Phase>,
+}
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Gate {
     pub t: GType,
     pub qs: Vec<usize>,
     pub phase: Phase,
+    pub opaque: Option<OpaqueGate>,
 }
 
 impl Gate {
     pub fn from_qasm_name(s: &str) -> Gate {
+        let t = GType::from_qasm_name(s);
         Gate {
-            t: GType::from_qasm_name(s),
+            t,
             qs: vec![],
             phase: Phase::zero(),
+            opaque: if t == UnknownGate {
+                Some(OpaqueGate {
+                    name: s.to_string(),
+                    params: vec![],
+                })
+            } else {
+                None
+            },
         }
     }
 
-    pub fn qasm_name(&self) -> &'static str {
-        self.t.qasm_name()
+    pub fn qasm_name(&self) -> &str {
+        match &self.opaque {
+            Some(op) => &op.name,
+            None => self.t.qasm_name(),
+        }
     }
 
     pub fn to_qasm(&self) -> String {
@@ -137,6 +163,15 @@ impl Gate {
 
         if let ZPhase | XPhase = self.t {
             s += &format!("({}*pi)", self.phase.to_f64());
+        } else if let Some(op) = &self.opaque {
+            if !op.params.is_empty() {
+                let ps: Vec<String> = op
+                    .params
+                    .iter()
+                    .map(|p| format!("{}*pi", p.to_f64()))
+                    .collect();
+                s += &format!("({})", ps.join(", "));
+            }
         }
 
         s += " ";
@@ -158,6 +193,74 @@ impl Gate {
             _ => {} // everything else is self-adjoint
         }
     }
+
+    /// The basis in which this gate is diagonal on the given qubit, if any
+    ///
+    /// Returns `Some(VType::Z)` if the gate acts as a Z-controlled operation on
+    /// qubit `q`, `Some(VType::X)` if it does so in the X basis, and `None` otherwise.
+    fn diagonal_basis(&self, q: usize) -> Option<VType> {
+        let i = self.qs.iter().position(|&q1| q1 == q)?;
+        match self.t {
+            ZPhase | Z | S | T | Sdg | Tdg | CZ | CCZ | ParityPhase => Some(VType::Z),
+            XPhase | NOT | XCX => Some(VType::X),
+            CNOT if i == 0 => Some(VType::Z),
+            CNOT => Some(VType::X),
+            TOFF if i < 2 => Some(VType::Z),
+            TOFF => Some(VType::X),
+            _ => None,
+        }
+    }
+
+    /// Checks whether the gate acts as the identity, i.e. a rotation by 0
+    fn is_identity(&self) -> bool {
+        match self.t {
+            ZPhase | XPhase | XCX | ParityPhase => self.phase.is_zero(),
+            _ => false,
+        }
+    }
+
+    /// Checks whether the gate is unchanged by exchanging qubits `a` and `b`
+    fn is_symmetric(&self, a: usize, b: usize) -> bool {
+        let pos = |q| self.qs.iter().position(|&q1| q1 == q);
+        match (pos(a), pos(b)) {
+            (None, None) => true,
+            (Some(i), Some(j)) => match self.t {
+                CZ | CCZ | XCX | ParityPhase | SWAP => true,
+                TOFF => i < 2 && j < 2,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Checks whether this gate commutes with another gate
+    ///
+    /// Gates on disjoint qubits always commute, as do identical gates and
+    /// rotations by 0. A SWAP commutes with the gates that are symmetric in its
+    /// two qubits. Otherwise, two gates commute if on every shared qubit they
+    /// are both diagonal in the same (Z or X) basis. For the unitary gates in
+    /// [GType] this is exact. Barriers, ancilla initialisations,
+    /// postselections and unknown gates don't commute with anything on their
+    /// qubits.
+    pub fn commutes_with(&self, other: &Gate) -> bool {
+        if self == other || self.is_identity() || other.is_identity() {
+            return true;
+        }
+        if self.t == SWAP {
+            return other.is_symmetric(self.qs[0], self.qs[1]);
+        }
+        if other.t == SWAP {
+            return self.is_symmetric(other.qs[0], other.qs[1]);
+        }
+
+        self.qs
+            .iter()
+            .filter(|q| other.qs.contains(q))
+            .all(|&q| match self.diagonal_basis(q) {
+                Some(b) => other.diagonal_basis(q) == Some(b),
+                None => false,
+            })
+    }
 }
 
 impl Gate {
@@ -166,6 +269,7 @@ impl Gate {
             t,
             qs,
             phase: Phase::zero(),
+            opaque: None,
         }
     }
 
@@ -174,6 +278,7 @@ impl Gate {
             t,
             qs,
             phase: phase.into(),
+            opaque: None,
         }
     }
 
@@ -253,6 +358,7 @@ impl Gate {
                 phase: phase.into(),
                 qubit: (qubit as i32),
                 row,
+                ground: false,
             });
             graph.add_edge_with_type(v0, v, et);
             qs[qubit] = Some(v);
@@ -506,7 +612,84 @@ impl Gate {
                     g.add_to_graph(graph, qs, postselect);
                 }
             }
+            // barriers only matter for circuit-level passes
+            Barrier => {}
             UnknownGate => {}
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commutation() {
+        let cnot = Gate::new(CNOT, vec![0, 1]);
+        assert!(cnot.commutes_with(&Gate::new(T, vec![0])));
+        assert!(cnot.commutes_with(&Gate::new(NOT, vec![1])));
+        assert!(cnot.commutes_with(&Gate::new(CNOT, vec![0, 2])));
+        assert!(cnot.commutes_with(&Gate::new(CNOT, vec![2, 1])));
+        assert!(cnot.commutes_with(&Gate::new(HAD, vec![2])));
+        assert!(!cnot.commutes_with(&Gate::new(T, vec![1])));
+        assert!(!cnot.commutes_with(&Gate::new(CNOT, vec![1, 0])));
+        assert!(!cnot.commutes_with(&Gate::new(HAD, vec![0])));
+
+        let cz = Gate::new(CZ, vec![0, 1]);
+        assert!(cz.commutes_with(&Gate::new(CCZ, vec![2, 1, 0])));
+        assert!(cz.commutes_with(&Gate::new_with_phase(ZPhase, vec![1], (1, 3))));
+        assert!(!cz.commutes_with(&Gate::new_with_phase(XPhase, vec![1], (1, 3))));
+        assert!(Gate::new(HAD, vec![0]).commutes_with(&Gate::new(HAD, vec![0])));
+
+        let swap = Gate::new(SWAP, vec![0, 1]);
+        assert!(swap.commutes_with(&Gate::new(CZ, vec![1, 0])));
+        assert!(swap.commutes_with(&Gate::new(TOFF, vec![1, 0, 2])));
+        assert!(!swap.commutes_with(&Gate::new(TOFF, vec![0, 2, 1])));
+        assert!(Gate::new_with_phase(XPhase, vec![0], 0).commutes_with(&swap));
+    }
+
+    #[test]
+    fn commutation_is_exact() {
+        use crate::tensor::ToTensor;
+
+        let mut gates = vec![Gate::new(CCZ, vec![0, 1, 2])];
+        for q in 0..3 {
+            for t in [NOT, Z, S, T, Sdg, Tdg, HAD] {
+                gates.push(Gate::new(t, vec![q]));
+            }
+            for p in [(0, 1), (1, 4), (1, 1)] {
+                gates.push(Gate::new_with_phase(ZPhase, vec![q], p));
+                gates.push(Gate::new_with_phase(XPhase, vec![q], p));
+            }
+            for r in 0..3 {
+                if q != r {
+                    gates.push(Gate::new(CNOT, vec![q, r]));
+                    gates.push(Gate::new(TOFF, vec![q, r, 3 - q - r]));
+                }
+                if q < r {
+                    gates.push(Gate::new(CZ, vec![q, r]));
+                    gates.push(Gate::new(SWAP, vec![q, r]));
+                    gates.push(Gate::new_with_phase(XCX, vec![q, r], (1, 2)));
+                }
+            }
+        }
+
+        for g in &gates {
+            for h in &gates {
+                let mut gh = Circuit::new(3);
+                gh.push(g.clone());
+                gh.push(h.clone());
+                let mut hg = Circuit::new(3);
+                hg.push(h.clone());
+                hg.push(g.clone());
+                assert_eq!(
+                    g.commutes_with(h),
+                    gh.to_tensor4() == hg.to_tensor4(),
+                    "{} and {}",
+                    g.to_qasm(),
+                    h.to_qasm()
+                );
+            }
+        }
+    }
+}
@@ -14,9 +14,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::linalg::BitMatrix;
 use crate::phase::Phase;
 use crate::scalar::*;
-use derive_more::{Display, From};
+use derive_more::{Display, Error, From};
 use num::rational::Rational64;
 use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{Deserialize, Serialize};
@@ -35,20 +36,44 @@ pub enum VType {
     X, // X-spider
     #[serde(rename = "hadamard")]
     H, // H-box
+    /// W-node input. A W-node (triangle generator) is represented, as in
+    /// pyzx, by a `WInput` connected to its inputs and a `WOutput` connected
+    /// to its single output, joined by an [`EType::Wio`] edge.
     #[serde(rename = "W_input")]
     WInput,
+    /// W-node output, see [`VType::WInput`].
     #[serde(rename = "W_output")]
     WOutput,
     #[serde(rename = "Z_box")]
     ZBox,
 }
 
+impl VType {
+    /// The phase given to new vertices of this type
+    ///
+    /// This is 1 for H-boxes, giving the usual label of -1 as in pyzx, and 0
+    /// for everything else.
+    pub fn default_phase(&self) -> Phase {
+        match self {
+            VType::H => Phase::one(),
+            _ => Phase::zero(),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct VData {
     pub ty: VType,
     pub phase: Phase,
     pub qubit: i32,
     pub row: i32,
+    /// Whether the vertex is grounded, i.e. joined to a ground (discard)
+    /// generator, as for mixed-state diagrams in pyzx
+    ///
+    /// The simplification passes leave grounded vertices and their
+    /// neighbours alone. Graphs with grounded vertices can't be converted to
+    /// tensors, as they are not pure.
+    pub ground: bool,
 }
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -66,7 +91,8 @@ pub enum EType {
 }
 
 impl EType {
-    pub fn opposite(&self) -> EType {
+    /// Swap normal and Hadamard edges, leaving W edges unchanged
+    pub fn toggle(self) -> EType {
         match self {
             EType::N => EType::H,
             EType::H => EType::N,
@@ -74,6 +100,11 @@ impl EType {
         }
     }
 
+    /// Same as [toggle](EType::toggle)
+    pub fn opposite(&self) -> EType {
+        self.toggle()
+    }
+
     pub fn merge(et0: EType, et1: EType) -> EType {
         if et0 == EType::N {
             et1
@@ -342,6 +373,60 @@ impl<'a> Iterator for IncidentEdgeIter<'a> {
 
 impl<'a> ExactSizeIterator for IncidentEdgeIter<'a> {}
 
+/// An error from [GraphLike::try_remove_vertex]
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RemoveError {
+    /// The vertex is not in the graph.
+    #[display("Vertex {v} is not in the graph")]
+    NoSuchVertex { v: V },
+    /// The vertex is a boundary, so removing it would break the inputs or outputs.
+    #[display("Vertex {v} is a boundary")]
+    Boundary { v: V },
+}
+
+/// An error from [GraphLike::fuse]
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FuseError {
+    /// The vertex is not in the graph.
+    #[display("Vertex {v} is not in the graph")]
+    NoSuchVertex { v: V },
+    /// The vertices are not both Z spiders or both X spiders.
+    #[display("Vertices {keep} and {remove} are not spiders of the same colour")]
+    DifferentTypes { keep: V, remove: V },
+    /// The vertices are not connected by a normal edge, e.g. because the edge
+    /// between them is a Hadamard edge, or they are the same vertex.
+    #[display("Vertices {keep} and {remove} are not connected by a normal edge")]
+    NoNormalEdge { keep: V, remove: V },
+}
+
+/// An error from [GraphLike::set_h_box_label]
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HBoxLabelError {
+    /// The vertex is not an H-box.
+    #[display("Vertex {v} is not an H-box")]
+    NotHBox { v: V },
+    /// The label is not a unit complex number, so it can't be stored as a
+    /// phase.
+    #[display("H-box label {label} is not a unit complex number")]
+    NotUnit { label: Scalar4 },
+}
+
+/// A phase gadget, as found by [GraphLike::phase_gadgets]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseGadget {
+    /// The degree-1 spider carrying the phase
+    pub gadget: V,
+    /// The phase-free spider connecting the gadget to its legs
+    pub hub: V,
+    /// The other neighbours of the hub, in increasing order
+    pub legs: Vec<V>,
+    /// The phase of the gadget
+    pub phase: Phase,
+}
+
 pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// Initialise a new empty graph
     fn new() -> Self;
@@ -356,12 +441,16 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     fn num_edges(&self) -> usize;
 
     /// Get iterator over all vertices
-    fn vertices(&self) -> VIter;
+    ///
+    /// The order depends on the backend, and for some backends on the vertices
+    /// added and removed before. Use [vertices_sorted](GraphLike::vertices_sorted)
+    /// for an order which doesn't.
+    fn vertices(&self) -> VIter<'_>;
 
     /// Get iterator over all edges
     ///
     /// An "edge" is a triple (s, t, edge_type), where s <= t.
-    fn edges(&self) -> EIter;
+    fn edges(&self) -> EIter<'_>;
 
     /// List of boundary vertices which serve as inputs
     fn inputs(&self) -> &Vec<V>;
@@ -387,11 +476,38 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// Add a vertex with the given VData struct
     fn add_vertex_with_data(&mut self, d: VData) -> V;
 
-    /// Remove a vertex from a graph
+    /// Add a vertex with the given index and VData struct
     ///
-    /// Behavior is undefined if the vertex is not in the graph.
+    /// This puts back a vertex removed by [remove_vertex](GraphLike::remove_vertex),
+    /// e.g. to undo a change. Panics if `v` is already in the graph.
+    fn add_vertex_at(&mut self, v: V, d: VData);
+
+    /// Remove a vertex from a graph, along with all of its edges
+    ///
+    /// The neighbours of `v` stay in the graph, so callers don't need to
+    /// disconnect it first. Panics if the vertex is not in the graph. The
+    /// inputs and outputs are not updated, so if `v` is one of them, the
+    /// caller has to remove it there too. See
+    /// [try_remove_vertex](GraphLike::try_remove_vertex) for a version which
+    /// refuses to remove boundaries.
     fn remove_vertex(&mut self, v: V);
 
+    /// Remove a vertex which isn't a boundary, along with all of its edges
+    ///
+    /// Unlike [remove_vertex](GraphLike::remove_vertex), this returns an error
+    /// and leaves the graph unchanged if `v` is not in the graph or is a
+    /// boundary.
+    fn try_remove_vertex(&mut self, v: V) -> Result<(), RemoveError> {
+        if !self.contains_vertex(v) {
+            Err(RemoveError::NoSuchVertex { v })
+        } else if self.vertex_type(v) == VType::B {
+            Err(RemoveError::Boundary { v })
+        } else {
+            self.remove_vertex(v);
+            Ok(())
+        }
+    }
+
     /// Add an edge with the given type
     ///
     /// Behaviour is undefined if an edge already exists between s and t.
@@ -403,11 +519,46 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     fn remove_edge(&mut self, s: V, t: V);
 
     /// Set the phase of a vertex
+    ///
+    /// Boundary vertices don't have phases, so rewrites should only ever
+    /// change the phase of the spider next to a boundary. This is checked in
+    /// debug builds, for this and [GraphLike::add_to_phase].
     fn set_phase(&mut self, v: V, phase: impl Into<Phase>);
 
     /// Returns the phase of vertex `v`
     fn phase(&self, v: V) -> Phase;
 
+    /// Returns the label of the H-box `v`
+    ///
+    /// H-box labels are restricted to unit complex numbers, and are stored as the
+    /// phase of the vertex, so the label is e^(i π phase).
+    fn h_box_label(&self, v: V) -> Scalar4 {
+        debug_assert_eq!(self.vertex_type(v), VType::H, "Vertex is not an H-box");
+        Scalar4::from_phase(self.phase(v))
+    }
+
+    /// Sets the label of the H-box `v`, see [GraphLike::h_box_label]
+    ///
+    /// Exact labels are powers of e^(i π/4), which are stored exactly, and
+    /// float labels are stored as the nearest rational phase. Labels which are
+    /// not unit complex numbers, e.g. the 0 or 2 of an H-box in the ZH-calculus
+    /// proper, are rejected, and so are vertices which aren't H-boxes.
+    fn set_h_box_label(&mut self, v: V, label: Scalar4) -> Result<(), HBoxLabelError> {
+        if self.vertex_type(v) != VType::H {
+            return Err(HBoxLabelError::NotHBox { v });
+        }
+        let c = label.complex_value();
+        if (c.norm() - 1.0).abs() > 1e-10 {
+            return Err(HBoxLabelError::NotUnit { label });
+        }
+        let phase = (0..8)
+            .map(|k| Phase::new(Rational64::new(k, 4)))
+            .find(|&p| Scalar4::from_phase(p) == label)
+            .unwrap_or_else(|| Phase::from_f64(c.arg() / std::f64::consts::PI));
+        self.set_phase(v, phase);
+        Ok(())
+    }
+
     /// Adds a value to the phase of a vertex
     fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
         self.set_phase(v, self.phase(v) + phase.into());
@@ -424,11 +575,52 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     fn qubit(&self, v: V) -> i32;
     fn set_row(&mut self, v: V, row: i32);
     fn row(&self, v: V) -> i32;
-    fn neighbors(&self, v: V) -> NeighborIter;
-    fn incident_edges(&self, v: V) -> IncidentEdgeIter;
-    fn degree(&self, v: V) -> usize;
+    /// Mark a vertex as grounded or not, see [VData::ground]
+    fn set_ground(&mut self, v: V, ground: bool);
+    /// Whether the vertex is grounded, see [VData::ground]
+    fn is_ground(&self, v: V) -> bool {
+        self.vertex_data(v).ground
+    }
+    /// Iterate over the neighbours of `v`
+    ///
+    /// See [incident_edges](GraphLike::incident_edges) to get the edge types
+    /// too.
+    fn neighbors(&self, v: V) -> NeighborIter<'_>;
+
+    /// Iterate over the neighbours of `v`, along with the type of the edge to each
+    ///
+    /// This reads the adjacency structure directly, so it is cheaper than
+    /// calling [edge_type](GraphLike::edge_type) for each neighbour. For
+    /// example, the Hadamard neighbours of `v` are
+    /// `g.incident_edges(v).filter(|&(_, et)| et == EType::H)`.
+    fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_>;
+
+    /// Number of edges incident to `v`
+    ///
+    /// Graph types should override this to read the size of the adjacency
+    /// structure directly, rather than going through [neighbors](GraphLike::neighbors).
+    fn degree(&self, v: V) -> usize {
+        self.neighbors(v).len()
+    }
+
     fn scalar(&self) -> &ScalarN;
     fn scalar_mut(&mut self) -> &mut ScalarN;
+
+    /// Whether rewrites keep the global scalar up to date
+    ///
+    /// This is `true` for new graphs.
+    fn track_scalar(&self) -> bool;
+
+    /// Turn scalar tracking on or off
+    ///
+    /// With tracking off, [add_edge_smart](GraphLike::add_edge_smart) and the
+    /// rules in [basic_rules](crate::basic_rules) leave the scalar alone, which
+    /// saves some work when only the shape of the graph matters, e.g. while
+    /// searching for small circuits. The scalar, and hence the tensor computed
+    /// by [to_tensor](crate::tensor::ToTensor::to_tensor), is only correct if
+    /// tracking was on for every rewrite. Turning tracking back on does not
+    /// restore the scalar; rewrite a fresh copy of the graph instead.
+    fn set_track_scalar(&mut self, track: bool);
     fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
     where
         F: Fn(V, V, EType) -> bool;
@@ -450,12 +642,63 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     }
 
     fn toggle_edge_type(&mut self, v0: V, v1: V) {
-        self.set_edge_type(v0, v1, self.edge_type(v0, v1).opposite());
+        self.set_edge_type(v0, v1, self.edge_type(v0, v1).toggle());
+    }
+
+    /// Swap the colour of a Z or X spider and toggle all of its edges
+    ///
+    /// The phase is left alone, so this preserves the linear map. Self-loops
+    /// keep their type, as both ends are toggled. Panics if `v` is not a Z or
+    /// X spider.
+    fn color_change(&mut self, v: V) {
+        let ty = match self.vertex_type(v) {
+            VType::Z => VType::X,
+            VType::X => VType::Z,
+            _ => panic!("Color change only supported on Z and X nodes"),
+        };
+        self.set_vertex_type(v, ty);
+
+        let mut nhd = self.neighbor_vec(v);
+        nhd.sort();
+        let mut i = 0;
+        while i < nhd.len() {
+            let w = nhd[i];
+            let n = nhd[i..].iter().take_while(|&&w1| w1 == w).count();
+            if w == v {
+                // leave self-loops alone
+            } else if n == 1 {
+                self.toggle_edge_type(v, w);
+            } else {
+                // set_edge_type can't tell parallel edges apart, so they are
+                // removed and added back
+                let ets = Vec::from_iter(
+                    self.incident_edges(v)
+                        .filter(|&(w1, _)| w1 == w)
+                        .map(|(_, et)| et),
+                );
+                for et in ets {
+                    self.remove_edge(v, w);
+                    self.add_edge_with_type(v, w, et.toggle());
+                }
+            }
+            i += n;
+        }
     }
 
     fn vertex_vec(&self) -> Vec<V> {
         self.vertices().collect()
     }
+
+    /// Iterate over all vertices in ascending order of index
+    ///
+    /// Unlike [vertices](GraphLike::vertices), the order only depends on which
+    /// vertices are in the graph, so code which picks among them behaves the
+    /// same across runs and backends.
+    fn vertices_sorted(&self) -> impl Iterator<Item = V> {
+        let mut vs = self.vertex_vec();
+        vs.sort_unstable();
+        vs.into_iter()
+    }
     fn edge_vec(&self) -> Vec<(V, V, EType)> {
         self.edges().collect()
     }
@@ -466,14 +709,32 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         self.incident_edges(v).collect()
     }
 
+    /// The biadjacency matrix of Hadamard edges between two lists of vertices
+    ///
+    /// Entry (i, j) is 1 if there is a Hadamard edge between `rows[i]` and
+    /// `cols[j]`. This is the matrix used to find generalised flow in a
+    /// graph-like diagram.
+    fn biadjacency(&self, rows: &[V], cols: &[V]) -> BitMatrix {
+        let col_index: FxHashMap<V, usize> =
+            cols.iter().enumerate().map(|(j, &v)| (v, j)).collect();
+        let mut m = BitMatrix::zeros(rows.len(), cols.len());
+        for (i, &v) in rows.iter().enumerate() {
+            for (w, et) in self.incident_edges(v) {
+                if et == EType::H {
+                    if let Some(&j) = col_index.get(&w) {
+                        m.set(i, j, true);
+                    }
+                }
+            }
+        }
+        m
+    }
+
     /// Convert all X spiders to Z with the colour-change rule
     fn x_to_z(&mut self) {
         for v in Vec::from_iter(self.vertices()) {
             if self.vertex_type(v) == VType::X {
-                self.set_vertex_type(v, VType::Z);
-                for w in Vec::from_iter(self.neighbors(v)) {
-                    self.toggle_edge_type(v, w);
-                }
+                self.color_change(v);
             }
         }
     }
@@ -481,21 +742,74 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// Add a vertex to the graph with the given type and phase
     fn add_vertex_with_phase(&mut self, ty: VType, phase: impl Into<Phase>) -> V {
         let v = self.add_vertex(ty);
-        self.set_phase(v, phase.into());
+        let phase = phase.into();
+        // new vertices are phase-free, and boundaries have to stay that way
+        if !phase.is_zero() {
+            self.set_phase(v, phase);
+        }
         v
     }
 
+    /// Build a graph from a list of vertex types and edges
+    ///
+    /// The vertices are numbered `0..num_vertices`, in the order of
+    /// `vertex_types`, and all phases are zero. Edges are added with
+    /// [add_edge_with_type](GraphLike::add_edge_with_type), so they are not
+    /// simplified. Panics if `vertex_types` doesn't have `num_vertices`
+    /// entries. See [from_edges_with_phases](GraphLike::from_edges_with_phases)
+    /// to give the phases too.
+    fn from_edges(
+        num_vertices: usize,
+        vertex_types: &[VType],
+        edges: &[(V, V, EType)],
+        inputs: &[V],
+        outputs: &[V],
+    ) -> Self {
+        let phases = vec![Phase::zero(); num_vertices];
+        Self::from_edges_with_phases(num_vertices, vertex_types, &phases, edges, inputs, outputs)
+    }
+
+    /// Same as [from_edges](GraphLike::from_edges), with the phase of each vertex
+    fn from_edges_with_phases(
+        num_vertices: usize,
+        vertex_types: &[VType],
+        phases: &[Phase],
+        edges: &[(V, V, EType)],
+        inputs: &[V],
+        outputs: &[V],
+    ) -> Self {
+        assert_eq!(vertex_types.len(), num_vertices, "Wrong number of types");
+        assert_eq!(phases.len(), num_vertices, "Wrong number of phases");
+        let mut g = Self::new();
+        for (&ty, &phase) in vertex_types.iter().zip(phases) {
+            g.add_vertex_with_phase(ty, phase);
+        }
+        for &(s, t, ety) in edges {
+            g.add_edge_with_type(s, t, ety);
+        }
+        g.set_inputs(inputs.to_vec());
+        g.set_outputs(outputs.to_vec());
+        g
+    }
+
     /// Add an edge and simplify if necessary to remove parallel edges
     ///
     /// The behaviour of this function depends on the type of source/target
     /// vertex as well as the type of the existing edge (if there is one).
+    ///
+    /// If s == t, no edge is added. A normal self-loop on a Z or X spider is
+    /// the identity, so it is dropped. A Hadamard self-loop adds π to the phase
+    /// and multiplies the scalar by 1/sqrt(2). Self-loops on any other type of
+    /// vertex panic.
     fn add_edge_smart(&mut self, s: V, t: V, ety: EType) {
         let st = self.vertex_type(s);
+        // power of sqrt(2) to multiply the scalar by
+        let mut sqrt2_pow = 0;
         if s == t {
             if st == VType::Z || st == VType::X {
                 if ety == EType::H {
                     self.add_to_phase(s, Phase::one());
-                    self.scalar_mut().mul_sqrt2_pow(-1);
+                    sqrt2_pow = -1;
                 }
             } else {
                 panic!("Self-loops only supported on Z and X nodes");
@@ -508,16 +822,16 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
                         (EType::N, EType::N) => {} // ignore new edge
                         (EType::H, EType::H) => {
                             self.remove_edge(s, t);
-                            self.scalar_mut().mul_sqrt2_pow(-2);
+                            sqrt2_pow = -2;
                         }
                         (EType::H, EType::N) => {
                             self.set_edge_type(s, t, EType::N);
                             self.add_to_phase(s, Rational64::new(1, 1));
-                            self.scalar_mut().mul_sqrt2_pow(-1);
+                            sqrt2_pow = -1;
                         }
                         (EType::N, EType::H) => {
                             self.add_to_phase(s, Rational64::new(1, 1));
-                            self.scalar_mut().mul_sqrt2_pow(-1);
+                            sqrt2_pow = -1;
                         }
                         (EType::Wio, _) | (_, EType::Wio) => {
                             unimplemented!("W nodes not supported")
@@ -528,16 +842,16 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
                     match (ety0, ety) {
                         (EType::N, EType::N) => {
                             self.remove_edge(s, t);
-                            self.scalar_mut().mul_sqrt2_pow(-2);
+                            sqrt2_pow = -2;
                         }
                         (EType::N, EType::H) => {
                             self.set_edge_type(s, t, EType::H);
                             self.add_to_phase(s, Rational64::new(1, 1));
-                            self.scalar_mut().mul_sqrt2_pow(-1);
+                            sqrt2_pow = -1;
                         }
                         (EType::H, EType::N) => {
                             self.add_to_phase(s, Rational64::new(1, 1));
-                            self.scalar_mut().mul_sqrt2_pow(-1);
+                            sqrt2_pow = -1;
                         }
                         (EType::H, EType::H) => {} // ignore new edge
                         (EType::Wio, _) | (_, EType::Wio) => {
@@ -550,6 +864,120 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         } else {
             self.add_edge_with_type(s, t, ety);
         }
+
+        if sqrt2_pow != 0 && self.track_scalar() {
+            self.scalar_mut().mul_sqrt2_pow(sqrt2_pow);
+        }
+    }
+
+    /// Fuse two spiders of the same colour connected by a normal edge
+    ///
+    /// The edges of `remove` are moved onto `keep` with
+    /// [add_edge_smart](GraphLike::add_edge_smart), which merges parallel
+    /// edges and updates the scalar, its phase is added to that of `keep`,
+    /// and `remove` is deleted. If either spider is
+    /// [grounded](VData::ground), so is the fused one. Self-loops on `remove`
+    /// become self-loops on `keep`, so they are applied as in
+    /// [add_edge_smart](GraphLike::add_edge_smart). Returns an error and
+    /// leaves the graph unchanged if the spiders can't be fused, see
+    /// [fuse_unchecked](GraphLike::fuse_unchecked) for the same without the
+    /// checks.
+    fn fuse(&mut self, keep: V, remove: V) -> Result<(), FuseError> {
+        for v in [keep, remove] {
+            if !self.contains_vertex(v) {
+                return Err(FuseError::NoSuchVertex { v });
+            }
+        }
+        let ty = self.vertex_type(keep);
+        if !matches!(ty, VType::Z | VType::X) || self.vertex_type(remove) != ty {
+            return Err(FuseError::DifferentTypes { keep, remove });
+        }
+        if keep == remove || self.edge_type_opt(keep, remove) != Some(EType::N) {
+            return Err(FuseError::NoNormalEdge { keep, remove });
+        }
+
+        self.fuse_unchecked(keep, remove);
+        Ok(())
+    }
+
+    /// Fuse two spiders as [fuse](GraphLike::fuse) does, without checking
+    /// they can be fused
+    ///
+    /// [spider_fusion](crate::basic_rules::spider_fusion) is done with this.
+    /// If the spiders are not of the same colour and connected by a normal
+    /// edge, the graph is changed anyway, and no longer represents the same
+    /// linear map.
+    fn fuse_unchecked(&mut self, keep: V, remove: V) {
+        while let Some(et) = self.edge_type_opt(remove, remove) {
+            self.remove_edge(remove, remove);
+            self.add_edge_smart(keep, keep, et);
+        }
+        for (v, et) in Vec::from_iter(self.incident_edges(remove)) {
+            if v != keep {
+                self.add_edge_smart(keep, v, et);
+            }
+        }
+        self.add_to_phase(keep, self.phase(remove));
+        if self.is_ground(remove) {
+            self.set_ground(keep, true);
+        }
+        self.remove_vertex(remove);
+    }
+
+    /// Merge parallel edges, as [GraphLike::add_edge_smart] would
+    ///
+    /// Depending on the backend, [GraphLike::add_edge_with_type] can leave
+    /// several edges between the same pair of vertices. These are removed and
+    /// added back one at a time with [GraphLike::add_edge_smart], which cancels
+    /// pairs of edges with the Hopf rule or turns them into a π phase, updating
+    /// the scalar. Self-loops are left alone.
+    ///
+    /// Panics if there are parallel edges at a boundary, since a boundary has
+    /// exactly one edge, and so the graph is not a valid diagram.
+    fn merge_parallel_edges(&mut self) {
+        let mut edges = self.edge_vec();
+        edges.retain(|&(s, t, _)| s != t);
+        edges.sort();
+
+        let mut i = 0;
+        while i < edges.len() {
+            let (s, t, _) = edges[i];
+            let n = edges[i..]
+                .iter()
+                .take_while(|&&(s1, t1, _)| (s1, t1) == (s, t))
+                .count();
+            if n > 1 {
+                if let Some(b) = [s, t]
+                    .into_iter()
+                    .find(|&v| self.vertex_type(v) == VType::B)
+                {
+                    panic!("Parallel edges at boundary vertex {}", b);
+                }
+                for _ in 0..n {
+                    self.remove_edge(s, t);
+                }
+                for &(_, _, et) in &edges[i..i + n] {
+                    self.add_edge_smart(s, t, et);
+                }
+            }
+            i += n;
+        }
+    }
+
+    /// Remove self-loops on spiders, as [GraphLike::add_edge_smart] would
+    ///
+    /// [GraphLike::add_edge_with_type] adds self-loops as ordinary edges,
+    /// which count twice towards the degree of the vertex. This removes them
+    /// and applies their effect on the phase and scalar.
+    ///
+    /// Panics if there is a self-loop on a vertex that isn't a Z or X spider.
+    fn remove_self_loops(&mut self) {
+        for v in self.vertex_vec() {
+            while let Some(et) = self.edge_type_opt(v, v) {
+                self.remove_edge(v, v);
+                self.add_edge_smart(v, v, et);
+            }
+        }
     }
 
     /// Replace a boundary vertex with the given basis element
@@ -614,15 +1042,33 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// The renaming map is returned. The scalars are multiplied, but the inputs/outputs
     /// of `self` are NOT updated.
     fn append_graph(&mut self, other: &impl GraphLike) -> FxHashMap<V, V> {
-        let mut vmap = FxHashMap::default();
+        self.insert_graph(other, &[])
+    }
+
+    /// Splices the given graph into the current one, gluing some of its vertices onto
+    /// existing ones
+    ///
+    /// Each pair `(w, v)` in `boundary_map` identifies the vertex `w` of `other` with the
+    /// vertex `v` of `self`, so `w` is not added and its edges are attached to `v`
+    /// instead, with [GraphLike::add_edge_smart]. The data of `w` is ignored. All other
+    /// vertices of `other` are added with fresh names. The renaming map, which sends each
+    /// `w` to its `v`, is returned. The scalars are multiplied, but the inputs/outputs of
+    /// `self` are NOT updated.
+    fn insert_graph(&mut self, other: &impl GraphLike, boundary_map: &[(V, V)]) -> FxHashMap<V, V> {
+        let mut vmap: FxHashMap<V, V> = boundary_map.iter().copied().collect();
 
         for v in other.vertices() {
-            let v1 = self.add_vertex_with_data(other.vertex_data(v));
-            vmap.insert(v, v1);
+            vmap.entry(v)
+                .or_insert_with(|| self.add_vertex_with_data(other.vertex_data(v)));
         }
 
+        let glued: FxHashSet<V> = boundary_map.iter().map(|&(w, _)| w).collect();
         for (v0, v1, et) in other.edges() {
-            self.add_edge_with_type(vmap[&v0], vmap[&v1], et);
+            if glued.contains(&v0) || glued.contains(&v1) {
+                self.add_edge_smart(vmap[&v0], vmap[&v1], et);
+            } else {
+                self.add_edge_with_type(vmap[&v0], vmap[&v1], et);
+            }
         }
 
         *self.scalar_mut() *= other.scalar();
@@ -662,13 +1108,154 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         self.set_outputs(outp);
     }
 
+    /// Number of qubits of a graph with matching inputs and outputs
+    ///
+    /// Panics if the number of inputs differs from the number of outputs.
+    fn num_qubits(&self) -> usize {
+        let n = self.inputs().len();
+        assert_eq!(
+            n,
+            self.outputs().len(),
+            "Number of inputs and outputs should match"
+        );
+        n
+    }
+
+    /// The input boundary vertex of qubit `q`
+    ///
+    /// Panics if there is no input `q`.
+    fn input_qubit(&self, q: usize) -> V {
+        self.inputs()[q]
+    }
+
+    /// The output boundary vertex of qubit `q`
+    ///
+    /// Panics if there is no output `q`.
+    fn output_qubit(&self, q: usize) -> V {
+        self.outputs()[q]
+    }
+
     /// Checks if the given graph only consists of wires from the inputs to outputs (in order)
     fn is_identity(&self) -> bool {
         let n = self.inputs().len();
-        self.inputs().len() == n
-            && self.outputs().len() == n
+        self.outputs().len() == n
+            && self.num_vertices() == 2 * n
+            && (0..n).all(|q| self.connected(self.input_qubit(q), self.output_qubit(q)))
+    }
+
+    /// Returns the permutation of qubits realised by the graph, if there is one
+    ///
+    /// If every input is connected to an output by a plain wire, possibly passing
+    /// through phase-free Z and X spiders of arity 2, this returns a vector `p` where
+    /// input `i` is connected to output `p[i]`. Otherwise, it returns `None`. The
+    /// scalar of the graph is ignored.
+    ///
+    /// Extracting a circuit with [`Extractor::up_to_perm`](crate::extract::Extractor::up_to_perm)
+    /// leaves behind such a graph, which can be queried with this method.
+    fn io_permutation(&self) -> Option<Vec<usize>> {
+        let n = self.inputs().len();
+        if self.outputs().len() != n {
+            return None;
+        }
+
+        let mut perm = Vec::with_capacity(n);
+        let mut num_seen = 0;
+        for &i in self.inputs() {
+            if self.degree(i) != 1 {
+                return None;
+            }
+
+            let mut prev = i;
+            let (mut v, mut et) = self.incident_edges(i).next()?;
+            loop {
+                if et != EType::N {
+                    return None;
+                }
+                match self.vertex_type(v) {
+                    VType::B if self.degree(v) == 1 => break,
+                    VType::Z | VType::X if self.degree(v) == 2 && self.phase(v).is_zero() => {
+                        let (w, et1) = self.incident_edges(v).find(|&(w, _)| w != prev)?;
+                        prev = v;
+                        (v, et) = (w, et1);
+                        num_seen += 1;
+                    }
+                    _ => return None,
+                }
+            }
+
+            perm.push(self.outputs().iter().position(|&o| o == v)?);
+            num_seen += 2;
+        }
+
+        // anything not on a wire means this isn't a permutation
+        if num_seen == self.num_vertices() {
+            Some(perm)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the scalar of the graph is zero, hence so is the whole diagram
+    ///
+    /// Rules such as [remove_single](crate::basic_rules::remove_single) set the
+    /// scalar to zero when they find the diagram is zero, e.g. after plugging
+    /// orthogonal states together, if the scalar is being tracked.
+    fn is_zero(&self) -> bool {
+        self.scalar().is_zero()
+    }
+
+    /// Replace the graph with the canonical diagram of the zero map
+    ///
+    /// The inputs and outputs are kept, and each one is connected to its own
+    /// phase-free Z spider. Everything else is removed and the scalar is set
+    /// to zero.
+    fn make_zero(&mut self) {
+        let bs: FxHashSet<V> = self
+            .inputs()
+            .iter()
+            .chain(self.outputs().iter())
+            .copied()
+            .collect();
+        for v in self.vertex_vec() {
+            if !bs.contains(&v) {
+                self.remove_vertex(v);
+            }
+        }
+        for (s, t, _) in self.edge_vec() {
+            self.remove_edge(s, t);
+        }
+        for &b in self
+            .inputs()
+            .clone()
+            .iter()
+            .chain(self.outputs().clone().iter())
+        {
+            let v = self.add_vertex_with_data(VData {
+                ty: VType::Z,
+                phase: Phase::zero(),
+                qubit: self.qubit(b),
+                row: self.row(b),
+                ground: false,
+            });
+            self.add_edge(b, v);
+        }
+        *self.scalar_mut() = ScalarN::zero();
+    }
+
+    /// Checks if the graph is the canonical zero diagram made by [make_zero](GraphLike::make_zero)
+    fn is_zero_canonical(&self) -> bool {
+        let n = self.inputs().len() + self.outputs().len();
+        self.is_zero()
             && self.num_vertices() == 2 * n
-            && (0..n).all(|i| self.connected(self.inputs()[i], self.outputs()[i]))
+            && self.num_edges() == n
+            && self.inputs().iter().chain(self.outputs().iter()).all(|&b| {
+                self.incident_edges(b).all(|(v, et)| {
+                    et == EType::N
+                        && self.vertex_type(v) == VType::Z
+                        && self.phase(v).is_zero()
+                        && self.degree(v) == 1
+                })
+            })
     }
 
     /// Return number of Z or X spiders with non-Clifford phase
@@ -683,6 +1270,30 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
         n
     }
 
+    /// Checks if the graph is a Clifford diagram
+    ///
+    /// This is the case if every Z and X spider has a phase which is a multiple of
+    /// 1/2, and every H-box is a plain Hadamard, i.e. has label -1 and arity at most 2.
+    /// W nodes and Z-boxes are not Clifford.
+    fn is_clifford(&self) -> bool {
+        self.vertices().all(|v| match self.vertex_type(v) {
+            VType::B => true,
+            VType::Z | VType::X => self.phase(v).is_clifford(),
+            VType::H => self.phase(v).is_one() && self.degree(v) <= 2,
+            VType::WInput | VType::WOutput | VType::ZBox => false,
+        })
+    }
+
+    /// Simplify a Clifford graph to a pseudo-normal form
+    ///
+    /// Shorthand for [`simplify::clifford_simp`](crate::simplify::clifford_simp). When
+    /// [`is_clifford`](GraphLike::is_clifford) holds, this removes all interior spiders,
+    /// leaving a graph state with local Cliffords between the inputs and outputs.
+    /// Returns `true` if any rewrite was applied.
+    fn clifford_simp(&mut self) -> bool {
+        crate::simplify::clifford_simp(self)
+    }
+
     /// Return a graphviz-friendly string representation of the graph
     fn to_dot(&self) -> String {
         let mut dot = String::from("graph {\n");
@@ -737,8 +1348,10 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
     /// Exchange inputs and outputs and reverse all phases
     fn adjoint(&mut self) {
         for v in self.vertex_vec() {
-            let p = self.phase(v);
-            self.set_phase(v, -p);
+            if self.vertex_type(v) != VType::B {
+                let p = self.phase(v);
+                self.set_phase(v, -p);
+            }
         }
 
         let inp = self.inputs().clone();
@@ -787,13 +1400,245 @@ pub trait GraphLike: Clone + Sized + Send + Sync + std::fmt::Debug {
 
         comps
     }
+
+    /// Returns the subgraph on the given vertices, with fresh names
+    ///
+    /// Only edges between two of the given vertices are kept. The new graph
+    /// has scalar one and no inputs or outputs.
+    fn induced_subgraph(&self, vs: &FxHashSet<V>) -> Self {
+        let mut g = Self::new();
+        let mut vmap = FxHashMap::default();
+        for v in self.vertices().filter(|v| vs.contains(v)) {
+            vmap.insert(v, g.add_vertex_with_data(self.vertex_data(v)));
+        }
+
+        for (v0, v1, et) in self.edges() {
+            if let (Some(&w0), Some(&w1)) = (vmap.get(&v0), vmap.get(&v1)) {
+                g.add_edge_with_type(w0, w1, et);
+            }
+        }
+
+        g
+    }
+
+    /// Returns the phase gadget with the given degree-1 spider, if there is one
+    ///
+    /// The gadget must be a Z spider connected by a Hadamard edge to a
+    /// phase-free Z spider, the hub. All the other neighbours of the hub are
+    /// its legs. These must be Z spiders connected by Hadamard edges, and
+    /// there must be at least one. None of them can have degree 1, so each hub
+    /// belongs to at most one gadget.
+    fn phase_gadget(&self, v: V) -> Option<PhaseGadget> {
+        if self.vertex_type(v) != VType::Z || self.degree(v) != 1 {
+            return None;
+        }
+        let (hub, et) = self.incident_edges(v).next().unwrap();
+        if et != EType::H
+            || hub == v
+            || self.vertex_type(hub) != VType::Z
+            || !self.phase(hub).is_zero()
+        {
+            return None;
+        }
+
+        let mut legs = Vec::new();
+        for (n, et) in self.incident_edges(hub) {
+            if n == v {
+                continue;
+            }
+            if et != EType::H || self.vertex_type(n) != VType::Z || self.degree(n) == 1 {
+                return None;
+            }
+            legs.push(n);
+        }
+        if legs.is_empty() {
+            return None;
+        }
+        legs.sort();
+
+        Some(PhaseGadget {
+            gadget: v,
+            hub,
+            legs,
+            phase: self.phase(v),
+        })
+    }
+
+    /// Iterates over the phase gadgets in the graph
+    ///
+    /// See [phase_gadget](GraphLike::phase_gadget) for what counts as a
+    /// phase gadget.
+    fn phase_gadgets(&self) -> impl Iterator<Item = PhaseGadget> + '_ {
+        self.vertices().filter_map(|v| self.phase_gadget(v))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tensor::ToTensor;
+    use crate::tensor::{Tensor4, ToTensor};
     use crate::vec_graph::Graph;
+    #[test]
+    fn io_permutation() {
+        use crate::circuit::Circuit;
+
+        let mut c = Circuit::new(3);
+        assert_eq!(c.to_graph::<Graph>().io_permutation(), Some(vec![0, 1, 2]));
+
+        c.swap(0, 1).swap(1, 2);
+        let g: Graph = c.to_graph();
+        assert_eq!(g.io_permutation(), Some(vec![2, 0, 1]));
+
+        // composing with another swap composes the permutations
+        let mut g1 = g.clone();
+        let mut c1 = Circuit::new(3);
+        c1.swap(0, 2);
+        g1.plug(&c1.to_graph::<Graph>());
+        assert_eq!(g1.io_permutation(), Some(vec![0, 2, 1]));
+
+        // phases, Hadamards, and entangling gates are not permutations
+        c.t(0);
+        assert_eq!(c.to_graph::<Graph>().io_permutation(), None);
+        let mut c2 = Circuit::new(2);
+        c2.h(1);
+        assert_eq!(c2.to_graph::<Graph>().io_permutation(), None);
+        let mut c3 = Circuit::new(2);
+        c3.cx(0, 1);
+        assert_eq!(c3.to_graph::<Graph>().io_permutation(), None);
+    }
+
+    #[test]
+    fn from_edges() {
+        use {EType::N, VType::*};
+        let g = Graph::from_edges_with_phases(
+            4,
+            &[B, Z, X, B],
+            &[
+                Phase::zero(),
+                Rational64::new(1, 4).into(),
+                Phase::one(),
+                Phase::zero(),
+            ],
+            &[(0, 1, N), (1, 2, EType::H), (2, 3, N)],
+            &[0],
+            &[3],
+        );
+        assert_eq!(g.vertex_vec(), vec![0, 1, 2, 3]);
+        assert_eq!(g.num_edges(), 3);
+        assert_eq!(g.edge_type(1, 2), EType::H);
+        assert_eq!(g.vertex_type(2), X);
+        assert_eq!(g.phase(1), Rational64::new(1, 4).into());
+        assert_eq!(g.inputs(), &vec![0]);
+        assert_eq!(g.outputs(), &vec![3]);
+
+        let h = Graph::from_edges(2, &[B, B], &[(0, 1, N)], &[0], &[1]);
+        assert!(h.is_identity());
+    }
+
+    #[test]
+    fn incident_edges() {
+        let mut g = Graph::new();
+        let v = g.add_vertex(VType::Z);
+        let ws: Vec<_> = (0..4).map(|_| g.add_vertex(VType::Z)).collect();
+        g.add_edge_with_type(v, ws[0], EType::H);
+        g.add_edge_with_type(v, ws[1], EType::N);
+        g.add_edge_with_type(v, ws[2], EType::H);
+        g.add_edge_with_type(ws[1], ws[3], EType::H);
+
+        let mut hs: Vec<_> = g
+            .incident_edges(v)
+            .filter(|&(_, et)| et == EType::H)
+            .map(|(w, _)| w)
+            .collect();
+        hs.sort();
+        assert_eq!(hs, vec![ws[0], ws[2]]);
+        for (w, et) in g.incident_edges(v) {
+            assert_eq!(g.edge_type(v, w), et);
+        }
+        assert_eq!(g.incident_edges(v).len(), g.degree(v));
+    }
+
+    #[test]
+    fn vertices_sorted() {
+        let mut g = crate::hash_graph::Graph::new();
+        let vs: Vec<_> = (0..100).map(|_| g.add_vertex(VType::Z)).collect();
+        for &v in vs.iter().step_by(3) {
+            g.remove_vertex(v);
+        }
+        let mut expected = g.vertex_vec();
+        expected.sort();
+        assert_eq!(g.vertices_sorted().collect::<Vec<_>>(), expected);
+        assert!(expected.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn qubit_boundaries() {
+        use crate::circuit::Circuit;
+
+        let mut c = Circuit::new(3);
+        c.cx(0, 2);
+        let g: Graph = c.to_graph();
+        assert_eq!(g.num_qubits(), 3);
+        for q in 0..3 {
+            assert_eq!(g.input_qubit(q), g.inputs()[q]);
+            assert_eq!(g.output_qubit(q), g.outputs()[q]);
+            assert_eq!(g.qubit(g.input_qubit(q)), q as i32);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn qubit_count_mismatch() {
+        let mut g = Graph::new();
+        let i = g.add_vertex(VType::B);
+        g.set_inputs(vec![i]);
+        g.num_qubits();
+    }
+
+    #[test]
+    fn clifford_graphs() {
+        use crate::circuit::Circuit;
+
+        for seed in 0..10 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(30)
+                .clifford_t(0.0)
+                .build();
+            let g: Graph = c.to_graph();
+            assert!(g.is_clifford());
+
+            let mut h = g.clone();
+            h.clifford_simp();
+            assert!(h.is_clifford());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+            // every remaining spider is next to a boundary
+            for v in h.vertices() {
+                if h.vertex_type(v) != VType::B {
+                    assert!(h.neighbors(v).any(|w| h.vertex_type(w) == VType::B));
+                }
+            }
+        }
+
+        let mut c = Circuit::new(2);
+        c.cx(0, 1).t(1);
+        assert!(!c.to_graph::<Graph>().is_clifford());
+
+        let mut g = Graph::new();
+        let h = g.add_vertex(VType::H);
+        assert!(g.is_clifford());
+        g.set_phase(h, Rational64::new(1, 2));
+        assert!(!g.is_clifford());
+        g.set_phase(h, 1);
+        for _ in 0..3 {
+            let v = g.add_vertex(VType::Z);
+            g.add_edge(h, v);
+        }
+        assert!(!g.is_clifford());
+    }
+
     #[test]
     fn smart_edges() {
         let mut g = Graph::new();
@@ -829,6 +1674,114 @@ mod tests {
         assert_eq!(tg, th);
     }
 
+    #[test]
+    fn color_change_twice() {
+        use crate::circuit::Circuit;
+
+        assert_eq!(EType::N.toggle(), EType::H);
+        assert_eq!(EType::H.toggle(), EType::N);
+        assert_eq!(EType::Wio.toggle(), EType::Wio);
+
+        let c = Circuit::from_qasm(
+            r#"
+            qreg q[3];
+            cx q[0], q[1];
+            h q[1];
+            rz(0.25*pi) q[1];
+            cz q[1], q[2];
+            rx(0.5*pi) q[0];
+            "#,
+        )
+        .unwrap();
+        let h: Graph = c.to_graph();
+        let t = h.to_tensor4();
+
+        for v in h.vertices() {
+            if h.vertex_type(v) == VType::B {
+                continue;
+            }
+            let mut g = h.clone();
+            g.color_change(v);
+            assert_ne!(g.vertex_type(v), h.vertex_type(v));
+            assert_eq!(g.phase(v), h.phase(v));
+            for (w, et) in h.incident_edges(v) {
+                assert_eq!(g.edge_type(v, w), et.toggle());
+            }
+            assert_eq!(g.to_tensor4(), t);
+
+            g.color_change(v);
+            assert_eq!(g, h);
+        }
+    }
+
+    #[test]
+    fn make_zero() {
+        use crate::circuit::Circuit;
+
+        let mut c = Circuit::new(2);
+        c.cx(0, 1).t(1).h(0);
+        let mut g: Graph = c.to_graph();
+        assert!(!g.is_zero_canonical());
+        g.make_zero();
+        assert!(g.is_zero());
+        assert!(g.is_zero_canonical());
+        assert_eq!(g.num_vertices(), 8);
+        assert_eq!(g.num_edges(), 4);
+        assert_eq!(g.to_tensor4(), Tensor4::zeros(vec![2; 4]));
+
+        // boundaries connected straight to each other
+        let mut g: Graph = Circuit::new(2).to_graph();
+        g.make_zero();
+        assert!(g.is_zero_canonical());
+        assert_eq!(g.to_tensor4(), Tensor4::zeros(vec![2; 4]));
+    }
+
+    #[test]
+    fn phase_gadgets() {
+        let mut g = Graph::new();
+        let legs: Vec<_> = (0..3).map(|_| g.add_vertex(VType::Z)).collect();
+        g.add_edge(legs[0], legs[2]);
+        let hub0 = g.add_vertex(VType::Z);
+        let gadget0 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        g.add_edge_with_type(gadget0, hub0, EType::H);
+        for &l in &legs {
+            g.add_edge_with_type(hub0, l, EType::H);
+        }
+        // a hub with a phase isn't a gadget
+        let hub1 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 1));
+        let gadget1 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        g.add_edge_with_type(gadget1, hub1, EType::H);
+        g.add_edge_with_type(hub1, legs[0], EType::H);
+        // neither is a hub with a plain edge to a leg
+        let hub2 = g.add_vertex(VType::Z);
+        let gadget2 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 2));
+        g.add_edge_with_type(gadget2, hub2, EType::H);
+        g.add_edge_with_type(hub2, legs[1], EType::N);
+
+        let gadgets: Vec<_> = g.phase_gadgets().collect();
+        assert_eq!(
+            gadgets,
+            vec![PhaseGadget {
+                gadget: gadget0,
+                hub: hub0,
+                legs: legs.clone(),
+                phase: Rational64::new(1, 4).into(),
+            }]
+        );
+
+        g.set_phase(hub1, Rational64::new(0, 1));
+        g.set_edge_type(hub2, legs[1], EType::H);
+        assert_eq!(g.phase_gadgets().count(), 3);
+        assert_eq!(g.phase_gadget(gadget2).unwrap().legs, vec![legs[1]]);
+        assert_eq!(g.phase_gadget(legs[0]), None);
+
+        // two phases on one hub are ambiguous
+        let extra = g.add_vertex(VType::Z);
+        g.add_edge_with_type(extra, hub0, EType::H);
+        assert_eq!(g.phase_gadget(gadget0), None);
+        assert_eq!(g.phase_gadget(extra), None);
+    }
+
     #[test]
     fn plugs() {
         let mut g = Graph::new();
@@ -879,4 +1832,128 @@ mod tests {
 
         assert_eq!(g.component_vertices().first().unwrap().len(), 4)
     }
+
+    #[test]
+    fn try_remove() {
+        let mut g: Graph = Graph::new();
+        let b = g.add_vertex(VType::B);
+        let z = g.add_vertex(VType::Z);
+        let x = g.add_vertex(VType::X);
+        g.add_edge(b, z);
+        g.add_edge(z, x);
+
+        assert_eq!(g.try_remove_vertex(b), Err(RemoveError::Boundary { v: b }));
+        assert_eq!(
+            g.try_remove_vertex(7),
+            Err(RemoveError::NoSuchVertex { v: 7 })
+        );
+        assert_eq!(g.num_vertices(), 3);
+
+        assert_eq!(g.try_remove_vertex(z), Ok(()));
+        assert!(!g.contains_vertex(z));
+        assert_eq!(g.num_edges(), 0);
+        assert_eq!(g.degree(b), 0);
+        assert_eq!(g.degree(x), 0);
+    }
+
+    #[test]
+    fn fuse() {
+        let mut g: Graph = Graph::new();
+        let b = g.add_vertex(VType::B);
+        let z0 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let z1 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 2));
+        let z2 = g.add_vertex(VType::Z);
+        let x = g.add_vertex(VType::X);
+        g.add_edge(b, z0);
+        g.add_edge(z0, z1);
+        g.add_edge_with_type(z0, z2, EType::H);
+        g.add_edge_with_type(z1, z2, EType::H);
+        g.add_edge(z1, x);
+        g.set_inputs(vec![b]);
+
+        assert_eq!(g.fuse(z0, 9), Err(FuseError::NoSuchVertex { v: 9 }));
+        assert_eq!(
+            g.fuse(z1, x),
+            Err(FuseError::DifferentTypes {
+                keep: z1,
+                remove: x
+            })
+        );
+        assert_eq!(
+            g.fuse(z0, z2),
+            Err(FuseError::NoNormalEdge {
+                keep: z0,
+                remove: z2
+            })
+        );
+        assert_eq!(
+            g.fuse(z0, z0),
+            Err(FuseError::NoNormalEdge {
+                keep: z0,
+                remove: z0
+            })
+        );
+        assert_eq!(g.num_vertices(), 5);
+
+        let h = g.clone();
+        assert_eq!(g.fuse(z0, z1), Ok(()));
+        assert!(!g.contains_vertex(z1));
+        assert_eq!(g.phase(z0), Rational64::new(3, 4).into());
+        // the two Hadamard edges to z2 cancel
+        assert!(!g.connected(z0, z2));
+        assert!(g.connected(z0, x));
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // a ground on either spider is kept
+        let z3 = g.add_vertex(VType::Z);
+        g.add_edge(z0, z3);
+        g.set_ground(z3, true);
+        assert_eq!(g.fuse(z0, z3), Ok(()));
+        assert!(g.is_ground(z0));
+    }
+
+    #[test]
+    fn fuse_self_loops() {
+        for et in [EType::N, EType::H] {
+            let mut g: Graph = Graph::new();
+            let b0 = g.add_vertex(VType::B);
+            let b1 = g.add_vertex(VType::B);
+            let z0 = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+            let z1 = g.add_vertex(VType::Z);
+            g.add_edge(b0, z0);
+            g.add_edge(z0, z1);
+            g.add_edge(z1, b1);
+            g.add_edge_with_type(z1, z1, et);
+            g.set_inputs(vec![b0]);
+            g.set_outputs(vec![b1]);
+
+            let mut h = g.clone();
+            h.remove_self_loops();
+            assert_eq!(g.fuse(z0, z1), Ok(()));
+            assert_eq!(g.num_edges(), 2);
+            assert_eq!(g.degree(z0), 2);
+            assert_eq!(g.to_tensor4(), h.to_tensor4(), "failed for {:?}", et);
+        }
+    }
+
+    #[test]
+    fn biadjacency() {
+        let mut g = Graph::new();
+        let vs: Vec<_> = (0..5).map(|_| g.add_vertex(VType::Z)).collect();
+        g.add_edge_with_type(vs[0], vs[3], EType::H);
+        g.add_edge_with_type(vs[0], vs[4], EType::H);
+        g.add_edge_with_type(vs[1], vs[4], EType::N);
+        g.add_edge_with_type(vs[2], vs[3], EType::H);
+        g.add_edge_with_type(vs[0], vs[1], EType::H);
+
+        let m = g.biadjacency(&vs[0..3], &vs[3..5]);
+        let expected = BitMatrix::build(3, 2, |i, j| [(0, 0), (0, 1), (2, 0)].contains(&(i, j)));
+        assert_eq!(m, expected);
+        assert_eq!(g.biadjacency(&vs[3..5], &vs[0..3]), expected.transpose());
+        assert_eq!(g.biadjacency(&[], &vs).num_rows(), 0);
+
+        let m = g.biadjacency(&vs, &vs);
+        assert_eq!(m, m.transpose());
+        assert!(m.get(0, 1) && !m.get(1, 4) && !m.get(0, 0));
+    }
 }
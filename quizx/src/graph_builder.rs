@@ -0,0 +1,213 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A fluent interface for building diagrams by hand.
+
+use crate::graph::{EType, GraphLike, VType, V};
+use crate::phase::Phase;
+use crate::vec_graph::Graph;
+use num::Zero;
+use std::mem;
+
+/// Builds a diagram one wire at a time
+///
+/// The builder keeps track of a current vertex. Adding a vertex connects it
+/// to the current one, then makes it current. Each new wire gets the next
+/// qubit index, and vertices along a wire get increasing rows. For example,
+/// a CNOT is built with:
+///
+/// ```
+/// use quizx::graph_builder::GraphBuilder;
+///
+/// let mut b = GraphBuilder::new();
+/// let c = b.input().z(0).last();
+/// b.output();
+/// b.input().x(0).edge_to(c).output();
+/// let g = b.build();
+/// ```
+pub struct GraphBuilder<G: GraphLike = Graph> {
+    g: G,
+    current: Option<V>,
+    edge_type: EType,
+    num_wires: usize,
+}
+
+impl GraphBuilder {
+    /// A builder for a [Graph]
+    pub fn new() -> Self {
+        GraphBuilder::with_graph()
+    }
+}
+
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        GraphBuilder::new()
+    }
+}
+
+impl<G: GraphLike> GraphBuilder<G> {
+    /// A builder for any type of graph
+    pub fn with_graph() -> Self {
+        GraphBuilder {
+            g: G::new(),
+            current: None,
+            edge_type: EType::N,
+            num_wires: 0,
+        }
+    }
+
+    /// Add a vertex, connected to the current one if there is one
+    ///
+    /// Without a current vertex, this starts a new wire.
+    fn push(&mut self, ty: VType, phase: Phase) -> V {
+        let v = self.g.add_vertex_with_phase(ty, phase);
+        if let Some(u) = self.current {
+            self.g.set_qubit(v, self.g.qubit(u));
+            self.g.set_row(v, self.g.row(u) + 1);
+            self.g.add_edge_with_type(u, v, self.edge_type);
+        } else {
+            self.g.set_qubit(v, self.num_wires as i32);
+            self.num_wires += 1;
+        }
+        self.current = Some(v);
+        self.edge_type = EType::N;
+        v
+    }
+
+    /// Start a new wire with an input
+    pub fn input(&mut self) -> &mut Self {
+        self.current = None;
+        let v = self.push(VType::B, Phase::zero());
+        self.g.inputs_mut().push(v);
+        self
+    }
+
+    /// End the current wire with an output
+    ///
+    /// There is no current vertex afterwards, so adding another vertex starts
+    /// a new wire.
+    pub fn output(&mut self) -> &mut Self {
+        let v = self.push(VType::B, Phase::zero());
+        self.g.outputs_mut().push(v);
+        self.current = None;
+        self
+    }
+
+    /// Add a Z spider
+    pub fn z(&mut self, phase: impl Into<Phase>) -> &mut Self {
+        self.push(VType::Z, phase.into());
+        self
+    }
+
+    /// Add an X spider
+    pub fn x(&mut self, phase: impl Into<Phase>) -> &mut Self {
+        self.push(VType::X, phase.into());
+        self
+    }
+
+    /// Make the next edge along the wire a Hadamard edge
+    pub fn h(&mut self) -> &mut Self {
+        self.edge_type = self.edge_type.opposite();
+        self
+    }
+
+    /// Continue from the given vertex
+    pub fn at(&mut self, v: V) -> &mut Self {
+        self.current = Some(v);
+        self.edge_type = EType::N;
+        self
+    }
+
+    /// Connect the current vertex to v with a plain edge
+    pub fn edge_to(&mut self, v: V) -> &mut Self {
+        self.g.add_edge(self.last(), v);
+        self
+    }
+
+    /// Connect the current vertex to v with a Hadamard edge
+    pub fn h_edge_to(&mut self, v: V) -> &mut Self {
+        self.g.add_edge_with_type(self.last(), v, EType::H);
+        self
+    }
+
+    /// The current vertex
+    ///
+    /// Panics if there is none, i.e. right after [GraphBuilder::output].
+    pub fn last(&self) -> V {
+        self.current.expect("No current vertex")
+    }
+
+    /// The graph built so far
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// Finish the graph, leaving the builder empty
+    pub fn build(&mut self) -> G {
+        self.current = None;
+        self.edge_type = EType::N;
+        self.num_wires = 0;
+        mem::replace(&mut self.g, G::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::{CompareTensors, Tensor4};
+    use num::Rational64;
+
+    #[test]
+    fn build_cnot() {
+        let mut b = GraphBuilder::new();
+        let c = b.input().z(0).last();
+        b.output();
+        b.input().x(0).edge_to(c).output();
+        let g = b.build();
+
+        assert_eq!(g.num_qubits(), 2);
+        assert_eq!(g.qubit(g.output_qubit(1)), 1);
+        assert_eq!(g.row(g.output_qubit(1)), 2);
+        let mut circ = Circuit::new(2);
+        circ.cx(0, 1);
+        assert!(Tensor4::scalar_compare(&g, &circ));
+    }
+
+    #[test]
+    fn build_gadget() {
+        // a phase gadget on two qubits, with Hadamard edges
+        let mut b = GraphBuilder::new();
+        let q0 = b.input().z(0).last();
+        b.output();
+        let q1 = b.input().z(0).last();
+        b.output();
+        b.z(0)
+            .h_edge_to(q0)
+            .h_edge_to(q1)
+            .h()
+            .z(Rational64::new(1, 4));
+        let g = b.build();
+
+        assert_eq!(g.num_vertices(), 8);
+        assert_eq!(g.num_edges(), 7);
+        let hub = g.neighbors(q0).find(|&n| g.vertex_type(n) == VType::Z);
+        let hub = hub.unwrap();
+        assert_eq!(g.edge_type(hub, q1), EType::H);
+        assert_eq!(g.degree(hub), 3);
+        assert!(b.graph().num_vertices() == 0);
+    }
+}
@@ -18,7 +18,6 @@ pub use crate::graph::*;
 use crate::json::JsonGraph;
 use crate::phase::Phase;
 use crate::scalar::*;
-use num::rational::Rational64;
 use rustc_hash::FxHashMap;
 use serde::de::Error as _;
 use serde::ser::Error as _;
@@ -36,6 +35,7 @@ pub struct Graph {
     nume: usize,
     freshv: V,
     scalar: ScalarN,
+    track_scalar: bool,
 }
 
 impl Graph {
@@ -58,6 +58,7 @@ impl GraphLike for Graph {
             nume: 0,
             freshv: 0,
             scalar: Scalar::one(),
+            track_scalar: true,
         }
     }
 
@@ -73,11 +74,11 @@ impl GraphLike for Graph {
         self.nume
     }
 
-    fn vertices(&self) -> VIter {
+    fn vertices(&self) -> VIter<'_> {
         VIter::Hash(self.vdata.keys())
     }
 
-    fn edges(&self) -> EIter {
+    fn edges(&self) -> EIter<'_> {
         EIter::Hash(self.nume, self.edata.iter(), None)
     }
 
@@ -103,9 +104,10 @@ impl GraphLike for Graph {
     fn add_vertex(&mut self, ty: VType) -> V {
         self.add_vertex_with_data(VData {
             ty,
-            phase: Rational64::new(0, 1).into(),
+            phase: ty.default_phase(),
             qubit: 0,
             row: 0,
+            ground: false,
         })
     }
 
@@ -118,6 +120,14 @@ impl GraphLike for Graph {
         v
     }
 
+    fn add_vertex_at(&mut self, v: V, d: VData) {
+        assert!(!self.vdata.contains_key(&v), "Vertex already in graph");
+        self.freshv = self.freshv.max(v + 1);
+        self.numv += 1;
+        self.vdata.insert(v, d);
+        self.edata.insert(v, FxHashMap::default());
+    }
+
     fn remove_vertex(&mut self, v: V) {
         self.numv -= 1;
 
@@ -150,7 +160,9 @@ impl GraphLike for Graph {
     }
 
     fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
-        self.vdata.get_mut(&v).expect("Vertex not found").phase = phase.into();
+        let d = self.vdata.get_mut(&v).expect("Vertex not found");
+        debug_assert_ne!(d.ty, VType::B, "Boundary vertex {} can't have a phase", v);
+        d.phase = phase.into();
     }
 
     fn phase(&self, v: V) -> Phase {
@@ -159,6 +171,7 @@ impl GraphLike for Graph {
 
     fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
         if let Some(d) = self.vdata.get_mut(&v) {
+            debug_assert_ne!(d.ty, VType::B, "Boundary vertex {} can't have a phase", v);
             d.phase = (d.phase + phase.into()).normalize();
         } else {
             panic!("Vertex not found");
@@ -228,11 +241,15 @@ impl GraphLike for Graph {
         self.vdata.get(&v).expect("Vertex not found").row
     }
 
-    fn neighbors(&self, v: V) -> NeighborIter {
+    fn set_ground(&mut self, v: V, ground: bool) {
+        self.vdata.get_mut(&v).expect("Vertex not found").ground = ground;
+    }
+
+    fn neighbors(&self, v: V) -> NeighborIter<'_> {
         NeighborIter::Hash(self.edata.get(&v).expect("Vertex not found").keys())
     }
 
-    fn incident_edges(&self, v: V) -> IncidentEdgeIter {
+    fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_> {
         IncidentEdgeIter::Hash(self.edata.get(&v).expect("Vertex not found").iter())
     }
 
@@ -246,6 +263,12 @@ impl GraphLike for Graph {
     fn scalar_mut(&mut self) -> &mut ScalarN {
         &mut self.scalar
     }
+    fn track_scalar(&self) -> bool {
+        self.track_scalar
+    }
+    fn set_track_scalar(&mut self, track: bool) {
+        self.track_scalar = track;
+    }
 
     fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
     where
@@ -45,10 +45,12 @@
 //! assert_eq!(g.to_tensor4(), g2.to_tensor4());
 //! ```
 
+mod dict;
 mod graph;
 mod phase;
 mod scalar;
 
+pub use dict::{from_pyzx_dict, to_pyzx_dict, PyzxDict};
 pub use phase::PhaseOptions;
 
 use crate::graph::VType;
@@ -341,6 +343,9 @@ pub enum JsonError {
     /// Found an invalid phase value in a node definition.
     #[display("Got an invalid phase value {phase} for node {name}")]
     InvalidNodePhase { name: String, phase: String },
+    /// An edge or boundary refers to a vertex which isn't in the graph.
+    #[display("Got a reference to unknown vertex {name}")]
+    UnknownVertex { name: usize },
     /// Some other serde error.
     #[from]
     SerdeError(serde_json::Error),
@@ -350,6 +355,7 @@ pub enum JsonError {
 mod test {
     use crate::graph::GraphLike;
     use crate::vec_graph::{Graph, V};
+    use num::Rational64;
 
     use super::*;
 
@@ -360,29 +366,23 @@ mod test {
     /// With `0` and `1` as inputs, and `6` and `7` as outputs.
     #[fixture]
     fn simple_graph() -> (Graph, Vec<V>) {
-        let mut g = Graph::new();
-        let vs = vec![
-            g.add_vertex(VType::B),
-            g.add_vertex(VType::B),
-            g.add_vertex(VType::Z),
-            g.add_vertex(VType::X),
-            g.add_vertex(VType::X),
-            g.add_vertex(VType::Z),
-            g.add_vertex(VType::B),
-            g.add_vertex(VType::B),
-        ];
-
-        g.set_inputs(vec![vs[0], vs[1]]);
-        g.set_outputs(vec![vs[6], vs[7]]);
-
-        g.add_edge(vs[0], vs[2]);
-        g.add_edge(vs[1], vs[3]);
-        g.add_edge(vs[2], vs[4]);
-        g.add_edge_with_type(vs[2], vs[3], EType::H);
-        g.add_edge(vs[3], vs[5]);
-        g.add_edge(vs[4], vs[6]);
-        g.add_edge(vs[5], vs[7]);
-        (g, vs)
+        use {EType::N, VType::*};
+        let g = Graph::from_edges(
+            8,
+            &[B, B, Z, X, X, Z, B, B],
+            &[
+                (0, 2, N),
+                (1, 3, N),
+                (2, 4, N),
+                (2, 3, EType::H),
+                (3, 5, N),
+                (4, 6, N),
+                (5, 7, N),
+            ],
+            &[0, 1],
+            &[6, 7],
+        );
+        (g, (0..8).collect())
     }
 
     //const TEST_JSON_SIMPLE: &str = include_str!("../../test_files/simple-graph.qgraph");
@@ -416,6 +416,68 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn json_roundtrip_w_node() {
+        let mut g = Graph::new();
+        let i0 = g.add_vertex(VType::B);
+        let i1 = g.add_vertex(VType::B);
+        let w_in = g.add_vertex(VType::WInput);
+        let w_out = g.add_vertex(VType::WOutput);
+        let o = g.add_vertex(VType::B);
+        g.set_inputs(vec![i0, i1]);
+        g.set_outputs(vec![o]);
+        g.add_edge(i0, w_in);
+        g.add_edge(i1, w_in);
+        g.add_edge_with_type(w_in, w_out, EType::Wio);
+        g.add_edge(w_out, o);
+
+        let s = encode_graph(&g).unwrap();
+        assert!(s.contains("\"W_input\""));
+        assert!(s.contains("\"W_output\""));
+
+        let g2: Graph = decode_graph(&s).unwrap();
+        assert_eq!(g2.num_vertices(), 5);
+        assert_eq!(g2.num_edges(), 4);
+        let w_in2 = g2.neighbors(g2.inputs()[0]).next().unwrap();
+        assert_eq!(g2.vertex_type(w_in2), VType::WInput);
+        assert_eq!(g2.degree(w_in2), 3);
+        let w_out2 = g2.neighbors(g2.outputs()[0]).next().unwrap();
+        assert_eq!(g2.vertex_type(w_out2), VType::WOutput);
+        assert_eq!(g2.edge_type(w_in2, w_out2), EType::Wio);
+    }
+
+    #[rstest]
+    fn json_roundtrip_ground(simple_graph: (Graph, Vec<V>)) {
+        let (mut g, vs) = simple_graph;
+        g.set_ground(vs[4], true);
+
+        let s = encode_graph(&g).unwrap();
+        assert_eq!(s.matches("\"ground\":true").count(), 1);
+        let g2: Graph = decode_graph(&s).unwrap();
+        let grounds: Vec<_> = g2.vertices().filter(|&v| g2.is_ground(v)).collect();
+        assert_eq!(grounds.len(), 1);
+        assert_eq!(g2.vertex_type(grounds[0]), VType::X);
+        let out = g2.neighbors(grounds[0]).find(|v| g2.outputs().contains(v));
+        assert_eq!(out, Some(g2.outputs()[0]));
+    }
+
+    #[test]
+    fn json_roundtrip_h_box() {
+        let mut g = Graph::new();
+        let h0 = g.add_vertex(VType::H);
+        let h1 = g.add_vertex(VType::H);
+        g.set_phase(h1, Rational64::new(1, 4));
+        g.add_edge(h0, h1);
+
+        let s = encode_graph(&g).unwrap();
+        let g2: Graph = decode_graph(&s).unwrap();
+        let mut labels: Vec<_> = g2.vertices().map(|v| g2.h_box_label(v)).collect();
+        labels.sort_by_key(|l| l.to_string());
+        let mut expected = vec![g.h_box_label(h0), g.h_box_label(h1)];
+        expected.sort_by_key(|l| l.to_string());
+        assert_eq!(labels, expected);
+    }
+
     #[rstest]
     //#[case::simple(TEST_JSON_SIMPLE, 9, 9)]  TODO: Parameters are not yet supported
     #[case::unitary_4q(TEST_JSON_4Q_UNITARY, 26, 30)]
@@ -0,0 +1,142 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Methods for converting between a `GraphLike` object and plain vertex and
+//! edge tables, without going through json text.
+
+use super::JsonError;
+use crate::graph::{EType, GraphLike, VData, V};
+use rustc_hash::FxHashMap;
+
+/// A graph as plain tables of vertex and edge data, like those kept by pyzx
+///
+/// This is meant for passing graphs to and from the python bindings in one
+/// go, which is much faster than encoding them as json. Vertices are named by
+/// their index in the graph they came from. The scalar is not included.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PyzxDict {
+    /// The vertices, by name
+    pub vertices: Vec<(V, VData)>,
+    /// The edges, between vertex names
+    pub edges: Vec<(V, V, EType)>,
+    /// The names of the inputs, in order
+    pub inputs: Vec<V>,
+    /// The names of the outputs, in order
+    pub outputs: Vec<V>,
+}
+
+/// Builds a graph from vertex and edge tables.
+///
+/// The vertices get fresh indices, so the map from names to the new
+/// vertices is also returned.
+pub fn from_pyzx_dict<G: GraphLike>(d: &PyzxDict) -> Result<(G, FxHashMap<V, V>), JsonError> {
+    let mut g = G::new();
+    let mut vmap = FxHashMap::default();
+    for &(name, data) in &d.vertices {
+        vmap.insert(name, g.add_vertex_with_data(data));
+    }
+
+    let lookup = |name: &V| {
+        vmap.get(name)
+            .copied()
+            .ok_or(JsonError::UnknownVertex { name: *name })
+    };
+    for (s, t, et) in &d.edges {
+        g.add_edge_smart(lookup(s)?, lookup(t)?, *et);
+    }
+    let inputs = d.inputs.iter().map(lookup).collect::<Result<_, _>>()?;
+    let outputs = d.outputs.iter().map(lookup).collect::<Result<_, _>>()?;
+    g.set_inputs(inputs);
+    g.set_outputs(outputs);
+
+    Ok((g, vmap))
+}
+
+/// Returns the vertex and edge tables of a graph.
+pub fn to_pyzx_dict(g: &impl GraphLike) -> PyzxDict {
+    PyzxDict {
+        vertices: g.vertices().map(|v| (v, g.vertex_data(v))).collect(),
+        edges: g.edges().collect(),
+        inputs: g.inputs().clone(),
+        outputs: g.outputs().clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::graph::VType;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn dict_roundtrip() {
+        let c = Circuit::from_qasm(
+            r#"
+            OPENQASM 2.0;
+            include "qelib1.inc";
+            qreg q[3];
+            h q[0];
+            cx q[0], q[1];
+            t q[1];
+            ccz q[0], q[1], q[2];
+            s q[2];
+        "#,
+        )
+        .unwrap();
+        let mut g: Graph = c.to_graph();
+        // leaves gaps in the vertex names
+        crate::simplify::clifford_simp(&mut g);
+        assert!(g.vindex() > g.num_vertices());
+
+        let d = to_pyzx_dict(&g);
+        assert_eq!(d.vertices.len(), g.num_vertices());
+        assert_eq!(d.edges.len(), g.num_edges());
+
+        let (mut h, vmap): (Graph, _) = from_pyzx_dict(&d).unwrap();
+        assert_eq!(h.num_vertices(), g.num_vertices());
+        assert_eq!(h.num_edges(), g.num_edges());
+        for v in g.vertices() {
+            assert_eq!(h.vertex_data(vmap[&v]), g.vertex_data(v));
+        }
+        let inputs: Vec<_> = g.inputs().iter().map(|v| vmap[v]).collect();
+        assert_eq!(h.inputs(), &inputs);
+        // the scalar is passed separately
+        *h.scalar_mut() = g.scalar().clone();
+        assert_eq!(h.to_tensor4(), g.to_tensor4());
+    }
+
+    #[test]
+    fn dict_unknown_vertex() {
+        let d = PyzxDict {
+            vertices: vec![(
+                3,
+                VData {
+                    ty: VType::Z,
+                    phase: 0.into(),
+                    qubit: 0,
+                    row: 0,
+                    ground: false,
+                },
+            )],
+            edges: vec![(3, 5, EType::H)],
+            ..Default::default()
+        };
+        let res = from_pyzx_dict::<Graph>(&d);
+        assert!(matches!(res, Err(JsonError::UnknownVertex { name: 5 })));
+    }
+}
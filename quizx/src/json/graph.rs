@@ -16,7 +16,7 @@
 
 //! Methods for converting between a `GraphLike` object and the json representation.
 
-use num::{One, Rational64, Zero};
+use num::{One, Zero};
 
 use super::phase::PhaseOptions;
 use super::{
@@ -73,13 +73,10 @@ impl JsonGraph {
                 wire_vertices.insert(v_name, attrs);
             } else {
                 let phase = graph.phase(v);
-                // Encode zero-phases as empty strings by default. If the vertex
-                // is a Hadamard node, encode "1" as empty strings instead.
+                // Encode default phases as empty strings. This is "1" for
+                // Hadamard nodes and "0" for everything else.
                 let phase_options = PhaseOptions {
-                    ignore_value: Some(match typ == VType::H {
-                        true => Phase::one(),
-                        false => Phase::zero(),
-                    }),
+                    ignore_value: Some(typ.default_phase()),
                     ..Default::default()
                 };
                 let value = JsonPhase::from_phase(phase, phase_options);
@@ -91,6 +88,7 @@ impl JsonGraph {
                     data: VertexData {
                         typ,
                         value,
+                        ground: graph.is_ground(v),
                         ..Default::default()
                     },
                 };
@@ -194,18 +192,13 @@ impl JsonGraph {
                     name: name.to_string(),
                     phase: attrs.data.value.0.clone(),
                 })?;
-            let phase = match (phase, attrs.data.typ) {
-                (Some(r), _) => r,
-                // The phase defaults to one for Hadamard nodes,
-                (None, VType::H) => Rational64::one().into(),
-                // and zero for all others.
-                (None, _) => Rational64::zero().into(),
-            };
+            let phase = phase.unwrap_or_else(|| attrs.data.typ.default_phase());
             let v = graph.add_vertex_with_data(VData {
                 ty: attrs.data.typ,
                 qubit: coord.qubit(),
                 row: coord.row(),
                 phase,
+                ground: attrs.data.ground,
             });
             names.insert(name.to_string(), v);
         }
@@ -220,6 +213,7 @@ impl JsonGraph {
                 qubit: coord.qubit(),
                 row: coord.row(),
                 phase: Phase::zero(),
+                ground: false,
             });
             names.insert(name.to_string(), v);
             if let Some(input) = attrs.annotation.input {
@@ -251,6 +245,7 @@ impl JsonGraph {
                         qubit: new_coord.qubit(),
                         row: new_coord.row(),
                         phase: Phase::zero(),
+                        ground: false,
                     });
                     let name = format!("v{}", graph.num_vertices());
                     names.insert(name, v);
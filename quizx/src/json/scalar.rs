@@ -5,7 +5,7 @@
 use num::complex::ComplexFloat;
 use std::f64::consts::PI;
 
-use num::{One, Zero};
+use num::{One, ToPrimitive, Zero};
 
 use crate::phase::Phase;
 use crate::scalar::{Coeffs, FromPhase, Scalar};
@@ -48,19 +48,19 @@ impl JsonScalar {
                 // In the Clifford+T case where we have Scalar4, we can extract factors of sqrt(2) directly from the
                 // coefficients. Since the coefficients are reduced, sqrt(2) is represented as
                 // [1, 0, +-1, 0], [0, 1, 0, +-1], where the +- lead to phase contributions already extracted in `phase`
-                let (power_sqrt2, floatfactor) =
-                    match coeffs.iter_coeffs().collect::<Vec<_>>().as_slice() {
-                        [a, 0, b, 0] | [0, a, 0, b]
-                            if a.abs() == 1 && b.abs() == 1 && coeffs.len() == 4 =>
-                        {
-                            (*pow * 2 + 1, Default::default()) // Coefficients represent a factor of sqrt(2)
-                        }
-                        cf => (
-                            // In all other cases, we simply assign the complex value to the pyzx floatfactor
-                            *pow * 2,
-                            Scalar::<Vec<_>>::from_int_coeffs(cf).complex_value().abs(),
-                        ),
-                    };
+                let cf: Option<Vec<isize>> = coeffs.iter_coeffs().map(|c| c.to_isize()).collect();
+                let (power_sqrt2, floatfactor) = match cf.as_deref().unwrap_or_default() {
+                    [a, 0, b, 0] | [0, a, 0, b]
+                        if a.abs() == 1 && b.abs() == 1 && coeffs.len() == 4 =>
+                    {
+                        (*pow * 2 + 1, Default::default()) // Coefficients represent a factor of sqrt(2)
+                    }
+                    _ => (
+                        // In all other cases, we simply assign the complex value to the pyzx floatfactor
+                        *pow * 2,
+                        Scalar::Exact(0, coeffs.clone()).complex_value().abs(),
+                    ),
+                };
 
                 JsonScalar {
                     power2: power_sqrt2,
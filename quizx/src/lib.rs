@@ -22,6 +22,7 @@ pub mod extract;
 pub mod gate;
 pub mod generate;
 pub mod graph;
+pub mod graph_builder;
 pub mod hash_graph;
 pub mod json;
 pub mod linalg;
@@ -30,5 +31,8 @@ pub mod phase;
 pub mod random_graph;
 pub mod scalar;
 pub mod simplify;
+pub mod simulate;
 pub mod tensor;
+pub mod tracked_graph;
 pub mod vec_graph;
+pub mod verify;
@@ -351,7 +351,7 @@ impl std::ops::IndexMut<usize> for Mat2 {
     }
 }
 
-impl<'a, 'b> std::ops::Mul<&'b Mat2> for &'a Mat2 {
+impl std::ops::Mul<&Mat2> for &Mat2 {
     type Output = Mat2;
 
     #[allow(clippy::suspicious_arithmetic_impl)]
@@ -371,13 +371,13 @@ impl<'a, 'b> std::ops::Mul<&'b Mat2> for &'a Mat2 {
     }
 }
 
-impl<'a> std::ops::Mul<Mat2> for &'a Mat2 {
+impl std::ops::Mul<Mat2> for &Mat2 {
     type Output = Mat2;
     fn mul(self, rhs: Mat2) -> Self::Output {
         self * &rhs
     }
 }
-impl<'a> std::ops::Mul<&'a Mat2> for Mat2 {
+impl std::ops::Mul<&Mat2> for Mat2 {
     type Output = Mat2;
     fn mul(self, rhs: &Mat2) -> Self::Output {
         &self * rhs
@@ -390,9 +390,188 @@ impl std::ops::Mul<Mat2> for Mat2 {
     }
 }
 
+/// A bit-packed matrix over F2
+///
+/// Each row is stored as a slice of 64-bit words, so adding one row to
+/// another is a word-wise xor. This makes Gaussian elimination on large
+/// matrices much faster than with [Mat2].
+#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+pub struct BitMatrix {
+    rows: usize,
+    cols: usize,
+    /// The number of words in each row
+    words: usize,
+    d: Vec<u64>,
+}
+
+impl BitMatrix {
+    /// A matrix full of zeros
+    pub fn zeros(rows: usize, cols: usize) -> BitMatrix {
+        let words = cols.div_ceil(64);
+        BitMatrix {
+            rows,
+            cols,
+            words,
+            d: vec![0; rows * words],
+        }
+    }
+
+    /// Build a matrix with the given number of rows and columns. Place a 1
+    /// wherever f(i,j) is true.
+    pub fn build<F>(rows: usize, cols: usize, f: F) -> BitMatrix
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let mut m = BitMatrix::zeros(rows, cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                if f(i, j) {
+                    m.set(i, j, true);
+                }
+            }
+        }
+        m
+    }
+
+    /// The identity matrix of a given size
+    pub fn id(dim: usize) -> BitMatrix {
+        let mut m = BitMatrix::zeros(dim, dim);
+        for i in 0..dim {
+            m.set(i, i, true);
+        }
+        m
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Return the entry in row i and column j
+    pub fn get(&self, i: usize, j: usize) -> bool {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        self.d[i * self.words + j / 64] >> (j % 64) & 1 == 1
+    }
+
+    /// Set the entry in row i and column j
+    pub fn set(&mut self, i: usize, j: usize, b: bool) {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        let w = &mut self.d[i * self.words + j / 64];
+        if b {
+            *w |= 1 << (j % 64);
+        } else {
+            *w &= !(1 << (j % 64));
+        }
+    }
+
+    /// The words of row i, with column j at bit j % 64 of word j / 64
+    pub fn row(&self, i: usize) -> &[u64] {
+        &self.d[i * self.words..(i + 1) * self.words]
+    }
+
+    /// Return the transpose as a copy
+    pub fn transpose(&self) -> BitMatrix {
+        BitMatrix::build(self.cols, self.rows, |i, j| self.get(j, i))
+    }
+
+    /// Return the hamming weight of the given row
+    pub fn row_weight(&self, i: usize) -> usize {
+        self.row(i).iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Compute the echelon form, returning the rank of the matrix
+    ///
+    /// If `full_reduce` is true, this computes the reduced row echelon
+    /// form, which is the same as [Mat2::gauss] gives. The row operations
+    /// are also applied to `x`, as for [Mat2::gauss_x].
+    pub fn gauss_x(&mut self, full_reduce: bool, x: &mut impl RowOps) -> usize {
+        let mut pivot_row = 0;
+        for p in 0..self.cols {
+            if pivot_row == self.rows {
+                break;
+            }
+            let (w, bit) = (p / 64, 1 << (p % 64));
+            let Some(r0) = (pivot_row..self.rows).find(|&r| self.d[r * self.words + w] & bit != 0)
+            else {
+                continue;
+            };
+            if r0 != pivot_row {
+                self.row_swap(r0, pivot_row);
+                x.row_swap(r0, pivot_row);
+            }
+            let start = if full_reduce { 0 } else { pivot_row + 1 };
+            for r1 in start..self.rows {
+                if r1 != pivot_row && self.d[r1 * self.words + w] & bit != 0 {
+                    self.row_add(pivot_row, r1);
+                    x.row_add(pivot_row, r1);
+                }
+            }
+            pivot_row += 1;
+        }
+        pivot_row
+    }
+
+    /// Compute the echelon form, returning the rank of the matrix
+    pub fn gauss(&mut self, full_reduce: bool) -> usize {
+        self.gauss_x(full_reduce, &mut ())
+    }
+
+    pub fn rank(&self) -> usize {
+        let mut m = self.clone();
+        m.gauss(false)
+    }
+}
+
+impl RowOps for BitMatrix {
+    fn row_add(&mut self, r0: usize, r1: usize) {
+        let n = self.words;
+        for k in 0..n {
+            self.d[r1 * n + k] ^= self.d[r0 * n + k];
+        }
+    }
+
+    fn row_swap(&mut self, r0: usize, r1: usize) {
+        if r0 != r1 {
+            let n = self.words;
+            let (lo, hi) = (min(r0, r1), r0.max(r1));
+            let (a, b) = self.d.split_at_mut(hi * n);
+            a[lo * n..(lo + 1) * n].swap_with_slice(&mut b[..n]);
+        }
+    }
+}
+
+impl From<&Mat2> for BitMatrix {
+    fn from(m: &Mat2) -> Self {
+        BitMatrix::build(m.num_rows(), m.num_cols(), |i, j| m[(i, j)] == 1)
+    }
+}
+
+impl From<&BitMatrix> for Mat2 {
+    fn from(m: &BitMatrix) -> Self {
+        Mat2::build(m.num_rows(), m.num_cols(), |i, j| m.get(i, j))
+    }
+}
+
+impl fmt::Display for BitMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.rows {
+            write!(f, "[ ")?;
+            for j in 0..self.cols {
+                write!(f, "{} ", self.get(i, j) as u8)?;
+            }
+            writeln!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
 
     #[test]
     fn mat_mul() {
@@ -503,4 +682,76 @@ mod tests {
         let vi_exp = Mat2::new(vec![vec![1, 1, 0], vec![0, 1, 1], vec![0, 0, 1]]);
         assert_eq!(vi_exp, vi);
     }
+
+    fn random_mat2(rng: &mut impl rand::Rng, rows: usize, cols: usize) -> Mat2 {
+        let d = (0..rows)
+            .map(|_| (0..cols).map(|_| rng.gen_range(0..2)).collect())
+            .collect();
+        Mat2::new(d)
+    }
+
+    #[test]
+    fn bit_matrix_entries() {
+        let mut m = BitMatrix::zeros(3, 130);
+        m.set(0, 0, true);
+        m.set(1, 64, true);
+        m.set(2, 129, true);
+        m.set(2, 63, true);
+        m.set(2, 63, false);
+        assert!(m.get(0, 0) && m.get(1, 64) && m.get(2, 129));
+        assert!(!m.get(2, 63) && !m.get(0, 64));
+        assert_eq!(m.row_weight(2), 1);
+        assert_eq!(m.transpose().transpose(), m);
+
+        let v = Mat2::new(vec![vec![1, 0, 1, 0], vec![1, 1, 1, 1], vec![0, 0, 1, 1]]);
+        let b = BitMatrix::from(&v);
+        assert_eq!(Mat2::from(&b), v);
+        assert_eq!(b.to_string(), v.to_string());
+        assert_eq!(Mat2::from(&b.transpose()), v.transpose());
+    }
+
+    #[test]
+    fn bit_matrix_row_ops() {
+        let v = Mat2::new(vec![vec![1, 0, 1, 0], vec![1, 1, 1, 1], vec![0, 0, 1, 1]]);
+        let mut b = BitMatrix::from(&v);
+
+        let w1 = Mat2::new(vec![vec![1, 0, 1, 0], vec![1, 1, 1, 1], vec![1, 1, 0, 0]]);
+        let w2 = Mat2::new(vec![vec![1, 1, 1, 1], vec![1, 0, 1, 0], vec![1, 1, 0, 0]]);
+
+        b.row_add(1, 2);
+        assert_eq!(Mat2::from(&b), w1);
+        b.row_swap(0, 1);
+        assert_eq!(Mat2::from(&b), w2);
+        b.row_swap(2, 0);
+        b.row_swap(0, 2);
+        assert_eq!(Mat2::from(&b), w2);
+    }
+
+    #[test]
+    fn bit_matrix_gauss() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for &(rows, cols) in &[(4, 4), (10, 7), (7, 10), (30, 70), (70, 130), (100, 100)] {
+            for _ in 0..5 {
+                let v = random_mat2(&mut rng, rows, cols);
+                let b = BitMatrix::from(&v);
+                assert_eq!(b.rank(), v.rank());
+
+                let mut v1 = v.clone();
+                let mut b1 = b.clone();
+                let mut x = BitMatrix::id(rows);
+                let rank = v1.gauss(true);
+                assert_eq!(b1.gauss_x(true, &mut x), rank);
+                assert_eq!(Mat2::from(&b1), v1);
+                assert_eq!(Mat2::from(&x) * &v, v1);
+
+                let mut b2 = b.clone();
+                let mut x = BitMatrix::id(rows);
+                assert_eq!(b2.gauss_x(false, &mut x), rank);
+                assert_eq!(Mat2::from(&x) * &v, Mat2::from(&b2));
+                for r in 0..rows {
+                    assert_eq!(b2.row_weight(r) == 0, r >= rank);
+                }
+            }
+        }
+    }
 }
@@ -1,4 +1,9 @@
 use crate::circuit::*;
+use crate::gate::*;
+use crate::phase::Phase;
+use crate::simplify::full_simp_tracking_phases;
+use crate::vec_graph::Graph;
+use num::{Rational64, Zero};
 
 impl Circuit {
     /// Implements (roughly) `basic_optimization` from pyzx
@@ -6,3 +11,113 @@ impl Circuit {
         panic!("Not implemented.");
     }
 }
+
+/// The phase of a single-qubit Z or X phase gate
+fn phase_gate_phase(g: &Gate) -> Option<Phase> {
+    match g.t {
+        ZPhase | XPhase => Some(g.phase),
+        T => Some(Rational64::new(1, 4).into()),
+        Tdg => Some(Rational64::new(-1, 4).into()),
+        _ => None,
+    }
+}
+
+/// Reduce the number of non-Clifford phases in a circuit by phase teleportation
+///
+/// This is the method of Kissinger and van de Wetering, "Reducing T-count with
+/// the ZX-calculus" (2019). The circuit is converted to a graph, which is
+/// simplified with [full_simp_tracking_phases] to find out which non-Clifford
+/// phases get fused together. The phases are then moved within the original
+/// circuit: one gate in each group gets the combined phase, and the others are
+/// removed. Since the circuit is never extracted, its structure is otherwise
+/// unchanged, apart from gates which are first decomposed by
+/// [Circuit::to_basic_gates].
+///
+/// The result is equal to the original circuit up to a global phase.
+pub fn phase_teleport(c: &Circuit) -> Circuit {
+    let mut c = c.to_basic_gates();
+    let (mut g, spiders): (Graph, _) = c.to_graph_with_spiders();
+
+    let mut tracked = Vec::new();
+    let mut vs = Vec::new();
+    let mut phases = Vec::new();
+    for (i, gate) in c.gates.iter().enumerate() {
+        if let (Some(p), Some(v)) = (phase_gate_phase(gate), spiders[i]) {
+            if !p.is_clifford() {
+                tracked.push(i);
+                vs.push(v);
+                phases.push(p);
+            }
+        }
+    }
+
+    for group in full_simp_tracking_phases(&mut g, &vs) {
+        let mut sum = Phase::zero();
+        for &(j, neg) in &group {
+            sum += if neg { -phases[j] } else { phases[j] };
+            phases[j] = Phase::zero();
+        }
+        let (j0, neg0) = group[0];
+        phases[j0] = if neg0 { -sum } else { sum };
+    }
+
+    let mut removed = vec![false; c.gates.len()];
+    for (&i, &p) in tracked.iter().zip(&phases) {
+        let gate = &mut c.gates[i];
+        if p.is_zero() {
+            removed[i] = true;
+        } else if gate.t == XPhase {
+            gate.phase = p;
+        } else if p == Rational64::new(1, 4).into() {
+            *gate = Gate::new(T, gate.qs.clone());
+        } else if p == Rational64::new(-1, 4).into() {
+            *gate = Gate::new(Tdg, gate.qs.clone());
+        } else {
+            *gate = Gate::new_with_phase(ZPhase, gate.qs.clone(), p);
+        }
+    }
+
+    let mut removed = removed.into_iter();
+    c.gates.retain(|_| !removed.next().unwrap());
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::{CompareTensors, Tensor4};
+
+    fn check_teleport(qasm: &str, tcount: usize) {
+        let c = Circuit::from_qasm(qasm).unwrap();
+        let c1 = phase_teleport(&c);
+        assert_eq!(c1.t_count(), tcount);
+        assert!(c1.num_gates() < c.to_basic_gates().num_gates());
+        assert!(Tensor4::scalar_compare(&c, &c1));
+    }
+
+    #[test]
+    fn teleport_tof_3() {
+        check_teleport(include_str!("../../circuits/small/tof_3.qasm"), 15);
+    }
+
+    #[test]
+    fn teleport_mod5_4() {
+        check_teleport(include_str!("../../circuits/small/mod5_4.qasm"), 8);
+    }
+
+    #[test]
+    fn teleport_random() {
+        for seed in 0..10 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(40)
+                .p_t(0.3)
+                .with_cliffords()
+                .build();
+            let c1 = phase_teleport(&c);
+            assert!(c1.t_count() <= c.t_count());
+            assert!(Tensor4::scalar_compare(&c, &c1), "failed for seed {}", seed);
+        }
+    }
+}
@@ -4,7 +4,9 @@ pub mod utils;
 
 use std::fmt::{self, Display};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
 
+use num::rational::ParseRatioError;
 use num::{FromPrimitive, One, Rational64, ToPrimitive, Zero};
 
 use utils::limit_denominator;
@@ -59,7 +61,7 @@ impl Phase {
 
     /// Returns `true` if the phase is a multiple of 1/2.
     pub fn is_clifford(&self) -> bool {
-        self.r.denom().abs() < 2
+        self.r.denom().abs() <= 2
     }
 
     /// Returns `true` if the phase is either -1/2 or 1/2.
@@ -87,12 +89,25 @@ impl Phase {
     }
 }
 
+/// Formats the phase as a fraction of half-turns, e.g. `1/4` for pi/4.
 impl Display for Phase {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.r)
     }
 }
 
+/// Parses a phase written as a fraction of half-turns, e.g. `1/4` or `-1`.
+///
+/// The result is normalized to be in the range (-1,1], so `3/2` parses to
+/// `-1/2`.
+impl FromStr for Phase {
+    type Err = ParseRatioError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Phase::new(s.trim().parse::<Rational64>()?))
+    }
+}
+
 impl From<Rational64> for Phase {
     fn from(r: Rational64) -> Phase {
         Phase::new(r)
@@ -241,3 +256,45 @@ impl DivAssign<i64> for Phase {
         *self = *self / other;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("0", 0, 1)]
+    #[case("1", 1, 1)]
+    #[case("1/4", 1, 4)]
+    #[case("-1/4", -1, 4)]
+    #[case("3/2", -1, 2)]
+    #[case("-1", 1, 1)]
+    #[case("7/4", -1, 4)]
+    #[case(" 1/2 ", 1, 2)]
+    fn parse_phase(#[case] s: &str, #[case] num: i64, #[case] den: i64) {
+        assert_eq!(s.parse::<Phase>().unwrap(), Phase::from((num, den)));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("pi")]
+    #[case("1/0")]
+    #[case("0.25")]
+    fn parse_phase_invalid(#[case] s: &str) {
+        assert!(s.parse::<Phase>().is_err());
+    }
+
+    #[test]
+    fn phase_string_roundtrip() {
+        for den in 1..=8 {
+            for num in -2 * den..=2 * den {
+                let p = Phase::from((num, den));
+                assert_eq!(Phase::from_str(&p.to_string()).unwrap(), p);
+            }
+        }
+        assert_eq!(Phase::new(Rational64::new(1, 4)).to_string(), "1/4");
+        assert_eq!(Phase::one().to_string(), "1");
+        assert_eq!(Phase::zero().to_string(), "0");
+    }
+}
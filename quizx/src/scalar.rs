@@ -15,12 +15,18 @@
 // limitations under the License.
 
 use approx::AbsDiffEq;
+use derive_more::{Display, Error};
 use num::complex::Complex;
+use num::traits::float::FloatCore;
 pub use num::traits::identities::{One, Zero};
+use num::traits::{CheckedAdd, CheckedMul, CheckedSub, Signed, ToPrimitive};
 use num::{integer, Integer, Rational64};
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
 use std::cmp::min;
 use std::f64::consts::PI;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ops::{Add, AddAssign, Mul};
 
 /// A type for exact and approximate representation of complex
@@ -43,6 +49,9 @@ use std::ops::{Add, AddAssign, Mul};
 ///
 /// The [Float] representation of a scalar is given as a 64-bit
 /// floating point [Complex] number.
+///
+/// Equality, hashing and printing use the canonical form of exact
+/// scalars, see [Scalar::canonical].
 #[derive(Debug, Clone)]
 pub enum Scalar<T: Coeffs> {
     Exact(i32, T),
@@ -72,12 +81,56 @@ pub trait Sqrt2: Sized {
     fn sqrt2_pow(p: i32) -> Self;
 }
 
+/// An integer type for the coefficients of exact scalars.
+///
+/// This is [isize] for the fixed-width scalars, whose arithmetic fails with
+/// [ScalarError::Overflow] when a coefficient doesn't fit, and `BigInt` for
+/// `ScalarB` with the `bigint` feature, which never overflows.
+pub trait Coeff:
+    Clone
+    + fmt::Debug
+    + fmt::Display
+    + Hash
+    + Integer
+    + Signed
+    + CheckedAdd
+    + CheckedSub
+    + CheckedMul
+    + ToPrimitive
+    + From<isize>
+{
+}
+
+impl Coeff for isize {}
+
+#[cfg(feature = "bigint")]
+impl Coeff for BigInt {}
+
+/// An error from exact arithmetic on scalars
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScalarError {
+    /// A coefficient is too big for the coefficient type. With the `bigint`
+    /// feature, `ScalarB` can be used instead.
+    #[display("Scalar coefficient overflow")]
+    Overflow,
+}
+
+/// Panic on overflow, for the operators and methods which can't return
+/// a [ScalarError]
+fn overflow() -> ! {
+    panic!("{}", ScalarError::Overflow)
+}
+
 /// A list of coefficients. We give this as a parameter to allow
-/// either fixed-size lists (e.g. `[i32;4]`) or dynamic ones (e.g.
-/// [Vec]\<i32\>). Only the former can be used in tensors and
+/// either fixed-size lists (e.g. `[isize;4]`) or dynamic ones (e.g.
+/// [Vec]\<isize\>). Only the former can be used in tensors and
 /// matrices, because they have to implement Copy (the size must be
 /// known at compile time).
-pub trait Coeffs: Clone + std::ops::IndexMut<usize, Output = isize> {
+pub trait Coeffs: Clone + std::ops::IndexMut<usize, Output = <Self as Coeffs>::Coeff> {
+    /// The type of each coefficient.
+    type Coeff: Coeff;
+
     /// Returns a coefficient list representing the number 0.
     fn zero() -> Self;
 
@@ -96,7 +149,7 @@ pub trait Coeffs: Clone + std::ops::IndexMut<usize, Output = isize> {
     }
 
     /// Iterate over the coefficient list.
-    fn iter_coeffs(&self) -> impl Iterator<Item = isize>;
+    fn iter_coeffs(&self) -> impl Iterator<Item = Self::Coeff>;
 }
 
 /// Implement Copy whenever our coefficient list allows us to.
@@ -124,6 +177,252 @@ fn lcm_with_padding(n1: usize, n2: usize) -> (usize, usize, usize) {
     }
 }
 
+/// The cyclotomic polynomial Phi_n, with the constant coefficient first.
+///
+/// This uses Phi_n(x) = prod_{d | n} (x^d - 1)^mu(n/d), where mu is the
+/// Moebius function.
+fn cyclotomic(n: usize) -> Vec<isize> {
+    let mut poly = vec![1];
+    let mut divisors = vec![];
+    for d in (1..=n).filter(|d| n % d == 0) {
+        match moebius(n / d) {
+            1 => {
+                // multiply by x^d - 1
+                let mut p = vec![0; poly.len() + d];
+                for (i, &c) in poly.iter().enumerate() {
+                    p[i + d] += c;
+                    p[i] -= c;
+                }
+                poly = p;
+            }
+            -1 => divisors.push(d),
+            _ => {}
+        }
+    }
+
+    for d in divisors {
+        // divide by x^d - 1, which is exact, using p_k = q_(k-d) - q_k
+        let mut q = vec![0; poly.len() - d];
+        for k in (d..poly.len()).rev() {
+            q[k - d] = poly[k] + q.get(k).copied().unwrap_or(0);
+        }
+        poly = q;
+    }
+    poly
+}
+
+fn moebius(mut n: usize) -> i32 {
+    let mut mu = 1;
+    let mut p = 2;
+    while p * p <= n {
+        if n % p == 0 {
+            n /= p;
+            if n % p == 0 {
+                return 0;
+            }
+            mu = -mu;
+        }
+        p += 1;
+    }
+    if n > 1 {
+        -mu
+    } else {
+        mu
+    }
+}
+
+fn prime_factors(mut n: usize) -> Vec<usize> {
+    let mut ps = vec![];
+    let mut p = 2;
+    while p * p <= n {
+        if n % p == 0 {
+            ps.push(p);
+            while n % p == 0 {
+                n /= p;
+            }
+        }
+        p += 1;
+    }
+    if n > 1 {
+        ps.push(n);
+    }
+    ps
+}
+
+/// Reduce a polynomial modulo a monic polynomial, leaving deg(m) coefficients
+///
+/// This returns `None` if the coefficients overflow.
+fn poly_rem<C: Coeff>(a: &mut Vec<C>, m: &[isize]) -> Option<()> {
+    let d = m.len() - 1;
+    for i in (d..a.len()).rev() {
+        let c = a[i].clone();
+        if !c.is_zero() {
+            for j in 0..=d {
+                let x = c.checked_mul(&C::from(m[j]))?;
+                a[i - d + j] = a[i - d + j].checked_sub(&x)?;
+            }
+        }
+    }
+    a.resize(d, C::zero());
+    Some(())
+}
+
+/// Write an element of Z\[zeta_n\], given by its coefficients reduced modulo
+/// Phi_n, as an element of Z\[zeta_m\] for m = n/p, if it is one
+///
+/// This is for an odd prime p which divides n exactly once. Then zeta_n =
+/// zeta_m^s zeta_p^t, with s = p^-1 mod m and t = m^-1 mod p, and
+/// 1, zeta_p, ..., zeta_p^(p-2) is a basis over Q(zeta_m), so the element is
+/// in Z\[zeta_m\] iff it only has a component along 1.
+///
+/// The outer `None` is for an overflow.
+fn descend_coprime<C: Coeff>(a: &[C], n: usize, p: usize) -> Option<Option<Vec<C>>> {
+    let m = n / p;
+    let s = (1..m).find(|&s| s * p % m == 1).unwrap_or(0);
+    let t = (1..p).find(|&t| t * m % p == 1).unwrap_or(0);
+    let mut g = vec![vec![C::zero(); m]; p];
+    for (i, c) in a.iter().enumerate() {
+        let x = &mut g[t * i % p][s * i % m];
+        *x = x.checked_add(c)?;
+    }
+
+    // use zeta_p^(p-1) = -(1 + zeta_p + ... + zeta_p^(p-2))
+    let last = g.pop().unwrap_or_default();
+    let phi_m = cyclotomic(m);
+    for gk in &mut g {
+        for (c, l) in gk.iter_mut().zip(&last) {
+            *c = c.checked_sub(l)?;
+        }
+        poly_rem(gk, &phi_m)?;
+    }
+
+    if g[1..].iter().all(|gk| gk.iter().all(|c| c.is_zero())) {
+        Some(g.into_iter().next())
+    } else {
+        Some(None)
+    }
+}
+
+/// The canonical form of an exact scalar 2^pow * sum_i coeffs\[i\] om^i, where
+/// om = e^(i pi / N) for N the number of coefficients, see [Scalar::canonical]
+///
+/// This returns `None` if the coefficients overflow on the way.
+fn canonical_parts<C: Coeff>(pow: i32, coeffs: &[C]) -> Option<(i32, Vec<C>)> {
+    // reduce modulo the minimal polynomial of om, which is x^N + 1 if N is a
+    // power of 2
+    let mut n = 2 * coeffs.len();
+    let mut a = coeffs.to_vec();
+    if !coeffs.len().is_power_of_two() {
+        poly_rem(&mut a, &cyclotomic(n))?;
+    }
+
+    // move to the smallest field Q(zeta_n) containing the scalar, dropping one
+    // prime factor of n at a time, and keeping n even
+    'descend: loop {
+        for p in prime_factors(n) {
+            if n % (p * p) == 0 {
+                // Phi_n(x) = Phi_(n/p)(x^p), so the scalar is in the subfield iff
+                // it only uses powers of zeta_n^p
+                if a.iter().enumerate().all(|(i, c)| c.is_zero() || i % p == 0) {
+                    a = a.iter().step_by(p).cloned().collect();
+                    n /= p;
+                    continue 'descend;
+                }
+            } else if p != 2 {
+                let b = descend_coprime(&a, n, p)?;
+                if let Some(b) = b {
+                    a = b;
+                    n /= p;
+                    continue 'descend;
+                }
+            }
+        }
+        break;
+    }
+
+    match a.iter().filter(|c| !c.is_zero()).map(twos).min() {
+        Some(tz) => {
+            // 2^tz itself overflows for isize::MIN
+            let two = C::from(2);
+            for c in &mut a {
+                for _ in 0..tz {
+                    *c = c.clone() / two.clone();
+                }
+            }
+            a.resize(n / 2, C::zero());
+            Some((pow + tz as i32, a))
+        }
+        None => Some((0, vec![C::zero()])),
+    }
+}
+
+/// The number of times 2 divides a non-zero coefficient
+fn twos<C: Coeff>(c: &C) -> usize {
+    let two = C::from(2);
+    let mut c = c.clone();
+    let mut k = 0;
+    while c.is_even() {
+        c = c / two.clone();
+        k += 1;
+    }
+    k
+}
+
+/// Convert a coefficient to another coefficient type, if it fits
+fn convert_coeff<C: Coeff, D: Coeff>(c: &C) -> Option<D> {
+    if let Some(x) = c.to_isize() {
+        return Some(D::from(x));
+    }
+
+    // go through 30-bit digits, so they fit in an isize on any platform
+    let base = 1isize << 30;
+    let mut digits = vec![];
+    let mut x = c.abs();
+    while !x.is_zero() {
+        let (q, r) = x.div_rem(&C::from(base));
+        digits.push(r.to_isize()?);
+        x = q;
+    }
+    let mut y = D::zero();
+    for d in digits.into_iter().rev() {
+        y = y.checked_mul(&D::from(base))?.checked_add(&D::from(d))?;
+    }
+    Some(if c.is_negative() { -y } else { y })
+}
+
+/// Write a finite complex number exactly as 2^pow * (a + b i)
+///
+/// Every finite float is a dyadic rational, so this only fails for infinite
+/// or NaN parts, or if the exponents of the two parts are too far apart.
+fn float_to_dyadic(c: Complex<f64>) -> Option<(i32, [isize; 2])> {
+    let decode = |x: f64| -> Option<(isize, i32)> {
+        if !x.is_finite() {
+            None
+        } else if x == 0.0 {
+            Some((0, i32::MAX))
+        } else {
+            let (m, e, sgn) = FloatCore::integer_decode(x);
+            Some((sgn as isize * m as isize, e as i32))
+        }
+    };
+    let (re, e0) = decode(c.re)?;
+    let (im, e1) = decode(c.im)?;
+    let pow = min(e0, e1);
+    if pow == i32::MAX {
+        return Some((0, [0, 0]));
+    }
+    let shift = |m: isize, e: i32| -> Option<isize> {
+        if m == 0 {
+            Some(0)
+        } else if e - pow < 63 {
+            m.checked_mul(1 << (e - pow))
+        } else {
+            None
+        }
+    };
+    Some((pow, [shift(re, e0)?, shift(im, e1)?]))
+}
+
 impl<T: Coeffs> Scalar<T> {
     /// Create a new complex scalar from a pair of floats.
     pub fn complex(re: f64, im: f64) -> Scalar<T> {
@@ -140,7 +439,7 @@ impl<T: Coeffs> Scalar<T> {
         match T::new(coeffs.len()) {
             Some((mut coeffs1, pad)) => {
                 for i in 0..coeffs.len() {
-                    coeffs1[i * pad] = coeffs[i];
+                    coeffs1[i * pad] = coeffs[i].into();
                 }
                 Exact(0, coeffs1).reduce()
             }
@@ -157,7 +456,8 @@ impl<T: Coeffs> Scalar<T> {
 
                 let mut num = Complex::new(0f64, 0f64);
                 for i in 0..coeffs.len() {
-                    num += pow2 * (coeffs[i] as f64) * omega.powu(i as u32);
+                    let c = coeffs[i].to_f64().unwrap_or(f64::NAN);
+                    num += pow2 * c * omega.powu(i as u32);
                 }
                 num
             }
@@ -173,7 +473,9 @@ impl<T: Coeffs> Scalar<T> {
         if let Exact(_, coeffs) = self {
             if coeffs.len() == 4 {
                 // cases where the phase is a multiple of 1/4 are handled exactly
-                match coeffs.iter_coeffs().collect::<Vec<_>>().as_slice() {
+                let coeffs: Option<Vec<isize>> =
+                    coeffs.iter_coeffs().map(|c| c.to_isize()).collect();
+                match coeffs.as_deref().unwrap_or_default() {
                     [_, b, 0, c] if -b == *c => {
                         return Phase::new(if self.complex_value().re > 0.0 { 0 } else { 1 })
                     }
@@ -223,6 +525,46 @@ impl<T: Coeffs> Scalar<T> {
         *self *= Scalar::from_phase(phase);
     }
 
+    /// Multiply the scalar by an arbitrary complex number.
+    ///
+    /// An exact scalar stays exact if `c` is a dyadic complex number
+    /// 2^k * (a + b i) with small integers a and b, such as 1/2, -3 or
+    /// 0.75i, and the product fits in the coefficient type. Otherwise this
+    /// makes the scalar a [Float], since an exact scalar can only represent
+    /// elements of D\[omega\], and a float such as 0.3 is most likely an
+    /// approximation.
+    pub fn mul_complex(&mut self, c: Complex<f64>) {
+        if c.is_one() {
+            return;
+        }
+        if self.is_exact() {
+            if let Some(s) = Scalar::small_dyadic(c).and_then(|d| self.checked_mul(&d).ok()) {
+                *self = s;
+                return;
+            }
+        }
+        *self *= Float(c);
+    }
+
+    /// The exact scalar equal to c, if it is 2^k * (a + b i) with a and b
+    /// less than 2^31 in absolute value
+    fn small_dyadic(c: Complex<f64>) -> Option<Scalar<T>> {
+        let (mut pow, [mut re, mut im]) = float_to_dyadic(c)?;
+        if re != 0 || im != 0 {
+            let tz = (re | im).trailing_zeros();
+            re >>= tz;
+            im >>= tz;
+            pow = pow.checked_add(tz as i32)?;
+        }
+        if re.unsigned_abs() >= 1 << 31 || im.unsigned_abs() >= 1 << 31 {
+            return None;
+        }
+        let (mut coeffs, pad) = T::new(2)?;
+        coeffs[0] = re.into();
+        coeffs[pad] = im.into();
+        Some(Exact(pow, coeffs).reduce())
+    }
+
     /// Returns an equivalent scalar using complex floating point numbers for the coefficients.
     pub fn to_float(&self) -> Scalar<T> {
         Float(self.complex_value())
@@ -239,27 +581,13 @@ impl<T: Coeffs> Scalar<T> {
     /// every coefficient is 0. For the zero scalar, set the power of 2 to 0.
     fn reduce(mut self) -> Scalar<T> {
         if let Exact(pow, coeffs) = &mut self {
-            let mut all_zero = true;
-            for i in 0..coeffs.len() {
-                if coeffs[i] != 0 {
-                    all_zero = false;
-                    break;
-                }
-            }
-
-            if all_zero {
+            if coeffs.iter_coeffs().all(|c| c.is_zero()) {
                 *pow = 0;
             } else {
-                let one: isize = 1;
-                'outer: loop {
-                    for i in 0..coeffs.len() {
-                        if one & coeffs[i] == one {
-                            break 'outer;
-                        }
-                    }
-
+                let two = T::Coeff::from(2);
+                while coeffs.iter_coeffs().all(|c| c.is_even()) {
                     for i in 0..coeffs.len() {
-                        coeffs[i] >>= 1;
+                        coeffs[i] = coeffs[i].clone() / two.clone();
                     }
                     *pow += 1;
                 }
@@ -269,6 +597,65 @@ impl<T: Coeffs> Scalar<T> {
         self
     }
 
+    /// Returns the canonical form of the scalar
+    ///
+    /// An exact scalar 2^pow * sum_i c_i om^i, with om = e^(i pi / N) for N
+    /// coefficients, has many representations, since the same number can be
+    /// written with a different power of 2, or with a larger N. For N not a
+    /// power of 2, the powers of om are not even linearly independent.
+    ///
+    /// The canonical form uses the smallest N such that the scalar is in
+    /// Q(om), and the coefficients of the powers om^i for i less than the
+    /// degree of the minimal polynomial of om, i.e. the coefficients are
+    /// reduced modulo the cyclotomic polynomial Phi_2N. The power of 2 is then
+    /// made as large as possible, so some coefficient is odd, or the scalar
+    /// is zero with power 0 and N = 1. Note that a factor of sqrt(2) is not
+    /// pulled out, since it is not in every field, e.g. sqrt(2) is
+    /// om - om^3 for N = 4.
+    ///
+    /// For a fixed-size coefficient type, the canonical N is padded to the
+    /// size of the type. Float scalars are returned as they are, and so are
+    /// exact scalars whose coefficients overflow on the way.
+    pub fn canonical(&self) -> Scalar<T> {
+        match self {
+            Exact(pow, coeffs) => {
+                let Some((pow, coeffs)) =
+                    canonical_parts(*pow, &Vec::from_iter(coeffs.iter_coeffs()))
+                else {
+                    return self.clone();
+                };
+                match T::new(coeffs.len()) {
+                    Some((mut coeffs1, pad)) => {
+                        for (i, c) in coeffs.into_iter().enumerate() {
+                            coeffs1[i * pad] = c;
+                        }
+                        Exact(pow, coeffs1)
+                    }
+                    None => self.clone(),
+                }
+            }
+            Float(c) => Float(*c),
+        }
+    }
+
+    /// The canonical power of 2 and coefficients, or `None` for a float
+    /// which is not a finite number
+    ///
+    /// Floats are written exactly as dyadic complex numbers, so a float is
+    /// equal to an exact scalar only if it is exactly the same number. If the
+    /// coefficients overflow on the way, the power and coefficients are
+    /// returned as they are.
+    fn canonical_key(&self) -> Option<(i32, Vec<T::Coeff>)> {
+        let key = |pow: i32, coeffs: Vec<T::Coeff>| {
+            canonical_parts(pow, &coeffs).unwrap_or((pow, coeffs))
+        };
+        match self {
+            Exact(pow, coeffs) => Some(key(*pow, coeffs.iter_coeffs().collect())),
+            Float(c) => float_to_dyadic(*c)
+                .map(|(pow, coeffs)| key(pow, coeffs.map(T::Coeff::from).to_vec())),
+        }
+    }
+
     /// Compute the complex conjugate of a scalar and return it
     pub fn conj(&self) -> Scalar<T> {
         match self {
@@ -277,12 +664,14 @@ impl<T: Coeffs> Scalar<T> {
                 let mut new_coeffs = T::new(coeffs.len()).unwrap().0;
 
                 // copy the real coeff
-                new_coeffs[0] = coeffs[0];
+                new_coeffs[0] = coeffs[0].clone();
 
                 // for each complex coeff, invert the index mod N and add the negative coeff
                 // to that position
                 for i in 1..coeffs.len() {
-                    new_coeffs[coeffs.len() - i] = -coeffs[i];
+                    new_coeffs[coeffs.len() - i] = T::Coeff::zero()
+                        .checked_sub(&coeffs[i])
+                        .unwrap_or_else(|| overflow());
                 }
 
                 Exact(*pow, new_coeffs)
@@ -303,6 +692,108 @@ impl<T: Coeffs> Scalar<T> {
         }
     }
 
+    /// Multiply two scalars, or return [ScalarError::Overflow] if a
+    /// coefficient doesn't fit in the coefficient type
+    ///
+    /// The `*` operator panics instead.
+    pub fn checked_mul(&self, rhs: &Scalar<T>) -> Result<Scalar<T>, ScalarError> {
+        match (self, rhs) {
+            (Float(c), x) => Ok(Float(c * x.complex_value())),
+            (x, Float(c)) => Ok(Float(x.complex_value() * c)),
+            (Exact(pow0, coeffs0), Exact(pow1, coeffs1)) => {
+                let (lcm, pad0, pad1) = lcm_with_padding(coeffs0.len(), coeffs1.len());
+                match T::new(lcm) {
+                    Some((mut coeffs, pad)) => {
+                        for i in 0..coeffs0.len() {
+                            for j in 0..coeffs1.len() {
+                                let pos = (i * pad * pad0 + j * pad * pad1).rem_euclid(2 * lcm);
+                                let x = coeffs0[i]
+                                    .checked_mul(&coeffs1[j])
+                                    .ok_or(ScalarError::Overflow)?;
+                                let c = if pos < lcm {
+                                    coeffs[pos].checked_add(&x)
+                                } else {
+                                    coeffs[pos - lcm].checked_sub(&x)
+                                };
+                                coeffs[pos % lcm] = c.ok_or(ScalarError::Overflow)?;
+                            }
+                        }
+
+                        let pow = i32::checked_add(*pow0, *pow1).ok_or(ScalarError::Overflow)?;
+                        Ok(Exact(pow, coeffs).reduce())
+                    }
+                    None => Ok(Float(self.complex_value() * rhs.complex_value())),
+                }
+            }
+        }
+    }
+
+    /// Add two scalars, or return [ScalarError::Overflow] if a coefficient
+    /// doesn't fit in the coefficient type
+    ///
+    /// Exact scalars are added with the smaller of their powers of 2, so this
+    /// overflows for fixed-width coefficients if the powers are very
+    /// different. The `+` operator panics instead.
+    pub fn checked_add(&self, rhs: &Scalar<T>) -> Result<Scalar<T>, ScalarError> {
+        // catch zeros early to prevent overflows for very large numbers
+        if rhs.is_zero() {
+            return Ok(self.clone());
+        }
+        if self.is_zero() {
+            return Ok(rhs.clone());
+        }
+        match (self, rhs) {
+            (Float(c), x) => Ok(Float(c + x.complex_value())),
+            (x, Float(c)) => Ok(Float(x.complex_value() + c)),
+            (Exact(pow0, coeffs0), Exact(pow1, coeffs1)) => {
+                let (lcm, pad0, pad1) = lcm_with_padding(coeffs0.len(), coeffs1.len());
+
+                let minpow = min(*pow0, *pow1);
+                let shift = |pow: i32| {
+                    num::checked_pow(T::Coeff::from(2), pow.abs_diff(minpow) as usize)
+                        .ok_or(ScalarError::Overflow)
+                };
+                let (base0, base1) = (shift(*pow0)?, shift(*pow1)?);
+
+                match T::new(lcm) {
+                    Some((mut coeffs, pad)) => {
+                        for (cs, base, p) in [(coeffs0, base0, pad0), (coeffs1, base1, pad1)] {
+                            for i in 0..cs.len() {
+                                let x = cs[i].checked_mul(&base).ok_or(ScalarError::Overflow)?;
+                                let c = &mut coeffs[i * pad * p];
+                                *c = c.checked_add(&x).ok_or(ScalarError::Overflow)?;
+                            }
+                        }
+
+                        Ok(Exact(minpow, coeffs).reduce())
+                    }
+                    None => Ok(Float(self.complex_value() + rhs.complex_value())),
+                }
+            }
+        }
+    }
+
+    /// Convert to a scalar with another coefficient type, or return
+    /// [ScalarError::Overflow] if a coefficient doesn't fit in it
+    ///
+    /// As for [FromScalar], an exact scalar whose number of coefficients
+    /// doesn't fit in the new type becomes a float.
+    pub fn try_convert<S: Coeffs>(&self) -> Result<Scalar<S>, ScalarError> {
+        match self {
+            Exact(pow, coeffs) => match S::new(coeffs.len()) {
+                Some((mut coeffs1, pad)) => {
+                    for i in 0..coeffs.len() {
+                        coeffs1[i * pad] =
+                            convert_coeff(&coeffs[i]).ok_or(ScalarError::Overflow)?;
+                    }
+                    Ok(Exact(*pow, coeffs1))
+                }
+                None => Ok(Float(self.complex_value())),
+            },
+            Float(c) => Ok(Float(*c)),
+        }
+    }
+
     // TODO: this is non-trivial (code below is wrong). Think about this some more.
     // /// Returns true if scalar is real
     // pub fn is_real(&self) -> bool {
@@ -331,7 +822,13 @@ impl<T: Coeffs> Zero for Scalar<T> {
     }
 
     fn is_zero(&self) -> bool {
-        *self == Scalar::zero()
+        match self {
+            // the powers of om are linearly independent if N is a power of 2
+            Exact(_, coeffs) if coeffs.len().is_power_of_two() => {
+                coeffs.iter_coeffs().all(|c| c.is_zero())
+            }
+            _ => *self == Scalar::zero(),
+        }
     }
 }
 
@@ -353,13 +850,13 @@ impl<T: Coeffs> Sqrt2 for Scalar<T> {
 
                 if p % 2 == 0 {
                     // for even p, use: sqrt(2)^p = 2^(p/2)
-                    coeffs[0] = 1;
+                    coeffs[0] = T::Coeff::one();
                     Exact(p / 2, coeffs)
                 } else {
                     // for odd p, use:
                     // sqrt(2)^p = sqrt(2)^(p-1) * sqrt(2) = 2^((p-1)/2) * (omega - omega^3)
-                    coeffs[pad] = 1;
-                    coeffs[3 * pad] = -1;
+                    coeffs[pad] = T::Coeff::one();
+                    coeffs[3 * pad] = -T::Coeff::one();
                     Exact((p - 1) / 2, coeffs)
                 }
             }
@@ -384,7 +881,7 @@ impl<T: Coeffs> FromPhase for Scalar<T> {
                 } else {
                     1
                 };
-                coeffs[rnumer as usize] = sgn;
+                coeffs[rnumer as usize] = T::Coeff::from(sgn);
                 Exact(0, coeffs)
             }
             None => {
@@ -399,54 +896,58 @@ impl<T: Coeffs> FromPhase for Scalar<T> {
     }
 }
 
+/// Prints the canonical form, see [Scalar::canonical], or the scalar as it
+/// is if the coefficients overflow on the way
+///
+/// Powers of the root of unity are written as e.g. `exp(3 i pi/4)`, and
+/// real Clifford+T scalars in terms of `sqrt2`.
 impl<T: Coeffs> fmt::Display for Scalar<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Exact(pow, coeffs) => {
-                // special output for real clifford+T
-                if coeffs.len() == 4 && coeffs[1] == -coeffs[3] && coeffs[2] == 0 {
-                    if *pow != 0 {
-                        write!(f, "2^{} * (", pow)?;
-                    }
+                let coeffs = Vec::from_iter(coeffs.iter_coeffs());
+                let (pow, coeffs) = canonical_parts(*pow, &coeffs).unwrap_or((*pow, coeffs));
+                let n = coeffs.len();
+                if pow != 0 {
+                    write!(f, "2^{} * (", pow)?;
+                }
+
+                if n == 4
+                    && coeffs[1]
+                        .checked_add(&coeffs[3])
+                        .is_some_and(|c| c.is_zero())
+                    && coeffs[2].is_zero()
+                {
+                    // special output for real clifford+T
                     write!(f, "{}", coeffs[0])?;
-                    if coeffs[1] != 0 {
+                    if !coeffs[1].is_zero() {
                         write!(f, " + {} * sqrt2", coeffs[1])?;
                     }
-                    if *pow != 0 {
-                        write!(f, ")")?;
-                    }
-                    return Ok(());
-                }
-
-                // otherwise normal exact output
-                let mut fst = true;
-                for i in 0..coeffs.len() {
-                    if !coeffs[i].is_zero() {
-                        if fst {
-                            fst = false;
-                            if *pow != 0 {
-                                write!(f, "2^{} * (", pow)?;
-                            }
-                        } else {
+                } else {
+                    let mut fst = true;
+                    for (i, c) in coeffs.iter().enumerate().filter(|(_, c)| !c.is_zero()) {
+                        if !fst {
                             write!(f, " + ")?;
                         }
-
-                        write!(f, "{}", coeffs[i])?;
-                        // if *pow != 0 { write!(f, " * 2^{}", pow)?; }
+                        fst = false;
+                        write!(f, "{}", c)?;
                         if i != 0 {
-                            write!(f, " * om^{}", i)?;
+                            let g = integer::gcd(i, n);
+                            match i / g {
+                                1 => write!(f, " * exp(i pi/{})", n / g)?,
+                                k => write!(f, " * exp({} i pi/{})", k, n / g)?,
+                            }
                         }
                     }
+                    if fst {
+                        write!(f, "0")?;
+                    }
                 }
 
-                if fst {
-                    write!(f, "0")
-                } else {
-                    if *pow != 0 {
-                        write!(f, ")")?;
-                    }
-                    Ok(())
+                if pow != 0 {
+                    write!(f, ")")?;
                 }
+                Ok(())
             }
             Float(c) => write!(f, "{}", c),
         }
@@ -455,34 +956,12 @@ impl<T: Coeffs> fmt::Display for Scalar<T> {
 
 // The main implementation of the Mul trait uses references, so
 // we don't need to make a copy of the scalars to multiply them.
-impl<'a, 'b, T: Coeffs> Mul<&'b Scalar<T>> for &'a Scalar<T> {
+impl<T: Coeffs> Mul<&Scalar<T>> for &Scalar<T> {
     type Output = Scalar<T>;
 
+    /// Multiply the scalars, panicking on overflow, see [Scalar::checked_mul]
     fn mul(self, rhs: &Scalar<T>) -> Self::Output {
-        match (self, rhs) {
-            (Float(c), x) => Float(c * x.complex_value()),
-            (x, Float(c)) => Float(x.complex_value() * c),
-            (Exact(pow0, coeffs0), Exact(pow1, coeffs1)) => {
-                let (lcm, pad0, pad1) = lcm_with_padding(coeffs0.len(), coeffs1.len());
-                match T::new(lcm) {
-                    Some((mut coeffs, pad)) => {
-                        for i in 0..coeffs0.len() {
-                            for j in 0..coeffs1.len() {
-                                let pos = (i * pad * pad0 + j * pad * pad1).rem_euclid(2 * lcm);
-                                if pos < lcm {
-                                    coeffs[pos] += coeffs0[i] * coeffs1[j];
-                                } else {
-                                    coeffs[pos - lcm] += -coeffs0[i] * coeffs1[j];
-                                }
-                            }
-                        }
-
-                        Exact(pow0 + pow1, coeffs).reduce()
-                    }
-                    None => Float(self.complex_value() * rhs.complex_value()),
-                }
-            }
-        }
+        self.checked_mul(rhs).unwrap_or_else(|_| overflow())
     }
 }
 
@@ -493,13 +972,13 @@ impl<T: Coeffs> Mul for Scalar<T> {
         &self * &rhs
     }
 }
-impl<'a, T: Coeffs> Mul<Scalar<T>> for &'a Scalar<T> {
+impl<T: Coeffs> Mul<Scalar<T>> for &Scalar<T> {
     type Output = Scalar<T>;
     fn mul(self, rhs: Scalar<T>) -> Self::Output {
         self * &rhs
     }
 }
-impl<'a, T: Coeffs> Mul<&'a Scalar<T>> for Scalar<T> {
+impl<T: Coeffs> Mul<&Scalar<T>> for Scalar<T> {
     type Output = Scalar<T>;
     fn mul(self, rhs: &Scalar<T>) -> Self::Output {
         &self * rhs
@@ -514,7 +993,7 @@ impl<T: Coeffs> std::ops::MulAssign<Scalar<T>> for Scalar<T> {
 }
 
 // Variation takes ownership of rhs
-impl<'a, T: Coeffs> std::ops::MulAssign<&'a Scalar<T>> for Scalar<T> {
+impl<T: Coeffs> std::ops::MulAssign<&Scalar<T>> for Scalar<T> {
     fn mul_assign(&mut self, rhs: &Scalar<T>) {
         *self = &*self * rhs;
     }
@@ -522,44 +1001,12 @@ impl<'a, T: Coeffs> std::ops::MulAssign<&'a Scalar<T>> for Scalar<T> {
 
 // The main implementation of the Add trait uses references, so we
 // don't need to make a copy of the scalars to add them.
-impl<'a, 'b, T: Coeffs> Add<&'b Scalar<T>> for &'a Scalar<T> {
+impl<T: Coeffs> Add<&Scalar<T>> for &Scalar<T> {
     type Output = Scalar<T>;
 
+    /// Add the scalars, panicking on overflow, see [Scalar::checked_add]
     fn add(self, rhs: &Scalar<T>) -> Self::Output {
-        // catch zeros early to prevent overflows for very large numbers
-        if rhs.is_zero() {
-            return self.clone();
-        }
-        if self.is_zero() {
-            return rhs.clone();
-        }
-        match (self, rhs) {
-            (Float(c), x) => Float(c + x.complex_value()),
-            (x, Float(c)) => Float(x.complex_value() + c),
-            (Exact(pow0, coeffs0), Exact(pow1, coeffs1)) => {
-                let (lcm, pad0, pad1) = lcm_with_padding(coeffs0.len(), coeffs1.len());
-
-                // nb. this can overflow if powers are too different
-                let minpow = min(*pow0, *pow1);
-                let base0 = 2isize.pow((*pow0 - minpow) as u32);
-                let base1 = 2isize.pow((*pow1 - minpow) as u32);
-
-                match T::new(lcm) {
-                    Some((mut coeffs, pad)) => {
-                        for i in 0..coeffs0.len() {
-                            coeffs[i * pad * pad0] += coeffs0[i] * base0;
-                        }
-
-                        for i in 0..coeffs1.len() {
-                            coeffs[i * pad * pad1] += coeffs1[i] * base1;
-                        }
-
-                        Exact(minpow, coeffs).reduce()
-                    }
-                    None => Float(self.complex_value() + self.complex_value()),
-                }
-            }
-        }
+        self.checked_add(rhs).unwrap_or_else(|_| overflow())
     }
 }
 
@@ -571,14 +1018,14 @@ impl<T: Coeffs> Add<Scalar<T>> for Scalar<T> {
     }
 }
 
-impl<'a, T: Coeffs> Add<Scalar<T>> for &'a Scalar<T> {
+impl<T: Coeffs> Add<Scalar<T>> for &Scalar<T> {
     type Output = Scalar<T>;
     fn add(self, rhs: Scalar<T>) -> Self::Output {
         self + &rhs
     }
 }
 
-impl<'a, T: Coeffs> Add<&'a Scalar<T>> for Scalar<T> {
+impl<T: Coeffs> Add<&Scalar<T>> for Scalar<T> {
     type Output = Scalar<T>;
     fn add(self, rhs: &Scalar<T>) -> Self::Output {
         &self + rhs
@@ -591,7 +1038,7 @@ impl<T: Coeffs> AddAssign for Scalar<T> {
     }
 }
 
-impl<'a, T: Coeffs> AddAssign<&'a Scalar<T>> for Scalar<T> {
+impl<T: Coeffs> AddAssign<&Scalar<T>> for Scalar<T> {
     fn add_assign(&mut self, rhs: &Scalar<T>) {
         *self = &*self + rhs;
     }
@@ -603,20 +1050,11 @@ impl<T: Coeffs> FromScalar<Scalar<T>> for Complex<f64> {
     }
 }
 
+/// Converts exactly if the coefficients fit, and otherwise to a float, see
+/// [Scalar::try_convert]
 impl<S: Coeffs, T: Coeffs> FromScalar<Scalar<T>> for Scalar<S> {
     fn from_scalar(s: &Scalar<T>) -> Scalar<S> {
-        match s {
-            Exact(pow, coeffs) => match S::new(coeffs.len()) {
-                Some((mut coeffs1, pad)) => {
-                    for i in 0..coeffs.len() {
-                        coeffs1[i * pad] = coeffs[i];
-                    }
-                    Exact(*pow, coeffs1)
-                }
-                None => Float(s.complex_value()),
-            },
-            Float(c) => Float(*c),
-        }
+        s.try_convert().unwrap_or_else(|_| Float(s.complex_value()))
     }
 }
 
@@ -642,28 +1080,73 @@ impl<T: Coeffs> AbsDiffEq<Scalar<T>> for Scalar<T> {
     }
 }
 
+/// Scalars are equal if they are the same number, see [Scalar::canonical]
+///
+/// A float is only equal to an exact scalar if it is exactly the same
+/// dyadic complex number, e.g. 0.5 is equal to 2^-1 but the float closest
+/// to sqrt(2) is not equal to sqrt(2). Unlike for [f64], floats with NaN or
+/// infinite parts are compared by their bits, so every scalar is equal to
+/// itself, as [Eq] requires.
 impl<T: Coeffs> PartialEq for Scalar<T> {
     fn eq(&self, other: &Self) -> bool {
+        // zero, if that can be seen without normalising
+        let zero = |s: &Self| match s {
+            Exact(_, coeffs) if coeffs.len().is_power_of_two() => {
+                Some(coeffs.iter_coeffs().all(|c| c.is_zero()))
+            }
+            Float(c) => Some(c.is_zero()),
+            _ => None,
+        };
+        if let (Some(z0), Some(z1)) = (zero(self), zero(other)) {
+            if z0 || z1 {
+                return z0 && z1;
+            }
+        }
+
         match (self, other) {
-            (Float(c0), Float(c1)) => c0 == c1,
-            (Exact(pow0, coeffs0), Exact(pow1, coeffs1)) => {
-                // since scalars are reduced via Scalar::reduce(), equal scalars
-                // must have the same power of 2.
-                if pow0 != pow1 {
-                    return false;
+            (Float(c0), Float(c1)) => {
+                c0 == c1
+                    || (c0.re.to_bits() == c1.re.to_bits() && c0.im.to_bits() == c1.im.to_bits())
+            }
+            (Exact(pow0, coeffs0), Exact(pow1, coeffs1))
+                if pow0 == pow1
+                    && coeffs0.len() == coeffs1.len()
+                    && coeffs0.iter_coeffs().eq(coeffs1.iter_coeffs()) =>
+            {
+                true
+            }
+            // the powers of om are linearly independent if N is a power of
+            // 2, so the reduced form is unique
+            (Exact(_, coeffs0), Exact(_, coeffs1))
+                if coeffs0.len() == coeffs1.len() && coeffs0.len().is_power_of_two() =>
+            {
+                match (self.clone().reduce(), other.clone().reduce()) {
+                    (Exact(pow0, coeffs0), Exact(pow1, coeffs1)) => {
+                        pow0 == pow1 && coeffs0.iter_coeffs().eq(coeffs1.iter_coeffs())
+                    }
+                    _ => unreachable!(),
                 }
-                let (lcm, pad0, pad1) = lcm_with_padding(coeffs0.len(), coeffs1.len());
+            }
+            _ => match (self.canonical_key(), other.canonical_key()) {
+                (Some(k0), Some(k1)) => k0 == k1,
+                _ => false,
+            },
+        }
+    }
+}
 
-                let mut all_eq = true;
-                for i in 0..lcm {
-                    let c0 = if i % pad0 == 0 { coeffs0[i / pad0] } else { 0 };
-                    let c1 = if i % pad1 == 0 { coeffs1[i / pad1] } else { 0 };
-                    all_eq = all_eq && c0 == c1;
-                }
+impl<T: Coeffs> Eq for Scalar<T> {}
 
-                all_eq
+/// Hashes the canonical form, see [Scalar::canonical]
+impl<T: Coeffs> Hash for Scalar<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.canonical_key() {
+            Some(k) => k.hash(state),
+            None => {
+                let c = self.complex_value();
+                c.re.to_bits().hash(state);
+                c.im.to_bits().hash(state);
             }
-            _ => self.complex_value() == other.complex_value(),
         }
     }
 }
@@ -673,6 +1156,7 @@ impl<T: Coeffs> PartialEq for Scalar<T> {
 macro_rules! fixed_size_scalar {
     ( $name:ident, $n:expr ) => {
         impl Coeffs for [isize; $n] {
+            type Coeff = isize;
             fn len(&self) -> usize {
                 $n
             }
@@ -711,6 +1195,7 @@ fixed_size_scalar!(Scalar7, 7);
 fixed_size_scalar!(Scalar8, 8);
 
 impl Coeffs for Vec<isize> {
+    type Coeff = isize;
     fn len(&self) -> usize {
         self.len()
     }
@@ -730,6 +1215,34 @@ impl Coeffs for Vec<isize> {
 
 pub type ScalarN = Scalar<Vec<isize>>;
 
+#[cfg(feature = "bigint")]
+impl Coeffs for Vec<BigInt> {
+    type Coeff = BigInt;
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn zero() -> Self {
+        vec![BigInt::zero()]
+    }
+    fn one() -> Self {
+        vec![BigInt::one()]
+    }
+    fn new(sz: usize) -> Option<(Self, usize)> {
+        Some((vec![BigInt::zero(); sz], 1))
+    }
+    fn iter_coeffs(&self) -> impl Iterator<Item = BigInt> {
+        self.iter().cloned()
+    }
+}
+
+/// A scalar with arbitrary-precision coefficients, which never overflow
+///
+/// This is like [ScalarN], but slower. Other scalars can be converted to
+/// it exactly with [FromScalar], e.g. `ScalarB::from_scalar(g.scalar())`
+/// for a graph `g`.
+#[cfg(feature = "bigint")]
+pub type ScalarB = Scalar<Vec<BigInt>>;
+
 /// tests {{{
 #[cfg(test)]
 mod tests {
@@ -737,6 +1250,7 @@ mod tests {
     use approx::assert_abs_diff_eq;
     use num::Rational64;
     use rstest::rstest;
+    use std::hash::{Hash, Hasher};
 
     #[test]
     fn approx_mul() {
@@ -746,6 +1260,32 @@ mod tests {
         assert_abs_diff_eq!(s, t);
     }
 
+    #[test]
+    fn mul_complex() {
+        let mut s = ScalarN::sqrt2_pow(3);
+        s.mul_complex(Complex::one());
+        assert!(s.is_exact());
+
+        // dyadic numbers are kept exact
+        s.mul_complex(Complex::new(0.5, -1.5));
+        assert!(s.is_exact());
+        let expected = ScalarN::sqrt2_pow(1) * ScalarN::from_int_coeffs(&[1, 0, -3, 0]);
+        assert_eq!(s, expected);
+        s.mul_complex(Complex::new(0.0, 0.25));
+        assert!(s.is_exact());
+        s = ScalarN::sqrt2_pow(3);
+
+        let c = Complex::new(0.3, -1.7);
+        s.mul_complex(c);
+        assert!(s.is_float());
+        assert_abs_diff_eq!(s, Scalar::from(c * 8f64.sqrt()));
+
+        s.mul_phase(Rational64::new(1, 4));
+        let expected = c * 8f64.sqrt() * Complex::from_polar(1.0, PI / 4.0);
+        assert_abs_diff_eq!(s.complex_value().re, expected.re, epsilon = 1e-10);
+        assert_abs_diff_eq!(s.complex_value().im, expected.im, epsilon = 1e-10);
+    }
+
     #[test]
     fn sqrt_i() {
         let s = Scalar4::from_int_coeffs(&[0, 1, 0, 0]);
@@ -886,15 +1426,120 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "attempt to multiply with overflow")]
+    #[should_panic(expected = "Scalar coefficient overflow")]
     fn add_diff_power_2() {
         let p1 = Scalar4::sqrt2_pow(200);
         let p2 = Scalar4::sqrt2_pow(-200);
         // adding very different powers of 2 will panic
+        assert_eq!(p1.checked_add(&p2), Err(ScalarError::Overflow));
         let p3 = p1 + p2;
         assert_eq!(p3, Scalar4::one());
     }
 
+    #[test]
+    fn checked_mul_overflow() {
+        // (1 + sqrt(2))^n = a + b sqrt(2), where a and b grow like 2.414^n
+        let x = ScalarN::one() + ScalarN::sqrt2();
+        let mut p = ScalarN::one();
+        let mut n = 0;
+        let err = loop {
+            match p.checked_mul(&x) {
+                Ok(q) => p = q,
+                Err(e) => break e,
+            }
+            n += 1;
+        };
+        assert_eq!(err, ScalarError::Overflow);
+        assert!(n > 40, "{}", n);
+        let v = (1.0 + 2f64.sqrt()).powi(n);
+        assert!((p.complex_value().re / v - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn try_convert() {
+        let s = ScalarN::Exact(3, vec![isize::MAX, -5]);
+        assert_eq!(
+            s.try_convert::<[isize; 4]>(),
+            Ok(Scalar4::Exact(3, [isize::MAX, 0, -5, 0]))
+        );
+        assert_eq!(s.try_convert::<Vec<isize>>(), Ok(s.clone()));
+        let f = ScalarN::real(0.25);
+        assert_eq!(f.try_convert::<[isize; 4]>(), Ok(Scalar4::real(0.25)));
+    }
+
+    #[cfg(feature = "bigint")]
+    mod bigint {
+        use super::*;
+        use num_bigint::BigInt;
+
+        #[test]
+        fn past_isize() {
+            let x = ScalarB::one() + ScalarB::sqrt2();
+            let xn = ScalarN::one() + ScalarN::sqrt2();
+            // (1 + sqrt(2))^n = a + b sqrt(2)
+            let (mut a, mut b) = (BigInt::one(), BigInt::zero());
+            let mut p = ScalarB::one();
+            let mut pn = Ok(ScalarN::one());
+            for _ in 0..100 {
+                (a, b) = (&a + 2 * &b, &a + &b);
+                p *= &x;
+                pn = pn.and_then(|q| q.checked_mul(&xn));
+                if let Ok(q) = &pn {
+                    assert_eq!(ScalarB::from_scalar(q), p);
+                }
+            }
+            assert_eq!(pn, Err(ScalarError::Overflow));
+            assert!(a > BigInt::from(i64::MAX));
+            assert_eq!(p, ScalarB::Exact(0, vec![a, b.clone(), BigInt::zero(), -b]));
+
+            // (1 + sqrt(2))(1 - sqrt(2)) = -1
+            let y = ScalarB::one() + ScalarB::sqrt2() * ScalarB::minus_one();
+            let mut q = ScalarB::one();
+            for _ in 0..100 {
+                q *= &y;
+            }
+            assert_eq!(p * q, ScalarB::one());
+        }
+
+        #[test]
+        fn different_powers() {
+            let p = ScalarB::sqrt2_pow(140);
+            let s = ScalarB::one() + &p;
+            assert_eq!(
+                s,
+                ScalarB::Exact(0, vec![BigInt::one() + (BigInt::one() << 70)])
+            );
+            assert_eq!(s + p * ScalarB::minus_one(), ScalarB::one());
+            assert_eq!(
+                ScalarN::one().checked_add(&ScalarN::sqrt2_pow(140)),
+                Err(ScalarError::Overflow)
+            );
+        }
+
+        #[test]
+        fn conversions() {
+            let big = ScalarB::Exact(
+                -3,
+                vec![BigInt::from(7) - (BigInt::one() << 100), BigInt::from(5)],
+            );
+            assert_eq!(big.try_convert::<Vec<BigInt>>(), Ok(big.clone()));
+            assert_eq!(big.try_convert::<Vec<isize>>(), Err(ScalarError::Overflow));
+            let f = ScalarN::from_scalar(&big);
+            assert!(f.is_float());
+            assert_eq!(f.complex_value(), big.complex_value());
+
+            let small = ScalarN::Exact(2, vec![isize::MIN + 1, 3, -7, isize::MAX]);
+            let b = ScalarB::from_scalar(&small);
+            assert_eq!(b.try_convert::<Vec<isize>>(), Ok(small.clone()));
+            assert_eq!(b.to_string(), small.to_string());
+
+            let z = ScalarB::from_int_coeffs(&[1, -1, 1]);
+            assert!(z.is_zero());
+            let s = ScalarN::from_phase(Rational64::new(1, 3));
+            assert_eq!(ScalarB::from_scalar(&s).to_string(), s.to_string());
+        }
+    }
+
     #[test]
     fn conjugates() {
         let ps = vec![
@@ -921,6 +1566,213 @@ mod tests {
             assert!(absf.re > 0.0);
         }
     }
+
+    /// Write an exact scalar with n coefficients, for a multiple n of its
+    /// current number, and with a smaller power of 2
+    fn lift(s: &ScalarN, n: usize, shift: i32) -> ScalarN {
+        match s {
+            Exact(pow, coeffs) => {
+                let pad = n / coeffs.len();
+                let mut coeffs1 = vec![0; n];
+                for (i, &c) in coeffs.iter().enumerate() {
+                    coeffs1[i * pad] = c << shift;
+                }
+                Exact(pow - shift, coeffs1)
+            }
+            Float(_) => s.clone(),
+        }
+    }
+
+    fn hash_of(s: &ScalarN) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        let mut h = DefaultHasher::new();
+        s.hash(&mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn canonical_zeros() {
+        // these sum to Phi_2N(om), so are zero
+        let zeros = [
+            ScalarN::from_int_coeffs(&[1, -1, 1]),
+            ScalarN::from_int_coeffs(&[1, -1, 1, -1, 1]),
+            ScalarN::from_int_coeffs(&[1, 0, -1, 0, 1, 0]),
+            Exact(-7, vec![3, -3, 3]),
+            Exact(5, vec![0, 0, 0, 0]),
+            ScalarN::real(0.0),
+            ScalarN::real(-0.0),
+        ];
+        for z in &zeros {
+            assert!(z.is_zero(), "{:?} should be zero", z);
+            assert_eq!(*z, ScalarN::zero());
+            assert_eq!(hash_of(z), hash_of(&ScalarN::zero()));
+            if z.is_exact() {
+                assert_eq!(z.to_string(), "0");
+            }
+        }
+        assert_eq!(
+            ScalarN::from_int_coeffs(&[1, -1, 1]).canonical(),
+            Exact(0, vec![0])
+        );
+        assert!(!ScalarN::from_int_coeffs(&[1, -1, 0]).is_zero());
+    }
+
+    #[test]
+    fn canonical_forms() {
+        // e^(2 i pi/3) = om^2 = om - 1 for om = e^(i pi/3)
+        let s = ScalarN::from_int_coeffs(&[0, 0, 1]);
+        assert_eq!(s.canonical(), Exact(0, vec![-1, 1, 0]));
+
+        // i, as a 12th root of unity, and 1/2 as a 6th root of unity
+        let s = Exact(0, vec![0, 0, 0, 1, 0, 0]);
+        assert_eq!(s.canonical(), Exact(0, vec![0, 1]));
+        assert_eq!(Exact(0, vec![2, 0, 0]).canonical(), Exact(1, vec![1]));
+
+        // sqrt(3) = e^(i pi/6) + e^(-i pi/6) is in Q(e^(i pi/6)) but no smaller
+        // cyclotomic field
+        let s = ScalarN::from_phase(Rational64::new(1, 6));
+        let sqrt3 = &s + s.conj();
+        assert_eq!(sqrt3.canonical(), Exact(0, vec![0, 2, 0, -1, 0, 0]));
+        assert_abs_diff_eq!(sqrt3.complex_value().re, 3f64.sqrt(), epsilon = 1e-10);
+
+        // sqrt(2)^-3 keeps its factor of sqrt(2) in the coefficients
+        let s = ScalarN::sqrt2_pow(-3);
+        assert_eq!(s.canonical(), Exact(-2, vec![0, 1, 0, -1]));
+        assert_eq!(lift(&s, 8, 3).canonical(), s.canonical());
+
+        // fixed-size scalars are padded
+        let s = Scalar4::Exact(-1, [0, 0, 2, 0]);
+        assert_eq!(s.canonical(), Scalar4::Exact(0, [0, 0, 1, 0]));
+
+        for s in [
+            ScalarN::from_int_coeffs(&[3, 1, 4, 1, 5]),
+            ScalarN::sqrt2_pow(7),
+        ] {
+            let c = s.canonical();
+            assert_eq!(c.canonical(), c);
+        }
+    }
+
+    #[test]
+    fn canonical_display() {
+        let s = ScalarN::from_phase(Rational64::new(1, 2));
+        assert_eq!(s.to_string(), "1 * exp(i pi/2)");
+        assert_eq!(lift(&s, 12, 2).to_string(), "1 * exp(i pi/2)");
+        assert_eq!(ScalarN::sqrt2_pow(-1).to_string(), "2^-1 * (0 + 1 * sqrt2)");
+        assert_eq!(
+            lift(&ScalarN::sqrt2_pow(1), 8, 0).to_string(),
+            "0 + 1 * sqrt2"
+        );
+        assert_eq!(
+            Scalar4::from_int_coeffs(&[1, 0, 0, 3]).to_string(),
+            "1 + 3 * exp(3 i pi/4)"
+        );
+        assert_eq!(
+            ScalarN::from_int_coeffs(&[0, 0, 1]).to_string(),
+            "-1 + 1 * exp(i pi/3)"
+        );
+        assert_eq!(Exact(3, vec![0, 0, 0, 0]).to_string(), "0");
+    }
+
+    #[test]
+    fn canonical_overflow() {
+        // reducing modulo Phi_6 = x^2 - x + 1 overflows
+        let s: ScalarN = Exact(0, vec![isize::MAX; 3]);
+        let m = isize::MAX;
+        assert_eq!(s.canonical(), s);
+        assert_eq!(
+            s.to_string(),
+            format!("{m} + {m} * exp(i pi/3) + {m} * exp(2 i pi/3)")
+        );
+        assert_eq!(s, s.clone());
+        assert_eq!(hash_of(&s), hash_of(&s.clone()));
+        assert_ne!(s, Exact(1, vec![isize::MAX; 3]));
+        assert_ne!(s, ScalarN::one());
+        assert_ne!(s, ScalarN::real(1.0));
+
+        let s: ScalarN = Exact(0, vec![isize::MIN, 0, 0, isize::MAX]);
+        assert_eq!(s, s.clone());
+        assert_eq!(hash_of(&s), hash_of(&s.clone()));
+        assert_eq!(
+            s.to_string(),
+            format!("{} + {m} * exp(3 i pi/4)", isize::MIN)
+        );
+
+        let s: ScalarN = Exact(0, vec![isize::MIN]);
+        assert_eq!(s.canonical(), Exact(63, vec![-1]));
+        assert_eq!(s, Exact(63, vec![-1]));
+        assert_eq!(hash_of(&s), hash_of(&Exact(63, vec![-1])));
+        assert_eq!(s.to_string(), "2^63 * (-1)");
+    }
+
+    #[test]
+    fn canonical_floats() {
+        assert_eq!(ScalarN::real(0.5), ScalarN::sqrt2_pow(-2));
+        assert_eq!(ScalarN::complex(-3.0, 0.25), Exact(-2, vec![-12, 1]));
+        assert_eq!(
+            hash_of(&ScalarN::real(0.5)),
+            hash_of(&ScalarN::sqrt2_pow(-2))
+        );
+        assert_ne!(ScalarN::real(f64::sqrt(2.0)), ScalarN::sqrt2_pow(1));
+        // so that Eq and Hash hold, NaN is equal to itself
+        let nan = ScalarN::complex(f64::NAN, 1.0);
+        assert_eq!(nan, nan.clone());
+        assert_eq!(hash_of(&nan), hash_of(&nan.clone()));
+        assert_ne!(nan, ScalarN::complex(f64::NAN, 2.0));
+        assert_ne!(nan, ScalarN::one());
+        let inf = ScalarN::real(f64::INFINITY);
+        assert_eq!(inf, inf.clone());
+        assert_ne!(inf, ScalarN::real(f64::NEG_INFINITY));
+        assert_eq!(ScalarN::real(0.0), ScalarN::real(-0.0));
+        assert_eq!(ScalarN::real(-0.0), ScalarN::zero());
+        assert_eq!(Scalar4::zero(), Scalar4::real(0.0));
+        assert_ne!(Scalar4::zero(), Scalar4::one());
+        assert_ne!(ScalarN::real(f64::NAN), ScalarN::zero());
+        assert_ne!(ScalarN::complex(1.0, 1e-300), ScalarN::one());
+    }
+
+    #[test]
+    fn canonical_eq_iff_same_value() {
+        let base: Vec<ScalarN> = vec![
+            ScalarN::zero(),
+            ScalarN::one(),
+            ScalarN::minus_one(),
+            ScalarN::sqrt2_pow(1),
+            ScalarN::sqrt2_pow(-3),
+            ScalarN::from_phase(Rational64::new(1, 2)),
+            ScalarN::from_phase(Rational64::new(1, 3)),
+            ScalarN::from_phase(Rational64::new(2, 3)),
+            ScalarN::from_phase(Rational64::new(1, 4)),
+            ScalarN::from_phase(Rational64::new(-1, 6)),
+            ScalarN::from_phase(Rational64::new(1, 5)),
+            Exact(-2, vec![3]),
+        ];
+
+        let mut grid = vec![];
+        for (i, a) in base.iter().enumerate() {
+            for b in &base[i..] {
+                for s in [a + b, a * b] {
+                    let Exact(_, coeffs) = &s else { unreachable!() };
+                    let n = coeffs.len();
+                    grid.push(lift(&s, 2 * n, 1));
+                    grid.push(lift(&s, 3 * n, 0));
+                    grid.push(s);
+                }
+            }
+        }
+
+        for a in &grid {
+            for b in &grid {
+                let (ca, cb) = (a.complex_value(), b.complex_value());
+                let close = (ca - cb).norm() < 1e-9;
+                assert_eq!(a == b, close, "{:?} vs {:?}", a, b);
+                if close {
+                    assert_eq!(hash_of(a), hash_of(b));
+                    assert_eq!(a.to_string(), b.to_string());
+                }
+            }
+        }
+    }
 }
 // }}}
 // vim:foldlevel=0:
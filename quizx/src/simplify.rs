@@ -16,9 +16,35 @@
 
 use crate::basic_rules::*;
 use crate::graph::*;
-use crate::phase::Phase;
-use num::{One, Zero};
+use crate::scalar::ScalarN;
+use derive_more::{Display, Error};
+use num::One;
 use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+pub mod audit;
+mod budget;
+mod greedy;
+mod gslc;
+mod hadamard;
+mod parallel;
+mod phases;
+mod region;
+mod stop;
+#[cfg(test)]
+pub(crate) mod test_util;
+mod trace;
+
+pub use budget::{clifford_simp_with, full_simp_with, SimpOptions, SimpStatus};
+pub use greedy::{greedy_simp, EdgeDegree, GreedyHeuristic};
+pub use gslc::{to_gslc, GsLcForm};
+pub use hadamard::{num_h_edges, reduce_hadamards};
+pub use parallel::full_simp_parallel;
+pub use phases::{full_simp_tracking_phases, phase_teleport, PhaseGroup};
+pub use region::{causal_cone, full_simp_region};
+pub use stop::{clifford_simp_until, full_simp_until, interior_clifford_simp_until, SimpStats};
+pub use trace::{full_simp_traced, SimpTrace, TraceError, TraceStep};
 
 /// Repeatedly apply the given rule at any vertex
 /// that matches the check function
@@ -59,7 +85,17 @@ pub fn edge_simp<G: GraphLike>(
     rule: fn(&mut G, V, V) -> (),
     force_reduce: bool,
 ) -> bool {
-    let mut got_match = false;
+    edge_simp_count(g, check, rule, force_reduce) > 0
+}
+
+/// Same as [edge_simp], but return the number of times the rule was applied
+pub fn edge_simp_count<G: GraphLike>(
+    g: &mut G,
+    check: fn(&G, V, V) -> bool,
+    rule: fn(&mut G, V, V) -> (),
+    force_reduce: bool,
+) -> usize {
+    let mut count = 0;
     let mut new_matches = true;
     let mut numv;
     while new_matches {
@@ -71,48 +107,322 @@ pub fn edge_simp<G: GraphLike>(
             }
             rule(g, s, t);
             new_matches = true;
-            got_match = true;
+            count += 1;
         }
         if force_reduce && numv >= g.num_vertices() {
             break;
         }
     }
 
+    count
+}
+
+/// Receives each rewrite applied by a simplification pass
+///
+/// The built-in passes are generic over the hook, and the plain versions use
+/// `()`, which does nothing. Since [SimpHook::TRACE] is a constant, the extra
+/// work to report each rewrite is compiled away when it isn't needed.
+trait SimpHook<G: GraphLike> {
+    /// Whether the hook should be called at all
+    const TRACE: bool;
+
+    /// Whether a rewrite may be applied at the given vertices
+    ///
+    /// This is asked for every match, before [SimpHook::before].
+    fn allows(&self, _g: &G, _vs: &[V]) -> bool {
+        true
+    }
+
+    /// Called before a rewrite is applied
+    fn before(&mut self, _g: &G, _rule: Rule, _vs: &[V]) {}
+
+    /// Called after a rewrite, with the vertices it was applied at and the
+    /// factor it multiplied the global scalar by
+    fn rewrite(&mut self, rule: Rule, vs: &[V], scalar: ScalarN);
+
+    /// Called with the rewritten graph, after [SimpHook::rewrite]
+    fn after(&mut self, _g: &G, _rule: Rule, _vs: &[V]) {}
+
+    /// Called after a rewrite which moved the phase of `from` onto the new vertex `to`
+    fn phase_moved(&mut self, _from: V, _to: V) {}
+
+    /// Whether the drivers should stop, asked between passes
+    fn stop(&mut self, _g: &G) -> bool {
+        false
+    }
+
+    /// Called before each rewrite, which is skipped if this returns true
+    ///
+    /// Once this has returned true, it should keep doing so, so that the
+    /// passes stop matching and return.
+    fn interrupt(&mut self) -> bool {
+        false
+    }
+}
+
+impl<G: GraphLike> SimpHook<G> for () {
+    const TRACE: bool = false;
+    fn rewrite(&mut self, _rule: Rule, _vs: &[V], _scalar: ScalarN) {}
+}
+
+/// Whether a rewrite may be applied at the given vertices
+///
+/// The hook must allow it, and the graph must not be zero already, as there is
+/// nothing left to simplify then. The rules don't know about grounds, so
+/// nothing is rewritten at or next to a [grounded](VData::ground) vertex,
+/// see [near_ground].
+fn allowed<G: GraphLike>(g: &G, hook: &impl SimpHook<G>, vs: &[V]) -> bool {
+    !g.is_zero() && !near_ground(g, vs) && hook.allows(g, vs)
+}
+
+/// Apply a rewrite and report it to the hook
+///
+/// Returns false, without applying the rewrite, if the hook says to stop.
+fn apply_hooked<G: GraphLike, H: SimpHook<G>>(
+    g: &mut G,
+    hook: &mut H,
+    rule: Rule,
+    vs: &[V],
+    f: impl FnOnce(&mut G),
+) -> bool {
+    if hook.interrupt() {
+        return false;
+    }
+    if H::TRACE {
+        hook.before(g, rule, vs);
+        let s = mem::replace(g.scalar_mut(), ScalarN::one());
+        f(g);
+        let factor = mem::replace(g.scalar_mut(), s);
+        *g.scalar_mut() *= &factor;
+        hook.rewrite(rule, vs, factor);
+        hook.after(g, rule, vs);
+    } else {
+        f(g);
+    }
+    true
+}
+
+/// Version of [vertex_simp] for the built-in passes, which reports to a hook
+fn vertex_simp_hooked<G: GraphLike>(
+    g: &mut G,
+    hook: &mut impl SimpHook<G>,
+    r: Rule,
+    check: fn(&G, V) -> bool,
+    rule: fn(&mut G, V) -> (),
+) -> bool {
+    let mut got_match = false;
+    let mut new_matches = true;
+    while new_matches {
+        new_matches = false;
+        for v in g.vertex_vec() {
+            if check(g, v)
+                && allowed(g, hook, &[v])
+                && apply_hooked(g, hook, r, &[v], |g| rule(g, v))
+            {
+                new_matches = true;
+                got_match = true;
+            }
+        }
+    }
+
     got_match
 }
 
+/// Version of [edge_simp_count] for the built-in passes, which reports to a hook
+///
+/// Rewrites are applied by `apply`, which should report them with
+/// [apply_hooked] and return whether they were applied. Use [hooked2] for
+/// rules which only need to report themselves.
+fn edge_simp_hooked<G: GraphLike, H: SimpHook<G>>(
+    g: &mut G,
+    hook: &mut H,
+    check: fn(&G, V, V) -> bool,
+    apply: impl Fn(&mut G, &mut H, V, V) -> bool,
+) -> usize {
+    let mut count = 0;
+    let mut new_matches = true;
+    while new_matches {
+        new_matches = false;
+        for (s, t, _) in g.edge_vec() {
+            if !g.contains_vertex(s)
+                || !g.contains_vertex(t)
+                || !check(g, s, t)
+                || !allowed(g, hook, &[s, t])
+            {
+                continue;
+            }
+            if !apply(g, hook, s, t) {
+                continue;
+            }
+            new_matches = true;
+            count += 1;
+        }
+    }
+
+    count
+}
+
+/// Apply a rule at two vertices and report it to the hook
+fn hooked2<G: GraphLike, H: SimpHook<G>>(
+    r: Rule,
+    rule: fn(&mut G, V, V) -> (),
+) -> impl Fn(&mut G, &mut H, V, V) -> bool {
+    move |g, hook, s, t| apply_hooked(g, hook, r, &[s, t], |g| rule(g, s, t))
+}
+
+/// Apply [gen_pivot_unchecked], also reporting phases moved onto new gadgets
+fn gen_pivot_hooked<G: GraphLike, H: SimpHook<G>>(g: &mut G, hook: &mut H, v0: V, v1: V) -> bool {
+    let mut leaves = [None, None];
+    let applied = apply_hooked(g, hook, Rule::GenPivot, &[v0, v1], |g| {
+        leaves = gen_pivot_unchecked_leaves(g, v0, v1)
+    });
+    if H::TRACE {
+        for (v, l) in [v0, v1].into_iter().zip(leaves) {
+            if let Some(l) = l {
+                hook.phase_moved(v, l);
+            }
+        }
+    }
+    applied
+}
+
 pub fn id_simp(g: &mut impl GraphLike) -> bool {
-    vertex_simp(g, check_remove_id, remove_id_unchecked, false)
+    id_simp_hooked(g, &mut ())
+}
+
+fn id_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    vertex_simp_hooked(
+        g,
+        hook,
+        Rule::RemoveId,
+        check_remove_id,
+        remove_id_unchecked,
+    )
 }
 
 pub fn local_comp_simp(g: &mut impl GraphLike) -> bool {
-    vertex_simp(g, check_local_comp, local_comp_unchecked, false)
+    local_comp_simp_hooked(g, &mut ())
+}
+
+fn local_comp_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    vertex_simp_hooked(
+        g,
+        hook,
+        Rule::LocalComp,
+        check_local_comp,
+        local_comp_unchecked,
+    )
 }
 
 pub fn spider_simp(g: &mut impl GraphLike) -> bool {
-    edge_simp(g, check_spider_fusion, spider_fusion_unchecked, false)
+    spider_simp_hooked(g, &mut ())
+}
+
+fn spider_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    edge_simp_hooked(
+        g,
+        hook,
+        check_spider_fusion,
+        hooked2(Rule::SpiderFusion, spider_fusion_unchecked),
+    ) > 0
 }
 
 pub fn pivot_simp(g: &mut impl GraphLike) -> bool {
-    edge_simp(g, check_pivot, pivot_unchecked, false)
+    pivot_simp_hooked(g, &mut ())
+}
+
+fn pivot_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    edge_simp_hooked(g, hook, check_pivot, hooked2(Rule::Pivot, pivot_unchecked)) > 0
 }
 
 pub fn gen_pivot_simp(g: &mut impl GraphLike) -> bool {
-    edge_simp(g, check_gen_pivot_reduce, gen_pivot_unchecked, false)
+    gen_pivot_simp_hooked(g, &mut ())
+}
+
+fn gen_pivot_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    edge_simp_hooked(g, hook, check_gen_pivot_reduce, gen_pivot_hooked) > 0
+}
+
+// edges are only visited in one direction, so check both orientations of
+// these asymmetric matchers. gen_pivot_unchecked doesn't care about the order.
+fn check_pivot_boundary_either(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_pivot_boundary(g, v0, v1) || check_pivot_boundary(g, v1, v0)
+}
+
+fn check_pivot_gadget_either(g: &impl GraphLike, v0: V, v1: V) -> bool {
+    check_pivot_gadget(g, v0, v1) || check_pivot_gadget(g, v1, v0)
+}
+
+/// Pivot interior Pauli spiders with neighbours on the boundary
+///
+/// See [check_pivot_boundary]. Returns the number of pivots applied.
+pub fn pivot_boundary_simp(g: &mut impl GraphLike) -> usize {
+    edge_simp_hooked(g, &mut (), check_pivot_boundary_either, gen_pivot_hooked)
+}
+
+/// Pivot interior Pauli spiders with interior non-Pauli neighbours
+///
+/// See [check_pivot_gadget]. Each pivot leaves a phase gadget behind.
+/// Returns the number of pivots applied.
+pub fn pivot_gadget_simp(g: &mut impl GraphLike) -> usize {
+    pivot_gadget_simp_hooked(g, &mut ())
+}
+
+fn pivot_gadget_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> usize {
+    edge_simp_hooked(g, hook, check_pivot_gadget_either, gen_pivot_hooked)
 }
 
 pub fn scalar_simp(g: &mut impl GraphLike) -> bool {
-    let mut m = vertex_simp(g, check_remove_single, remove_single_unchecked, false);
-    m = edge_simp(g, check_remove_pair, remove_pair_unchecked, false) || m;
+    scalar_simp_hooked(g, &mut ())
+}
+
+fn scalar_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    let mut m = vertex_simp_hooked(
+        g,
+        hook,
+        Rule::RemoveSingle,
+        check_remove_single,
+        remove_single_unchecked,
+    );
+    m = edge_simp_hooked(
+        g,
+        hook,
+        check_remove_pair,
+        hooked2(Rule::RemovePair, remove_pair_unchecked),
+    ) > 0
+        || m;
     m
 }
 
+/// Same as [GraphLike::x_to_z], but as a sequence of colour changes
+fn x_to_z_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) {
+    for v in g.vertex_vec() {
+        if g.vertex_type(v) == VType::X && allowed(g, hook, &[v]) {
+            apply_hooked(g, hook, Rule::ColorChange, &[v], |g| {
+                color_change_unchecked(g, v)
+            });
+        }
+    }
+}
+
+/// Replace a zero graph with the canonical zero diagram
+///
+/// Returns whether the graph changed. See [GraphLike::make_zero].
+fn canonical_zero(g: &mut impl GraphLike) -> bool {
+    if g.is_zero() && !g.is_zero_canonical() {
+        g.make_zero();
+        true
+    } else {
+        false
+    }
+}
+
 pub fn flow_simp(g: &mut impl GraphLike) -> bool {
     spider_simp(g);
     g.x_to_z();
     let mut got_match = false;
     let mut m = true;
-    while m {
+    while m && !g.is_zero() {
         m = id_simp(g);
         m = spider_simp(g) || m;
         m = scalar_simp(g) || m;
@@ -121,20 +431,25 @@ pub fn flow_simp(g: &mut impl GraphLike) -> bool {
         }
     }
 
-    got_match
+    canonical_zero(g) || got_match
 }
 
 pub fn interior_clifford_simp(g: &mut impl GraphLike) -> bool {
-    spider_simp(g);
-    g.x_to_z();
+    let got_match = interior_clifford_simp_hooked(g, &mut ());
+    canonical_zero(g) || got_match
+}
+
+fn interior_clifford_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    spider_simp_hooked(g, hook);
+    x_to_z_hooked(g, hook);
     let mut got_match = false;
     let mut m = true;
-    while m {
-        m = id_simp(g);
-        m = spider_simp(g) || m;
-        m = pivot_simp(g) || m;
-        m = local_comp_simp(g) || m;
-        m = scalar_simp(g) || m;
+    while m && !g.is_zero() && !hook.stop(g) {
+        m = id_simp_hooked(g, hook);
+        m = spider_simp_hooked(g, hook) || m;
+        m = pivot_simp_hooked(g, hook) || m;
+        m = local_comp_simp_hooked(g, hook) || m;
+        m = scalar_simp_hooked(g, hook) || m;
         if m {
             got_match = true;
         }
@@ -144,13 +459,18 @@ pub fn interior_clifford_simp(g: &mut impl GraphLike) -> bool {
 }
 
 pub fn clifford_simp(g: &mut impl GraphLike) -> bool {
+    let got_match = clifford_simp_hooked(g, &mut ());
+    canonical_zero(g) || got_match
+}
+
+fn clifford_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
     let mut got_match = false;
     let mut m = true;
-    while m {
+    while m && !g.is_zero() && !hook.stop(g) {
         // let numv = g.num_vertices();
         // println!("v: {}", numv);
-        m = interior_clifford_simp(g);
-        m = gen_pivot_simp(g) || m;
+        m = interior_clifford_simp_hooked(g, hook);
+        m = gen_pivot_simp_hooked(g, hook) || m;
         if m {
             got_match = true;
         }
@@ -161,59 +481,140 @@ pub fn clifford_simp(g: &mut impl GraphLike) -> bool {
 }
 
 pub fn fuse_gadgets(g: &mut impl GraphLike) -> bool {
-    let mut gadgets: FxHashMap<Vec<V>, Vec<(V, V)>> = FxHashMap::default();
+    fuse_gadgets_hooked(g, &mut ())
+}
+
+fn fuse_gadgets_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    let mut gadgets: FxHashMap<Vec<V>, Vec<V>> = FxHashMap::default();
+
+    for gadget in g.phase_gadgets() {
+        gadgets.entry(gadget.legs).or_default().push(gadget.hub);
+    }
+
+    // println!("{:?}", gadgets);
+
+    let mut fused = false;
+    for gs in gadgets.values() {
+        for &w in &gs[1..] {
+            if !allowed(g, hook, &[gs[0], w]) {
+                continue;
+            }
+            fused |= apply_hooked(g, hook, Rule::GadgetFusion, &[gs[0], w], |g| {
+                gadget_fusion_unchecked(g, gs[0], w)
+            });
+        }
+    }
+
+    fused
+}
+
+/// Move every interior non-Clifford phase onto a phase gadget
+///
+/// See [check_gadgetize]. Afterwards, every non-Clifford phase on a Z spider
+/// not connected to a boundary belongs to a [PhaseGadget], as returned by
+/// [GraphLike::phase_gadgets]. This is the usual starting point for fusing
+/// gadgets and other phase-polynomial optimisations.
+pub fn to_gadget_form(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    for v in g.vertex_vec() {
+        got_match = gadgetize(g, v) || got_match;
+    }
+    got_match
+}
+
+/// Remove pairs of non-Clifford spiders using the supplementarity rule
+///
+/// Candidate spiders are grouped by their neighbourhoods, so only spiders
+/// which could form a pair get compared.
+pub fn supplementarity_simp(g: &mut impl GraphLike) -> bool {
+    supplementarity_simp_hooked(g, &mut ())
+}
+
+fn supplementarity_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    let mut groups: FxHashMap<Vec<V>, Vec<V>> = FxHashMap::default();
 
     for v in g.vertices() {
-        if g.degree(v) == 1 && g.vertex_type(v) == VType::Z {
-            let w = g.neighbors(v).next().unwrap();
-            if g.vertex_type(w) != VType::Z || !g.phase(w).is_zero() {
+        if g.vertex_type(v) != VType::Z
+            || g.phase(v).is_clifford()
+            || !g
+                .incident_edges(v)
+                .all(|(w, et)| g.vertex_type(w) == VType::Z && et == EType::H)
+        {
+            continue;
+        }
+
+        // spiders that aren't connected share the same neighbourhood, and
+        // spiders that are connected share the same neighbourhood plus themselves
+        let mut nhd = g.neighbor_vec(v);
+        nhd.sort();
+        groups.entry(nhd.clone()).or_default().push(v);
+        nhd.push(v);
+        nhd.sort();
+        groups.entry(nhd).or_default().push(v);
+    }
+
+    let mut got_match = false;
+    for vs in groups.values() {
+        for (i, &v0) in vs.iter().enumerate() {
+            if !g.contains_vertex(v0) {
                 continue;
             }
-            let mut nhd = Vec::new();
-            for (n, et) in g.incident_edges(w) {
-                if g.vertex_type(n) != VType::Z || et != EType::H {
-                    continue;
-                }
-                if n != v {
-                    nhd.push(n);
+            for &v1 in &vs[i + 1..] {
+                if g.contains_vertex(v1)
+                    && check_supplementarity(g, v0, v1)
+                    && allowed(g, hook, &[v0, v1])
+                    && apply_hooked(g, hook, Rule::Supplementarity, &[v0, v1], |g| {
+                        supplementarity_unchecked(g, v0, v1)
+                    })
+                {
+                    got_match = true;
+                    break;
                 }
             }
-            nhd.sort();
-
-            if let Some(gs) = gadgets.get_mut(&nhd) {
-                gs.push((w, v));
-            } else {
-                gadgets.insert(nhd, vec![(w, v)]);
-            }
         }
     }
 
-    // println!("{:?}", gadgets);
+    got_match
+}
 
-    let mut fused = false;
-    for (vs, gs) in gadgets.iter() {
-        if gs.len() > 1 {
-            let num = gs.len() as i32;
-            let degree = vs.len() as i32;
-            fused = true;
-            let mut ph = Phase::zero();
-            for (u, v) in gs.iter().skip(1).copied() {
-                ph += g.phase(v);
-                g.remove_vertex(u);
-                g.remove_vertex(v);
+/// Copy Pauli states through the diagram as far as possible
+///
+/// Spiders of arity 1 with phase 0 or π are copied through their neighbours
+/// with [copy]. Copies which land on a spider of the same colour are then fused
+/// into it with [spider_simp], which can create new states to copy.
+pub fn state_copy_simp(g: &mut impl GraphLike) -> bool {
+    state_copy_simp_hooked(g, &mut ())
+}
+
+fn state_copy_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    let mut got_match = false;
+    loop {
+        let mut m = false;
+        for v in g.vertex_vec() {
+            if g.contains_vertex(v) && g.degree(v) == 1 {
+                let w = g.neighbors(v).next().unwrap();
+                if check_copy(g, v, w)
+                    && allowed(g, hook, &[v, w])
+                    && apply_hooked(g, hook, Rule::Copy, &[v, w], |g| copy_unchecked(g, v, w))
+                {
+                    m = true;
+                }
             }
+        }
 
-            g.add_to_phase(gs[0].1, ph);
-            g.scalar_mut().mul_sqrt2_pow(-(num - 1) * (degree - 1));
+        if !m {
+            break;
         }
+        got_match = true;
+        spider_simp_hooked(g, hook);
     }
 
-    fused
+    got_match
 }
 
 /// Perform a pi-copies to remove all pi phases from the
 /// centers of phase gadgets.
-fn remove_gadget_pi(g: &mut impl GraphLike) -> bool {
+fn remove_gadget_pi<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
     let gadgets = g
         .vertices()
         // Look for the outsides of phase gadgets
@@ -221,34 +622,57 @@ fn remove_gadget_pi(g: &mut impl GraphLike) -> bool {
         .map(|v| (g.neighbors(v).next().unwrap(), v))
         // Check that the middle is a pi-phase
         .filter(|&(n, v)| {
-            g.edge_type(v, n) == EType::H && g.vertex_type(n) == VType::Z && g.phase(n).is_one()
+            g.edge_type(v, n) == EType::H
+                && g.vertex_type(n) == VType::Z
+                && g.phase(n).is_one()
+                && allowed(g, hook, &[v])
         })
         // Collect them in a hash-map keyed by the central vertex
         // so that multiple phases hanging off a single gadget
         // are only mapped to one phase to flip
         .collect::<FxHashMap<_, _>>();
 
-    // If this isn't empty, we matched at least one
-    let matched = !gadgets.is_empty();
-
+    let mut matched = false;
     for &v in gadgets.values() {
         // Use a pi-copy to remove all the pi phases.
         // We can use unchecked because we verified that
         // this vertex has the phase-gadget structure:
         // Z-spider connected to a single Z-spider with a H edge
-        pi_copy_unchecked(g, v);
+        matched |= apply_hooked(g, hook, Rule::PiCopy, &[v], |g| pi_copy_unchecked(g, v));
     }
 
     matched
 }
 
+/// Simplify as far as possible, following the order of PyZX's `full_reduce`
+///
+/// After an initial [interior_clifford_simp] and [pivot_gadget_simp], this
+/// alternates [clifford_simp] and the gadget rules with another round of
+/// interior Clifford simplification and gadget pivots until nothing matches.
+/// If the diagram turns out to be zero (see [GraphLike::is_zero]), this stops
+/// straight away and replaces it with the canonical zero diagram (see
+/// [GraphLike::make_zero]).
+///
+/// Use [full_simp_traced] to also record each rewrite. This doesn't replace
+/// a zero diagram, so the trace can be replayed on a copy of the original.
+/// Use [full_simp_with] to stop early.
 pub fn full_simp(g: &mut impl GraphLike) -> bool {
-    let mut got_match = false;
+    let got_match = full_simp_hooked(g, &mut ());
+    canonical_zero(g) || got_match
+}
+
+fn full_simp_hooked<G: GraphLike>(g: &mut G, hook: &mut impl SimpHook<G>) -> bool {
+    let mut got_match = interior_clifford_simp_hooked(g, hook);
+    got_match = pivot_gadget_simp_hooked(g, hook) > 0 || got_match;
     let mut m = true;
-    while m {
-        m = clifford_simp(g);
-        m = fuse_gadgets(g) || m;
-        m = remove_gadget_pi(g) || m;
+    while m && !g.is_zero() && !hook.stop(g) {
+        m = clifford_simp_hooked(g, hook);
+        m = fuse_gadgets_hooked(g, hook) || m;
+        m = supplementarity_simp_hooked(g, hook) || m;
+        m = state_copy_simp_hooked(g, hook) || m;
+        m = remove_gadget_pi(g, hook) || m;
+        m = interior_clifford_simp_hooked(g, hook) || m;
+        m = pivot_gadget_simp_hooked(g, hook) > 0 || m;
         if m {
             got_match = true;
         }
@@ -257,12 +681,541 @@ pub fn full_simp(g: &mut impl GraphLike) -> bool {
     got_match
 }
 
+/// A single rewrite rule, which can be applied with [apply_rule]
+///
+/// See the corresponding functions in [crate::basic_rules] for details
+/// of each rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rule {
+    /// [spider_fusion], at two spiders
+    SpiderFusion,
+    /// [remove_id], at one spider
+    RemoveId,
+    /// [color_change], at one spider
+    ColorChange,
+    /// [local_comp], at one spider
+    LocalComp,
+    /// [gen_local_comp], at one spider
+    GenLocalComp,
+    /// [pivot], at two spiders
+    Pivot,
+    /// [gadget_fusion], at the hubs of two phase gadgets
+    GadgetFusion,
+    /// [pi_copy], at one spider
+    PiCopy,
+    /// [bialgebra], at two spiders
+    Bialgebra,
+    /// [copy], at a Pauli state and the spider it is connected to
+    Copy,
+    /// [gen_pivot], at two spiders
+    GenPivot,
+    /// [supplementarity], at two spiders
+    Supplementarity,
+    /// [remove_single], at an isolated spider
+    RemoveSingle,
+    /// [remove_pair], at two spiders connected only to each other
+    RemovePair,
+}
+
+impl Rule {
+    /// The number of vertices the rule is applied at
+    pub fn arity(&self) -> usize {
+        match self {
+            Rule::RemoveId
+            | Rule::ColorChange
+            | Rule::LocalComp
+            | Rule::GenLocalComp
+            | Rule::PiCopy
+            | Rule::RemoveSingle => 1,
+            Rule::SpiderFusion
+            | Rule::Pivot
+            | Rule::GadgetFusion
+            | Rule::Bialgebra
+            | Rule::Copy
+            | Rule::GenPivot
+            | Rule::Supplementarity
+            | Rule::RemovePair => 2,
+        }
+    }
+
+    /// A description of when the rule applies
+    pub fn precondition(&self) -> &'static str {
+        match self {
+            Rule::SpiderFusion => "spiders must have the same colour and be connected by a normal edge",
+            Rule::RemoveId => "spider must have phase 0 and exactly two neighbours",
+            Rule::ColorChange => "vertex must be a Z or X spider",
+            Rule::LocalComp => "spider must be Z with phase ±π/2, with only Hadamard edges to Z spiders",
+            Rule::GenLocalComp => "spider must be Z, with only Hadamard edges to Z spiders or edges to boundaries",
+            Rule::Pivot => "spiders must be Z with phase 0 or π, connected by a Hadamard edge, with only Hadamard edges to Z spiders",
+            Rule::GadgetFusion => "vertices must be the hubs of two phase gadgets on the same spiders",
+            Rule::PiCopy => "spider must only have normal edges to spiders of the other colour and Hadamard edges to spiders of the same colour",
+            Rule::Bialgebra => "spiders must be phase-free, of opposite colours, and connected by a normal edge",
+            Rule::Copy => "first spider must have arity 1 and phase 0 or π, and be connected to the second by a normal edge if they are opposite colours, or a Hadamard edge if they are the same colour",
+            Rule::GenPivot => "spiders must be Z and connected by a Hadamard edge, with only Hadamard edges to Z spiders or edges to boundaries",
+            Rule::Supplementarity => "spiders must be Z with the same neighbours, only Hadamard edges to Z spiders, and supplementary phases",
+            Rule::RemoveSingle => "vertex must be a Z or X spider with no neighbours",
+            Rule::RemovePair => "vertices must be Z or X spiders connected only to each other",
+        }
+    }
+}
+
+/// An error from trying to apply a [Rule]
+#[derive(Debug, Display, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RuleError {
+    /// The rule was given the wrong number of vertices.
+    #[display("{rule:?} expects {expected} vertices, got {got}")]
+    WrongArity {
+        rule: Rule,
+        expected: usize,
+        got: usize,
+    },
+    /// One of the given vertices is not in the graph.
+    #[display("Vertex {v} is not in the graph")]
+    NoSuchVertex { v: V },
+    /// The rule does not apply at the given vertices.
+    #[display("{rule:?} does not apply at {vs:?}: {}", rule.precondition())]
+    NotApplicable { rule: Rule, vs: Vec<V> },
+    /// One of the given vertices, or one of their neighbours, is grounded,
+    /// see [near_ground].
+    #[display("{rule:?} does not apply next to grounded vertex {v}")]
+    Grounded { rule: Rule, v: V },
+}
+
+/// Apply a single rule at the given vertices
+///
+/// The preconditions of the rule are checked first, so on an error the graph
+/// is left unchanged.
+///
+/// ```
+/// # use quizx::graph::*;
+/// # use quizx::vec_graph::Graph;
+/// # use quizx::simplify::{apply_rule, Rule, RuleError};
+/// let mut g = Graph::new();
+/// let v0 = g.add_vertex(VType::Z);
+/// let v1 = g.add_vertex(VType::Z);
+/// let v2 = g.add_vertex(VType::X);
+/// g.add_edge(v0, v1);
+/// g.add_edge(v1, v2);
+///
+/// assert!(apply_rule(&mut g, Rule::SpiderFusion, &[v0, v1]).is_ok());
+/// assert_eq!(
+///     apply_rule(&mut g, Rule::SpiderFusion, &[v0, v2]),
+///     Err(RuleError::NotApplicable { rule: Rule::SpiderFusion, vs: vec![v0, v2] })
+/// );
+/// ```
+pub fn apply_rule(g: &mut impl GraphLike, rule: Rule, vs: &[V]) -> Result<(), RuleError> {
+    if vs.len() != rule.arity() {
+        return Err(RuleError::WrongArity {
+            rule,
+            expected: rule.arity(),
+            got: vs.len(),
+        });
+    }
+
+    if let Some(&v) = vs.iter().find(|&&v| !g.contains_vertex(v)) {
+        return Err(RuleError::NoSuchVertex { v });
+    }
+
+    let mut nhd = vs
+        .iter()
+        .flat_map(|&v| std::iter::once(v).chain(g.neighbors(v)));
+    if let Some(v) = nhd.find(|&w| g.is_ground(w)) {
+        return Err(RuleError::Grounded { rule, v });
+    }
+
+    let applied = match rule {
+        Rule::SpiderFusion => spider_fusion(g, vs[0], vs[1]),
+        Rule::RemoveId => remove_id(g, vs[0]),
+        Rule::ColorChange => color_change(g, vs[0]),
+        Rule::LocalComp => local_comp(g, vs[0]),
+        Rule::GenLocalComp => gen_local_comp(g, vs[0]),
+        Rule::Pivot => pivot(g, vs[0], vs[1]),
+        Rule::GadgetFusion => gadget_fusion(g, vs[0], vs[1]),
+        Rule::PiCopy => pi_copy(g, vs[0]),
+        Rule::Bialgebra => bialgebra(g, vs[0], vs[1]),
+        Rule::Copy => copy(g, vs[0], vs[1]),
+        Rule::GenPivot => gen_pivot(g, vs[0], vs[1]),
+        Rule::Supplementarity => supplementarity(g, vs[0], vs[1]),
+        Rule::RemoveSingle => remove_single(g, vs[0]),
+        Rule::RemovePair => remove_pair(g, vs[0], vs[1]),
+    };
+
+    if applied {
+        Ok(())
+    } else {
+        Err(RuleError::NotApplicable {
+            rule,
+            vs: vs.to_vec(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::circuit::*;
-    use crate::tensor::ToTensor;
+    use crate::tensor::{CompareTensors, Tensor4, ToTensor};
     use crate::vec_graph::Graph;
+    use num::Zero;
+
+    /// Add a boundary connected to v, as a new output
+    fn add_output(g: &mut Graph, v: V, et: EType) {
+        let b = g.add_vertex(VType::B);
+        g.add_edge_with_type(v, b, et);
+        let mut outs = g.outputs().clone();
+        outs.push(b);
+        g.set_outputs(outs);
+    }
+
+    fn check_rule(g: &mut Graph, rule: Rule, vs: &[V]) {
+        let h = g.clone();
+        assert_eq!(apply_rule(g, rule, vs), Ok(()));
+        assert_eq!(
+            g.to_tensor4(),
+            h.to_tensor4(),
+            "{:?} changed the tensor",
+            rule
+        );
+    }
+
+    #[test]
+    fn apply_rules() {
+        let mut g = Graph::new();
+        let z0 = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let z1 = g.add_vertex_with_phase(VType::Z, (1, 2));
+        g.add_edge(z0, z1);
+        add_output(&mut g, z0, EType::N);
+        add_output(&mut g, z0, EType::H);
+        add_output(&mut g, z1, EType::N);
+        check_rule(&mut g, Rule::SpiderFusion, &[z0, z1]);
+
+        let mut g = Graph::new();
+        let z = g.add_vertex(VType::Z);
+        add_output(&mut g, z, EType::N);
+        add_output(&mut g, z, EType::H);
+        check_rule(&mut g, Rule::RemoveId, &[z]);
+
+        let mut g = Graph::new();
+        let x = g.add_vertex_with_phase(VType::X, (1, 4));
+        add_output(&mut g, x, EType::N);
+        add_output(&mut g, x, EType::H);
+        add_output(&mut g, x, EType::N);
+        check_rule(&mut g, Rule::ColorChange, &[x]);
+
+        let mut g = Graph::new();
+        let z = g.add_vertex_with_phase(VType::Z, (-1, 2));
+        for _ in 0..3 {
+            let n = g.add_vertex_with_phase(VType::Z, (1, 4));
+            g.add_edge_with_type(z, n, EType::H);
+            add_output(&mut g, n, EType::N);
+        }
+        check_rule(&mut g, Rule::LocalComp, &[z]);
+
+        let mut g = Graph::new();
+        let z0 = g.add_vertex(VType::Z);
+        let z1 = g.add_vertex_with_phase(VType::Z, 1);
+        g.add_edge_with_type(z0, z1, EType::H);
+        let ns: Vec<_> = (0..3).map(|_| g.add_vertex(VType::Z)).collect();
+        g.add_edge_with_type(z0, ns[0], EType::H);
+        g.add_edge_with_type(z0, ns[1], EType::H);
+        g.add_edge_with_type(z1, ns[1], EType::H);
+        g.add_edge_with_type(z1, ns[2], EType::H);
+        for &n in &ns {
+            add_output(&mut g, n, EType::N);
+        }
+        check_rule(&mut g, Rule::Pivot, &[z0, z1]);
+
+        let mut g = Graph::new();
+        let ns: Vec<_> = (0..2).map(|_| g.add_vertex(VType::Z)).collect();
+        let mut hubs = vec![];
+        for p in [(1, 4), (3, 4)] {
+            let hub = g.add_vertex(VType::Z);
+            let leaf = g.add_vertex_with_phase(VType::Z, p);
+            g.add_edge_with_type(hub, leaf, EType::H);
+            for &n in &ns {
+                g.add_edge_with_type(hub, n, EType::H);
+            }
+            hubs.push(hub);
+        }
+        for &n in &ns {
+            add_output(&mut g, n, EType::N);
+        }
+        check_rule(&mut g, Rule::GadgetFusion, &hubs);
+
+        let mut g = Graph::new();
+        let z = g.add_vertex_with_phase(VType::Z, (1, 4));
+        for _ in 0..2 {
+            let x = g.add_vertex(VType::X);
+            g.add_edge(z, x);
+            add_output(&mut g, x, EType::N);
+        }
+        check_rule(&mut g, Rule::PiCopy, &[z]);
+
+        let mut g = Graph::new();
+        let z = g.add_vertex(VType::Z);
+        let x = g.add_vertex(VType::X);
+        g.add_edge(z, x);
+        for _ in 0..2 {
+            add_output(&mut g, z, EType::N);
+            add_output(&mut g, x, EType::N);
+        }
+        check_rule(&mut g, Rule::Bialgebra, &[z, x]);
+
+        let mut g = Graph::new();
+        let z = g.add_vertex_with_phase(VType::Z, 1);
+        let x = g.add_vertex_with_phase(VType::X, (1, 4));
+        g.add_edge(z, x);
+        for _ in 0..2 {
+            add_output(&mut g, x, EType::N);
+        }
+        check_rule(&mut g, Rule::Copy, &[z, x]);
+    }
+
+    #[test]
+    fn apply_rule_errors() {
+        let mut g = Graph::new();
+        let z = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let x = g.add_vertex(VType::X);
+        g.add_edge(z, x);
+        add_output(&mut g, x, EType::N);
+        let h = g.clone();
+
+        assert_eq!(
+            apply_rule(&mut g, Rule::SpiderFusion, &[z]),
+            Err(RuleError::WrongArity {
+                rule: Rule::SpiderFusion,
+                expected: 2,
+                got: 1
+            })
+        );
+        assert_eq!(
+            apply_rule(&mut g, Rule::RemoveId, &[42]),
+            Err(RuleError::NoSuchVertex { v: 42 })
+        );
+
+        let err = apply_rule(&mut g, Rule::Bialgebra, &[z, x]).unwrap_err();
+        assert_eq!(
+            err,
+            RuleError::NotApplicable {
+                rule: Rule::Bialgebra,
+                vs: vec![z, x]
+            }
+        );
+        assert!(err.to_string().contains("phase-free"));
+
+        for rule in [Rule::SpiderFusion, Rule::Pivot, Rule::GadgetFusion] {
+            assert!(apply_rule(&mut g, rule, &[z, x]).is_err());
+        }
+        for rule in [Rule::RemoveId, Rule::LocalComp] {
+            assert!(apply_rule(&mut g, rule, &[z]).is_err());
+        }
+        assert_eq!(g, h);
+    }
+
+    #[test]
+    fn gadget_form() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(60)
+            .clifford_t(0.3)
+            .build();
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+        let h = g.clone();
+        let gadgets = h.phase_gadgets().count();
+
+        assert!(to_gadget_form(&mut g));
+        assert!(!to_gadget_form(&mut g));
+        assert!(g.phase_gadgets().count() > gadgets);
+        assert_eq!(g.tcount(), h.tcount());
+        for v in g.vertices() {
+            let interior = g.neighbors(v).all(|n| g.vertex_type(n) != VType::B);
+            if interior && !g.phase(v).is_clifford() {
+                assert!(g.phase_gadget(v).is_some());
+            }
+        }
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn supplementarity_in_full_simp() {
+        // two spiders on the same three qubits, with phases α and α+π
+        let mut c = Circuit::new(3);
+        for q in 0..3 {
+            c.h(q);
+        }
+        let mut g: Graph = c.to_graph();
+        let outs = g.outputs().clone();
+        let ws: Vec<_> = outs
+            .iter()
+            .map(|&o| g.neighbors(o).next().unwrap())
+            .collect();
+        for p in [(1, 4), (-3, 4)] {
+            let v = g.add_vertex_with_phase(VType::Z, p);
+            for &w in &ws {
+                g.add_edge_with_type(v, w, EType::H);
+            }
+        }
+        let h = g.clone();
+        assert_eq!(g.tcount(), 2);
+
+        full_simp(&mut g);
+        assert_eq!(g.tcount(), 0);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    /// full_simp without the supplementarity pass
+    fn full_simp_no_supplementarity(g: &mut Graph) {
+        let hook = &mut ();
+        interior_clifford_simp_hooked(g, hook);
+        pivot_gadget_simp_hooked(g, hook);
+        let mut m = true;
+        while m && !g.is_zero() {
+            m = clifford_simp_hooked(g, hook);
+            m = fuse_gadgets_hooked(g, hook) || m;
+            m = state_copy_simp_hooked(g, hook) || m;
+            m = remove_gadget_pi(g, hook) || m;
+            m = interior_clifford_simp_hooked(g, hook) || m;
+            m = pivot_gadget_simp_hooked(g, hook) > 0 || m;
+        }
+    }
+
+    #[test]
+    fn supplementarity_qaoa() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        // the <0...0|C|0...0> amplitude of a 1-layer QAOA circuit for a
+        // random graph, with some single-qubit terms
+        let n = 8;
+        let gamma = (3, 4);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut c = Circuit::new(n);
+        for q in 0..n {
+            c.h(q);
+        }
+        for i in 0..n {
+            for j in i + 1..n {
+                if rng.gen_bool(0.4) {
+                    c.cx(i, j).rz(j, gamma).cx(i, j);
+                }
+            }
+        }
+        for q in 0..n {
+            if rng.gen_bool(0.5) {
+                c.rz(q, gamma);
+            }
+        }
+        for q in 0..n {
+            c.rx(q, (1, 2));
+        }
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; n]);
+        g.plug_outputs(&vec![BasisElem::Z0; n]);
+        let h = g.clone();
+
+        let mut g1 = g.clone();
+        full_simp_no_supplementarity(&mut g1);
+        full_simp(&mut g);
+        assert!(
+            g.tcount() < g1.tcount(),
+            "{} >= {}",
+            g.tcount(),
+            g1.tcount()
+        );
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert_eq!(g1.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn state_copy_amplitudes() {
+        use crate::circuit::Circuit;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(1337);
+        let basis = [BasisElem::Z0, BasisElem::Z1, BasisElem::X0, BasisElem::X1];
+        for seed in 0..10 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(5)
+                .depth(30)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+            let mut g: Graph = c.to_graph();
+            let ins: Vec<_> = (0..5).map(|_| basis[rng.gen_range(0..4)]).collect();
+            let outs: Vec<_> = (0..3).map(|_| basis[rng.gen_range(0..4)]).collect();
+            g.plug_inputs(&ins);
+            g.plug_outputs(&outs);
+
+            let h = g.clone();
+            assert!(state_copy_simp(&mut g));
+            assert!(g.num_vertices() < h.num_vertices());
+            assert_eq!(g.to_tensor4(), h.to_tensor4(), "failed for seed {}", seed);
+
+            // the same, after the graph is put in graph-like form
+            let mut g = h.clone();
+            clifford_simp(&mut g);
+            let h = g.clone();
+            state_copy_simp(&mut g);
+            assert_eq!(g.to_tensor4(), h.to_tensor4(), "failed for seed {}", seed);
+        }
+    }
+
+    /// Two spiders on the boundary, each with two outputs
+    fn boundary_spiders(g: &mut Graph) -> (V, V) {
+        let a = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let c = g.add_vertex_with_phase(VType::Z, (1, 4));
+        for v in [a, a, c, c] {
+            add_output(g, v, EType::N);
+        }
+        (a, c)
+    }
+
+    #[test]
+    fn pivot_gadget_only() {
+        let mut g = Graph::new();
+        let (a, c) = boundary_spiders(&mut g);
+        let v0 = g.add_vertex(VType::Z);
+        let v1 = g.add_vertex_with_phase(VType::Z, (1, 4));
+        let d = g.add_vertex_with_phase(VType::Z, (-1, 4));
+        g.add_edge_with_type(v0, v1, EType::H);
+        g.add_edge_with_type(v0, d, EType::H);
+        for (s, t) in [(v1, a), (v1, c), (d, a), (d, c)] {
+            g.add_edge_with_type(s, t, EType::H);
+        }
+
+        let h = g.clone();
+        assert_eq!(pivot_boundary_simp(&mut g), 0);
+        assert!(!pivot_simp(&mut g));
+        assert_eq!(g, h);
+
+        assert_eq!(pivot_gadget_simp(&mut g), 1);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert_eq!(pivot_gadget_simp(&mut g), 0);
+    }
+
+    #[test]
+    fn pivot_boundary_only() {
+        let mut g = Graph::new();
+        let (a, c) = boundary_spiders(&mut g);
+        let v0 = g.add_vertex_with_phase(VType::Z, 1);
+        g.add_edge_with_type(v0, a, EType::H);
+        g.add_edge_with_type(v0, c, EType::H);
+        g.add_edge_with_type(a, c, EType::H);
+
+        let h = g.clone();
+        assert_eq!(pivot_gadget_simp(&mut g), 0);
+        assert!(!pivot_simp(&mut g));
+        assert_eq!(g, h);
+
+        assert_eq!(pivot_boundary_simp(&mut g), 1);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+        assert_eq!(pivot_boundary_simp(&mut g), 0);
+    }
 
     #[test]
     fn simp_cnot() {
@@ -343,6 +1296,116 @@ mod tests {
         assert_eq!(g.to_tensor4(), h.to_tensor4());
     }
 
+    #[test]
+    fn grounds_left_alone() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        let v = g
+            .vertices()
+            .find(|&v| g.vertex_type(v) == VType::Z && g.degree(v) == 3)
+            .unwrap();
+        g.set_ground(v, true);
+        let mut nhd = g.neighbor_vec(v);
+        nhd.sort();
+        let nverts = g.num_vertices();
+
+        full_simp(&mut g);
+        assert!(g.num_vertices() < nverts);
+        assert!(g.is_ground(v));
+        let mut nhd1 = g.neighbor_vec(v);
+        nhd1.sort();
+        assert_eq!(nhd, nhd1);
+        assert!(matches!(
+            local_comp_at(&mut g, v),
+            Err(RuleError::Grounded { v: w, .. }) if w == v
+        ));
+    }
+
+    /// Ground a spider which `pass` rewrites on g, and check the pass then
+    /// leaves it and its neighbourhood alone
+    fn check_ground_survives(g: &Graph, pass: impl Fn(&mut Graph)) {
+        let sorted_nhd = |g: &Graph, v: V| {
+            let mut nhd = g.neighbor_vec(v);
+            nhd.sort();
+            nhd
+        };
+        let mut h = g.clone();
+        pass(&mut h);
+        let v = g
+            .vertices()
+            .find(|&v| {
+                g.vertex_type(v) == VType::Z
+                    && (!h.contains_vertex(v) || sorted_nhd(g, v) != sorted_nhd(&h, v))
+            })
+            .expect("the pass doesn't rewrite anything");
+
+        let mut g = g.clone();
+        g.set_ground(v, true);
+        let nhd = sorted_nhd(&g, v);
+        let phase = g.phase(v);
+        pass(&mut g);
+        assert!(g.contains_vertex(v) && g.is_ground(v));
+        assert_eq!(sorted_nhd(&g, v), nhd);
+        assert_eq!(g.phase(v), phase);
+    }
+
+    #[test]
+    fn grounds_left_alone_by_other_passes() {
+        use crate::annealer::{Annealer, RandomGenPivot, RandomLocalComp, RandomPivot};
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        check_ground_survives(&g, |g| {
+            greedy_simp(g, &mut EdgeDegree::new());
+        });
+
+        // an output spider with phase π/2, whose neighbours are all connected
+        let mut h = Graph::new();
+        let vs: Vec<V> = (0..4)
+            .map(|i| {
+                let phase = if i == 0 { (1, 2) } else { (1, 4) };
+                let v = h.add_vertex_with_phase(VType::Z, phase);
+                let b = h.add_vertex(VType::B);
+                h.add_edge(v, b);
+                h.outputs_mut().push(b);
+                v
+            })
+            .collect();
+        for i in 0..4 {
+            for j in (i + 1)..4 {
+                h.add_edge_with_type(vs[i], vs[j], EType::H);
+            }
+        }
+        check_ground_survives(&h, |h| {
+            reduce_hadamards(h, 10);
+        });
+
+        flow_simp(&mut g);
+        check_ground_survives(&g, |g| {
+            let mut a = Annealer::new(g.clone());
+            a.seed(1)
+                .iters(100)
+                .scoref(|g| g.num_vertices())
+                .clear_moves()
+                .add_move(RandomLocalComp)
+                .add_move(RandomPivot)
+                .add_move(RandomGenPivot);
+            a.anneal();
+            *g = a.g;
+        });
+    }
+
     #[test]
     fn full_scalar() {
         let c = Circuit::random()
@@ -360,6 +1423,55 @@ mod tests {
         assert_eq!(g.to_tensor4(), h.to_tensor4());
     }
 
+    #[test]
+    fn full_untracked_scalar() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(50)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let g: Graph = c.to_graph();
+        let mut h0 = g.clone();
+        let mut h1 = g.clone();
+        h1.set_track_scalar(false);
+        full_simp(&mut h0);
+        full_simp(&mut h1);
+        assert_ne!(h0.scalar(), g.scalar());
+        assert_eq!(h1.scalar(), g.scalar());
+        assert_eq!(h0.num_vertices(), h1.num_vertices());
+        assert_eq!(h0.num_edges(), h1.num_edges());
+        assert!(Tensor4::scalar_compare(&g, &h1));
+    }
+
+    #[test]
+    fn full_zero() {
+        // |0> on qubit 0 becomes |1> and controls the rest, then meets <0|
+        let mut c = Circuit::new(3);
+        c.x(0).cx(0, 1).t(1).ccz(0, 1, 2).t(2).cx(2, 0).h(2).t(2);
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&[BasisElem::Z0; 3]);
+        g.plug_outputs(&[BasisElem::Z1, BasisElem::Z0, BasisElem::X0]);
+        assert!(!g.is_zero());
+
+        let trace = full_simp_traced(&mut g);
+        assert!(g.is_zero());
+        // the rewrite which found the zero was the last one
+        let last = trace.steps.last().unwrap();
+        assert!(last.scalar.is_zero(), "{:?}", trace.steps);
+
+        // the plain version also replaces it with the canonical zero diagram
+        assert!(full_simp(&mut g));
+        assert!(g.is_zero_canonical());
+        assert_eq!(g.num_vertices(), 0);
+
+        // nothing more happens to a zero graph
+        let h = g.clone();
+        assert!(!full_simp(&mut g));
+        assert_eq!(g, h);
+    }
+
     #[test]
     fn full1() {
         let c = Circuit::random()
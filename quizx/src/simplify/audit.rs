@@ -0,0 +1,119 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checking that [full_simp](super::full_simp) preserves the tensor of a graph.
+//!
+//! The auditor compares the exact tensor of the graph, including its scalar,
+//! before and after every rewrite, and panics at the first one which changes
+//! it. This is exponential in the number of boundaries, so graphs with more
+//! than a given number of qubits are simplified without checks.
+
+use super::{full_simp_hooked, Rule, SimpHook};
+use crate::graph::{GraphLike, V};
+use crate::scalar::ScalarN;
+use crate::tensor::{Tensor4, ToTensor};
+
+/// The default qubit threshold of [full_simp_audited]
+pub const DEFAULT_MAX_QUBITS: usize = 6;
+
+/// A hook which checks the tensor of the graph after every rewrite
+struct Auditor {
+    max_qubits: usize,
+    /// The tensor of the graph after the last rewrite
+    tensor: Option<Tensor4>,
+}
+
+impl Auditor {
+    fn new(max_qubits: usize) -> Self {
+        Auditor {
+            max_qubits,
+            tensor: None,
+        }
+    }
+
+    fn is_small(&self, g: &impl GraphLike) -> bool {
+        g.inputs().len().max(g.outputs().len()) <= self.max_qubits
+    }
+}
+
+impl<G: GraphLike> SimpHook<G> for Auditor {
+    const TRACE: bool = true;
+
+    fn before(&mut self, g: &G, _rule: Rule, _vs: &[V]) {
+        if self.tensor.is_none() && self.is_small(g) {
+            self.tensor = Some(g.to_tensor4());
+        }
+    }
+
+    fn rewrite(&mut self, _rule: Rule, _vs: &[V], _scalar: ScalarN) {}
+
+    fn after(&mut self, g: &G, rule: Rule, vs: &[V]) {
+        let Some(t0) = &self.tensor else { return };
+        let t1 = g.to_tensor4();
+        if t1 != *t0 {
+            panic!("{:?} at {:?} changed the tensor of the graph", rule, vs);
+        }
+    }
+}
+
+/// Same as [full_simp](super::full_simp), but check every rewrite against the tensor
+///
+/// Only graphs with at most [DEFAULT_MAX_QUBITS] inputs and outputs are
+/// checked.
+///
+/// # Panics
+///
+/// Panics with the rule and vertices of the first rewrite that changes the
+/// tensor, including the scalar.
+pub fn full_simp_audited(g: &mut impl GraphLike) -> bool {
+    full_simp_audited_with(g, DEFAULT_MAX_QUBITS)
+}
+
+/// Same as [full_simp_audited], with a custom qubit threshold
+pub fn full_simp_audited_with(g: &mut impl GraphLike, max_qubits: usize) -> bool {
+    full_simp_hooked(g, &mut Auditor::new(max_qubits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::simplify::test_util::random_graph;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn audit_random() {
+        for seed in 0..5 {
+            let mut g = random_graph(seed, 3, 20, 0.2);
+            full_simp_audited(&mut g);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "SpiderFusion at [0, 1] changed the tensor")]
+    fn audit_bad_scalar() {
+        let mut g: Graph = Circuit::random()
+            .seed(1)
+            .qubits(2)
+            .depth(5)
+            .build()
+            .to_graph();
+        let mut audit = Auditor::new(2);
+        audit.before(&g, Rule::SpiderFusion, &[0, 1]);
+        g.scalar_mut().mul_sqrt2_pow(1);
+        audit.after(&g, Rule::SpiderFusion, &[0, 1]);
+    }
+}
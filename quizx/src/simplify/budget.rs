@@ -0,0 +1,190 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplification that can be cut short, see [full_simp_with].
+
+use super::{canonical_zero, clifford_simp_hooked, full_simp_hooked, Rule, SimpHook};
+use crate::graph::{GraphLike, V};
+use crate::scalar::ScalarN;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Limits on how long [full_simp_with] and [clifford_simp_with] run for
+///
+/// The limits are checked before each rewrite, so an interrupted graph is
+/// always one that a whole number of rewrites has been applied to.
+#[derive(Debug, Clone, Default)]
+pub struct SimpOptions {
+    /// Stop after this many rewrites.
+    pub max_iterations: Option<usize>,
+    /// Stop once this much time has passed since the start.
+    pub deadline: Option<Duration>,
+    /// Stop once this is set, e.g. from another thread.
+    pub stop_flag: Option<Arc<AtomicBool>>,
+}
+
+/// How a call to [full_simp_with] or [clifford_simp_with] finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimpStatus {
+    /// Nothing is left to simplify.
+    Converged,
+    /// [SimpOptions::max_iterations] rewrites were applied.
+    MaxIterations,
+    /// The [SimpOptions::deadline] passed.
+    Deadline,
+    /// The [SimpOptions::stop_flag] was set.
+    Stopped,
+}
+
+impl SimpStatus {
+    /// Whether the simplification ran to the end rather than being interrupted
+    pub fn is_converged(self) -> bool {
+        self == SimpStatus::Converged
+    }
+}
+
+/// Stops the passes once one of the limits in [SimpOptions] is reached
+struct Budget<'a> {
+    opts: &'a SimpOptions,
+    start: Instant,
+    rewrites: usize,
+    status: SimpStatus,
+}
+
+impl<'a> Budget<'a> {
+    fn new(opts: &'a SimpOptions) -> Self {
+        Budget {
+            opts,
+            start: Instant::now(),
+            rewrites: 0,
+            status: SimpStatus::Converged,
+        }
+    }
+}
+
+impl<G: GraphLike> SimpHook<G> for Budget<'_> {
+    const TRACE: bool = false;
+
+    fn rewrite(&mut self, _rule: Rule, _vs: &[V], _scalar: ScalarN) {}
+
+    fn interrupt(&mut self) -> bool {
+        if self.status == SimpStatus::Converged {
+            if self.opts.max_iterations.is_some_and(|n| self.rewrites >= n) {
+                self.status = SimpStatus::MaxIterations;
+            } else if self
+                .opts
+                .deadline
+                .is_some_and(|d| self.start.elapsed() >= d)
+            {
+                self.status = SimpStatus::Deadline;
+            } else if (self.opts.stop_flag.as_ref()).is_some_and(|f| f.load(Ordering::Relaxed)) {
+                self.status = SimpStatus::Stopped;
+            } else {
+                self.rewrites += 1;
+            }
+        }
+        self.status != SimpStatus::Converged
+    }
+}
+
+/// Same as [full_simp](super::full_simp), but stop early once one of the
+/// limits in `opts` is reached
+///
+/// Stopping only ever happens between rewrites, so whatever the returned
+/// status, the graph has the same tensor as the original one, up to the
+/// tolerance of [full_simp](super::full_simp) itself. As for
+/// [full_simp](super::full_simp), a zero diagram is replaced with the
+/// canonical one.
+pub fn full_simp_with(g: &mut impl GraphLike, opts: &SimpOptions) -> SimpStatus {
+    let mut budget = Budget::new(opts);
+    full_simp_hooked(g, &mut budget);
+    canonical_zero(g);
+    budget.status
+}
+
+/// Same as [clifford_simp](super::clifford_simp), but stop early once one of
+/// the limits in `opts` is reached, see [full_simp_with]
+pub fn clifford_simp_with(g: &mut impl GraphLike, opts: &SimpOptions) -> SimpStatus {
+    let mut budget = Budget::new(opts);
+    clifford_simp_hooked(g, &mut budget);
+    canonical_zero(g);
+    budget.status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simplify::full_simp;
+    use crate::simplify::test_util::random_graph;
+    use crate::tensor::ToTensor;
+
+    #[test]
+    fn unlimited_converges() {
+        let g = random_graph(1, 3, 20, 0.2);
+        let mut h0 = g.clone();
+        let mut h1 = g.clone();
+        full_simp(&mut h0);
+        assert_eq!(
+            full_simp_with(&mut h1, &SimpOptions::default()),
+            SimpStatus::Converged
+        );
+        assert_eq!(h0, h1);
+    }
+
+    #[test]
+    fn max_iterations() {
+        let g = random_graph(2, 3, 20, 0.2);
+        for n in [0, 1, 5, 20] {
+            let mut h = g.clone();
+            let opts = SimpOptions {
+                max_iterations: Some(n),
+                ..Default::default()
+            };
+            assert_eq!(full_simp_with(&mut h, &opts), SimpStatus::MaxIterations);
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+            // carrying on from there gets the rest of the way
+            assert!(full_simp_with(&mut h, &SimpOptions::default()).is_converged());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+        }
+    }
+
+    #[test]
+    fn stop_flag_and_deadline() {
+        let g = random_graph(3, 3, 20, 0.2);
+        let flag = Arc::new(AtomicBool::new(true));
+        let mut h = g.clone();
+        let opts = SimpOptions {
+            stop_flag: Some(flag.clone()),
+            ..Default::default()
+        };
+        assert_eq!(clifford_simp_with(&mut h, &opts), SimpStatus::Stopped);
+        assert_eq!(g, h);
+
+        flag.store(false, Ordering::Relaxed);
+        assert_eq!(clifford_simp_with(&mut h, &opts), SimpStatus::Converged);
+
+        let mut h = g.clone();
+        let opts = SimpOptions {
+            deadline: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        assert_eq!(full_simp_with(&mut h, &opts), SimpStatus::Deadline);
+        assert_eq!(g, h);
+    }
+}
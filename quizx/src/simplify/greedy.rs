@@ -0,0 +1,297 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplification which only applies rewrites that a heuristic likes.
+
+use super::{id_simp, spider_simp, Rule};
+use crate::basic_rules::*;
+use crate::graph::{GraphLike, VType, V};
+
+use rustc_hash::FxHashMap;
+
+/// Scores the rewrites [greedy_simp] could apply
+///
+/// Closures taking the graph, the rule and the vertices it would be applied
+/// at also implement this.
+pub trait GreedyHeuristic<G: GraphLike> {
+    /// Called once for each round of [greedy_simp], before any scoring
+    ///
+    /// This can be used to cache things about g which don't depend on the
+    /// rewrite being scored.
+    fn prepare(&mut self, _g: &G) {}
+
+    /// The change in cost from applying `rule` at `vs`
+    ///
+    /// Lower is better, and only rewrites with a negative score are applied.
+    fn score(&mut self, g: &G, rule: Rule, vs: &[V]) -> isize;
+}
+
+impl<G: GraphLike, F: FnMut(&G, Rule, &[V]) -> isize> GreedyHeuristic<G> for F {
+    fn score(&mut self, g: &G, rule: Rule, vs: &[V]) -> isize {
+        self(g, rule, vs)
+    }
+}
+
+/// The default heuristic for [greedy_simp]
+///
+/// Scores a rewrite by the change in the number of edges, plus the change in
+/// the maximum degree of a vertex. Both of these tend to go along with the
+/// number of 2-qubit gates in the extracted circuit.
+#[derive(Debug, Clone, Default)]
+pub struct EdgeDegree {
+    /// All the vertices, by decreasing degree
+    by_degree: Vec<(usize, V)>,
+}
+
+impl EdgeDegree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change in the number of edges and maximum degree from removing some
+    /// vertices and toggling the edges between some pairs of vertices
+    fn change(&self, g: &impl GraphLike, removed: &[V], pairs: &[(V, V)]) -> isize {
+        let mut edges = 0;
+        let mut deg: FxHashMap<V, isize> = FxHashMap::default();
+        for &v in removed {
+            for w in g.neighbors(v) {
+                if !removed.contains(&w) {
+                    *deg.entry(w).or_default() -= 1;
+                    edges -= 2;
+                } else {
+                    edges -= 1;
+                }
+            }
+        }
+        // edges between removed vertices were counted from both ends
+        edges /= 2;
+
+        for &(s, t) in pairs {
+            let d = if g.connected(s, t) { -1 } else { 1 };
+            edges += d;
+            *deg.entry(s).or_default() += d;
+            *deg.entry(t).or_default() += d;
+        }
+
+        let old_max = self.by_degree.first().map_or(0, |&(d, _)| d) as isize;
+        let mut new_max = self
+            .by_degree
+            .iter()
+            .find(|(_, v)| !removed.contains(v) && !deg.contains_key(v))
+            .map_or(0, |&(d, _)| d) as isize;
+        for (&v, &d) in &deg {
+            new_max = new_max.max(g.degree(v) as isize + d);
+        }
+
+        edges + new_max - old_max
+    }
+}
+
+impl<G: GraphLike> GreedyHeuristic<G> for EdgeDegree {
+    fn prepare(&mut self, g: &G) {
+        self.by_degree.clear();
+        self.by_degree
+            .extend(g.vertices().map(|v| (g.degree(v), v)));
+        self.by_degree.sort_by(|a, b| b.cmp(a));
+    }
+
+    fn score(&mut self, g: &G, rule: Rule, vs: &[V]) -> isize {
+        match rule {
+            Rule::LocalComp => {
+                let ns = g.neighbor_vec(vs[0]);
+                let pairs: Vec<(V, V)> = ns
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, &s)| ns[i + 1..].iter().map(move |&t| (s, t)))
+                    .collect();
+                self.change(g, vs, &pairs)
+            }
+            Rule::Pivot => {
+                let (u, v) = (vs[0], vs[1]);
+                let nu: Vec<V> = g.neighbors(u).filter(|&w| w != v).collect();
+                let nv: Vec<V> = g.neighbors(v).filter(|&w| w != u).collect();
+                // pairs with both ends next to u and v are toggled twice
+                let pairs: Vec<(V, V)> = nu
+                    .iter()
+                    .flat_map(|&s| nv.iter().map(move |&t| (s, t)))
+                    .filter(|&(s, t)| s != t && !(nv.contains(&s) && nu.contains(&t)))
+                    .collect();
+                self.change(g, vs, &pairs)
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Simplify a graph, applying a local complementation or pivot only if the
+/// given heuristic gives it a negative score
+///
+/// The graph is first made graph-like by fusing spiders and turning X spiders
+/// into Z spiders. Then, at each step, every local complementation and pivot
+/// which applies is scored, and the one with the lowest score is applied,
+/// followed by removing identities and fusing spiders. This stops when no
+/// rewrite has a negative score, so it finds a local optimum of the
+/// heuristic rather than the smallest graph. Each rewrite removes at least one
+/// spider, so this always terminates.
+///
+/// Use [EdgeDegree] for the default heuristic, or a closure for a custom one.
+///
+/// Returns `true` if any rewrite was applied.
+pub fn greedy_simp<G: GraphLike>(g: &mut G, heuristic: &mut impl GreedyHeuristic<G>) -> bool {
+    let mut got_match = spider_simp(g);
+    if g.vertices().any(|v| g.vertex_type(v) == VType::X) {
+        g.x_to_z();
+        got_match = true;
+    }
+    got_match = simp_ids(g) || got_match;
+
+    while !g.is_zero() {
+        heuristic.prepare(g);
+        let mut best: Option<(isize, Rule, Vec<V>)> = None;
+        let mut consider = |g: &G, rule: Rule, vs: Vec<V>| {
+            let score = heuristic.score(g, rule, &vs);
+            if score < 0 && best.as_ref().map_or(true, |(s, _, _)| score < *s) {
+                best = Some((score, rule, vs));
+            }
+        };
+
+        for v in g.vertices() {
+            if check_local_comp(g, v) {
+                consider(g, Rule::LocalComp, vec![v]);
+            }
+        }
+        for (s, t, _) in g.edges() {
+            if check_pivot(g, s, t) {
+                consider(g, Rule::Pivot, vec![s, t]);
+            }
+        }
+
+        let Some((_, rule, vs)) = best else {
+            break;
+        };
+        match rule {
+            Rule::LocalComp => local_comp_unchecked(g, vs[0]),
+            _ => pivot_unchecked(g, vs[0], vs[1]),
+        }
+        simp_ids(g);
+        got_match = true;
+    }
+
+    got_match
+}
+
+/// Remove identities and fuse spiders until neither applies
+fn simp_ids(g: &mut impl GraphLike) -> bool {
+    let mut got_match = false;
+    while id_simp(g) | spider_simp(g) {
+        got_match = true;
+    }
+    got_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::ToCircuit;
+    use crate::simplify::test_util::{random_circuit, random_graph};
+    use crate::tensor::{CompareTensors, Tensor4, ToTensor};
+    use crate::vec_graph::Graph;
+
+    fn num_moves(g: &Graph) -> usize {
+        g.vertices().filter(|&v| check_local_comp(g, v)).count()
+            + g.edges().filter(|&(s, t, _)| check_pivot(g, s, t)).count()
+    }
+
+    #[test]
+    fn greedy_edge_degree() {
+        for seed in 0..10 {
+            let c = random_circuit(seed, 4, 40, 0.2);
+            let mut g: Graph = c.to_graph();
+            let h = g.clone();
+            greedy_simp(&mut g, &mut EdgeDegree::new());
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+            let c1 = g.to_circuit().unwrap();
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+    }
+
+    #[test]
+    fn greedy_edge_degree_scores() {
+        let mut g = random_graph(7, 4, 40, 0.2);
+        greedy_simp(&mut g, &mut |_: &Graph, _, _: &[V]| 0);
+        let mut heuristic = EdgeDegree::new();
+        heuristic.prepare(&g);
+        let max_deg = |g: &Graph| g.vertices().map(|v| g.degree(v)).max().unwrap() as isize;
+
+        // the scores should match what actually happens to the graph
+        let mut scored = 0;
+        for (s, t, _) in g.edges() {
+            if check_pivot(&g, s, t) {
+                let mut h = g.clone();
+                pivot_unchecked(&mut h, s, t);
+                let change =
+                    h.num_edges() as isize - g.num_edges() as isize + max_deg(&h) - max_deg(&g);
+                assert_eq!(heuristic.score(&g, Rule::Pivot, &[s, t]), change);
+                scored += 1;
+            }
+        }
+        for v in g.vertices() {
+            if check_local_comp(&g, v) {
+                let mut h = g.clone();
+                local_comp_unchecked(&mut h, v);
+                let change =
+                    h.num_edges() as isize - g.num_edges() as isize + max_deg(&h) - max_deg(&g);
+                assert_eq!(heuristic.score(&g, Rule::LocalComp, &[v]), change);
+                scored += 1;
+            }
+        }
+        assert!(scored > 0);
+    }
+
+    #[test]
+    fn greedy_custom_heuristic() {
+        let mut g = random_graph(3, 4, 40, 0.2);
+        let h = g.clone();
+
+        // nothing looks good, so no local complementations or pivots
+        greedy_simp(&mut g, &mut |_: &Graph, _, _: &[V]| 0);
+        let n = num_moves(&g);
+        assert!(n > 0);
+
+        // everything looks good, so all of them
+        greedy_simp(&mut g, &mut |_: &Graph, _, _: &[V]| -1);
+        assert_eq!(num_moves(&g), 0);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn greedy_extract_heuristic() {
+        let c = random_circuit(11, 4, 40, 0.2);
+        let mut g: Graph = c.to_graph();
+        let twoq = |g: &Graph| g.to_circuit().unwrap().stats().twoq as isize;
+        let mut extract = |g: &Graph, rule: Rule, vs: &[V]| {
+            let mut h = g.clone();
+            crate::simplify::apply_rule(&mut h, rule, vs).unwrap();
+            twoq(&h) - twoq(g)
+        };
+
+        greedy_simp(&mut g, &mut |_: &Graph, _, _: &[V]| 0);
+        let before = twoq(&g);
+        greedy_simp(&mut g, &mut extract);
+        assert!(twoq(&g) <= before);
+        assert!(Tensor4::scalar_compare(&c, &g.to_circuit().unwrap()));
+    }
+}
@@ -0,0 +1,365 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A normal form for Clifford diagrams, as a graph state with local Cliffords.
+
+use super::clifford_simp;
+use crate::circuit::Circuit;
+use crate::graph::{EType, GraphLike, VType, V};
+use crate::linalg::Mat2;
+
+use rustc_hash::FxHashSet;
+use std::mem;
+
+/// A Clifford diagram as a graph state with a local Clifford on each qubit
+///
+/// The diagram is treated as a state on its inputs followed by its outputs.
+/// It is the graph state with the given adjacency matrix, followed by the
+/// one-qubit circuit `local_cliffords[q]` on each qubit `q`.
+///
+/// The form computed by [to_gslc] is canonical: two Clifford diagrams with
+/// the same inputs and outputs are equal up to a scalar if and only if their
+/// normal forms are equal. Each local Clifford is one of 1, S, Z or S†,
+/// optionally followed by a Hadamard.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct GsLcForm {
+    num_inputs: usize,
+    pub adjacency: Mat2,
+    pub local_cliffords: Vec<Circuit>,
+}
+
+/// Stabilizer tableau of a state, with one generator per qubit
+///
+/// Each generator is `(-1)^r i^(x.z) X^x Z^z`. The updates follow Aaronson
+/// and Gottesman, "Improved simulation of stabilizer circuits" (2004).
+struct Tableau {
+    x: Vec<Vec<bool>>,
+    z: Vec<Vec<bool>>,
+    r: Vec<bool>,
+}
+
+impl Tableau {
+    /// The stabilizers of the graph state with the given adjacency matrix
+    fn graph_state(adjacency: &Mat2) -> Self {
+        let n = adjacency.num_rows();
+        Tableau {
+            x: (0..n).map(|i| (0..n).map(|j| i == j).collect()).collect(),
+            z: (0..n)
+                .map(|i| (0..n).map(|j| adjacency[(i, j)] == 1).collect())
+                .collect(),
+            r: vec![false; n],
+        }
+    }
+
+    fn h(&mut self, q: usize) {
+        for i in 0..self.r.len() {
+            self.r[i] ^= self.x[i][q] && self.z[i][q];
+            mem::swap(&mut self.x[i][q], &mut self.z[i][q]);
+        }
+    }
+
+    fn s(&mut self, q: usize) {
+        for i in 0..self.r.len() {
+            self.r[i] ^= self.x[i][q] && self.z[i][q];
+            self.z[i][q] ^= self.x[i][q];
+        }
+    }
+
+    /// Multiply generator h by generator i
+    fn row_mul(&mut self, h: usize, i: usize) {
+        // exponent of i picked up by multiplying the Paulis on each qubit
+        let mut e: i32 = 2 * (self.r[h] as i32 + self.r[i] as i32);
+        for q in 0..self.r.len() {
+            let (x1, z1) = (self.x[i][q] as i32, self.z[i][q] as i32);
+            let (x2, z2) = (self.x[h][q] as i32, self.z[h][q] as i32);
+            e += match (x1, z1) {
+                (0, 0) => 0,
+                (1, 1) => z2 - x2,
+                (1, 0) => z2 * (2 * x2 - 1),
+                _ => x2 * (1 - 2 * z2),
+            };
+            self.x[h][q] ^= self.x[i][q];
+            self.z[h][q] ^= self.z[i][q];
+        }
+        self.r[h] = e.rem_euclid(4) == 2;
+    }
+
+    fn row_swap(&mut self, i: usize, j: usize) {
+        self.x.swap(i, j);
+        self.z.swap(i, j);
+        self.r.swap(i, j);
+    }
+
+    /// Bring the X part into reduced row echelon form, returning the pivot columns
+    fn reduce_x(&mut self) -> Vec<usize> {
+        let n = self.r.len();
+        let mut pivots = Vec::new();
+        for q in 0..n {
+            let row = pivots.len();
+            let Some(p) = (row..n).find(|&i| self.x[i][q]) else {
+                continue;
+            };
+            self.row_swap(row, p);
+            for i in 0..n {
+                if i != row && self.x[i][q] {
+                    self.row_mul(i, row);
+                }
+            }
+            pivots.push(q);
+        }
+        pivots
+    }
+}
+
+/// Make every boundary the only boundary on its own spider
+///
+/// Returns the spider of each input, then each output.
+fn split_boundaries(g: &mut impl GraphLike) -> Vec<V> {
+    let boundaries: Vec<V> = g.inputs().iter().chain(g.outputs()).copied().collect();
+    let mut spiders = Vec::with_capacity(boundaries.len());
+    let mut claimed = FxHashSet::default();
+    for b in boundaries {
+        let (n, et) = g
+            .incident_edges(b)
+            .next()
+            .expect("Boundary should have 1 neighbor.");
+        let s = if g.vertex_type(n) == VType::B {
+            // a bare wire between two boundaries
+            g.remove_edge(b, n);
+            let s = g.add_vertex(VType::Z);
+            g.add_edge_with_type(b, s, et);
+            g.add_edge(s, n);
+            s
+        } else if claimed.contains(&n) {
+            // n -- b == n -H- s -H*et- b, as s is an identity
+            g.remove_edge(b, n);
+            let s = g.add_vertex(VType::Z);
+            g.add_edge_with_type(n, s, EType::H);
+            g.add_edge_with_type(s, b, et.opposite());
+            s
+        } else {
+            n
+        };
+        claimed.insert(s);
+        spiders.push(s);
+    }
+    spiders
+}
+
+/// Compute the graph state with local Cliffords normal form of a Clifford diagram
+///
+/// The diagram is reduced with [clifford_simp], which leaves a graph state
+/// on the spiders next to the boundary, with a phase and possibly a Hadamard
+/// edge on each boundary. This is then brought into a canonical form using
+/// its stabilizers. The scalar of the diagram is ignored.
+///
+/// # Panics
+///
+/// Panics if `g` is not a Clifford diagram.
+pub fn to_gslc(g: &impl GraphLike) -> GsLcForm {
+    let mut g = g.clone();
+    clifford_simp(&mut g);
+    let spiders = split_boundaries(&mut g);
+    let n = spiders.len();
+
+    let index = |v: V| spiders.iter().position(|&s| s == v);
+    for v in g.vertices() {
+        if g.vertex_type(v) != VType::B {
+            assert!(
+                index(v).is_some(),
+                "Diagram should be Clifford, but interior spider {} remains",
+                v
+            );
+        }
+    }
+
+    let adjacency = Mat2::build(n, n, |i, j| {
+        i != j && g.edge_type_opt(spiders[i], spiders[j]) == Some(EType::H)
+    });
+    let mut t = Tableau::graph_state(&adjacency);
+    let boundaries = g.inputs().iter().chain(g.outputs());
+    for (q, (&s, &b)) in spiders.iter().zip(boundaries).enumerate() {
+        let p = g.phase(s).to_rational() * 2;
+        assert!(p.is_integer(), "Diagram should be Clifford");
+        for _ in 0..p.to_integer().rem_euclid(4) {
+            t.s(q);
+        }
+        if g.edge_type(s, b) == EType::H {
+            t.h(q);
+        }
+    }
+
+    // Hadamards on the columns which aren't pivots make the X part invertible
+    let pivots = t.reduce_x();
+    let had: Vec<bool> = (0..n).map(|q| !pivots.contains(&q)).collect();
+    for (q, &h) in had.iter().enumerate() {
+        if h {
+            t.h(q);
+        }
+    }
+    let pivots = t.reduce_x();
+    assert_eq!(pivots.len(), n, "Stabilizers should be independent");
+
+    // now the generators are (-1)^r i^z[q][q] X_q Z_N(q)
+    let adjacency = Mat2::build(n, n, |i, j| i != j && t.z[i][j]);
+    let local_cliffords = (0..n)
+        .map(|q| {
+            let mut c = Circuit::new(1);
+            match (t.r[q], t.z[q][q]) {
+                (false, false) => {}
+                (false, true) => {
+                    c.s(0);
+                }
+                (true, false) => {
+                    c.z(0);
+                }
+                (true, true) => {
+                    c.sdg(0);
+                }
+            }
+            if had[q] {
+                c.h(0);
+            }
+            c
+        })
+        .collect();
+
+    GsLcForm {
+        num_inputs: g.inputs().len(),
+        adjacency,
+        local_cliffords,
+    }
+}
+
+impl GsLcForm {
+    /// Number of inputs of the diagram, which are the first qubits of the state
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    /// Number of outputs of the diagram, which are the last qubits of the state
+    pub fn num_outputs(&self) -> usize {
+        self.local_cliffords.len() - self.num_inputs
+    }
+
+    /// Build a diagram with this normal form
+    pub fn to_graph<G: GraphLike>(&self) -> G {
+        let n = self.local_cliffords.len();
+        let mut g = G::new();
+        let spiders: Vec<V> = (0..n)
+            .map(|q| {
+                let s = g.add_vertex(VType::Z);
+                g.set_qubit(s, q as i32);
+                g.set_row(s, 1);
+                s
+            })
+            .collect();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                if self.adjacency[(i, j)] == 1 {
+                    g.add_edge_with_type(spiders[i], spiders[j], EType::H);
+                }
+            }
+        }
+
+        let mut boundaries = Vec::with_capacity(n);
+        for (&s, c) in spiders.iter().zip(&self.local_cliffords) {
+            let h: G = c.to_graph();
+            let vmap = g.append_graph(&h);
+            let i = vmap[&h.inputs()[0]];
+            g.set_vertex_type(i, VType::Z);
+            g.add_edge(s, i);
+            boundaries.push(vmap[&h.outputs()[0]]);
+        }
+        let outputs = boundaries.split_off(self.num_inputs);
+        g.set_inputs(boundaries);
+        g.set_outputs(outputs);
+        g
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::ToCircuit;
+    use crate::tensor::{CompareTensors, Tensor4};
+    use crate::vec_graph::Graph;
+
+    fn random_clifford(seed: u64, qubits: usize, depth: usize) -> Circuit {
+        Circuit::random()
+            .seed(seed)
+            .qubits(qubits)
+            .depth(depth)
+            .clifford_t(0.0)
+            .build()
+    }
+
+    /// An equal circuit, by simplifying and extracting
+    fn recompile(c: &Circuit) -> Circuit {
+        let mut g: Graph = c.to_graph();
+        clifford_simp(&mut g);
+        g.to_circuit().unwrap()
+    }
+
+    #[test]
+    fn gslc_equal_circuits() {
+        for seed in 0..10 {
+            let c0 = random_clifford(seed, 5, 60);
+            let c1 = recompile(&c0);
+            assert_ne!(c0, c1);
+            let f0 = to_gslc(&c0.to_graph::<Graph>());
+            let f1 = to_gslc(&c1.to_graph::<Graph>());
+            assert_eq!(f0, f1, "normal forms differ for seed {}", seed);
+            assert_eq!(f0.num_inputs(), 5);
+            assert_eq!(f0.num_outputs(), 5);
+
+            let mut c2 = c0.clone();
+            c2.s(seed as usize % 5);
+            assert_ne!(f0, to_gslc(&c2.to_graph::<Graph>()));
+        }
+    }
+
+    #[test]
+    fn gslc_to_graph() {
+        for seed in 0..5 {
+            let g: Graph = random_clifford(seed, 3, 30).to_graph();
+            let f = to_gslc(&g);
+            let h: Graph = f.to_graph();
+            assert!(Tensor4::scalar_compare(&g, &h));
+            assert_eq!(to_gslc(&h), f);
+        }
+    }
+
+    #[test]
+    fn gslc_wires() {
+        // bare wires, and a swap, which leaves two boundaries on each spider
+        let mut c = Circuit::new(3);
+        c.swap(0, 1);
+        let g: Graph = c.to_graph();
+        let h: Graph = to_gslc(&g).to_graph();
+        assert!(Tensor4::scalar_compare(&g, &h));
+    }
+
+    #[test]
+    fn gslc_large() {
+        let c0 = random_clifford(1337, 100, 1000);
+        let c1 = recompile(&c0);
+        assert_eq!(
+            to_gslc(&c0.to_graph::<Graph>()),
+            to_gslc(&c1.to_graph::<Graph>())
+        );
+    }
+}
@@ -0,0 +1,293 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reducing the number of Hadamard edges in a simplified graph.
+
+use crate::basic_rules::{local_comp_unchecked, near_ground, pivot_unchecked, remove_id_unchecked};
+use crate::graph::{EType, GraphLike, VType, V};
+use num::Zero;
+
+/// A rewrite which keeps the number of spiders the same, but changes edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Move {
+    /// Local complementation about an output spider with phase ±π/2
+    LocalComp(V),
+    /// Pivot about two connected output spiders with phases 0 or π
+    Pivot(V, V),
+}
+
+/// The output connected to v, if it is the only boundary next to v
+fn output_of(g: &impl GraphLike, v: V) -> Option<(V, EType)> {
+    let mut bs = g
+        .incident_edges(v)
+        .filter(|&(w, _)| g.vertex_type(w) == VType::B);
+    let (b, et) = bs.next()?;
+    if bs.next().is_some() || !g.outputs().contains(&b) {
+        return None;
+    }
+    Some((b, et))
+}
+
+/// Whether v is the phase-free centre of a phase gadget
+fn is_gadget_hub(g: &impl GraphLike, v: V) -> bool {
+    g.vertex_type(v) == VType::Z
+        && g.phase(v).is_zero()
+        && g.neighbors(v)
+            .any(|w| g.degree(w) == 1 && g.vertex_type(w) == VType::Z)
+}
+
+/// Change in the number of Hadamard edges from toggling the edges between
+/// each pair of vertices from the given sets
+fn toggle_cost(g: &impl GraphLike, pairs: impl Iterator<Item = (V, V)>) -> isize {
+    pairs
+        .map(|(s, t)| match g.edge_type_opt(s, t) {
+            Some(EType::H) => -1,
+            None => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Change in the number of Hadamard edges from toggling a boundary edge
+fn boundary_cost(et: EType) -> isize {
+    if et == EType::H {
+        -1
+    } else {
+        1
+    }
+}
+
+/// Change in the number of Hadamard edges a move would make
+fn move_cost(g: &impl GraphLike, m: Move) -> isize {
+    let spiders = |v: V, skip: V| -> Vec<V> {
+        g.neighbors(v)
+            .filter(|&w| w != skip && g.vertex_type(w) != VType::B)
+            .collect()
+    };
+    match m {
+        Move::LocalComp(v) => {
+            let ns = spiders(v, v);
+            let pairs = ns
+                .iter()
+                .enumerate()
+                .flat_map(|(i, &s)| ns[i + 1..].iter().map(move |&t| (s, t)));
+            toggle_cost(g, pairs) + boundary_cost(output_of(g, v).unwrap().1)
+        }
+        Move::Pivot(u, v) => {
+            let nu = spiders(u, v);
+            let nv = spiders(v, u);
+            let c: Vec<V> = nu.iter().copied().filter(|w| nv.contains(w)).collect();
+            let a: Vec<V> = nu.iter().copied().filter(|w| !c.contains(w)).collect();
+            let b: Vec<V> = nv.iter().copied().filter(|w| !c.contains(w)).collect();
+            let pairs = |xs: &[V], ys: &[V]| -> Vec<(V, V)> {
+                xs.iter()
+                    .flat_map(|&x| ys.iter().map(move |&y| (x, y)))
+                    .collect()
+            };
+            let cost = toggle_cost(g, pairs(&a, &b).into_iter())
+                + toggle_cost(g, pairs(&a, &c).into_iter())
+                + toggle_cost(g, pairs(&b, &c).into_iter());
+            cost + boundary_cost(output_of(g, u).unwrap().1)
+                + boundary_cost(output_of(g, v).unwrap().1)
+        }
+    }
+}
+
+/// All the moves that apply to g, in a fixed order
+fn moves(g: &impl GraphLike) -> Vec<Move> {
+    let mut ms = Vec::new();
+    for &b in g.outputs() {
+        let Some(v) = g.neighbors(b).next() else {
+            continue;
+        };
+        // the moves call the unchecked rules, so skip grounds here
+        if g.vertex_type(v) != VType::Z || output_of(g, v).is_none() || near_ground(g, &[v]) {
+            continue;
+        }
+        // local complementation adds a phase to the hub of a phase gadget,
+        // which breaks it up. Pivots keep it, but make extraction worse.
+        if g.neighbors(v).any(|w| is_gadget_hub(g, w)) {
+            continue;
+        }
+        let p = g.phase(v);
+        if p.is_proper_clifford() {
+            ms.push(Move::LocalComp(v));
+        } else if p.is_pauli() {
+            for (w, et) in g.incident_edges(v) {
+                if v < w
+                    && et == EType::H
+                    && g.vertex_type(w) == VType::Z
+                    && g.phase(w).is_pauli()
+                    && output_of(g, w).is_some()
+                    && !near_ground(g, &[w])
+                    && g.neighbors(w).all(|n| !is_gadget_hub(g, n))
+                {
+                    ms.push(Move::Pivot(v, w));
+                }
+            }
+        }
+    }
+    ms.sort();
+    ms
+}
+
+/// Move the boundary of v onto a new identity spider, making v interior
+///
+/// Replaces v -- b with v -H- p -H- q -- b, and returns q.
+fn unfuse_boundary(g: &mut impl GraphLike, v: V) -> V {
+    let (b, et) = output_of(g, v).unwrap();
+    let p = g.add_vertex(VType::Z);
+    let q = g.add_vertex(VType::Z);
+    for w in [p, q] {
+        g.set_qubit(w, g.qubit(v));
+        g.set_row(w, g.row(v));
+    }
+    g.remove_edge(v, b);
+    g.add_edge_with_type(v, p, EType::H);
+    g.add_edge_with_type(p, q, EType::H);
+    g.add_edge_with_type(q, b, et);
+    q
+}
+
+fn apply_move(g: &mut impl GraphLike, m: Move) {
+    match m {
+        Move::LocalComp(v) => {
+            let q = unfuse_boundary(g, v);
+            local_comp_unchecked(g, v);
+            remove_id_unchecked(g, q);
+        }
+        Move::Pivot(u, v) => {
+            let qu = unfuse_boundary(g, u);
+            let qv = unfuse_boundary(g, v);
+            pivot_unchecked(g, u, v);
+            remove_id_unchecked(g, qu);
+            remove_id_unchecked(g, qv);
+        }
+    }
+}
+
+/// Number of Hadamard edges in g
+pub fn num_h_edges(g: &impl GraphLike) -> usize {
+    g.edges().filter(|&(_, _, et)| et == EType::H).count()
+}
+
+/// Greedily reduce the number of Hadamard edges next to the outputs
+///
+/// This looks for local complementations about output spiders with phase
+/// ±π/2, and pivots about pairs of connected output spiders with phase 0 or
+/// π. Each of these keeps the number of spiders the same, and the spiders
+/// next to the outputs stay there, but the edges between their neighbours
+/// change. Spiders next to the hub of a phase gadget are left alone, as
+/// rewriting them tends to make extraction worse. At each step, the move
+/// which removes the most Hadamard edges is applied, breaking ties by the
+/// smallest vertex. This stops when no move removes any edges, or after
+/// `max_iters` moves.
+///
+/// This is meant to be run on a graph-like diagram, after e.g. [full_simp]
+/// and before extraction. Returns the number of Hadamard edges before and
+/// after.
+///
+/// [full_simp]: super::full_simp
+pub fn reduce_hadamards(g: &mut impl GraphLike, max_iters: usize) -> (usize, usize) {
+    let before = num_h_edges(g);
+    if g.is_zero() {
+        return (before, before);
+    }
+    for _ in 0..max_iters {
+        let best = moves(g).into_iter().map(|m| (move_cost(g, m), m)).min();
+        match best {
+            Some((cost, m)) if cost < 0 => apply_move(g, m),
+            _ => break,
+        }
+    }
+    (before, num_h_edges(g))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::extract::ToCircuit;
+    use crate::simplify::full_simp;
+    use crate::tensor::{CompareTensors, Tensor4, ToTensor};
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn local_comp_move() {
+        // v has phase π/2, and its neighbours are all connected
+        let mut g = Graph::new();
+        let vs: Vec<V> = (0..4)
+            .map(|i| {
+                let phase = if i == 0 { (1, 2) } else { (1, 4) };
+                let v = g.add_vertex_with_phase(VType::Z, phase);
+                let b = g.add_vertex(VType::B);
+                g.add_edge(v, b);
+                g.outputs_mut().push(b);
+                v
+            })
+            .collect();
+        for i in 0..4 {
+            for j in (i + 1)..4 {
+                g.add_edge_with_type(vs[i], vs[j], EType::H);
+            }
+        }
+
+        assert_eq!(moves(&g), vec![Move::LocalComp(vs[0])]);
+        assert_eq!(move_cost(&g, Move::LocalComp(vs[0])), -2);
+        let mut h = g.clone();
+        assert_eq!(reduce_hadamards(&mut h, 10), (6, 4));
+        assert_eq!(h.num_vertices(), g.num_vertices());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+    }
+
+    #[test]
+    fn reduce_random() {
+        let mut applied = 0;
+        for seed in 0..20 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(40)
+                .clifford_t(0.1)
+                .build();
+            let mut g: Graph = c.to_graph();
+            full_simp(&mut g);
+            let mut h = g.clone();
+            let (before, after) = reduce_hadamards(&mut h, 100);
+            assert!(after <= before);
+            if after < before {
+                applied += 1;
+            }
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+            let c1 = h.to_circuit().unwrap();
+            assert!(Tensor4::scalar_compare(&c, &c1));
+        }
+        assert!(applied > 0);
+    }
+
+    #[test]
+    fn reduce_csla_mux_3() {
+        let c = Circuit::from_qasm(include_str!("../../../circuits/small/csla_mux_3.qasm"))
+            .unwrap()
+            .to_basic_gates();
+        let mut g: Graph = c.to_graph();
+        full_simp(&mut g);
+        let c0 = g.to_circuit().unwrap();
+        reduce_hadamards(&mut g, 1000);
+        let c1 = g.to_circuit().unwrap();
+        assert!(c1.stats().twoq < c0.stats().twoq);
+    }
+}
@@ -0,0 +1,196 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplifying the connected components of a graph in parallel.
+
+use super::full_simp;
+use crate::graph::{GraphLike, V};
+use crate::vec_graph::Graph;
+
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Split g into a graph for each of the given connected components
+///
+/// Vertices and edges are added in the same order as they appear in g, and
+/// each component keeps the inputs and outputs of g it contains, in order.
+/// Also returns the component and position of each input, then each output.
+fn split_components(g: &Graph, comps: &[FxHashSet<V>]) -> (Vec<Graph>, Vec<(usize, usize)>) {
+    let mut graphs: Vec<Graph> = comps.iter().map(|_| Graph::new()).collect();
+    let mut vmap: FxHashMap<V, (usize, V)> = FxHashMap::default();
+    for v in g.vertices() {
+        let c = comps.iter().position(|comp| comp.contains(&v)).unwrap();
+        vmap.insert(v, (c, graphs[c].add_vertex_with_data(g.vertex_data(v))));
+    }
+    for (s, t, et) in g.edges() {
+        let (c, s1) = vmap[&s];
+        graphs[c].add_edge_with_type(s1, vmap[&t].1, et);
+    }
+
+    let mut boundary = Vec::with_capacity(g.inputs().len() + g.outputs().len());
+    for &b in g.inputs() {
+        let (c, b1) = vmap[&b];
+        boundary.push((c, graphs[c].inputs().len()));
+        graphs[c].inputs_mut().push(b1);
+    }
+    for &b in g.outputs() {
+        let (c, b1) = vmap[&b];
+        boundary.push((c, graphs[c].outputs().len()));
+        graphs[c].outputs_mut().push(b1);
+    }
+    (graphs, boundary)
+}
+
+/// Same as [full_simp], but simplify each connected component on its own thread
+///
+/// The components are then put back together in a new graph, multiplying
+/// their scalars, with the inputs and outputs in the same order as in g.
+/// Vertex indices are not preserved. A graph with a single component is
+/// simplified with [full_simp] directly.
+pub fn full_simp_parallel(g: &mut Graph) -> bool {
+    let comps = g.component_vertices();
+    if comps.len() <= 1 {
+        return full_simp(g);
+    }
+    let (mut graphs, boundary) = split_components(g, &comps);
+
+    let got_match = graphs
+        .par_iter_mut()
+        .map(full_simp)
+        .reduce(|| false, |m0, m1| m0 || m1);
+
+    let mut h = Graph::new();
+    *h.scalar_mut() = g.scalar().clone();
+    let vmaps: Vec<_> = graphs.iter().map(|comp| h.append_graph(comp)).collect();
+    let num_inputs = g.inputs().len();
+    let (inputs, outputs) = boundary.split_at(num_inputs);
+    h.set_inputs(
+        inputs
+            .iter()
+            .map(|&(c, i)| vmaps[c][&graphs[c].inputs()[i]])
+            .collect(),
+    );
+    h.set_outputs(
+        outputs
+            .iter()
+            .map(|&(c, i)| vmaps[c][&graphs[c].outputs()[i]])
+            .collect(),
+    );
+    h.set_track_scalar(g.track_scalar());
+    *g = h;
+    got_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::graph::{BasisElem, EType, VType};
+    use std::time::Instant;
+
+    /// Plug states into some of the qubits of random circuits, and put them
+    /// side by side with interleaved inputs and outputs
+    fn components(n: usize, qubits: usize, depth: usize) -> Graph {
+        let mut g = Graph::new();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for seed in 0..n {
+            let mut h: Graph = Circuit::random()
+                .seed(seed as u64)
+                .qubits(qubits)
+                .depth(depth)
+                .clifford_t(0.1)
+                .build()
+                .to_graph();
+            h.plug_inputs(&[BasisElem::Z0, BasisElem::X1]);
+            let vmap = g.append_graph(&h);
+            inputs.push(h.inputs().iter().map(|v| vmap[v]).collect::<Vec<_>>());
+            outputs.push(h.outputs().iter().map(|v| vmap[v]).collect::<Vec<_>>());
+        }
+        for q in 0..qubits {
+            g.inputs_mut()
+                .extend(inputs.iter().filter_map(|vs| vs.get(q)));
+            g.outputs_mut()
+                .extend(outputs.iter().filter_map(|vs| vs.get(q)));
+        }
+        g
+    }
+
+    /// The type, phase and neighbourhood of a vertex, up to renaming vertices
+    fn vertex_sig(g: &Graph, v: V) -> (VType, String, Vec<(VType, EType)>) {
+        let mut nhd: Vec<_> = g
+            .incident_edges(v)
+            .map(|(w, et)| (g.vertex_type(w), et))
+            .collect();
+        nhd.sort();
+        (g.vertex_type(v), g.phase(v).to_string(), nhd)
+    }
+
+    fn assert_same_shape(g: &Graph, h: &Graph) {
+        assert_eq!(g.num_vertices(), h.num_vertices());
+        assert_eq!(g.num_edges(), h.num_edges());
+        assert_eq!(g.scalar(), h.scalar());
+        let mut sg: Vec<_> = g.vertices().map(|v| vertex_sig(g, v)).collect();
+        let mut sh: Vec<_> = h.vertices().map(|v| vertex_sig(h, v)).collect();
+        sg.sort();
+        sh.sort();
+        assert_eq!(sg, sh);
+        for (&b0, &b1) in g.outputs().iter().zip(h.outputs()) {
+            let n0 = g.neighbors(b0).next().unwrap();
+            let n1 = h.neighbors(b1).next().unwrap();
+            assert_eq!(vertex_sig(g, n0), vertex_sig(h, n1));
+        }
+    }
+
+    #[test]
+    fn parallel_components() {
+        let g = components(8, 6, 300);
+        assert_eq!(g.component_vertices().len(), 8);
+        assert!(g.num_vertices() > 8 * 200);
+
+        let mut h0 = g.clone();
+        let mut h1 = g.clone();
+        full_simp(&mut h0);
+        assert!(full_simp_parallel(&mut h1));
+        assert_eq!(h1.inputs().len(), g.inputs().len());
+        assert_eq!(h1.outputs().len(), g.outputs().len());
+        assert_same_shape(&h0, &h1);
+    }
+
+    #[test]
+    fn parallel_one_component() {
+        let g = components(1, 4, 40);
+        let mut h0 = g.clone();
+        let mut h1 = g.clone();
+        full_simp(&mut h0);
+        full_simp_parallel(&mut h1);
+        assert_eq!(h0, h1);
+    }
+
+    #[test]
+    #[ignore]
+    fn parallel_timing() {
+        let g = components(8, 20, 2000);
+        let mut h = g.clone();
+        let t = Instant::now();
+        full_simp(&mut h);
+        println!("serial: {:?}", t.elapsed());
+        let mut h = g.clone();
+        let t = Instant::now();
+        full_simp_parallel(&mut h);
+        println!("parallel: {:?}", t.elapsed());
+    }
+}
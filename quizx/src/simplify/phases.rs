@@ -0,0 +1,198 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracking where the phases of spiders end up during [full_simp](super::full_simp).
+
+use super::{full_simp_hooked, Rule, SimpHook};
+use crate::graph::{GraphLike, VType, V};
+use crate::phase::Phase;
+use crate::scalar::ScalarN;
+
+use num::Zero;
+use rustc_hash::FxHashMap;
+
+/// A set of tracked spiders whose phases were fused onto a single spider
+///
+/// Each member is an index into the list of spiders passed to
+/// [full_simp_tracking_phases], together with a flag saying whether its
+/// phase was negated on the way.
+pub type PhaseGroup = Vec<(usize, bool)>;
+
+/// A hook that follows the phases of some spiders through the rewrites
+///
+/// Every group of phases lives on at most one vertex. Rules which remove a
+/// vertex consume its phases, so they can't end up anywhere else, and
+/// vertex indices can be reused by the graph afterwards.
+struct PhaseTracker {
+    groups: Vec<PhaseGroup>,
+    /// The group carried by each vertex which still has one
+    vertex_group: FxHashMap<V, usize>,
+    /// The leaves of the phase gadgets about to be fused
+    gadget_leaves: Option<(V, V)>,
+    /// Groups of the vertices removed by the last pivot, which may have moved
+    moved: FxHashMap<V, usize>,
+}
+
+impl PhaseTracker {
+    fn new(vs: &[V]) -> Self {
+        PhaseTracker {
+            groups: (0..vs.len()).map(|i| vec![(i, false)]).collect(),
+            vertex_group: vs.iter().enumerate().map(|(i, &v)| (v, i)).collect(),
+            gadget_leaves: None,
+            moved: FxHashMap::default(),
+        }
+    }
+
+    /// Record that the phase of v1 was added to the phase of v0
+    fn fuse(&mut self, v0: V, v1: V) {
+        if let Some(g1) = self.vertex_group.remove(&v1) {
+            if let Some(&g0) = self.vertex_group.get(&v0) {
+                let members = std::mem::take(&mut self.groups[g1]);
+                self.groups[g0].extend(members);
+            } else {
+                self.vertex_group.insert(v0, g1);
+            }
+        }
+    }
+}
+
+impl<G: GraphLike> SimpHook<G> for PhaseTracker {
+    const TRACE: bool = true;
+
+    fn before(&mut self, g: &G, rule: Rule, vs: &[V]) {
+        self.moved.clear();
+        if rule == Rule::GadgetFusion {
+            let leaf = |v| g.neighbors(v).find(|&n| g.degree(n) == 1).unwrap();
+            self.gadget_leaves = Some((leaf(vs[0]), leaf(vs[1])));
+        }
+    }
+
+    fn rewrite(&mut self, rule: Rule, vs: &[V], _scalar: ScalarN) {
+        match rule {
+            Rule::SpiderFusion => self.fuse(vs[0], vs[1]),
+            Rule::GadgetFusion => {
+                let (l0, l1) = self.gadget_leaves.take().unwrap();
+                self.fuse(l0, l1);
+            }
+            Rule::PiCopy => {
+                if let Some(&g) = self.vertex_group.get(&vs[0]) {
+                    for (_, neg) in &mut self.groups[g] {
+                        *neg = !*neg;
+                    }
+                }
+            }
+            Rule::ColorChange => {}
+            Rule::GenPivot => {
+                for v in vs {
+                    if let Some(g) = self.vertex_group.remove(v) {
+                        self.moved.insert(*v, g);
+                    }
+                }
+            }
+            // everything else removes the vertices it is applied at
+            _ => {
+                for v in vs {
+                    self.vertex_group.remove(v);
+                }
+            }
+        }
+    }
+
+    fn phase_moved(&mut self, from: V, to: V) {
+        if let Some(g) = self.moved.remove(&from) {
+            self.vertex_group.insert(to, g);
+        }
+    }
+}
+
+/// Same as [full_simp](super::full_simp), but track which phases get fused together
+///
+/// Returns the groups of spiders in `vs` whose phases ended up on the same
+/// spider, leaving out spiders whose phase was never fused with another. The
+/// rules used by `full_simp` only depend on the value of a phase when they
+/// remove the spider carrying it, so the result only depends on the sum of the
+/// phases in each group (negated where flagged), and not how the sum is
+/// distributed among them.
+pub fn full_simp_tracking_phases(g: &mut impl GraphLike, vs: &[V]) -> Vec<PhaseGroup> {
+    let mut tracker = PhaseTracker::new(vs);
+    full_simp_hooked(g, &mut tracker);
+    tracker
+        .groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// Reduce the number of non-Clifford phases by phase teleportation
+///
+/// This runs [full_simp_tracking_phases] on a copy of `g`, tracking every Z
+/// or X spider with a non-Clifford phase. In each group of phases which get
+/// fused together, the first spider of `g` gets the sum of the phases and the
+/// others get phase zero. Nothing else in `g` changes, so its structure,
+/// boundaries and scalar are untouched, and it is still the same linear map.
+/// See [phase_teleport](crate::optimize_circuit::phase_teleport) for the same
+/// thing on circuits.
+///
+/// Returns how much the T-count went down.
+pub fn phase_teleport(g: &mut impl GraphLike) -> usize {
+    let tcount = g.tcount();
+    let vs: Vec<V> = g
+        .vertices()
+        .filter(|&v| matches!(g.vertex_type(v), VType::Z | VType::X) && !g.phase(v).is_clifford())
+        .collect();
+
+    let mut h = g.clone();
+    for group in full_simp_tracking_phases(&mut h, &vs) {
+        let mut sum = Phase::zero();
+        for &(j, neg) in &group {
+            let p = g.phase(vs[j]);
+            sum += if neg { -p } else { p };
+            g.set_phase(vs[j], Phase::zero());
+        }
+        let (j0, neg0) = group[0];
+        g.set_phase(vs[j0], if neg0 { -sum } else { sum });
+    }
+
+    tcount - g.tcount()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn teleport_tof_3() {
+        let c = Circuit::from_qasm(include_str!("../../../circuits/small/tof_3.qasm")).unwrap();
+        let mut g: Graph = c.to_graph();
+        let h = g.clone();
+        assert_eq!(g.tcount(), 21);
+        assert_eq!(phase_teleport(&mut g), 6);
+        assert_eq!(g.tcount(), 15);
+
+        assert_eq!(g.num_vertices(), h.num_vertices());
+        assert_eq!(g.num_edges(), h.num_edges());
+        assert_eq!(g.inputs(), h.inputs());
+        assert_eq!(g.outputs(), h.outputs());
+        assert_eq!(g.scalar(), h.scalar());
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // nothing left to combine
+        assert_eq!(phase_teleport(&mut g), 0);
+    }
+}
@@ -0,0 +1,174 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplifying part of a graph, leaving the rest untouched.
+
+use super::{full_simp_hooked, Rule, SimpHook};
+use crate::graph::{GraphLike, V};
+use crate::scalar::ScalarN;
+
+use rustc_hash::FxHashSet;
+
+/// A hook which only allows rewrites inside a region of the graph
+///
+/// A rewrite at some vertices can change their neighbours, so it is only
+/// allowed if the vertices and all of their neighbours are in the region.
+/// Vertices created by a rewrite join the region.
+struct Region {
+    region: FxHashSet<V>,
+    /// The vertices the current rewrite may change
+    touched: Vec<V>,
+    /// Neighbours of `touched` outside the region, before the current rewrite
+    nearby: FxHashSet<V>,
+}
+
+impl<G: GraphLike> SimpHook<G> for Region {
+    const TRACE: bool = true;
+
+    fn allows(&self, g: &G, vs: &[V]) -> bool {
+        vs.iter()
+            .all(|&v| self.region.contains(&v) && g.neighbors(v).all(|w| self.region.contains(&w)))
+    }
+
+    fn before(&mut self, g: &G, _rule: Rule, vs: &[V]) {
+        self.touched.clear();
+        self.nearby.clear();
+        for &v in vs {
+            self.touched.push(v);
+            self.touched.extend(g.neighbors(v));
+        }
+        for &v in &self.touched {
+            self.nearby
+                .extend(g.neighbors(v).filter(|w| !self.region.contains(w)));
+        }
+    }
+
+    fn rewrite(&mut self, _rule: Rule, _vs: &[V], _scalar: ScalarN) {}
+
+    fn after(&mut self, g: &G, _rule: Rule, _vs: &[V]) {
+        // new vertices can only be reached from the touched vertices through
+        // other new vertices
+        let mut stack: Vec<V> = self
+            .touched
+            .iter()
+            .copied()
+            .filter(|&v| g.contains_vertex(v))
+            .collect();
+        while let Some(v) = stack.pop() {
+            for w in g.neighbors(v) {
+                if !self.region.contains(&w) && !self.nearby.contains(&w) {
+                    self.region.insert(w);
+                    stack.push(w);
+                }
+            }
+        }
+    }
+}
+
+/// Same as [full_simp](super::full_simp), but only rewrite inside the given region
+///
+/// Rewrites are only applied where they can't change any vertex outside of
+/// `region`, so those vertices, their data, and the edges between them are
+/// left exactly as they were. Since vertex indices are never renumbered, they
+/// stay valid outside the region.
+pub fn full_simp_region(g: &mut impl GraphLike, region: &FxHashSet<V>) -> bool {
+    let mut hook = Region {
+        region: region.clone(),
+        touched: Vec::new(),
+        nearby: FxHashSet::default(),
+    };
+    full_simp_hooked(g, &mut hook)
+}
+
+/// The vertices in the past of the given outputs
+///
+/// This follows edges backwards from each of `outputs` to vertices of the
+/// same or an earlier row, so it is the causal cone of the outputs for graphs
+/// which come from a circuit, e.g. via [Circuit::to_graph](crate::circuit::Circuit::to_graph).
+pub fn causal_cone(g: &impl GraphLike, outputs: &[V]) -> FxHashSet<V> {
+    let mut cone: FxHashSet<V> = outputs.iter().copied().collect();
+    let mut stack = outputs.to_vec();
+    while let Some(v) = stack.pop() {
+        let row = g.row(v);
+        for w in g.neighbors(v) {
+            if g.row(w) <= row && cone.insert(w) {
+                stack.push(w);
+            }
+        }
+    }
+    cone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::graph::VData;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::{EType, Graph};
+
+    type Snapshot = Vec<(V, VData, Vec<(V, EType)>)>;
+
+    fn snapshot(g: &Graph, vs: impl Iterator<Item = V>) -> Snapshot {
+        let mut snap: Snapshot = vs
+            .map(|v| {
+                let mut nhd = g.incident_edge_vec(v);
+                nhd.sort();
+                (v, g.vertex_data(v), nhd)
+            })
+            .collect();
+        snap.sort_by_key(|&(v, _, _)| v);
+        snap
+    }
+
+    #[test]
+    fn cone_of_one_output() {
+        let mut c = Circuit::new(3);
+        c.h(0).cx(0, 1).t(2).h(2);
+        let g: Graph = c.to_graph();
+        let cone = causal_cone(&g, &[g.output_qubit(1)]);
+        assert!(cone.contains(&g.input_qubit(0)));
+        assert!(cone.contains(&g.input_qubit(1)));
+        assert!(!cone.contains(&g.input_qubit(2)));
+        assert!(!cone.contains(&g.output_qubit(0)));
+    }
+
+    #[test]
+    fn simp_in_cone() {
+        for seed in 0..5 {
+            let c = Circuit::random()
+                .seed(seed)
+                .qubits(4)
+                .depth(30)
+                .p_t(0.2)
+                .with_cliffords()
+                .build();
+            let g: Graph = c.to_graph();
+            let region = causal_cone(&g, &[g.output_qubit(0)]);
+            let outside: Vec<V> = g.vertices().filter(|v| !region.contains(v)).collect();
+
+            let mut h = g.clone();
+            full_simp_region(&mut h, &region);
+            assert!(h.num_vertices() < g.num_vertices());
+            assert!(outside.iter().all(|&v| h.contains_vertex(v)));
+            assert_eq!(
+                snapshot(&g, outside.iter().copied()),
+                snapshot(&h, outside.iter().copied())
+            );
+            assert_eq!(g.to_tensor4(), h.to_tensor4());
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Simplifying until a user-defined condition holds.
+
+use super::{
+    clifford_simp_hooked, full_simp_hooked, interior_clifford_simp_hooked, Rule, SimpHook,
+};
+use crate::graph::{GraphLike, V};
+use crate::scalar::ScalarN;
+
+use rustc_hash::FxHashMap;
+
+/// What a simplification with a stop condition did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpStats {
+    /// How many times the stop condition was evaluated.
+    pub checks: usize,
+    /// The total number of rewrites applied.
+    pub rewrites: usize,
+    /// The number of rewrites applied for each rule.
+    pub rules: FxHashMap<Rule, usize>,
+    /// Whether the stop condition fired, rather than the graph reaching a fixed point.
+    pub stopped: bool,
+}
+
+/// A hook which counts rewrites and stops once the condition holds
+///
+/// Once stopped, no more rewrites are allowed, so the remaining passes of the
+/// current round fall through without changing the graph.
+struct Until<F> {
+    stop: F,
+    stats: SimpStats,
+}
+
+impl<G: GraphLike, F: FnMut(&G) -> bool> SimpHook<G> for Until<F> {
+    const TRACE: bool = true;
+
+    fn allows(&self, _g: &G, _vs: &[V]) -> bool {
+        !self.stats.stopped
+    }
+
+    fn rewrite(&mut self, rule: Rule, _vs: &[V], _scalar: ScalarN) {
+        self.stats.rewrites += 1;
+        *self.stats.rules.entry(rule).or_insert(0) += 1;
+    }
+
+    fn stop(&mut self, g: &G) -> bool {
+        if !self.stats.stopped {
+            self.stats.checks += 1;
+            self.stats.stopped = (self.stop)(g);
+        }
+        self.stats.stopped
+    }
+}
+
+/// Run a driver with an [Until] hook, unless the condition already holds
+fn run_until<G: GraphLike, F: FnMut(&G) -> bool>(
+    g: &mut G,
+    stop: F,
+    driver: impl FnOnce(&mut G, &mut Until<F>) -> bool,
+) -> SimpStats {
+    let mut hook = Until {
+        stop,
+        stats: SimpStats::default(),
+    };
+    if !hook.stop(g) {
+        driver(g, &mut hook);
+    }
+    hook.stats
+}
+
+/// Same as [full_simp](super::full_simp), but stop as soon as `stop` returns true
+///
+/// The condition is checked between passes, so it always sees a graph with
+/// every rewrite fully applied. Returns the statistics for the rewrites
+/// applied up to that point.
+///
+/// ```
+/// # use quizx::circuit::Circuit;
+/// # use quizx::graph::*;
+/// # use quizx::simplify::full_simp_until;
+/// # use quizx::vec_graph::Graph;
+/// let c = Circuit::random()
+///     .seed(1)
+///     .qubits(5)
+///     .depth(200)
+///     .p_t(0.2)
+///     .with_cliffords()
+///     .build();
+/// let mut g: Graph = c.to_graph();
+/// let stats = full_simp_until(&mut g, |g| g.tcount() <= 30);
+/// assert!(!stats.stopped || g.tcount() <= 30);
+/// ```
+pub fn full_simp_until<G: GraphLike>(g: &mut G, stop: impl FnMut(&G) -> bool) -> SimpStats {
+    run_until(g, stop, full_simp_hooked)
+}
+
+/// Same as [clifford_simp](super::clifford_simp), but stop as soon as `stop` returns true
+///
+/// See [full_simp_until].
+pub fn clifford_simp_until<G: GraphLike>(g: &mut G, stop: impl FnMut(&G) -> bool) -> SimpStats {
+    run_until(g, stop, clifford_simp_hooked)
+}
+
+/// Same as [interior_clifford_simp](super::interior_clifford_simp), but stop
+/// as soon as `stop` returns true
+///
+/// See [full_simp_until].
+pub fn interior_clifford_simp_until<G: GraphLike>(
+    g: &mut G,
+    stop: impl FnMut(&G) -> bool,
+) -> SimpStats {
+    run_until(g, stop, interior_clifford_simp_hooked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simplify::test_util::random_graph;
+    use crate::simplify::{full_simp, full_simp_traced};
+    use crate::tensor::ToTensor;
+    use crate::vec_graph::Graph;
+
+    #[test]
+    fn never_stop() {
+        for seed in 0..5 {
+            let g = random_graph(seed, 3, 40, 0.3);
+            let mut h = g.clone();
+            full_simp(&mut h);
+            let trace = full_simp_traced(&mut g.clone());
+
+            let mut g1 = g.clone();
+            let stats = full_simp_until(&mut g1, |_| false);
+            assert!(!stats.stopped);
+            assert!(stats.checks > 0);
+            assert_eq!(stats.rewrites, trace.steps.len());
+            assert_eq!(stats.rules.values().sum::<usize>(), stats.rewrites);
+            assert_eq!(g1, h);
+        }
+    }
+
+    #[test]
+    fn stop_on_tcount() {
+        for seed in 0..5 {
+            let g = random_graph(seed, 3, 40, 0.3);
+            let mut h = g.clone();
+            full_simp(&mut h);
+            let limit = (g.tcount() + h.tcount()) / 2;
+            let all = full_simp_traced(&mut g.clone()).steps.len();
+
+            let mut g1 = g.clone();
+            let mut seen = vec![];
+            let stats = full_simp_until(&mut g1, |g| {
+                seen.push(g.tcount());
+                g.tcount() <= limit
+            });
+            assert!(stats.stopped, "seed {}", seed);
+            assert_eq!(seen.len(), stats.checks);
+            assert!(seen[..seen.len() - 1].iter().all(|&t| t > limit));
+            assert!(g1.tcount() <= limit);
+            assert!(stats.rewrites < all);
+            assert_eq!(g1.to_tensor4(), g.to_tensor4());
+        }
+    }
+
+    #[test]
+    fn stop_immediately() {
+        let g = random_graph(1, 3, 40, 0.3);
+        let stop: fn(&Graph) -> bool = |_| true;
+        for f in [
+            full_simp_until,
+            clifford_simp_until,
+            interior_clifford_simp_until,
+        ] {
+            let mut g1 = g.clone();
+            let stats = f(&mut g1, stop);
+            assert!(stats.stopped);
+            assert_eq!(stats.checks, 1);
+            assert_eq!(stats.rewrites, 0);
+            assert_eq!(g1, g);
+        }
+    }
+}
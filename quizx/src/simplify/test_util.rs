@@ -0,0 +1,35 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fixtures shared by the tests of the simplifiers.
+
+use crate::circuit::Circuit;
+use crate::vec_graph::Graph;
+
+/// A random Clifford+T circuit, with T gates at the given rate
+pub fn random_circuit(seed: u64, qubits: usize, depth: usize, p_t: f32) -> Circuit {
+    Circuit::random()
+        .seed(seed)
+        .qubits(qubits)
+        .depth(depth)
+        .clifford_t(p_t)
+        .build()
+}
+
+/// The graph of [random_circuit]
+pub fn random_graph(seed: u64, qubits: usize, depth: usize, p_t: f32) -> Graph {
+    random_circuit(seed, qubits, depth, p_t).to_graph()
+}
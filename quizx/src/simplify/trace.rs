@@ -0,0 +1,227 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording and replaying the rewrites applied by [full_simp](super::full_simp).
+
+use super::{apply_rule, full_simp_hooked, Rule, RuleError, SimpHook};
+use crate::graph::{GraphLike, V};
+use crate::json::{JsonError, JsonScalar};
+use crate::scalar::ScalarN;
+use crate::tensor::ToTensor;
+
+use approx::AbsDiffEq;
+use derive_more::{Display, Error};
+use num::One;
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+/// A single rewrite in a [SimpTrace]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// The rule that was applied.
+    pub rule: Rule,
+    /// The vertices it was applied at, as passed to [apply_rule].
+    pub vertices: Vec<V>,
+    /// The factor the rewrite multiplied the global scalar by.
+    pub scalar: ScalarN,
+}
+
+/// The sequence of rewrites applied by [full_simp_traced]
+///
+/// Vertex indices refer to the graph as it was at each step, so the trace
+/// can be replayed on a copy of the original graph.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimpTrace {
+    pub steps: Vec<TraceStep>,
+}
+
+/// An error from replaying a [SimpTrace]
+#[derive(Debug, Display, Error)]
+pub enum TraceError {
+    /// A rule could not be applied.
+    #[display("Step {step}: {err}")]
+    Rule { step: usize, err: RuleError },
+    /// A rule multiplied the scalar by a different factor than the one recorded.
+    #[display("Step {step}: {rule:?} gave a scalar factor of {got}, expected {expected}")]
+    Scalar {
+        step: usize,
+        rule: Rule,
+        expected: ScalarN,
+        got: ScalarN,
+    },
+    /// A rule changed the tensor of the graph.
+    #[display("Step {step}: {rule:?} changed the tensor")]
+    Tensor { step: usize, rule: Rule },
+}
+
+/// Json encoding of a [TraceStep]
+#[derive(Serialize, Deserialize)]
+struct JsonTraceStep {
+    rule: Rule,
+    vertices: Vec<V>,
+    scalar: JsonScalar,
+}
+
+impl<G: GraphLike> SimpHook<G> for SimpTrace {
+    const TRACE: bool = true;
+
+    fn rewrite(&mut self, rule: Rule, vs: &[V], scalar: ScalarN) {
+        self.steps.push(TraceStep {
+            rule,
+            vertices: vs.to_vec(),
+            scalar,
+        });
+    }
+}
+
+/// Same as [full_simp](super::full_simp), but record every rewrite applied
+pub fn full_simp_traced(g: &mut impl GraphLike) -> SimpTrace {
+    let mut trace = SimpTrace::default();
+    full_simp_hooked(g, &mut trace);
+    trace
+}
+
+impl SimpTrace {
+    /// Apply the rewrites in the trace to g
+    ///
+    /// For this to reproduce the original simplification, g should be a copy
+    /// of the graph the trace was recorded on.
+    pub fn replay(&self, g: &mut impl GraphLike) -> Result<(), TraceError> {
+        for (i, step) in self.steps.iter().enumerate() {
+            apply_rule(g, step.rule, &step.vertices)
+                .map_err(|err| TraceError::Rule { step: i, err })?;
+        }
+        Ok(())
+    }
+
+    /// Replay the trace on a copy of original, checking every step
+    ///
+    /// After each rewrite, this checks the scalar factor against the one in
+    /// the trace and the tensor against the tensor of the original graph.
+    /// Computing tensors is exponential in the number of boundaries, so this
+    /// is only practical for small graphs.
+    pub fn check_against_tensor(&self, original: &impl GraphLike) -> Result<(), TraceError> {
+        let mut g = original.clone();
+        let t = original.to_tensor4();
+        for (i, step) in self.steps.iter().enumerate() {
+            let s = mem::replace(g.scalar_mut(), ScalarN::one());
+            apply_rule(&mut g, step.rule, &step.vertices)
+                .map_err(|err| TraceError::Rule { step: i, err })?;
+            let factor = mem::replace(g.scalar_mut(), s);
+            *g.scalar_mut() *= &factor;
+
+            // scalars are only approximate after a round-trip through json
+            if !factor.abs_diff_eq(&step.scalar, ScalarN::default_epsilon()) {
+                return Err(TraceError::Scalar {
+                    step: i,
+                    rule: step.rule,
+                    expected: step.scalar.clone(),
+                    got: factor,
+                });
+            }
+
+            if g.to_tensor4() != t {
+                return Err(TraceError::Tensor {
+                    step: i,
+                    rule: step.rule,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the json-encoded representation of the trace.
+    pub fn to_json(&self) -> Result<String, JsonError> {
+        let steps: Vec<_> = self
+            .steps
+            .iter()
+            .map(|step| JsonTraceStep {
+                rule: step.rule,
+                vertices: step.vertices.clone(),
+                scalar: JsonScalar::from_scalar(&step.scalar),
+            })
+            .collect();
+        Ok(serde_json::to_string(&steps)?)
+    }
+
+    /// Reads a trace from its json-encoded representation.
+    pub fn from_json(s: &str) -> Result<Self, JsonError> {
+        let steps: Vec<JsonTraceStep> = serde_json::from_str(s)?;
+        let steps = steps
+            .into_iter()
+            .map(|step| {
+                Ok(TraceStep {
+                    rule: step.rule,
+                    vertices: step.vertices,
+                    scalar: step.scalar.to_scalar()?,
+                })
+            })
+            .collect::<Result<_, JsonError>>()?;
+        Ok(SimpTrace { steps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simplify::test_util::random_graph;
+
+    #[test]
+    fn traced_matches_untraced() {
+        for seed in 0..5 {
+            let g = random_graph(seed, 3, 20, 0.2);
+            let mut h0 = g.clone();
+            let mut h1 = g.clone();
+            crate::simplify::full_simp(&mut h0);
+            let trace = full_simp_traced(&mut h1);
+            assert_eq!(h0, h1);
+            assert!(!trace.steps.is_empty());
+
+            let mut h2 = g.clone();
+            trace.replay(&mut h2).unwrap();
+            assert_eq!(h1, h2);
+            trace.check_against_tensor(&g).unwrap();
+        }
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let g = random_graph(42, 3, 20, 0.2);
+        let trace = full_simp_traced(&mut g.clone());
+        let trace1 = SimpTrace::from_json(&trace.to_json().unwrap()).unwrap();
+        assert_eq!(trace.steps.len(), trace1.steps.len());
+        for (s0, s1) in trace.steps.iter().zip(&trace1.steps) {
+            assert_eq!((s0.rule, &s0.vertices), (s1.rule, &s1.vertices));
+        }
+        trace1.check_against_tensor(&g).unwrap();
+    }
+
+    #[test]
+    fn wrong_scalar() {
+        let g = random_graph(7, 3, 20, 0.2);
+        let mut trace = full_simp_traced(&mut g.clone());
+        let i = trace
+            .steps
+            .iter()
+            .position(|s| s.scalar != ScalarN::one())
+            .unwrap();
+        trace.steps[i].scalar = ScalarN::one();
+        match trace.check_against_tensor(&g) {
+            Err(TraceError::Scalar { step, .. }) => assert_eq!(step, i),
+            r => panic!("expected a scalar error, got {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,743 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Computing amplitudes and probabilities of circuits.
+//!
+//! These plug computational basis states and effects into a circuit, simplify
+//! the resulting scalar diagram with [full_simp] and finish it off with a
+//! stabiliser [Decomposer]. The result is exact. [amplitudes] shares the
+//! simplification of the circuit between many outputs.
+//!
+//! [sample] draws outputs one qubit at a time, from marginal probabilities
+//! computed the same way, and [expectation] computes expectation values of
+//! Pauli observables, which [noisy_expectation] estimates under depolarizing
+//! noise. [norm_squared] computes the norm of any state given as a graph.
+
+use crate::circuit::Circuit;
+use crate::decompose::Decomposer;
+use crate::graph::*;
+use crate::scalar::{FromPhase, ScalarN};
+use crate::simplify::{full_simp, state_copy_simp};
+use crate::vec_graph::Graph;
+
+use num::{Rational64, Zero};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+
+mod noise;
+pub use noise::{noisy_expectation, noisy_expectation_with, NoiseModel, NoisyExpectation};
+
+/// Options for [amplitude_with], [amplitudes_with] and [probability_with]
+///
+/// By default, cat decompositions are picked automatically and decomposition
+/// runs on a single thread.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimulateOptions {
+    /// Whether to use cat decompositions. If this is `None`, they are used
+    /// when the simplified diagram contains a cat state.
+    pub use_cats: Option<bool>,
+    /// If set, decompose in parallel, splitting into separate tasks at this
+    /// depth. See [Decomposer::decomp_parallel].
+    pub parallel_depth: Option<usize>,
+    /// Whether [amplitudes_with] works on the outputs in parallel, one rayon
+    /// task each
+    pub parallel_batch: bool,
+}
+
+fn basis(b: bool) -> BasisElem {
+    if b {
+        BasisElem::Z1
+    } else {
+        BasisElem::Z0
+    }
+}
+
+/// Simplify and decompose a graph with no inputs or outputs
+fn contract(g: Graph, opts: SimulateOptions) -> ScalarN {
+    contract_terms(g, opts).0
+}
+
+/// Same as [contract], also returning the number of terms
+fn contract_terms(mut g: Graph, opts: SimulateOptions) -> (ScalarN, usize) {
+    full_simp(&mut g);
+    let use_cats = opts
+        .use_cats
+        .unwrap_or_else(|| !Decomposer::cat_ts(&g).is_empty());
+    let mut d = Decomposer::new(&g);
+    d.use_cats(use_cats).with_full_simp();
+    let d = match opts.parallel_depth {
+        Some(depth) => d.decomp_parallel(depth),
+        None => {
+            d.decomp_all();
+            d
+        }
+    };
+    (d.scalar, d.nterms)
+}
+
+/// The amplitude `<output|c|input>` for computational basis states
+///
+/// # Panics
+///
+/// Panics if `input` or `output` doesn't have one entry per qubit.
+pub fn amplitude(c: &Circuit, input: &[bool], output: &[bool]) -> ScalarN {
+    amplitude_with(c, input, output, SimulateOptions::default())
+}
+
+/// Like [amplitude], with the given options
+pub fn amplitude_with(
+    c: &Circuit,
+    input: &[bool],
+    output: &[bool],
+    opts: SimulateOptions,
+) -> ScalarN {
+    let mut g: Graph = c.to_graph();
+    assert_eq!(input.len(), g.inputs().len(), "wrong number of inputs");
+    assert_eq!(output.len(), g.outputs().len(), "wrong number of outputs");
+    g.plug_inputs(&input.iter().map(|&b| basis(b)).collect::<Vec<_>>());
+    g.plug_outputs(&output.iter().map(|&b| basis(b)).collect::<Vec<_>>());
+    contract(g, opts)
+}
+
+/// The amplitudes `<x|c|0...0>` for a batch of computational basis states x
+///
+/// The diagram for `c|0...0>` is simplified once, with its outputs left open.
+/// Each x is then plugged into a copy of the simplified diagram, which is
+/// usually much smaller than the circuit, and copied through it with
+/// [state_copy_simp], before the rest is simplified and decomposed as in
+/// [amplitude]. The scalars are the same as those from [amplitude].
+///
+/// # Panics
+///
+/// Panics if an entry of `outputs` doesn't have one entry per qubit.
+pub fn amplitudes(c: &Circuit, outputs: &[Vec<bool>]) -> Vec<ScalarN> {
+    amplitudes_with(c, outputs, SimulateOptions::default())
+}
+
+/// Like [amplitudes], with the given options
+pub fn amplitudes_with(c: &Circuit, outputs: &[Vec<bool>], opts: SimulateOptions) -> Vec<ScalarN> {
+    let mut g: Graph = c.to_graph();
+    let n = g.inputs().len();
+    g.plug_inputs(&vec![BasisElem::Z0; n]);
+    full_simp(&mut g);
+
+    let amplitude = |output: &Vec<bool>| {
+        assert_eq!(output.len(), n, "wrong number of outputs");
+        let mut h = g.clone();
+        h.plug_outputs(&output.iter().map(|&b| basis(b)).collect::<Vec<_>>());
+        state_copy_simp(&mut h);
+        contract(h, opts)
+    };
+    if opts.parallel_batch {
+        outputs.par_iter().map(amplitude).collect()
+    } else {
+        outputs.iter().map(amplitude).collect()
+    }
+}
+
+/// The probability of measuring the given outputs on `c|input>`
+///
+/// Outputs which are `None` in `output_mask` are summed over. This is done by
+/// composing the diagram with its adjoint, rather than summing amplitudes.
+///
+/// # Panics
+///
+/// Panics if `input` or `output_mask` doesn't have one entry per qubit.
+pub fn probability(c: &Circuit, input: &[bool], output_mask: &[Option<bool>]) -> ScalarN {
+    probability_with(c, input, output_mask, SimulateOptions::default())
+}
+
+/// Like [probability], with the given options
+pub fn probability_with(
+    c: &Circuit,
+    input: &[bool],
+    output_mask: &[Option<bool>],
+    opts: SimulateOptions,
+) -> ScalarN {
+    let mut g: Graph = c.to_graph();
+    assert_eq!(input.len(), g.inputs().len(), "wrong number of inputs");
+    assert_eq!(
+        output_mask.len(),
+        g.outputs().len(),
+        "wrong number of outputs"
+    );
+    g.plug_inputs(&input.iter().map(|&b| basis(b)).collect::<Vec<_>>());
+    // plug from the right, so the remaining indices don't shift
+    for (i, b) in output_mask.iter().enumerate().rev() {
+        if let Some(b) = b {
+            g.plug_output(i, basis(*b));
+        }
+    }
+    g.plug(&g.to_adjoint());
+    contract(g, opts)
+}
+
+/// The state `c|0...0>` next to its conjugate
+///
+/// The outputs alternate between the two, so the outputs for qubit `q` are
+/// `2q` and `2q + 1`.
+fn doubled_state(c: &Circuit) -> Graph {
+    let mut g: Graph = c.to_graph();
+    let n = g.inputs().len();
+    g.plug_inputs(&vec![BasisElem::Z0; n]);
+    let mut h = g.clone();
+    // after this, the inputs of h are the outputs of the conjugate
+    h.adjoint();
+    let vmap = g.append_graph(&h);
+    let outputs = g
+        .outputs()
+        .iter()
+        .zip(h.inputs())
+        .flat_map(|(&o, i)| [o, vmap[i]])
+        .collect();
+    g.set_outputs(outputs);
+    g
+}
+
+/// Join up the remaining pairs of outputs of a [doubled_state] and evaluate
+/// the result
+fn trace(g: &Graph, opts: SimulateOptions) -> f64 {
+    let mut g = g.clone();
+    for pair in g.outputs().clone().chunks(2) {
+        // two phase-free Z spiders joined by an edge form a cap
+        g.set_vertex_type(pair[0], VType::Z);
+        g.set_vertex_type(pair[1], VType::Z);
+        g.add_edge(pair[0], pair[1]);
+    }
+    g.set_outputs(vec![]);
+    contract(g, opts).complex_value().re
+}
+
+/// A [doubled_state] with the outputs for the first few qubits plugged, and
+/// the probability of seeing those outputs
+struct Prefix {
+    g: Graph,
+    prob: f64,
+}
+
+impl Prefix {
+    /// Plug the next qubit with `b`, and simplify
+    ///
+    /// If the probability of the result isn't given, it is computed.
+    fn plug(&self, b: bool, prob: Option<f64>, opts: SimulateOptions) -> Prefix {
+        let mut g = self.g.clone();
+        g.plug_output(0, basis(b));
+        g.plug_output(0, basis(b));
+        full_simp(&mut g);
+        let prob = prob.unwrap_or_else(|| trace(&g, opts));
+        Prefix { g, prob }
+    }
+}
+
+/// Draw samples from the outputs of `c` on input `|0...0>`
+///
+/// Each qubit is sampled in turn, from its probability conditioned on the
+/// qubits already sampled. These are worked out from a diagram for the state
+/// next to its conjugate, with the sampled outputs plugged in. The simplified
+/// diagram for each prefix of outputs is kept between shots, along with its
+/// probability, so each new prefix costs one decomposition.
+pub fn sample(c: &Circuit, shots: usize, seed: u64) -> Vec<Vec<bool>> {
+    sample_with(c, shots, seed, SimulateOptions::default())
+}
+
+/// Like [sample], with the given options
+pub fn sample_with(c: &Circuit, shots: usize, seed: u64, opts: SimulateOptions) -> Vec<Vec<bool>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut g = doubled_state(c);
+    let qubits = g.outputs().len() / 2;
+    full_simp(&mut g);
+    let prob = trace(&g, opts);
+
+    let mut cache: FxHashMap<Vec<bool>, Prefix> = FxHashMap::default();
+    cache.insert(vec![], Prefix { g, prob });
+
+    (0..shots)
+        .map(|_| {
+            let mut bits = Vec::with_capacity(qubits);
+            for _ in 0..qubits {
+                let mut bits0 = bits.clone();
+                bits0.push(false);
+                if !cache.contains_key(&bits0) {
+                    let p = cache[&bits].plug(false, None, opts);
+                    cache.insert(bits0.clone(), p);
+                }
+                let prob = cache[&bits].prob;
+                let prob0 = cache[&bits0].prob;
+
+                let b = rng.gen::<f64>() * prob >= prob0;
+                if b {
+                    let mut bits1 = bits.clone();
+                    bits1.push(true);
+                    if !cache.contains_key(&bits1) {
+                        let p = cache[&bits].plug(true, Some(prob - prob0), opts);
+                        cache.insert(bits1, p);
+                    }
+                }
+                bits.push(b);
+            }
+            bits
+        })
+        .collect()
+}
+
+/// A single-qubit Pauli operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pauli {
+    X,
+    Y,
+    Z,
+}
+
+/// A tensor product of Paulis, each given with the qubit it acts on
+///
+/// Qubits which don't appear get the identity.
+pub type PauliString = Vec<(Pauli, usize)>;
+
+/// The expectation value of a sum of Pauli strings, see [expectation_sum]
+#[derive(Debug, Clone)]
+pub struct Expectation {
+    /// The weighted sum of the terms
+    pub value: f64,
+    /// The exact expectation value of each Pauli string, without its
+    /// coefficient
+    pub terms: Vec<ScalarN>,
+}
+
+/// The Pauli string as a circuit on n qubits, and the phase it is missing,
+/// as a multiple of π
+fn pauli_circuit(n: usize, observable: &[(Pauli, usize)]) -> (Circuit, Rational64) {
+    let mut p = Circuit::new(n);
+    let mut ys = 0;
+    for &(pauli, q) in observable {
+        assert!(q < n, "qubit {} out of range", q);
+        match pauli {
+            Pauli::X => {
+                p.x(q);
+            }
+            Pauli::Z => {
+                p.z(q);
+            }
+            // Y = iXZ
+            Pauli::Y => {
+                p.z(q).x(q);
+                ys += 1;
+            }
+        }
+    }
+    (p, Rational64::new(ys, 2))
+}
+
+/// The diagram for `<0...0|c^dagger P c|0...0>`
+fn pauli_sandwich(c: &Circuit, observable: &[(Pauli, usize)]) -> Graph {
+    let mut g: Graph = c.to_graph();
+    let n = g.inputs().len();
+    g.plug_inputs(&vec![BasisElem::Z0; n]);
+    let bra = g.to_adjoint();
+
+    let (p, phase) = pauli_circuit(n, observable);
+    g.plug(&p.to_graph::<Graph>());
+    g.scalar_mut().mul_phase(phase);
+    g.plug(&bra);
+    g
+}
+
+/// The expectation value `<0...0|c^dagger P c|0...0>` of a Pauli string `P`
+///
+/// The Pauli string is inserted between the state and its adjoint as
+/// π-phase spiders, and the resulting scalar diagram is simplified and
+/// decomposed. A qubit may appear more than once, in which case the Paulis
+/// are applied in order, like gates. Then the result need not be real.
+///
+/// # Panics
+///
+/// Panics if a qubit is out of range.
+pub fn expectation(c: &Circuit, observable: &[(Pauli, usize)]) -> ScalarN {
+    expectation_with(c, observable, SimulateOptions::default())
+}
+
+/// Like [expectation], with the given options
+pub fn expectation_with(
+    c: &Circuit,
+    observable: &[(Pauli, usize)],
+    opts: SimulateOptions,
+) -> ScalarN {
+    contract(pauli_sandwich(c, observable), opts)
+}
+
+/// The expectation value of a weighted sum of Pauli strings
+///
+/// Each term is computed exactly with [expectation], then the real parts are
+/// added up with their coefficients.
+pub fn expectation_sum(c: &Circuit, terms: &[(f64, PauliString)]) -> Expectation {
+    expectation_sum_with(c, terms, SimulateOptions::default())
+}
+
+/// Like [expectation_sum], with the given options
+pub fn expectation_sum_with(
+    c: &Circuit,
+    terms: &[(f64, PauliString)],
+    opts: SimulateOptions,
+) -> Expectation {
+    let terms: Vec<_> = terms
+        .iter()
+        .map(|(coeff, p)| (coeff, expectation_with(c, p, opts)))
+        .collect();
+    Expectation {
+        value: terms
+            .iter()
+            .map(|(coeff, e)| *coeff * e.complex_value().re)
+            .sum(),
+        terms: terms.into_iter().map(|(_, e)| e).collect(),
+    }
+}
+
+/// The state `g` composed with its adjoint, and the map from each vertex of
+/// `g` to its copy in the adjoint
+fn doubled(g: &Graph) -> (Graph, FxHashMap<V, V>) {
+    let mut d = g.clone();
+    let mut bra = g.clone();
+    bra.adjoint();
+    let vmap = d.append_graph(&bra);
+    for &o in g.outputs() {
+        // two phase-free Z spiders joined by an edge form a cap
+        d.set_vertex_type(o, VType::Z);
+        d.set_vertex_type(vmap[&o], VType::Z);
+        d.add_edge(o, vmap[&o]);
+    }
+    d.set_outputs(vec![]);
+    (d, vmap)
+}
+
+/// Whether v and w are Z spiders with opposite T-like phases
+fn is_t_pair(g: &Graph, v: V, w: V) -> bool {
+    v != w
+        && g.contains_vertex(v)
+        && g.contains_vertex(w)
+        && g.vertex_type(v) == VType::Z
+        && g.vertex_type(w) == VType::Z
+        && g.phase(v).is_t()
+        && g.phase(w) == -g.phase(v)
+}
+
+/// The two terms of a pair of Z spiders with phases α and -α
+///
+/// Writing x and y for the values of the spiders, `e^(iαx) e^(-iαy)` is
+/// `δ(x, y) + e^(-iα) δ(x, ¬y) e^(2iαx)`. In the first term the spiders are
+/// fused and their phases cancel. In the second they are joined through a
+/// π-spider and one of them gets the phase 2α, which is Clifford for a T
+/// phase.
+fn pair_terms(g: &Graph, v: V, w: V) -> [Graph; 2] {
+    let a = g.phase(v);
+    let mut g0 = g.clone();
+    g0.set_phase(v, 0);
+    g0.set_phase(w, 0);
+    g0.add_edge_smart(v, w, EType::N);
+
+    let mut g1 = g.clone();
+    g1.set_phase(v, a + a);
+    g1.set_phase(w, 0);
+    let z = g1.add_vertex_with_phase(VType::Z, 1);
+    g1.add_edge_with_type(v, z, EType::H);
+    g1.add_edge_with_type(z, w, EType::H);
+    *g1.scalar_mut() *= ScalarN::from_phase(-a);
+
+    [g0, g1]
+}
+
+/// The squared norm `<ψ|ψ>` of the state given by `g`
+///
+/// This composes the state with its adjoint and computes the resulting
+/// scalar, like [probability] does for circuits. The state is simplified
+/// first, so that each T-spider of the ket has a conjugate in the bra.
+/// Before anything else is decomposed, these pairs are split with
+/// `pair_terms`, which costs 2 terms for the two T gates, and tends to undo
+/// the doubling: the first term fuses a spider with its conjugate. What is
+/// left is handed to a [Decomposer].
+///
+/// # Panics
+///
+/// Panics if `g` has inputs.
+pub fn norm_squared(g: &impl GraphLike) -> ScalarN {
+    norm_squared_with(g, SimulateOptions::default())
+}
+
+/// Like [norm_squared], with the given options
+pub fn norm_squared_with(g: &impl GraphLike, opts: SimulateOptions) -> ScalarN {
+    norm_squared_terms(g, opts).0
+}
+
+/// Same as [norm_squared_with], also returning the number of terms
+fn norm_squared_terms(g: &impl GraphLike, opts: SimulateOptions) -> (ScalarN, usize) {
+    assert!(g.inputs().is_empty(), "a state can't have inputs");
+    let mut ket = Graph::new();
+    let vmap = ket.append_graph(g);
+    ket.set_outputs(g.outputs().iter().map(|o| vmap[o]).collect());
+    full_simp(&mut ket);
+
+    let (mut d, bra) = doubled(&ket);
+    let pairs: Vec<(V, V)> = ket
+        .vertices()
+        .filter(|&v| is_t_pair(&d, v, bra[&v]))
+        .map(|v| (v, bra[&v]))
+        .collect();
+    full_simp(&mut d);
+
+    let mut scalar = ScalarN::zero();
+    let mut nterms = 0;
+    let mut stack = vec![d];
+    while let Some(g) = stack.pop() {
+        if g.is_zero() {
+            continue;
+        }
+        match pairs.iter().find(|&&(v, w)| is_t_pair(&g, v, w)) {
+            Some(&(v, w)) => {
+                for mut h in pair_terms(&g, v, w) {
+                    full_simp(&mut h);
+                    stack.push(h);
+                }
+            }
+            None => {
+                let (s, n) = contract_terms(g, opts);
+                scalar += s;
+                nterms += n;
+            }
+        }
+    }
+    (scalar, nterms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::ToTensor;
+    use approx::assert_abs_diff_eq;
+
+    /// Check that p is the given real number
+    fn assert_real(p: ScalarN, x: f64) {
+        let p = p.complex_value();
+        assert_abs_diff_eq!(p.re, x, epsilon = 1e-10);
+        assert_abs_diff_eq!(p.im, 0.0, epsilon = 1e-10);
+    }
+
+    fn test_circuit() -> Circuit {
+        Circuit::random()
+            .seed(1337)
+            .qubits(4)
+            .depth(40)
+            .clifford_t(0.3)
+            .build()
+    }
+
+    fn bits(x: usize, n: usize) -> Vec<bool> {
+        (0..n).map(|i| x & (1 << i) != 0).collect()
+    }
+
+    #[test]
+    fn amplitudes_normalised() {
+        let c = test_circuit();
+        let input = [false, true, false, false];
+        let mut total = 0.0;
+        for x in 0..16 {
+            let a = amplitude(&c, &input, &bits(x, 4)).complex_value();
+            total += a.norm_sqr();
+        }
+        assert_abs_diff_eq!(total, 1.0, epsilon = 1e-10);
+    }
+
+    #[test]
+    fn batch_amplitudes() {
+        let c = test_circuit();
+        let outputs: Vec<_> = (0..16).map(|x| bits(x, 4)).collect();
+        let expected: Vec<_> = outputs
+            .iter()
+            .map(|x| amplitude(&c, &[false; 4], x))
+            .collect();
+        assert_eq!(amplitudes(&c, &outputs), expected);
+
+        let opts = SimulateOptions {
+            parallel_batch: true,
+            ..Default::default()
+        };
+        assert_eq!(amplitudes_with(&c, &outputs, opts), expected);
+        assert!(amplitudes(&c, &[]).is_empty());
+    }
+
+    #[test]
+    fn probability_matches_amplitude() {
+        let c = test_circuit();
+        let input = [true, false, false, true];
+        let output = bits(5, 4);
+        let a = amplitude(&c, &input, &output).complex_value();
+        let mask: Vec<_> = output.iter().map(|&b| Some(b)).collect();
+        assert_real(probability(&c, &input, &mask), a.norm_sqr());
+    }
+
+    #[test]
+    fn marginal_probability() {
+        let c = test_circuit();
+        let input = [false; 4];
+        // qubit 1 is 1, qubit 3 is 0, and the others are summed over
+        let mask = [None, Some(true), None, Some(false)];
+        let mut expected = 0.0;
+        for x in 0..16 {
+            let output = bits(x, 4);
+            if output[1] && !output[3] {
+                expected += amplitude(&c, &input, &output).complex_value().norm_sqr();
+            }
+        }
+        assert_real(probability(&c, &input, &mask), expected);
+        assert_real(probability(&c, &input, &[None; 4]), 1.0);
+    }
+
+    #[test]
+    fn sample_frequencies() {
+        let c = test_circuit();
+        let shots = 2000;
+        let samples = sample(&c, shots, 42);
+        assert_eq!(samples.len(), shots);
+
+        let mut counts = [0; 16];
+        for s in &samples {
+            let x = (0..4).filter(|&i| s[i]).map(|i| 1 << i).sum::<usize>();
+            counts[x] += 1;
+        }
+        for (x, &n) in counts.iter().enumerate() {
+            let p = amplitude(&c, &[false; 4], &bits(x, 4))
+                .complex_value()
+                .norm_sqr();
+            let freq = n as f64 / shots as f64;
+            // allow 4 standard deviations, plus a little for tiny p
+            let tol = 4.0 * (p * (1.0 - p) / shots as f64).sqrt() + 1e-3;
+            assert!(
+                (freq - p).abs() < tol,
+                "outcome {}: frequency {}, probability {}",
+                x,
+                freq,
+                p
+            );
+        }
+
+        assert_eq!(sample(&c, 50, 42), samples[..50]);
+    }
+
+    #[test]
+    fn expectation_single_qubit() {
+        // T H|0> = (|0> + e^{iπ/4}|1>)/√2
+        let mut c = Circuit::new(2);
+        c.h(0).t(0);
+        assert_real(expectation(&c, &[(Pauli::X, 0)]), 0.5f64.sqrt());
+        assert_real(expectation(&c, &[(Pauli::Y, 0)]), 0.5f64.sqrt());
+        assert_real(expectation(&c, &[(Pauli::Z, 0)]), 0.0);
+        assert_real(expectation(&c, &[(Pauli::Z, 1)]), 1.0);
+        assert_real(expectation(&c, &[]), 1.0);
+        // X then Z gives ZX = iY
+        let e = expectation(&c, &[(Pauli::X, 0), (Pauli::Z, 0)]).complex_value();
+        assert_abs_diff_eq!(e.re, 0.0, epsilon = 1e-10);
+        assert_abs_diff_eq!(e.im, 0.5f64.sqrt(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn expectation_matches_probabilities() {
+        let c = test_circuit();
+        let zz = vec![(Pauli::Z, 0), (Pauli::Z, 2)];
+        let x = vec![(Pauli::X, 1)];
+        let mut expected = 0.0;
+        for x in 0..16 {
+            let output = bits(x, 4);
+            let p = amplitude(&c, &[false; 4], &output)
+                .complex_value()
+                .norm_sqr();
+            expected += if output[0] == output[2] { p } else { -p };
+        }
+        assert_real(expectation(&c, &zz), expected);
+
+        let e = expectation_sum(&c, &[(0.5, zz), (-2.0, x.clone())]);
+        assert_eq!(e.terms.len(), 2);
+        assert_real(e.terms[0].clone(), expected);
+        let ex = expectation(&c, &x);
+        assert_eq!(e.terms[1], ex);
+        assert_abs_diff_eq!(
+            e.value,
+            0.5 * expected - 2.0 * ex.complex_value().re,
+            epsilon = 1e-10
+        );
+    }
+
+    /// The state `c|0...0>` with the first few outputs post-selected on |+>
+    fn post_selected(qubits: usize, depth: usize, post: usize, seed: u64) -> Graph {
+        let c = Circuit::random()
+            .seed(seed)
+            .qubits(qubits)
+            .depth(depth)
+            .p_t(0.2)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        g.plug_inputs(&vec![BasisElem::Z0; qubits]);
+        g.plug_outputs(&vec![BasisElem::X0; post]);
+        g
+    }
+
+    #[test]
+    fn norm_squared_matches_tensor() {
+        for (qubits, depth, post) in [(4, 100, 2), (5, 150, 2), (6, 120, 3)] {
+            for seed in 1..4 {
+                let g = post_selected(qubits, depth, post, seed);
+                let expected: f64 = g.to_tensorf().iter().map(|z| z.norm_sqr()).sum();
+                assert_real(norm_squared(&g), expected);
+
+                let mut h = crate::hash_graph::Graph::new();
+                let vmap = h.append_graph(&g);
+                h.set_outputs(g.outputs().iter().map(|o| vmap[o]).collect());
+                assert_eq!(norm_squared(&h), norm_squared(&g));
+            }
+        }
+    }
+
+    #[test]
+    fn norm_squared_pairs() {
+        let g = post_selected(6, 200, 3, 3);
+        let mut k = g.clone();
+        full_simp(&mut k);
+        assert!(k.tcount() >= 19);
+
+        let opts = SimulateOptions::default();
+        let (s, n) = norm_squared_terms(&g, opts);
+        let (s0, n0) = contract_terms(doubled(&g).0, opts);
+        assert_eq!(s, s0);
+        assert!(n < n0, "{} terms with pairs, {} without", n, n0);
+    }
+
+    #[test]
+    fn options_agree() {
+        let c = test_circuit();
+        let input = [false; 4];
+        let mask = [Some(false), None, Some(true), None];
+        let p = probability(&c, &input, &mask);
+        for use_cats in [Some(false), Some(true)] {
+            for parallel_depth in [None, Some(2)] {
+                let opts = SimulateOptions {
+                    use_cats,
+                    parallel_depth,
+                    ..Default::default()
+                };
+                assert_eq!(probability_with(&c, &input, &mask, opts), p);
+            }
+        }
+    }
+}
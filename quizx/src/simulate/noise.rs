@@ -0,0 +1,382 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Expectation values under depolarizing noise, for [noisy_expectation].
+
+use super::{contract, pauli_circuit, Pauli, SimulateOptions};
+use crate::circuit::Circuit;
+use crate::gate::{GType, Gate};
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::simplify::full_simp;
+use crate::vec_graph::Graph;
+
+use num::Zero;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustc_hash::FxHashMap;
+
+const PAULIS: [Pauli; 3] = [Pauli::X, Pauli::Y, Pauli::Z];
+
+/// Depolarizing noise after gates, for [noisy_expectation]
+///
+/// After a gate with error rate p, each qubit it acts on independently gets an
+/// X, Y or Z error, each with probability p/3. Barriers and post-selections
+/// never get errors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NoiseModel {
+    rate: f64,
+    gate_rates: FxHashMap<GType, f64>,
+}
+
+impl NoiseModel {
+    /// Noise with the same error rate after every gate
+    pub fn depolarizing(p: f64) -> Self {
+        NoiseModel {
+            rate: p,
+            gate_rates: FxHashMap::default(),
+        }
+    }
+
+    /// Set the error rate after gates of the given type
+    pub fn gate_rate(&mut self, t: GType, p: f64) -> &mut Self {
+        self.gate_rates.insert(t, p);
+        self
+    }
+
+    /// The error rate after the given gate
+    pub fn rate(&self, gate: &Gate) -> f64 {
+        match gate.t {
+            GType::Barrier | GType::PostSelect => 0.0,
+            t => self.gate_rates.get(&t).copied().unwrap_or(self.rate),
+        }
+    }
+}
+
+/// The result of [noisy_expectation]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoisyExpectation {
+    /// The mean of the sampled expectation values
+    pub value: f64,
+    /// The standard error of the mean
+    pub std_error: f64,
+}
+
+/// The simplified diagram for a noiseless expectation value, with a leg for
+/// each place a Pauli error can go
+///
+/// An error site is a qubit just after a gate with a non-zero error rate. At
+/// each site, the circuit and its adjoint get a phase-free Z spider and then
+/// a phase-free X spider, each with an extra leg. The legs are the outputs of
+/// the graph, four for each site: the Z and X legs in the circuit, then the
+/// ones in the adjoint. Boundaries are never removed by simplification, so
+/// this keeps track of where the error spiders went.
+struct NoisySandwich {
+    graph: Graph,
+    /// The error rate at each site
+    rates: Vec<f64>,
+}
+
+impl NoisySandwich {
+    fn new(c: &Circuit, observable: &[(Pauli, usize)], noise: &NoiseModel) -> Self {
+        let n = c.num_qubits();
+        let qubits: Vec<usize> = (0..n).collect();
+        let mut ket = Circuit::new(n);
+        let mut rates = vec![];
+        // the index in ket of the Z spider at each site
+        let mut sites = vec![];
+        for gate in &c.gates {
+            ket.push(gate.clone());
+            let p = noise.rate(gate);
+            if p > 0.0 {
+                for &q in &gate.qs {
+                    sites.push(ket.num_gates());
+                    ket.rz(q, Phase::zero()).rx(q, Phase::zero());
+                    rates.push(p);
+                }
+            }
+        }
+
+        let (p, phase) = pauli_circuit(n, observable);
+        let mut s = ket.clone();
+        s.add_circuit(&p, &qubits)
+            .add_circuit(&ket.to_adjoint(), &qubits);
+        let (mut graph, spiders): (Graph, _) = s.to_graph_with_spiders();
+        graph.plug_inputs(&vec![BasisElem::Z0; graph.inputs().len()]);
+        graph.plug_outputs(&vec![BasisElem::Z0; graph.outputs().len()]);
+        graph.scalar_mut().mul_phase(phase);
+
+        // the gate of the adjoint which gate i of ket ends up as
+        let bra = |i: usize| s.num_gates() - 1 - i;
+        let mut legs = Vec::with_capacity(4 * sites.len());
+        for &i in &sites {
+            for j in [i, i + 1, bra(i), bra(i + 1)] {
+                let v = spiders[j].expect("Error site on a removed qubit");
+                let b = graph.add_vertex(VType::B);
+                graph.add_edge(v, b);
+                legs.push(b);
+            }
+        }
+        graph.set_outputs(legs);
+        full_simp(&mut graph);
+
+        NoisySandwich { graph, rates }
+    }
+
+    /// The expectation value with the given errors, each at the site with
+    /// the given index
+    ///
+    /// A Z error turns the Z legs at its site into π-phase Z spiders, which
+    /// then fuse into the spiders the legs are attached to, and no error
+    /// gives phase-free spiders. X errors do the same with the X legs, and
+    /// Y errors with both, up to a global phase which cancels with the one
+    /// in the adjoint. The spiders plugged in are not normalised, so the
+    /// scalar is unchanged.
+    fn value(&self, errors: &[(usize, Pauli)], opts: SimulateOptions) -> f64 {
+        let mut g = self.graph.clone();
+        let mut flips = vec![(false, false); self.rates.len()];
+        for &(site, pauli) in errors {
+            flips[site] = (pauli != Pauli::Z, pauli != Pauli::X);
+        }
+        let legs = g.outputs().clone();
+        for (legs, (x, z)) in legs.chunks(4).zip(flips) {
+            let zb = if z { BasisElem::X1 } else { BasisElem::X0 };
+            let xb = if x { BasisElem::Z1 } else { BasisElem::Z0 };
+            for (&v, b) in legs.iter().zip([zb, xb, zb, xb]) {
+                g.plug_vertex(v, b);
+            }
+        }
+        g.set_outputs(vec![]);
+        contract(g, opts).complex_value().re
+    }
+}
+
+/// Estimate the expectation value `<0...0|c^dagger P c|0...0>` of a Pauli
+/// string `P` under depolarizing noise
+///
+/// Each sample draws Pauli errors after the gates of `c` as given by
+/// `noise`, and computes the expectation value of the noisy circuit exactly,
+/// as [expectation](super::expectation) does. The result is the mean over
+/// all samples, with its standard error.
+///
+/// The diagram of the noiseless circuit is only built and simplified once,
+/// with an extra spider on each qubit after each noisy gate, attached to a
+/// leg which is kept through simplification. Each sample then plugs the legs
+/// to put the errors on those spiders, and finishes off the simplification
+/// and decomposition. The value for each set of errors is only computed
+/// once, so this is cheap when errors are rare.
+///
+/// # Panics
+///
+/// Panics if `samples` is zero or a qubit is out of range.
+pub fn noisy_expectation(
+    c: &Circuit,
+    observable: &[(Pauli, usize)],
+    noise: &NoiseModel,
+    samples: usize,
+    seed: u64,
+) -> NoisyExpectation {
+    noisy_expectation_with(
+        c,
+        observable,
+        noise,
+        samples,
+        seed,
+        SimulateOptions::default(),
+    )
+}
+
+/// Like [noisy_expectation], with the given options
+pub fn noisy_expectation_with(
+    c: &Circuit,
+    observable: &[(Pauli, usize)],
+    noise: &NoiseModel,
+    samples: usize,
+    seed: u64,
+    opts: SimulateOptions,
+) -> NoisyExpectation {
+    assert!(samples > 0, "At least one sample is needed");
+    let sandwich = NoisySandwich::new(c, observable, noise);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut cache: FxHashMap<Vec<(usize, Pauli)>, f64> = FxHashMap::default();
+
+    let values: Vec<f64> = (0..samples)
+        .map(|_| {
+            let errors: Vec<_> = sandwich
+                .rates
+                .iter()
+                .enumerate()
+                .filter_map(|(site, &p)| {
+                    if rng.gen::<f64>() < p {
+                        Some((site, PAULIS[rng.gen_range(0..3)]))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            *cache
+                .entry(errors)
+                .or_insert_with_key(|errors| sandwich.value(errors, opts))
+        })
+        .collect();
+
+    let n = samples as f64;
+    let value = values.iter().sum::<f64>() / n;
+    let std_error = if samples > 1 {
+        let var = values.iter().map(|x| (x - value).powi(2)).sum::<f64>() / (n - 1.0);
+        (var / n).sqrt()
+    } else {
+        0.0
+    };
+    NoisyExpectation { value, std_error }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate::expectation;
+    use crate::tensor::ToTensor;
+    use approx::assert_abs_diff_eq;
+    use ndarray::Array2;
+    use num::complex::Complex;
+
+    type Matrix = Array2<Complex<f64>>;
+
+    fn test_circuit() -> Circuit {
+        Circuit::random()
+            .seed(42)
+            .qubits(3)
+            .depth(12)
+            .clifford_t(0.3)
+            .build()
+    }
+
+    /// The matrix of a circuit, with qubit 0 as the most significant bit
+    fn matrix(c: &Circuit) -> Matrix {
+        let d = 1 << c.num_qubits();
+        let t = c.to_tensorf().into_shape_with_order((d, d)).unwrap();
+        t.reversed_axes()
+    }
+
+    fn dagger(m: &Matrix) -> Matrix {
+        m.t().mapv(|z| z.conj())
+    }
+
+    /// The noisy expectation value, computed with density matrices
+    fn density_matrix_expectation(c: &Circuit, observable: &[(Pauli, usize)], p: f64) -> f64 {
+        let n = c.num_qubits();
+        let d = 1 << n;
+        let mut rho = Matrix::zeros((d, d));
+        rho[[0, 0]] = Complex::new(1.0, 0.0);
+        for gate in &c.gates {
+            let mut gc = Circuit::new(n);
+            gc.push(gate.clone());
+            let u = matrix(&gc);
+            rho = u.dot(&rho).dot(&dagger(&u));
+            for &q in &gate.qs {
+                let mut noisy = rho.mapv(|z| z * (1.0 - p));
+                for pauli in PAULIS {
+                    let e = matrix(&pauli_circuit(n, &[(pauli, q)]).0);
+                    noisy = noisy + e.dot(&rho).dot(&dagger(&e)).mapv(|z| z * (p / 3.0));
+                }
+                rho = noisy;
+            }
+        }
+
+        let (oc, phase) = pauli_circuit(n, observable);
+        let phase = *phase.numer() as f64 / *phase.denom() as f64;
+        let o = matrix(&oc) * Complex::from_polar(1.0, std::f64::consts::PI * phase);
+        o.dot(&rho).diag().sum().re
+    }
+
+    #[test]
+    fn noiseless() {
+        let c = test_circuit();
+        let obs = [(Pauli::Z, 0), (Pauli::X, 2)];
+        let e = noisy_expectation(&c, &obs, &NoiseModel::default(), 10, 0);
+        assert_abs_diff_eq!(
+            e.value,
+            expectation(&c, &obs).complex_value().re,
+            epsilon = 1e-10
+        );
+        assert_eq!(e.std_error, 0.0);
+        assert_abs_diff_eq!(
+            e.value,
+            density_matrix_expectation(&c, &obs, 0.0),
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn single_errors() {
+        // an error at each site is the same as adding the Pauli to the circuit
+        let c = test_circuit();
+        let obs = [(Pauli::Y, 1)];
+        let sandwich = NoisySandwich::new(&c, &obs, &NoiseModel::depolarizing(0.1));
+        let mut site = 0;
+        for (i, gate) in c.gates.iter().enumerate() {
+            for &q in &gate.qs {
+                for pauli in PAULIS {
+                    let mut c1 = Circuit::new(c.num_qubits());
+                    c1.gates = c.gates.clone();
+                    let e = pauli_circuit(c.num_qubits(), &[(pauli, q)]).0;
+                    for (j, g) in e.gates.into_iter().enumerate() {
+                        c1.gates.insert(i + 1 + j, g);
+                    }
+                    assert_abs_diff_eq!(
+                        sandwich.value(&[(site, pauli)], SimulateOptions::default()),
+                        expectation(&c1, &obs).complex_value().re,
+                        epsilon = 1e-10
+                    );
+                }
+                site += 1;
+            }
+        }
+        assert_eq!(site, sandwich.rates.len());
+    }
+
+    #[test]
+    fn matches_density_matrix() {
+        let c = test_circuit();
+        let p = 0.05;
+        for obs in [
+            vec![(Pauli::Z, 0)],
+            vec![(Pauli::Y, 1)],
+            vec![(Pauli::X, 2)],
+        ] {
+            let e = noisy_expectation(&c, &obs, &NoiseModel::depolarizing(p), 2000, 1);
+            let exact = density_matrix_expectation(&c, &obs, p);
+            assert!(e.std_error > 0.0);
+            assert!(
+                (e.value - exact).abs() <= 4.0 * e.std_error,
+                "{} is not within 4 standard errors ({}) of {}",
+                e.value,
+                e.std_error,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn gate_rates() {
+        let mut noise = NoiseModel::depolarizing(0.01);
+        noise.gate_rate(GType::CNOT, 0.1).gate_rate(GType::T, 0.0);
+        assert_eq!(noise.rate(&Gate::new(GType::CNOT, vec![0, 1])), 0.1);
+        assert_eq!(noise.rate(&Gate::new(GType::T, vec![0])), 0.0);
+        assert_eq!(noise.rate(&Gate::new(GType::HAD, vec![0])), 0.01);
+        assert_eq!(noise.rate(&Gate::new(GType::Barrier, vec![0])), 0.0);
+    }
+}
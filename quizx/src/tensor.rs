@@ -121,8 +121,17 @@ pub trait QubitOps<A: TensorElem> {
     fn cphase_at(&mut self, p: impl Into<Phase>, qs: &[usize]);
     fn hadamard_at(&mut self, i: usize);
 
+    /// multiply by a W-node on the given indices, where `qs[0]` is the output
+    ///
+    /// The W-node is 1 where the output is the sum of the inputs, and at most
+    /// one input is 1, and 0 elsewhere.
+    fn w_at(&mut self, qs: &[usize]);
+
     /// split into two non-overlapping pieces, where index q=0 and q=1
-    fn slice_qubit_mut(&mut self, q: usize) -> (ArrayViewMut<A, IxDyn>, ArrayViewMut<A, IxDyn>);
+    fn slice_qubit_mut(
+        &mut self,
+        q: usize,
+    ) -> (ArrayViewMut<'_, A, IxDyn>, ArrayViewMut<'_, A, IxDyn>);
 
     /// contract the last n qubit indices with the first n qubits of other
     ///
@@ -177,7 +186,10 @@ impl<A: TensorElem> CompareTensors for Tensor<A> {
 }
 
 impl<A: TensorElem> QubitOps<A> for Tensor<A> {
-    fn slice_qubit_mut(&mut self, q: usize) -> (ArrayViewMut<A, IxDyn>, ArrayViewMut<A, IxDyn>) {
+    fn slice_qubit_mut(
+        &mut self,
+        q: usize,
+    ) -> (ArrayViewMut<'_, A, IxDyn>, ArrayViewMut<'_, A, IxDyn>) {
         let slice0: SliceInfo<_, IxDyn, IxDyn> =
             SliceInfo::try_from(Vec::from_iter((0..self.ndim()).map(|i| {
                 if i == q {
@@ -263,6 +275,28 @@ impl<A: TensorElem> QubitOps<A> for Tensor<A> {
         *self *= &cp;
     }
 
+    fn w_at(&mut self, qs: &[usize]) {
+        let mut shape: Vec<usize> = vec![1; self.ndim()];
+        for &q in qs {
+            shape[q] = 2;
+        }
+        // the reshape below takes the indices in increasing order
+        let mut sorted = qs.to_vec();
+        sorted.sort_unstable();
+        let out = sorted.iter().position(|&q| q == qs[0]).unwrap();
+        let w: Tensor<A> = Tensor::from_shape_fn(vec![2; qs.len()], |ix| {
+            let ones = (0..qs.len()).filter(|&i| i != out && ix[i] == 1).count();
+            if ones == ix[out] {
+                A::one()
+            } else {
+                A::zero()
+            }
+        })
+        .into_shape_with_order(shape)
+        .expect("Bad indices for w_at");
+        *self *= &w;
+    }
+
     fn hadamard_at(&mut self, q: usize) {
         let n = A::one_over_sqrt2();
         let minus = A::from_phase(1); // -1 = e^(i pi)
@@ -308,10 +342,68 @@ impl<A: TensorElem> QubitOps<A> for Tensor<A> {
     }
 }
 
+/// Replace each W-node by a Z-spider on each of its legs
+///
+/// Returns the spiders on the legs of each W-node, with the output first.
+fn expand_w_nodes(g: &mut impl GraphLike) -> Vec<Vec<V>> {
+    // move the edges of a W vertex other than the Wio one onto new spiders
+    fn legs(g: &mut impl GraphLike, v: V) -> Vec<V> {
+        let edges: Vec<_> = g
+            .incident_edges(v)
+            .filter(|&(_, et)| et != EType::Wio)
+            .collect();
+        edges
+            .into_iter()
+            .map(|(w, et)| {
+                let z = g.add_vertex(VType::Z);
+                g.remove_edge(v, w);
+                g.add_edge_with_type(z, w, et);
+                z
+            })
+            .collect()
+    }
+
+    let outputs: Vec<V> = g
+        .vertices()
+        .filter(|&v| g.vertex_type(v) == VType::WOutput)
+        .collect();
+    let mut ws = Vec::with_capacity(outputs.len());
+    for o in outputs {
+        let i = g
+            .incident_edges(o)
+            .find(|&(w, et)| et == EType::Wio && g.vertex_type(w) == VType::WInput)
+            .expect("W output without a W input")
+            .0;
+        let mut vs = legs(g, o);
+        if vs.len() != 1 {
+            panic!("W output must have exactly one leg, got {}", vs.len());
+        }
+        vs.extend(legs(g, i));
+        g.remove_vertex(o);
+        g.remove_vertex(i);
+        ws.push(vs);
+    }
+    ws
+}
+
 impl<G: GraphLike + Clone> ToTensor for G {
     fn to_tensor<A: TensorElem>(&self) -> Tensor<A> {
+        // a discard gives a mixed state, which isn't a single tensor
+        if let Some(v) = self.vertices().find(|&v| self.is_ground(v)) {
+            panic!("Grounded vertices are unsupported: {}", v);
+        }
         let mut g = self.clone();
         g.x_to_z();
+        let ws = expand_w_nodes(&mut g);
+        let mut w_of: FxHashMap<V, usize> = FxHashMap::default();
+        for (i, vs) in ws.iter().enumerate() {
+            for &v in vs {
+                w_of.insert(v, i);
+            }
+        }
+        // the legs of a W-node count it as an extra neighbour
+        let degree = |g: &G, v: V| g.degree(v) + w_of.contains_key(&v) as usize;
+
         // H-boxes are not implemented yet
         for v in g.vertices() {
             let t = g.vertex_type(v);
@@ -377,14 +469,36 @@ impl<G: GraphLike + Clone> ToTensor for G {
                         // num_had += 1;
                     }
 
-                    if g.vertex_type(w) != VType::B && g.degree(w) == *deg_w {
+                    if g.vertex_type(w) != VType::B && degree(&g, w) == *deg_w {
                         a = a.sum_axis(Axis(wi));
                         indexv.remove(wi);
                     }
                 }
             }
 
-            if g.vertex_type(v) != VType::B && g.degree(v) == deg_v {
+            // once all the legs of a W-node are there, multiply it in
+            if let Some(&i) = w_of.get(&v) {
+                if ws[i].iter().all(|w| *w == v || seenv.contains_key(w)) {
+                    let pos: Vec<usize> = ws[i]
+                        .iter()
+                        .map(|w| indexv.iter().position(|x| x == w).unwrap())
+                        .collect();
+                    a.w_at(&pos);
+                    deg_v += 1;
+                    for w in &ws[i] {
+                        if let Some(deg_w) = seenv.get_mut(w) {
+                            *deg_w += 1;
+                            if degree(&g, *w) == *deg_w {
+                                let wi = indexv.iter().position(|x| x == w).unwrap();
+                                a = a.sum_axis(Axis(wi));
+                                indexv.remove(wi);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if g.vertex_type(v) != VType::B && degree(&g, v) == deg_v {
                 a = a.sum_axis(Axis(0));
                 indexv.remove(0);
             }
@@ -456,10 +570,11 @@ impl ToTensor for Circuit {
                 PostSelect => {
                     panic!("Unsupported gate: PostSelect")
                 }
+                Barrier => {}
                 UnknownGate => {} // unknown gates are quietly ignored
             }
         }
-        a
+        a * A::from_scalar(&self.scalar)
     }
 }
 
@@ -523,6 +638,79 @@ mod tests {
         assert_eq!(t, Tensor::delta(4));
     }
 
+    /// A W-node with inputs the given vertices, returning its output
+    fn add_w(g: &mut Graph, inputs: &[V]) -> V {
+        let w_in = g.add_vertex(VType::WInput);
+        let w_out = g.add_vertex(VType::WOutput);
+        for &v in inputs {
+            g.add_edge(v, w_in);
+        }
+        g.add_edge_with_type(w_in, w_out, EType::Wio);
+        w_out
+    }
+
+    #[test]
+    #[should_panic(expected = "Grounded vertices are unsupported")]
+    fn tensor_ground() {
+        let mut g = Graph::new();
+        let v = g.add_vertex(VType::Z);
+        g.set_ground(v, true);
+        g.to_tensor4();
+    }
+
+    #[test]
+    fn tensor_w() {
+        for n in 1..4 {
+            let mut g = Graph::new();
+            let inputs: Vec<_> = (0..n).map(|_| g.add_vertex(VType::B)).collect();
+            let w = add_w(&mut g, &inputs);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(w, o);
+            g.set_inputs(inputs);
+            g.set_outputs(vec![o]);
+
+            let t = g.to_tensor4();
+            assert_eq!(t.shape(), vec![2; n + 1].as_slice());
+            for (ix, x) in t.indexed_iter() {
+                let ones = (0..n).filter(|&i| ix[i] == 1).count();
+                let expected = if ones == ix[n] { 1 } else { 0 };
+                assert_eq!(*x, Scalar4::from_int_coeffs(&[expected]), "{:?}", ix);
+            }
+            if n == 1 {
+                assert_eq!(t, Tensor::ident(1));
+            }
+        }
+
+        // W-nodes are associative, whether joined directly or through two
+        // Hadamard edges
+        let mut g1 = Graph::new();
+        let inputs: Vec<_> = (0..3).map(|_| g1.add_vertex(VType::B)).collect();
+        let w = add_w(&mut g1, &inputs);
+        let o = g1.add_vertex(VType::B);
+        g1.add_edge(w, o);
+        g1.set_inputs(inputs);
+        g1.set_outputs(vec![o]);
+
+        for direct in [true, false] {
+            let mut g = Graph::new();
+            let inputs: Vec<_> = (0..3).map(|_| g.add_vertex(VType::B)).collect();
+            let mut w = add_w(&mut g, &inputs[1..]);
+            if !direct {
+                let h0 = g.add_vertex(VType::Z);
+                let h1 = g.add_vertex(VType::Z);
+                g.add_edge_with_type(w, h0, EType::H);
+                g.add_edge_with_type(h0, h1, EType::H);
+                w = h1;
+            }
+            let w1 = add_w(&mut g, &[inputs[0], w]);
+            let o = g.add_vertex(VType::B);
+            g.add_edge(w1, o);
+            g.set_inputs(inputs);
+            g.set_outputs(vec![o]);
+            assert_eq!(g.to_tensor4(), g1.to_tensor4());
+        }
+    }
+
     #[test]
     fn tensor_cz() {
         let mut g = Graph::new();
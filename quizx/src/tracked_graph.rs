@@ -0,0 +1,557 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A graph wrapper which records changes so they can be undone
+//!
+//! [TrackedGraph] implements [GraphLike] by passing everything through to the
+//! graph it wraps, and logging enough about each change to reverse it. This
+//! makes it cheap to try a rewrite and back it out again: rolling back a local
+//! complementation costs about as much as applying it, rather than a copy of
+//! the whole graph.
+//!
+//! ```
+//! # use quizx::graph::*;
+//! # use quizx::vec_graph::Graph;
+//! # use quizx::basic_rules::local_comp;
+//! # use quizx::tracked_graph::TrackedGraph;
+//! let mut g = TrackedGraph::from_graph(Graph::new());
+//! let vs: Vec<_> = (0..4).map(|_| g.add_vertex(VType::Z)).collect();
+//! for &w in &vs[1..] {
+//!     g.add_edge_with_type(vs[0], w, EType::H);
+//! }
+//! g.set_phase(vs[0], (1, 2));
+//!
+//! let mark = g.checkpoint();
+//! local_comp(&mut g, vs[0]);
+//! assert_eq!(g.num_vertices(), 3);
+//! g.rollback(mark).unwrap();
+//! assert_eq!(g.num_vertices(), 4);
+//! ```
+
+use crate::graph::*;
+use crate::phase::Phase;
+use crate::scalar::*;
+use derive_more::{Display, Error};
+use std::collections::VecDeque;
+
+/// Default maximum number of changes a [TrackedGraph] remembers
+pub const DEFAULT_MAX_LOG_LEN: usize = 1 << 20;
+
+/// A point in the history of a [TrackedGraph], which can be rolled back to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mark(usize);
+
+/// An error from [TrackedGraph::rollback]
+#[derive(Debug, Display, Error, PartialEq, Eq)]
+pub enum RollbackError {
+    /// Changes made after the mark have been dropped from the log.
+    #[display("Cannot roll back to change {mark}, the oldest change kept is {oldest}")]
+    Compacted { mark: usize, oldest: usize },
+    /// The mark is later than the current state, e.g. because it was taken
+    /// before an earlier rollback.
+    #[display("Cannot roll back to change {mark}, there are only {len} changes")]
+    Ahead { mark: usize, len: usize },
+}
+
+/// A single change to a graph, holding what is needed to undo it
+#[derive(Debug, Clone)]
+enum Change {
+    AddVertex(V),
+    RemoveVertex(V, VData),
+    AddEdge(V, V),
+    RemoveEdge(V, V, EType),
+    EdgeType(V, V, EType),
+    VertexData(V, VData),
+    Inputs(Vec<V>),
+    Outputs(Vec<V>),
+    Scalar(ScalarN),
+    TrackScalar(bool),
+}
+
+/// A graph which logs its changes, so they can be rolled back
+///
+/// The log holds at most [max_log_len](TrackedGraph::max_log_len) changes. Once
+/// it is full, the oldest changes are dropped, and it is no longer possible to
+/// roll back past them.
+#[derive(Debug, Clone)]
+pub struct TrackedGraph<G: GraphLike> {
+    g: G,
+    log: VecDeque<Change>,
+    dropped: usize,
+    max_log_len: usize,
+}
+
+impl<G: GraphLike> TrackedGraph<G> {
+    /// Start tracking changes to g
+    pub fn from_graph(g: G) -> Self {
+        TrackedGraph {
+            g,
+            log: VecDeque::new(),
+            dropped: 0,
+            max_log_len: DEFAULT_MAX_LOG_LEN,
+        }
+    }
+
+    /// The wrapped graph
+    pub fn graph(&self) -> &G {
+        &self.g
+    }
+
+    /// Stop tracking changes and return the wrapped graph
+    pub fn into_graph(self) -> G {
+        self.g
+    }
+
+    /// Maximum number of changes kept in the log
+    pub fn max_log_len(&self) -> usize {
+        self.max_log_len
+    }
+
+    /// Set the maximum number of changes kept in the log
+    ///
+    /// If the log is longer than this, the oldest changes are dropped.
+    pub fn set_max_log_len(&mut self, max_log_len: usize) {
+        self.max_log_len = max_log_len;
+        self.trim();
+    }
+
+    /// Number of changes currently in the log
+    pub fn log_len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Drop the whole log, so the current state can no longer be rolled back
+    pub fn compact(&mut self) {
+        self.dropped += self.log.len();
+        self.log.clear();
+    }
+
+    /// The current point in the history
+    pub fn checkpoint(&self) -> Mark {
+        Mark(self.dropped + self.log.len())
+    }
+
+    /// Undo all the changes made since `mark` was taken
+    ///
+    /// Fails, leaving the graph unchanged, if some of these changes have been
+    /// dropped from the log. Marks taken after `mark` should not be used again,
+    /// as later changes will reuse their positions in the history.
+    ///
+    /// This restores the vertices, edges, data, boundaries and scalar of the
+    /// graph. Internal details, such as the order of neighbours or the next
+    /// fresh vertex index, may differ.
+    pub fn rollback(&mut self, mark: Mark) -> Result<(), RollbackError> {
+        let len = self.dropped + self.log.len();
+        if mark.0 > len {
+            return Err(RollbackError::Ahead { mark: mark.0, len });
+        }
+        if mark.0 < self.dropped {
+            return Err(RollbackError::Compacted {
+                mark: mark.0,
+                oldest: self.dropped,
+            });
+        }
+
+        for _ in mark.0..len {
+            let c = self.log.pop_back().unwrap();
+            self.undo(c);
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, c: Change) {
+        match c {
+            Change::AddVertex(v) => self.g.remove_vertex(v),
+            Change::RemoveVertex(v, d) => self.g.add_vertex_at(v, d),
+            Change::AddEdge(s, t) => self.g.remove_edge(s, t),
+            Change::RemoveEdge(s, t, et) => self.g.add_edge_with_type(s, t, et),
+            Change::EdgeType(s, t, et) => self.g.set_edge_type(s, t, et),
+            Change::VertexData(v, d) => {
+                self.g.set_vertex_type(v, d.ty);
+                self.g.set_phase(v, d.phase);
+                self.g.set_qubit(v, d.qubit);
+                self.g.set_row(v, d.row);
+                self.g.set_ground(v, d.ground);
+            }
+            Change::Inputs(inputs) => self.g.set_inputs(inputs),
+            Change::Outputs(outputs) => self.g.set_outputs(outputs),
+            Change::Scalar(s) => *self.g.scalar_mut() = s,
+            Change::TrackScalar(track) => self.g.set_track_scalar(track),
+        }
+    }
+
+    fn record(&mut self, c: Change) {
+        self.log.push_back(c);
+        self.trim();
+    }
+
+    fn record_vertex(&mut self, v: V) {
+        self.record(Change::VertexData(v, self.g.vertex_data(v)));
+    }
+
+    fn trim(&mut self) {
+        while self.log.len() > self.max_log_len {
+            self.log.pop_front();
+            self.dropped += 1;
+        }
+    }
+}
+
+impl<G: GraphLike> GraphLike for TrackedGraph<G> {
+    fn new() -> Self {
+        TrackedGraph::from_graph(G::new())
+    }
+
+    fn vindex(&self) -> V {
+        self.g.vindex()
+    }
+
+    fn num_vertices(&self) -> usize {
+        self.g.num_vertices()
+    }
+
+    fn num_edges(&self) -> usize {
+        self.g.num_edges()
+    }
+
+    fn vertices(&self) -> VIter<'_> {
+        self.g.vertices()
+    }
+
+    fn edges(&self) -> EIter<'_> {
+        self.g.edges()
+    }
+
+    fn inputs(&self) -> &Vec<V> {
+        self.g.inputs()
+    }
+
+    fn inputs_mut(&mut self) -> &mut Vec<V> {
+        self.record(Change::Inputs(self.g.inputs().clone()));
+        self.g.inputs_mut()
+    }
+
+    fn set_inputs(&mut self, inputs: Vec<V>) {
+        self.record(Change::Inputs(self.g.inputs().clone()));
+        self.g.set_inputs(inputs);
+    }
+
+    fn outputs(&self) -> &Vec<V> {
+        self.g.outputs()
+    }
+
+    fn outputs_mut(&mut self) -> &mut Vec<V> {
+        self.record(Change::Outputs(self.g.outputs().clone()));
+        self.g.outputs_mut()
+    }
+
+    fn set_outputs(&mut self, outputs: Vec<V>) {
+        self.record(Change::Outputs(self.g.outputs().clone()));
+        self.g.set_outputs(outputs);
+    }
+
+    fn add_vertex(&mut self, ty: VType) -> V {
+        let v = self.g.add_vertex(ty);
+        self.record(Change::AddVertex(v));
+        v
+    }
+
+    fn add_vertex_with_data(&mut self, d: VData) -> V {
+        let v = self.g.add_vertex_with_data(d);
+        self.record(Change::AddVertex(v));
+        v
+    }
+
+    fn add_vertex_at(&mut self, v: V, d: VData) {
+        self.g.add_vertex_at(v, d);
+        self.record(Change::AddVertex(v));
+    }
+
+    fn remove_vertex(&mut self, v: V) {
+        // edges are logged separately, so undoing puts the vertex back first
+        for (w, et) in self.g.incident_edge_vec(v) {
+            self.record(Change::RemoveEdge(v, w, et));
+        }
+        self.record(Change::RemoveVertex(v, self.g.vertex_data(v)));
+        self.g.remove_vertex(v);
+    }
+
+    fn add_edge_with_type(&mut self, s: V, t: V, ety: EType) {
+        self.g.add_edge_with_type(s, t, ety);
+        self.record(Change::AddEdge(s, t));
+    }
+
+    fn remove_edge(&mut self, s: V, t: V) {
+        self.record(Change::RemoveEdge(s, t, self.g.edge_type(s, t)));
+        self.g.remove_edge(s, t);
+    }
+
+    fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
+        self.record_vertex(v);
+        self.g.set_phase(v, phase);
+    }
+
+    fn phase(&self, v: V) -> Phase {
+        self.g.phase(v)
+    }
+
+    fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
+        self.record_vertex(v);
+        self.g.add_to_phase(v, phase);
+    }
+
+    fn set_vertex_type(&mut self, v: V, ty: VType) {
+        self.record_vertex(v);
+        self.g.set_vertex_type(v, ty);
+    }
+
+    fn vertex_type(&self, v: V) -> VType {
+        self.g.vertex_type(v)
+    }
+
+    fn vertex_data(&self, v: V) -> VData {
+        self.g.vertex_data(v)
+    }
+
+    fn set_edge_type(&mut self, s: V, t: V, ety: EType) {
+        self.record(Change::EdgeType(s, t, self.g.edge_type(s, t)));
+        self.g.set_edge_type(s, t, ety);
+    }
+
+    fn edge_type_opt(&self, s: V, t: V) -> Option<EType> {
+        self.g.edge_type_opt(s, t)
+    }
+
+    fn set_coord(&mut self, v: V, coord: impl Into<Coord>) {
+        self.record_vertex(v);
+        self.g.set_coord(v, coord);
+    }
+
+    fn coord(&self, v: V) -> Coord {
+        self.g.coord(v)
+    }
+
+    fn set_qubit(&mut self, v: V, qubit: i32) {
+        self.record_vertex(v);
+        self.g.set_qubit(v, qubit);
+    }
+
+    fn qubit(&self, v: V) -> i32 {
+        self.g.qubit(v)
+    }
+
+    fn set_row(&mut self, v: V, row: i32) {
+        self.record_vertex(v);
+        self.g.set_row(v, row);
+    }
+
+    fn row(&self, v: V) -> i32 {
+        self.g.row(v)
+    }
+
+    fn set_ground(&mut self, v: V, ground: bool) {
+        self.record_vertex(v);
+        self.g.set_ground(v, ground);
+    }
+
+    fn neighbors(&self, v: V) -> NeighborIter<'_> {
+        self.g.neighbors(v)
+    }
+
+    fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_> {
+        self.g.incident_edges(v)
+    }
+
+    fn degree(&self, v: V) -> usize {
+        self.g.degree(v)
+    }
+
+    fn scalar(&self) -> &ScalarN {
+        self.g.scalar()
+    }
+
+    fn scalar_mut(&mut self) -> &mut ScalarN {
+        self.record(Change::Scalar(self.g.scalar().clone()));
+        self.g.scalar_mut()
+    }
+
+    fn track_scalar(&self) -> bool {
+        self.g.track_scalar()
+    }
+
+    fn set_track_scalar(&mut self, track: bool) {
+        self.record(Change::TrackScalar(self.g.track_scalar()));
+        self.g.set_track_scalar(track);
+    }
+
+    fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
+    where
+        F: Fn(V, V, EType) -> bool,
+    {
+        self.g.find_edge(f)
+    }
+
+    fn find_vertex<F>(&self, f: F) -> Option<V>
+    where
+        F: Fn(V) -> bool,
+    {
+        self.g.find_vertex(f)
+    }
+
+    fn contains_vertex(&self, v: V) -> bool {
+        self.g.contains_vertex(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::basic_rules::*;
+    use crate::circuit::Circuit;
+    use crate::hash_graph;
+    use crate::simplify::full_simp;
+    use crate::tensor::ToTensor;
+    use crate::vec_graph;
+
+    /// Check g and h have the same vertices, edges, boundaries and scalar
+    fn assert_same_graph(g: &impl GraphLike, h: &impl GraphLike) {
+        let mut vs: Vec<_> = g.vertices().map(|v| (v, g.vertex_data(v))).collect();
+        let mut ws: Vec<_> = h.vertices().map(|v| (v, h.vertex_data(v))).collect();
+        vs.sort_by_key(|&(v, _)| v);
+        ws.sort_by_key(|&(v, _)| v);
+        assert_eq!(vs, ws);
+        let mut es = g.edge_vec();
+        let mut fs = h.edge_vec();
+        es.sort();
+        fs.sort();
+        assert_eq!(es, fs);
+        assert_eq!(g.inputs(), h.inputs());
+        assert_eq!(g.outputs(), h.outputs());
+        assert_eq!(g.scalar(), h.scalar());
+    }
+
+    #[test]
+    fn rollback_pivot() {
+        let c = Circuit::random()
+            .seed(1337)
+            .qubits(5)
+            .depth(60)
+            .clifford_t(0.2)
+            .build();
+        let mut g: hash_graph::Graph = c.to_graph();
+        crate::simplify::spider_simp(&mut g);
+        g.x_to_z();
+
+        let mut tg = TrackedGraph::from_graph(g);
+        let mut pivots = 0;
+        while let Some((s, t, _)) = tg.find_edge(|s, t, _| check_pivot(&tg, s, t)) {
+            let pristine = tg.graph().clone();
+            let mark = tg.checkpoint();
+            pivot_unchecked(&mut tg, s, t);
+            tg.rollback(mark).unwrap();
+            assert_eq!(tg.graph(), &pristine);
+
+            // commit to this pivot, and try the next one
+            pivot_unchecked(&mut tg, s, t);
+            tg.compact();
+            pivots += 1;
+        }
+        assert!(pivots > 0);
+    }
+
+    #[test]
+    fn rollback_nested() {
+        let c = Circuit::random()
+            .seed(42)
+            .qubits(4)
+            .depth(40)
+            .clifford_t(0.3)
+            .build();
+        let g: vec_graph::Graph = c.to_graph();
+        let mut tg = TrackedGraph::from_graph(g.clone());
+
+        let m0 = tg.checkpoint();
+        crate::simplify::spider_simp(&mut tg);
+        let g1 = tg.graph().clone();
+        let m1 = tg.checkpoint();
+        full_simp(&mut tg);
+        assert_eq!(g.to_tensor4(), tg.to_tensor4());
+
+        tg.rollback(m1).unwrap();
+        assert_same_graph(tg.graph(), &g1);
+        tg.rollback(m0).unwrap();
+        assert_same_graph(tg.graph(), &g);
+        assert_eq!(tg.log_len(), 0);
+        assert_eq!(
+            tg.rollback(m1),
+            Err(RollbackError::Ahead {
+                mark: m1.0,
+                len: m0.0
+            })
+        );
+
+        // the graph is still usable after rolling back
+        full_simp(&mut tg);
+        assert_eq!(g.to_tensor4(), tg.to_tensor4());
+    }
+
+    #[test]
+    fn bounded_log() {
+        let mut tg: TrackedGraph<vec_graph::Graph> = TrackedGraph::new();
+        tg.set_max_log_len(3);
+        let m0 = tg.checkpoint();
+        let v0 = tg.add_vertex(VType::Z);
+        let v1 = tg.add_vertex(VType::Z);
+        let m1 = tg.checkpoint();
+        tg.add_edge(v0, v1);
+        tg.set_phase(v0, (1, 4));
+        assert_eq!(tg.log_len(), 3);
+
+        assert_eq!(
+            tg.rollback(m0),
+            Err(RollbackError::Compacted { mark: 0, oldest: 1 })
+        );
+        assert_eq!(tg.num_edges(), 1);
+        tg.rollback(m1).unwrap();
+        assert_eq!(tg.num_edges(), 0);
+        assert!(tg.phase(v0).is_zero());
+
+        tg.compact();
+        tg.rollback(m1).unwrap();
+        assert_eq!(
+            tg.rollback(m0),
+            Err(RollbackError::Compacted { mark: 0, oldest: 2 })
+        );
+        assert_eq!(tg.num_vertices(), 2);
+    }
+
+    #[test]
+    fn rollback_vertex_data() {
+        let mut tg: TrackedGraph<vec_graph::Graph> = TrackedGraph::new();
+        let v = tg.add_vertex(VType::Z);
+        let pristine = tg.graph().clone();
+        let mark = tg.checkpoint();
+        tg.set_ground(v, true);
+        tg.set_vertex_type(v, VType::X);
+        tg.set_phase(v, (1, 2));
+        tg.set_qubit(v, 3);
+        tg.set_row(v, 2);
+        assert!(tg.is_ground(v));
+        tg.rollback(mark).unwrap();
+        assert!(!tg.is_ground(v));
+        assert_same_graph(tg.graph(), &pristine);
+    }
+}
@@ -17,7 +17,7 @@
 pub use crate::graph::*;
 use crate::phase::Phase;
 use crate::scalar::*;
-use num::rational::Rational64;
+use rustc_hash::FxHashMap;
 use std::mem;
 
 pub type VTab<T> = Vec<Option<T>>;
@@ -32,6 +32,7 @@ pub struct Graph {
     numv: usize,
     nume: usize,
     scalar: ScalarN,
+    track_scalar: bool,
 }
 
 impl Graph {
@@ -66,6 +67,39 @@ impl Graph {
         }
     }
 
+    /// Renumber the vertices into the range `0..num_vertices()`
+    ///
+    /// Vertices keep their relative order. Inputs, outputs, and the scalar are
+    /// preserved. Returns the map from old to new indices, which can be used
+    /// to update any other references to vertices in the graph.
+    pub fn compact(&mut self) -> FxHashMap<V, V> {
+        let vmap: FxHashMap<V, V> = self
+            .vertices()
+            .enumerate()
+            .map(|(new, old)| (old, new))
+            .collect();
+
+        let mut vdata = Vec::with_capacity(self.numv);
+        let mut edata = Vec::with_capacity(self.numv);
+        for (d, nhd) in self.vdata.iter_mut().zip(self.edata.iter_mut()) {
+            if let (Some(d), Some(nhd)) = (d.take(), nhd.take()) {
+                vdata.push(Some(d));
+                edata.push(Some(
+                    nhd.into_iter().map(|(v, et)| (vmap[&v], et)).collect(),
+                ));
+            }
+        }
+
+        self.vdata = vdata;
+        self.edata = edata;
+        self.holes.clear();
+        for v in self.inputs.iter_mut().chain(self.outputs.iter_mut()) {
+            *v = vmap[v];
+        }
+
+        vmap
+    }
+
     // Here are some simpler implementations of the vertices and edges functions,
     // but they can't be moved into the trait because they return "impl" types.
     // pub fn vertices2(&self) -> impl Iterator<Item=V> + '_ {
@@ -97,6 +131,7 @@ impl GraphLike for Graph {
             numv: 0,
             nume: 0,
             scalar: Scalar::one(),
+            track_scalar: true,
         }
     }
 
@@ -112,11 +147,11 @@ impl GraphLike for Graph {
         self.nume
     }
 
-    fn vertices(&self) -> VIter {
+    fn vertices(&self) -> VIter<'_> {
         VIter::Vec(self.numv, self.vdata.iter().enumerate())
     }
 
-    fn edges(&self) -> EIter {
+    fn edges(&self) -> EIter<'_> {
         EIter::Vec(self.nume, self.edata.iter().enumerate(), None)
     }
 
@@ -142,9 +177,10 @@ impl GraphLike for Graph {
     fn add_vertex(&mut self, ty: VType) -> V {
         self.add_vertex_with_data(VData {
             ty,
-            phase: Rational64::new(0, 1).into(),
+            phase: ty.default_phase(),
             qubit: 0,
             row: 0,
+            ground: false,
         })
     }
 
@@ -161,6 +197,25 @@ impl GraphLike for Graph {
         }
     }
 
+    fn add_vertex_at(&mut self, v: V, d: VData) {
+        if v >= self.vdata.len() {
+            self.holes.extend(self.vdata.len()..v);
+            self.vdata.resize(v + 1, None);
+            self.edata.resize(v + 1, None);
+        } else {
+            // holes are reused last-in-first-out, so v is usually at the end
+            let i = self
+                .holes
+                .iter()
+                .rposition(|&h| h == v)
+                .expect("Vertex already in graph");
+            self.holes.remove(i);
+        }
+        self.numv += 1;
+        self.vdata[v] = Some(d);
+        self.edata[v] = Some(Vec::new());
+    }
+
     fn remove_vertex(&mut self, v: V) {
         self.numv -= 1;
         self.holes.push(v);
@@ -199,6 +254,7 @@ impl GraphLike for Graph {
 
     fn set_phase(&mut self, v: V, phase: impl Into<Phase>) {
         if let Some(Some(d)) = self.vdata.get_mut(v) {
+            debug_assert_ne!(d.ty, VType::B, "Boundary vertex {} can't have a phase", v);
             d.phase = phase.into();
         } else {
             panic!("Vertex not found");
@@ -211,6 +267,7 @@ impl GraphLike for Graph {
 
     fn add_to_phase(&mut self, v: V, phase: impl Into<Phase>) {
         if let Some(Some(d)) = self.vdata.get_mut(v) {
+            debug_assert_ne!(d.ty, VType::B, "Boundary vertex {} can't have a phase", v);
             d.phase = (d.phase + phase.into()).normalize();
         } else {
             panic!("Vertex not found");
@@ -296,7 +353,15 @@ impl GraphLike for Graph {
         self.vdata[v].expect("Vertex not found").row
     }
 
-    fn neighbors(&self, v: V) -> NeighborIter {
+    fn set_ground(&mut self, v: V, ground: bool) {
+        if let Some(Some(d)) = self.vdata.get_mut(v) {
+            d.ground = ground;
+        } else {
+            panic!("Vertex not found")
+        }
+    }
+
+    fn neighbors(&self, v: V) -> NeighborIter<'_> {
         if let Some(Some(nhd)) = self.edata.get(v) {
             NeighborIter::Vec(nhd.iter())
         } else {
@@ -304,7 +369,7 @@ impl GraphLike for Graph {
         }
     }
 
-    fn incident_edges(&self, v: V) -> IncidentEdgeIter {
+    fn incident_edges(&self, v: V) -> IncidentEdgeIter<'_> {
         if let Some(Some(nhd)) = self.edata.get(v) {
             IncidentEdgeIter::Vec(nhd.iter())
         } else {
@@ -326,6 +391,12 @@ impl GraphLike for Graph {
     fn scalar_mut(&mut self) -> &mut ScalarN {
         &mut self.scalar
     }
+    fn track_scalar(&self) -> bool {
+        self.track_scalar
+    }
+    fn set_track_scalar(&mut self, track: bool) {
+        self.track_scalar = track;
+    }
 
     fn find_edge<F>(&self, f: F) -> Option<(V, V, EType)>
     where
@@ -365,6 +436,9 @@ impl GraphLike for Graph {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::circuit::Circuit;
+    use crate::tensor::ToTensor;
+    use num::Rational64;
 
     #[test]
     fn create_empty_graph() {
@@ -373,27 +447,155 @@ mod tests {
         assert_eq!(g.num_edges(), 0);
     }
 
-    fn simple_graph() -> (Graph, Vec<V>) {
+    #[test]
+    fn h_box_label() {
         let mut g = Graph::new();
-        let vs = vec![
-            g.add_vertex(VType::B),
-            g.add_vertex(VType::B),
-            g.add_vertex(VType::Z),
-            g.add_vertex(VType::Z),
-            g.add_vertex(VType::X),
-            g.add_vertex(VType::X),
-            g.add_vertex(VType::B),
-            g.add_vertex(VType::B),
-        ];
-        g.add_edge(vs[0], vs[2]);
-        g.add_edge(vs[1], vs[3]);
-        g.add_edge(vs[2], vs[4]);
-        g.add_edge(vs[2], vs[5]);
-        g.add_edge(vs[3], vs[4]);
-        g.add_edge(vs[3], vs[5]);
-        g.add_edge(vs[4], vs[6]);
-        g.add_edge(vs[5], vs[7]);
-        (g, vs)
+        let h = g.add_vertex(VType::H);
+        let z = g.add_vertex(VType::Z);
+        assert_eq!(g.phase(z), Phase::zero());
+        assert_eq!(g.h_box_label(h), Scalar4::minus_one());
+        g.set_phase(h, Rational64::new(1, 2));
+        assert_eq!(g.h_box_label(h), Scalar4::from_phase(Rational64::new(1, 2)));
+
+        // labels which are powers of e^(i π/4) are stored exactly
+        let label = Scalar4::from_phase(Rational64::new(-3, 4));
+        assert_eq!(g.set_h_box_label(h, label), Ok(()));
+        assert_eq!(g.phase(h), Phase::new(Rational64::new(-3, 4)));
+        assert_eq!(g.h_box_label(h), label);
+
+        // other unit labels are stored approximately
+        assert_eq!(g.set_h_box_label(h, Scalar4::complex(0.6, 0.8)), Ok(()));
+        let c = g.h_box_label(h).complex_value();
+        assert!((c.re - 0.6).abs() < 1e-10 && (c.im - 0.8).abs() < 1e-10);
+
+        // and everything else is rejected
+        for label in [
+            Scalar4::zero(),
+            Scalar4::from_int_coeffs(&[2]),
+            Scalar4::real(0.5),
+        ] {
+            assert_eq!(
+                g.set_h_box_label(h, label),
+                Err(HBoxLabelError::NotUnit { label })
+            );
+        }
+        assert_eq!(
+            g.set_h_box_label(z, Scalar4::one()),
+            Err(HBoxLabelError::NotHBox { v: z })
+        );
+        assert_eq!(g.phase(z), Phase::zero());
+    }
+
+    /// Two Z spiders on one output each, to put parallel edges between
+    fn two_spiders(t1: VType) -> (Graph, V, V) {
+        let mut g = Graph::new();
+        let v0 = g.add_vertex(VType::Z);
+        let v1 = g.add_vertex(t1);
+        let b0 = g.add_vertex(VType::B);
+        let b1 = g.add_vertex(VType::B);
+        g.add_edge(v0, b0);
+        g.add_edge(v1, b1);
+        g.set_outputs(vec![b0, b1]);
+        (g, v0, v1)
+    }
+
+    #[test]
+    fn merge_parallel_h_edges() {
+        let (mut g, v0, v1) = two_spiders(VType::Z);
+        g.add_edge_with_type(v0, v1, EType::H);
+        g.add_edge_with_type(v0, v1, EType::H);
+        g.add_edge_with_type(v0, v1, EType::H);
+        assert_eq!(g.num_edges(), 5);
+        let t = g.to_tensor4();
+
+        g.merge_parallel_edges();
+        assert_eq!(g.to_tensor4(), t);
+        assert_eq!(g.num_edges(), 3);
+        assert_eq!(g.edge_type(v0, v1), EType::H);
+        assert_eq!(*g.scalar(), Scalar::sqrt2_pow(-2));
+
+        // merging again does nothing
+        let h = g.clone();
+        g.merge_parallel_edges();
+        assert_eq!(g, h);
+    }
+
+    #[test]
+    fn merge_parallel_mixed_edges() {
+        let (mut g, v0, v1) = two_spiders(VType::Z);
+        g.add_edge_with_type(v0, v1, EType::N);
+        g.add_edge_with_type(v0, v1, EType::H);
+        let t = g.to_tensor4();
+
+        g.merge_parallel_edges();
+        assert_eq!(g.to_tensor4(), t);
+        assert_eq!(g.num_edges(), 3);
+        assert_eq!(g.edge_type(v0, v1), EType::N);
+        assert_eq!(g.phase(v0) + g.phase(v1), Phase::one());
+        assert_eq!(*g.scalar(), Scalar::sqrt2_pow(-1));
+
+        let (mut g, v0, v1) = two_spiders(VType::X);
+        g.add_edge_with_type(v0, v1, EType::N);
+        g.add_edge_with_type(v0, v1, EType::N);
+        let t = g.to_tensor4();
+
+        g.merge_parallel_edges();
+        assert_eq!(g.to_tensor4(), t);
+        assert_eq!(g.num_edges(), 2);
+        assert!(!g.connected(v0, v1));
+        assert_eq!(*g.scalar(), Scalar::sqrt2_pow(-2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Parallel edges at boundary vertex 2")]
+    fn merge_parallel_boundary_edges() {
+        let (mut g, v0, _) = two_spiders(VType::Z);
+        g.add_edge(v0, 2);
+        g.merge_parallel_edges();
+    }
+
+    #[test]
+    fn self_loops() {
+        let mut g = Graph::new();
+        let z = g.add_vertex_with_phase(VType::Z, Rational64::new(1, 4));
+        let x = g.add_vertex(VType::X);
+        let b = g.add_vertex(VType::B);
+        g.add_edge(z, b);
+        g.add_edge(z, x);
+        g.add_edge_with_type(z, z, EType::H);
+        g.add_edge_with_type(z, z, EType::N);
+        g.add_edge_with_type(x, x, EType::H);
+        assert_eq!(g.num_edges(), 5);
+        assert_eq!(g.degree(z), 6);
+
+        g.remove_self_loops();
+        assert_eq!(g.num_edges(), 2);
+        assert_eq!(g.degree(z), 2);
+        assert_eq!(g.degree(x), 1);
+        assert_eq!(g.phase(z), Rational64::new(-3, 4).into());
+        assert_eq!(g.phase(x), Phase::one());
+        assert_eq!(*g.scalar(), Scalar::sqrt2_pow(-2));
+    }
+
+    fn simple_graph() -> (Graph, Vec<V>) {
+        use {EType::N, VType::*};
+        let g = Graph::from_edges(
+            8,
+            &[B, B, Z, Z, X, X, B, B],
+            &[
+                (0, 2, N),
+                (1, 3, N),
+                (2, 4, N),
+                (2, 5, N),
+                (3, 4, N),
+                (3, 5, N),
+                (4, 6, N),
+                (5, 7, N),
+            ],
+            &[],
+            &[],
+        );
+        (g, (0..8).collect())
     }
 
     #[test]
@@ -403,6 +605,38 @@ mod tests {
         assert_eq!(g.num_edges(), 8);
     }
 
+    #[test]
+    fn insert_graph() {
+        use {
+            EType::{H, N},
+            VType::{B, X, Z},
+        };
+        let (mut g, vs) = simple_graph();
+        // a chain of 3 spiders between two boundaries
+        let h = Graph::from_edges(
+            5,
+            &[B, Z, X, Z, B],
+            &[(0, 1, N), (1, 2, H), (2, 3, H), (3, 4, N)],
+            &[],
+            &[],
+        );
+        let vmap = g.insert_graph(&h, &[(0, vs[4]), (4, vs[5])]);
+
+        assert_eq!(vmap.len(), 5);
+        assert_eq!(vmap[&0], vs[4]);
+        assert_eq!(vmap[&4], vs[5]);
+        assert_eq!(g.num_vertices(), 11);
+        assert_eq!(g.num_edges(), 12);
+        for i in 1..4 {
+            assert!(!vs.contains(&vmap[&i]));
+            assert_eq!(g.vertex_type(vmap[&i]), h.vertex_type(i));
+        }
+        assert_eq!(g.edge_type(vs[4], vmap[&1]), N);
+        assert_eq!(g.edge_type(vmap[&1], vmap[&2]), H);
+        assert_eq!(g.edge_type(vmap[&3], vs[5]), N);
+        assert_eq!(g.vertex_type(vs[4]), X);
+    }
+
     #[test]
     fn clone_graph() {
         let (g, _) = simple_graph();
@@ -472,4 +706,73 @@ mod tests {
         //     Vec::from_iter(h.edges()));
         assert_eq!(h.edge_type(vs[1], vs[2]), EType::H);
     }
+
+    #[test]
+    fn compact() {
+        use crate::simplify::full_simp;
+
+        let c = Circuit::random()
+            .seed(1)
+            .qubits(3)
+            .depth(30)
+            .p_t(0.3)
+            .with_cliffords()
+            .build();
+        let mut g: Graph = c.to_graph();
+        full_simp(&mut g);
+        assert!(g.vindex() > g.num_vertices());
+
+        let h = g.clone();
+        let vmap = g.compact();
+        assert_eq!(g.vindex(), g.num_vertices());
+        assert_eq!(g.num_vertices(), h.num_vertices());
+        assert_eq!(g.num_edges(), h.num_edges());
+        assert_eq!(g.scalar(), h.scalar());
+        assert_eq!(vmap.len(), h.num_vertices());
+        for v in h.vertices() {
+            assert_eq!(g.vertex_data(vmap[&v]), h.vertex_data(v));
+            for (w, et) in h.incident_edges(v) {
+                assert_eq!(g.edge_type(vmap[&v], vmap[&w]), et);
+            }
+        }
+        let ins: Vec<_> = h.inputs().iter().map(|v| vmap[v]).collect();
+        let outs: Vec<_> = h.outputs().iter().map(|v| vmap[v]).collect();
+        assert_eq!(g.inputs(), &ins);
+        assert_eq!(g.outputs(), &outs);
+        assert_eq!(g.to_tensor4(), h.to_tensor4());
+
+        // new vertices go on the end
+        let v = g.add_vertex(VType::Z);
+        assert_eq!(v, h.num_vertices());
+
+        // compacting a dense graph changes nothing
+        let h = g.clone();
+        let vmap = g.compact();
+        assert!(vmap.iter().all(|(v, w)| v == w));
+        assert_eq!(g, h);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "can't have a phase")]
+    fn boundary_phase() {
+        let mut g = Graph::new();
+        let b = g.add_vertex(VType::B);
+        g.add_to_phase(b, Rational64::new(1, 2));
+    }
+
+    #[test]
+    fn boundaries_stay_phase_free() {
+        let mut c = Circuit::new(2);
+        c.t(0).cx(0, 1).s(1);
+        let g: Graph = c.to_graph();
+        let mut h = g.to_adjoint();
+        h.plug(&g);
+        h.plug_input(0, BasisElem::Z1);
+        for v in h.vertices() {
+            if h.vertex_type(v) == VType::B {
+                assert!(h.phase(v).is_zero());
+            }
+        }
+    }
 }
@@ -0,0 +1,305 @@
+// QuiZX - Rust library for quantum circuit rewriting and optimisation
+//         using the ZX-calculus
+// Copyright (C) 2021 - Aleks Kissinger
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checking equality of graphs by simplification.
+//!
+//! To check two graphs `a` and `b` are equal, we compose `a` with the adjoint
+//! of `b` and simplify, hoping to get the identity. This is the graph-level
+//! version of checking two circuits are equal, and like that, it assumes `b`
+//! is unitary (e.g. it came from a circuit). Otherwise, `a` times the adjoint
+//! of `b` need not be the identity even if the graphs are equal.
+
+use crate::decompose::Decomposer;
+use crate::graph::*;
+use crate::scalar::ScalarN;
+use crate::simplify::full_simp;
+
+use approx::AbsDiffEq;
+use num::complex::Complex;
+use num::One;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Options for [graphs_equal]
+///
+/// By default, the global phase must match and there is no random sampling.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EqualityOptions {
+    /// Whether graphs which only differ by a global phase are equal.
+    pub up_to_global_phase: bool,
+    /// How many random amplitudes of the two graphs to compare when
+    /// simplification doesn't decide equality. Zero disables this.
+    pub samples: usize,
+    /// The seed for picking random amplitudes.
+    pub seed: u64,
+}
+
+/// The outcome of [graphs_equal]
+#[derive(Debug, Clone)]
+pub enum EqualityResult<G: GraphLike> {
+    /// Simplification reduced the graphs to the identity.
+    Equal,
+    /// The graphs are not equal.
+    NotEqual,
+    /// Simplification got stuck, but the given number of random amplitudes
+    /// of the two graphs agreed.
+    ProbablyEqual { samples: usize, residual: G },
+    /// Simplification got stuck, leaving the given graph.
+    Unknown { residual: G },
+}
+
+impl<G: GraphLike> EqualityResult<G> {
+    /// Whether the graphs were shown to be equal
+    pub fn is_equal(&self) -> bool {
+        matches!(self, EqualityResult::Equal)
+    }
+
+    /// Whether the graphs were shown not to be equal
+    pub fn is_not_equal(&self) -> bool {
+        matches!(self, EqualityResult::NotEqual)
+    }
+
+    /// The graph simplification got stuck on, if equality wasn't decided
+    pub fn residual(&self) -> Option<&G> {
+        match self {
+            EqualityResult::ProbablyEqual { residual, .. }
+            | EqualityResult::Unknown { residual } => Some(residual),
+            _ => None,
+        }
+    }
+}
+
+/// Check whether two graphs are equal by simplifying `a` composed with the
+/// adjoint of `b`
+///
+/// If [full_simp] reduces this to wires, the graphs are equal if the wires go
+/// straight from inputs to outputs and the scalar is one, or any scalar of
+/// modulus one if [EqualityOptions::up_to_global_phase] is set. Otherwise,
+/// if [EqualityOptions::samples] is non-zero, this compares that many
+/// amplitudes of `a` and `b` for random basis states on the inputs and
+/// outputs, computed with a [Decomposer].
+///
+/// ```
+/// # use quizx::circuit::Circuit;
+/// # use quizx::vec_graph::Graph;
+/// # use quizx::verify::{graphs_equal, EqualityOptions};
+/// let mut c = Circuit::new(2);
+/// c.cx(0, 1).cx(1, 0).cx(0, 1);
+/// let mut d = Circuit::new(2);
+/// d.swap(0, 1);
+/// let a: Graph = c.to_graph();
+/// let b: Graph = d.to_graph();
+/// assert!(graphs_equal(&a, &b, EqualityOptions::default()).is_equal());
+/// ```
+pub fn graphs_equal<G: GraphLike>(
+    a: &G,
+    b: &impl GraphLike,
+    opts: EqualityOptions,
+) -> EqualityResult<G> {
+    if a.inputs().len() != b.inputs().len() || a.outputs().len() != b.outputs().len() {
+        return EqualityResult::NotEqual;
+    }
+
+    let mut g = a.clone();
+    g.plug(&b.to_adjoint());
+    full_simp(&mut g);
+
+    if g.is_zero() {
+        return EqualityResult::NotEqual;
+    }
+
+    if let Some(perm) = g.io_permutation() {
+        let s = g.scalar();
+        let scalar_ok = if opts.up_to_global_phase {
+            s.complex_value()
+                .norm()
+                .abs_diff_eq(&1.0, ScalarN::default_epsilon())
+        } else {
+            s.abs_diff_eq(&ScalarN::one(), ScalarN::default_epsilon())
+        };
+        return if scalar_ok && perm.iter().enumerate().all(|(i, &j)| i == j) {
+            EqualityResult::Equal
+        } else {
+            EqualityResult::NotEqual
+        };
+    }
+
+    if opts.samples == 0 {
+        return EqualityResult::Unknown { residual: g };
+    }
+
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    let basis = [BasisElem::Z0, BasisElem::Z1, BasisElem::X0, BasisElem::X1];
+    // the ratio of the first non-zero amplitudes, if we allow a global phase
+    let mut phase: Option<Complex<f64>> = None;
+    for _ in 0..opts.samples {
+        let ins: Vec<_> = (0..a.inputs().len())
+            .map(|_| *basis.choose(&mut rng).unwrap())
+            .collect();
+        let outs: Vec<_> = (0..a.outputs().len())
+            .map(|_| *basis.choose(&mut rng).unwrap())
+            .collect();
+        let x = amplitude(a, &ins, &outs);
+        let mut y = amplitude(b, &ins, &outs);
+
+        if opts.up_to_global_phase && !is_zero(y) {
+            let p = *phase.get_or_insert(x / y);
+            y *= p;
+        }
+
+        if !(x - y).norm().abs_diff_eq(&0.0, ScalarN::default_epsilon()) {
+            return EqualityResult::NotEqual;
+        }
+    }
+
+    if let Some(p) = phase {
+        if !p.norm().abs_diff_eq(&1.0, ScalarN::default_epsilon()) {
+            return EqualityResult::NotEqual;
+        }
+    }
+
+    EqualityResult::ProbablyEqual {
+        samples: opts.samples,
+        residual: g,
+    }
+}
+
+fn is_zero(x: Complex<f64>) -> bool {
+    x.norm().abs_diff_eq(&0.0, ScalarN::default_epsilon())
+}
+
+/// The amplitude of g for the given basis states on the inputs and outputs
+fn amplitude(g: &impl GraphLike, ins: &[BasisElem], outs: &[BasisElem]) -> Complex<f64> {
+    let mut h = g.clone();
+    h.plug_inputs(ins);
+    h.plug_outputs(outs);
+    full_simp(&mut h);
+    let mut d = Decomposer::new(&h);
+    d.with_full_simp().decomp_all();
+    d.scalar.complex_value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::Circuit;
+    use crate::extract::ToCircuit;
+    use crate::scalar::*;
+    use crate::vec_graph::Graph;
+
+    fn random_circuit(seed: u64) -> Circuit {
+        Circuit::random()
+            .seed(seed)
+            .qubits(4)
+            .depth(40)
+            .p_t(0.3)
+            .with_cliffords()
+            .build()
+    }
+
+    #[test]
+    fn extracted_equal() {
+        let opts = EqualityOptions {
+            up_to_global_phase: true,
+            ..Default::default()
+        };
+        for seed in 0..10 {
+            let a: Graph = random_circuit(seed).to_graph();
+            let mut g = a.clone();
+            full_simp(&mut g);
+            let b: Graph = g.to_circuit().unwrap().to_graph();
+            assert!(graphs_equal(&a, &b, opts).is_equal(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn global_phase() {
+        let a: Graph = random_circuit(1).to_graph();
+        let mut b = a.clone();
+        *b.scalar_mut() *= ScalarN::from_phase(num::Rational64::new(1, 4));
+
+        assert!(graphs_equal(&a, &a, EqualityOptions::default()).is_equal());
+        assert!(graphs_equal(&a, &b, EqualityOptions::default()).is_not_equal());
+        let opts = EqualityOptions {
+            up_to_global_phase: true,
+            ..Default::default()
+        };
+        assert!(graphs_equal(&a, &b, opts).is_equal());
+
+        // a global scalar which is not a phase is never allowed
+        *b.scalar_mut() *= ScalarN::sqrt2_pow(1);
+        assert!(graphs_equal(&a, &b, opts).is_not_equal());
+    }
+
+    #[test]
+    fn not_equal() {
+        let c = random_circuit(2);
+        let a: Graph = c.to_graph();
+
+        // this simplifies to a swap
+        let mut d = Circuit::new(4);
+        d.swap(0, 1);
+        d += &c;
+        let b: Graph = d.to_graph();
+        assert!(graphs_equal(&a, &b, EqualityOptions::default()).is_not_equal());
+
+        // an extra T gate is left over, which simplification alone can't
+        // show isn't the identity
+        let mut d = c.clone();
+        d.t(3);
+        let b: Graph = d.to_graph();
+        let result = graphs_equal(&a, &b, EqualityOptions::default());
+        assert_eq!(result.residual().unwrap().tcount(), 1);
+        let opts = EqualityOptions {
+            samples: 10,
+            ..Default::default()
+        };
+        assert!(graphs_equal(&a, &b, opts).is_not_equal());
+
+        let b: Graph = Circuit::new(3).to_graph();
+        assert!(graphs_equal(&a, &b, EqualityOptions::default()).is_not_equal());
+    }
+
+    #[test]
+    fn stuck() {
+        // plugging an output makes the graphs non-unitary, so composing one
+        // with the adjoint of the other doesn't simplify to the identity
+        let c = random_circuit(3);
+        let mut a: Graph = c.to_graph();
+        a.plug_outputs(&[BasisElem::X0]);
+
+        let result = graphs_equal(&a, &a, EqualityOptions::default());
+        assert!(matches!(result, EqualityResult::Unknown { .. }));
+        assert!(result.residual().unwrap().tcount() > 0);
+
+        let opts = EqualityOptions {
+            samples: 10,
+            ..Default::default()
+        };
+        let result = graphs_equal(&a, &a, opts);
+        assert!(matches!(
+            result,
+            EqualityResult::ProbablyEqual { samples: 10, .. }
+        ));
+
+        let mut d = c.clone();
+        d.t(3);
+        let mut b: Graph = d.to_graph();
+        b.plug_outputs(&[BasisElem::X0]);
+        assert!(graphs_equal(&a, &b, opts).is_not_equal());
+    }
+}